@@ -0,0 +1,172 @@
+//! Memory used to process a directory of large resource tables, with the
+//! files read into buffers (`create_cursor_from_axml`) or mapped
+//! (`create_cursor_from_axml_mmap`). Run with:
+//!
+//! ```text
+//! cargo bench --features mmap --bench mmap_memory
+//! ```
+//!
+//! The tables are generated in a temporary directory: a global string pool
+//! of 4 MB followed by a package chunk, like `resources.arsc` files where the
+//! entries and values make up most of the size. Each file is parsed with
+//! `ResTable::parse`, which decodes the string pool and stops at the package
+//! header. Each mode runs in its own process, which reports its peak RSS
+//! (`VmHWM`) and the largest anonymous memory (`RssAnon`) seen while a file
+//! is open, from `/proc/self/status` (Linux only). Mapped pages are
+//! file-backed: they are shared with the page cache, which the system can
+//! reclaim, unlike the buffers.
+
+use std::fs;
+use std::io::{ Read, Seek };
+use std::path::{ Path, PathBuf };
+use std::process::Command;
+use std::time::Instant;
+
+use rusty_axml::res_table::ResTable;
+use rusty_axml::{ create_cursor_from_axml, create_cursor_from_axml_mmap };
+
+/// Environment variable giving the mode of a child process
+const CHILD_MODE: &str = "RUSTY_AXML_MMAP_BENCH_MODE";
+/// Environment variable giving the directory of the tables to a child
+const CHILD_DIR: &str = "RUSTY_AXML_MMAP_BENCH_DIR";
+
+/// Number of strings of the global string pool, and their length
+const STRINGS: usize = 40_000;
+const STRING_LEN: usize = 96;
+
+fn write_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Resource table with a UTF-8 global string pool, and a package chunk of
+/// `package_size` bytes whose entries are left empty
+fn resource_table(package_size: usize) -> Vec<u8> {
+    let mut offsets = Vec::with_capacity(STRINGS);
+    let mut strings = Vec::new();
+    for idx in 0..STRINGS {
+        offsets.push(strings.len() as u32);
+        let string = format!("{idx:0STRING_LEN$}");
+        // Lengths in UTF-16 units and in bytes, two bytes each above 0x7f
+        for _ in 0..2 {
+            strings.push(0x80 | (STRING_LEN >> 8) as u8);
+            strings.push(STRING_LEN as u8);
+        }
+        strings.extend_from_slice(string.as_bytes());
+        strings.push(0);
+    }
+    while strings.len() % 4 != 0 {
+        strings.push(0);
+    }
+
+    let strings_start = 28 + 4 * STRINGS;
+    let mut pool = Vec::with_capacity(strings_start + strings.len());
+    write_u16(&mut pool, 0x0001);
+    write_u16(&mut pool, 28);
+    write_u32(&mut pool, (strings_start + strings.len()) as u32);
+    write_u32(&mut pool, STRINGS as u32);
+    write_u32(&mut pool, 0);                            // style count
+    write_u32(&mut pool, 0x100);                        // UTF-8
+    write_u32(&mut pool, strings_start as u32);
+    write_u32(&mut pool, 0);                            // styles start
+    for offset in offsets {
+        write_u32(&mut pool, offset);
+    }
+    pool.extend(strings);
+
+    let mut package = Vec::with_capacity(package_size);
+    write_u16(&mut package, 0x0200);
+    write_u16(&mut package, 288);
+    write_u32(&mut package, package_size as u32);
+    write_u32(&mut package, 0x7f);                      // package ID
+    package.resize(package_size, 0);
+
+    let mut table = Vec::with_capacity(12 + pool.len() + package.len());
+    write_u16(&mut table, 0x0002);
+    write_u16(&mut table, 12);
+    write_u32(&mut table, (12 + pool.len() + package.len()) as u32);
+    write_u32(&mut table, 1);                           // package count
+    table.extend(pool);
+    table.extend(package);
+    table
+}
+
+/// Value in kB of a field of `/proc/self/status`, if available
+fn status_kb(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status.lines()
+          .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+          .and_then(|value| value.trim().trim_end_matches(" kB").parse().ok())
+}
+
+fn parse_table<R: Read + Seek>(mut cursor: R) {
+    ResTable::parse(&mut cursor).unwrap();
+    // Sample while the buffer or the mapping is still alive
+    PEAK_ANON.with(|peak| peak.set(peak.get().max(status_kb("RssAnon").unwrap_or(0))));
+}
+
+thread_local! {
+    static PEAK_ANON: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+/// Parse every table of `dir` in the given mode, and print the measurements
+fn run_child(mode: &str, dir: &Path) {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    files.sort();
+
+    let start = Instant::now();
+    for file in files.iter() {
+        let path = file.to_str().unwrap();
+        match mode {
+            "read" => parse_table(create_cursor_from_axml(path)),
+            "mmap" => parse_table(create_cursor_from_axml_mmap(path).unwrap()),
+            _ => panic!("unknown mode {mode}"),
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let mb = |kb: Option<u64>| kb.map_or_else(|| String::from("n/a"), |kb| format!("{:.0} MB", kb as f64 / 1024.0));
+    println!("{mode:<5} {:>10} {:>14} {:>10.0?}",
+             mb(status_kb("VmHWM")),
+             mb(Some(PEAK_ANON.with(|peak| peak.get())).filter(|kb| *kb > 0)),
+             elapsed);
+}
+
+fn main() {
+    if let (Ok(mode), Ok(dir)) = (std::env::var(CHILD_MODE), std::env::var(CHILD_DIR)) {
+        run_child(&mode, Path::new(&dir));
+        return;
+    }
+
+    // Without `--bench` (e.g., `cargo test --benches`), only check that the
+    // benchmark runs, on a small file
+    let (files, package_size) = if std::env::args().any(|arg| arg == "--bench") {
+        (8, 44 << 20)
+    } else {
+        (1, 1 << 20)
+    };
+
+    let dir = std::env::temp_dir().join(format!("rusty-axml-mmap-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let table = resource_table(package_size);
+    for idx in 0..files {
+        fs::write(dir.join(format!("resources-{idx}.arsc")), &table).unwrap();
+    }
+    println!("{files} resource tables of {} MB (string pool of {} MB)",
+             table.len() >> 20, (table.len() - package_size) >> 20);
+    println!("{:<5} {:>10} {:>14} {:>10}", "mode", "peak RSS", "peak anon RSS", "time");
+
+    for mode in ["read", "mmap"] {
+        let status = Command::new(std::env::current_exe().unwrap())
+            .env(CHILD_MODE, mode)
+            .env(CHILD_DIR, &dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "{mode} run failed");
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,124 @@
+//! Benchmarks of the parsing hot paths, run with `cargo bench`
+//!
+//! The inputs are checked in `tests/data`, so that results can be compared
+//! between changes:
+//!
+//! - `AndroidManifest.xml`: small manifest, with a few components
+//! - `LargeManifest.xml`: 604 components, with intent filters and meta-data,
+//!   also parsed into an arena (`parse_xml_arena`) for comparison, and
+//!   read up to the package name only (`get_package_name`)
+//! - `strings-utf8.bin` and `strings-utf16.bin`: string pool chunks with the
+//!   same 4000 strings (some of them non-ASCII), in both encodings
+//!
+//! The `apk_manifest` benchmarks read the small manifest from a 200 MB APK
+//! generated in memory, with 20000 other entries and the manifest stored
+//! without compression, in place or through the zip crate.
+
+use std::io::Cursor;
+
+use criterion::{ criterion_group, criterion_main, Criterion, Throughput };
+
+use rusty_axml::arena::parse_xml_arena;
+use rusty_axml::get_package_name;
+use rusty_axml::parser::parse_xml;
+use rusty_axml::string_pool::StringPool;
+
+const SMALL_MANIFEST: &[u8] = include_bytes!("../tests/data/AndroidManifest.xml");
+const LARGE_MANIFEST: &[u8] = include_bytes!("../tests/data/LargeManifest.xml");
+const STRINGS_UTF8: &[u8] = include_bytes!("../tests/data/strings-utf8.bin");
+const STRINGS_UTF16: &[u8] = include_bytes!("../tests/data/strings-utf16.bin");
+
+/// Number and size of the entries besides the manifest in the generated APK
+#[cfg(feature = "apk")]
+const APK_ENTRIES: usize = 20_000;
+#[cfg(feature = "apk")]
+const APK_ENTRY_SIZE: usize = 10_000;
+
+fn bench_parse_xml(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_xml");
+    for (name, data) in [("small", SMALL_MANIFEST), ("large", LARGE_MANIFEST)] {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(name, |b| b.iter(|| parse_xml(Cursor::new(data))));
+    }
+    group.finish();
+}
+
+fn bench_parse_xml_arena(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_xml_arena");
+    group.throughput(Throughput::Bytes(LARGE_MANIFEST.len() as u64));
+    group.bench_function("rc_tree", |b| b.iter(|| parse_xml(Cursor::new(LARGE_MANIFEST))));
+    group.bench_function("arena", |b| b.iter(|| parse_xml_arena(Cursor::new(LARGE_MANIFEST))));
+    group.finish();
+}
+
+fn bench_package_name(c: &mut Criterion) {
+    let mut group = c.benchmark_group("package_name");
+    group.bench_function("get_package_name", |b| b.iter(|| {
+        get_package_name(Cursor::new(LARGE_MANIFEST)).unwrap()
+    }));
+    group.bench_function("parse_xml", |b| b.iter(|| {
+        let root = parse_xml(Cursor::new(LARGE_MANIFEST));
+        let package = root.borrow().get_attr("package").map(String::from);
+        package.unwrap()
+    }));
+    group.finish();
+}
+
+fn decode_pool(data: &[u8]) -> Vec<String> {
+    let mut strings = Vec::new();
+    StringPool::from_buff(&mut Cursor::new(data), &mut strings).unwrap();
+    strings
+}
+
+fn bench_string_pool(c: &mut Criterion) {
+    assert_eq!(decode_pool(STRINGS_UTF8), decode_pool(STRINGS_UTF16));
+
+    let mut group = c.benchmark_group("string_pool");
+    for (name, data) in [("utf8", STRINGS_UTF8), ("utf16", STRINGS_UTF16)] {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(name, |b| b.iter(|| decode_pool(data)));
+    }
+    group.finish();
+}
+
+#[cfg(feature = "apk")]
+fn large_apk() -> Vec<u8> {
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::CompressionMethod;
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let entry = vec![0x5a; APK_ENTRY_SIZE];
+    for i in 0..APK_ENTRIES {
+        writer.start_file(format!("assets/data/{i:05}.bin"), options).unwrap();
+        writer.write_all(&entry).unwrap();
+    }
+    writer.start_file("AndroidManifest.xml", options).unwrap();
+    writer.write_all(SMALL_MANIFEST).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(feature = "apk")]
+fn bench_apk_manifest(c: &mut Criterion) {
+    use rusty_axml::{ create_cursor_from_apk_bytes, manifest_slice_from_apk };
+
+    let apk = large_apk();
+    assert_eq!(manifest_slice_from_apk(&apk), Some(SMALL_MANIFEST));
+
+    let mut group = c.benchmark_group("apk_manifest");
+    group.bench_function("slice", |b| b.iter(|| {
+        parse_xml(Cursor::new(manifest_slice_from_apk(&apk).unwrap()))
+    }));
+    group.bench_function("zip", |b| b.iter(|| {
+        parse_xml(create_cursor_from_apk_bytes(&apk).unwrap())
+    }));
+    group.finish();
+}
+
+#[cfg(feature = "apk")]
+criterion_group!(benches, bench_parse_xml, bench_parse_xml_arena, bench_package_name, bench_string_pool,
+                 bench_apk_manifest);
+#[cfg(not(feature = "apk"))]
+criterion_group!(benches, bench_parse_xml, bench_parse_xml_arena, bench_package_name, bench_string_pool);
+criterion_main!(benches);
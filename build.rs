@@ -0,0 +1,14 @@
+fn main() {
+    // Generate the C header of the FFI module
+    #[cfg(feature = "ffi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        cbindgen::generate(&crate_dir)
+            .expect("cannot generate the C header")
+            .write_to_file(std::path::Path::new(&out_dir).join("rusty_axml.h"));
+    }
+}
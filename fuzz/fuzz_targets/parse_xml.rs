@@ -0,0 +1,14 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use rusty_axml::parser::{ parse_xml_with_warnings, try_parse_xml_from_slice };
+
+fuzz_target!(|data: &[u8]| {
+    let result = parse_xml_with_warnings(Cursor::new(data));
+    // Whatever was parsed must also be printable
+    let _ = result.root.borrow().to_xml_string();
+
+    let _ = try_parse_xml_from_slice(data);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use rusty_axml::string_pool::StringPool;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = StringPool::from_buff(&mut Cursor::new(data), &mut Vec::new());
+});
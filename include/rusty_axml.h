@@ -0,0 +1,54 @@
+#ifndef RUSTY_AXML_H
+#define RUSTY_AXML_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Return value of the functions on success
+ */
+#define RUSTY_AXML_OK 0
+
+/**
+ * Return value of the functions on error
+ */
+#define RUSTY_AXML_ERROR -1
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Decode the binary manifest in `data` (`len` bytes) into an XML string.
+ *
+ * On success, returns `RUSTY_AXML_OK` and stores the XML document in
+ * `*out_xml`. On error, returns `RUSTY_AXML_ERROR` and stores an error
+ * message in `*out_err`. The other output is set to `NULL`. Both strings
+ * must be released with `rusty_axml_free_string`. `out_err` can be `NULL` if
+ * the caller does not need the error message.
+ *
+ * # Safety
+ *
+ * `data` must point to `len` readable bytes (or be `NULL` if `len` is 0),
+ * and `out_xml` and `out_err` must be `NULL` or point to writable pointers.
+ */
+int rusty_axml_parse_manifest(const uint8_t *data, size_t len, char **out_xml, char **out_err);
+
+/**
+ * Release a string returned by the library. Does nothing if `value` is
+ * `NULL`.
+ *
+ * # Safety
+ *
+ * `value` must be `NULL` or a string returned by the library that was not
+ * released yet.
+ */
+void rusty_axml_free_string(char *value);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RUSTY_AXML_H */
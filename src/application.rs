@@ -0,0 +1,355 @@
+//! Application attributes
+//!
+//! Structured view of the security-relevant attributes of the
+//! `<application>` element, of its backup configuration, and of the classes
+//! it declares.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::parser::XmlElement;
+use crate::owned::ElementRef;
+use crate::{ find_elements_by_type, manifest_package, normalize_component_name };
+#[cfg(feature = "apk")]
+use crate::{ parse_all_axml_from_apk, AxmlError };
+
+/// State of a boolean attribute, before applying its default value
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlagState {
+    /// The attribute is not set, its default value applies
+    #[default]
+    NotSet,
+    /// The attribute is explicitly set to `true`
+    ExplicitTrue,
+    /// The attribute is explicitly set to `false`
+    ExplicitFalse,
+    /// The attribute refers to a boolean resource (e.g., `@bool/debug`)
+    Reference(u32),
+}
+
+impl FlagState {
+    /// Read the state of the attribute `local_name` of the `android`
+    /// namespace of an element
+    pub fn from_attr<E: ElementRef>(element: &E, local_name: &str) -> Self {
+        let Some(attr) = element.get_android_attribute(local_name) else {
+            return FlagState::NotSet;
+        };
+        match attr.as_bool() {
+            Some(true) => FlagState::ExplicitTrue,
+            Some(false) => FlagState::ExplicitFalse,
+            None => attr.as_reference().map_or(FlagState::NotSet, FlagState::Reference),
+        }
+    }
+
+    /// Value of the flag, with `default` if it is not set. Returns `None`
+    /// if the value refers to a resource.
+    pub fn value_or(self, default: bool) -> Option<bool> {
+        match self {
+            FlagState::NotSet => Some(default),
+            FlagState::ExplicitTrue => Some(true),
+            FlagState::ExplicitFalse => Some(false),
+            FlagState::Reference(_) => None,
+        }
+    }
+}
+
+/// Security-relevant flags of the `<application>` element, see
+/// [`get_application_flags`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ApplicationFlags {
+    /// `android:debuggable`, `false` by default
+    pub debuggable: FlagState,
+    /// `android:allowBackup`, `true` by default
+    pub allow_backup: FlagState,
+    /// `android:usesCleartextTraffic`, `true` by default up to API level 27
+    /// and `false` from API level 28
+    pub uses_cleartext_traffic: FlagState,
+    /// `android:testOnly`, `false` by default
+    pub test_only: FlagState,
+    /// `android:extractNativeLibs`, `true` by default up to API level 22 and
+    /// `false` from API level 23 when building with recent tools
+    pub extract_native_libs: FlagState,
+    /// Resource ID of the network security configuration
+    /// (`android:networkSecurityConfig`)
+    pub network_security_config: Option<u32>,
+}
+
+/// Get the security-relevant flags of the `<application>` element. The
+/// flags are reported as they are set in the manifest, without applying
+/// their defaults, since most of them depend on the SDK versions (see
+/// [`crate::sdk::get_sdk_versions`] and [`FlagState::value_or`]). If there is
+/// no `<application>` element, no flag is set.
+pub fn get_application_flags<E: ElementRef>(root: &E) -> ApplicationFlags {
+    let Some(application) = find_elements_by_type(root, "application").into_iter().next() else {
+        return ApplicationFlags::default();
+    };
+
+    ApplicationFlags {
+        debuggable: FlagState::from_attr(&application, "debuggable"),
+        allow_backup: FlagState::from_attr(&application, "allowBackup"),
+        uses_cleartext_traffic: FlagState::from_attr(&application, "usesCleartextTraffic"),
+        test_only: FlagState::from_attr(&application, "testOnly"),
+        extract_native_libs: FlagState::from_attr(&application, "extractNativeLibs"),
+        network_security_config: application.get_android_attribute("networkSecurityConfig")
+                                            .and_then(|attr| attr.as_reference()),
+    }
+}
+
+/// Classes declared on the `<application>` element, see
+/// [`get_application_identity`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApplicationIdentity {
+    /// Fully-qualified name of the `Application` subclass (`android:name`)
+    pub class_name: Option<String>,
+    /// Fully-qualified name of the `AppComponentFactory` subclass
+    /// (`android:appComponentFactory`)
+    pub app_component_factory: Option<String>,
+    /// Fully-qualified name of the backup agent class (`android:backupAgent`)
+    pub backup_agent: Option<String>,
+}
+
+/// Get the classes declared on the `<application>` element, with names
+/// expanded like component names (see [`normalize_component_name`]). Every
+/// value is `None` if there is no `<application>` element (e.g., in some
+/// split APKs).
+pub fn get_application_identity<E: ElementRef>(root: &E) -> ApplicationIdentity {
+    let Some(application) = find_elements_by_type(root, "application").into_iter().next() else {
+        return ApplicationIdentity::default();
+    };
+
+    let pkg_name = manifest_package(root);
+    let class = |attr: &str| {
+        application.get_attr(attr).map(|name| normalize_component_name(&pkg_name, &name))
+    };
+
+    ApplicationIdentity {
+        class_name: class("name"),
+        app_component_factory: class("appComponentFactory"),
+        backup_agent: class("backupAgent"),
+    }
+}
+
+/// Value of `android:fullBackupContent`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FullBackupContent {
+    /// Boolean value: `false` disables full backups, `true` keeps the
+    /// default rules
+    Enabled(bool),
+    /// Reference to the XML file with the backup rules
+    Rules(u32),
+}
+
+/// Backup configuration of the app, see [`get_backup_config`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackupConfig {
+    /// Fully-qualified name of the backup agent class (`android:backupAgent`)
+    pub backup_agent: Option<String>,
+    /// Rules for full backups (`android:fullBackupContent`), used up to API
+    /// level 30
+    pub full_backup_content: Option<FullBackupContent>,
+    /// Whether full backups are used even if a backup agent is declared
+    /// (`android:fullBackupOnly`)
+    pub full_backup_only: Option<bool>,
+    /// Reference to the XML file with the backup and device transfer rules
+    /// (`android:dataExtractionRules`), used from API level 31
+    pub data_extraction_rules: Option<u32>,
+}
+
+/// Get the backup configuration of the `<application>` element. Whether
+/// backups are allowed at all is given by [`ApplicationFlags::allow_backup`].
+pub fn get_backup_config<E: ElementRef>(root: &E) -> BackupConfig {
+    let Some(application) = find_elements_by_type(root, "application").into_iter().next() else {
+        return BackupConfig::default();
+    };
+
+    let full_backup_content = application.get_android_attribute("fullBackupContent").and_then(|attr| {
+        match attr.as_bool() {
+            Some(enabled) => Some(FullBackupContent::Enabled(enabled)),
+            None => attr.as_reference().map(FullBackupContent::Rules),
+        }
+    });
+
+    BackupConfig {
+        backup_agent: application.get_android_attribute("backupAgent")
+                                 .map(|agent| normalize_component_name(&manifest_package(root), &agent.value)),
+        full_backup_content,
+        full_backup_only: application.get_android_attribute("fullBackupOnly").and_then(|attr| attr.as_bool()),
+        data_extraction_rules: application.get_android_attribute("dataExtractionRules")
+                                          .and_then(|attr| attr.as_reference()),
+    }
+}
+
+/// Function resolving the ID of a file resource (e.g., `@xml/backup_rules`)
+/// to its path in the APK (e.g., `res/xml/backup_rules.xml`), if known
+pub type PathResolver<'a> = &'a dyn Fn(u32) -> Option<String>;
+
+/// Parsed backup rules referenced by a [`BackupConfig`]
+#[derive(Debug, Default)]
+pub struct BackupRules {
+    /// Document referenced by `android:fullBackupContent` (`<full-backup-content>`)
+    pub full_backup_content: Option<Rc<RefCell<XmlElement>>>,
+    /// Document referenced by `android:dataExtractionRules` (`<data-extraction-rules>`)
+    pub data_extraction_rules: Option<Rc<RefCell<XmlElement>>>,
+}
+
+/// Parse the backup rules referenced by `config` from the APK at `apk_path`.
+///
+/// The entries of `resources.arsc` are not decoded yet, so the paths of the
+/// referenced files are given by `resolve`. Rules whose path is unknown, or
+/// that cannot be found or parsed, are `None`.
+#[cfg(feature = "apk")]
+pub fn resolve_backup_rules(apk_path: &str, config: &BackupConfig, resolve: PathResolver) -> Result<BackupRules, AxmlError> {
+    let mut files = parse_all_axml_from_apk(apk_path, Some("res/**"))?.files;
+    let mut document = |id: Option<u32>| files.remove(&resolve(id?)?);
+
+    let full_backup_content = match config.full_backup_content {
+        Some(FullBackupContent::Rules(id)) => document(Some(id)),
+        _ => None,
+    };
+
+    Ok(BackupRules {
+        full_backup_content,
+        data_extraction_rules: document(config.data_extraction_rules),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn flags(application_attrs: Vec<Attr>) -> ApplicationFlags {
+        get_application_flags(&parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("application", application_attrs)
+            .end("manifest")
+            .build())))
+    }
+
+    #[test]
+    fn test_set_flags() {
+        let flags = flags(vec![Attr::boolean("android:debuggable", true),
+                               Attr::boolean("android:allowBackup", false),
+                               Attr::boolean("android:usesCleartextTraffic", true),
+                               Attr::boolean("android:testOnly", true),
+                               Attr::boolean("android:extractNativeLibs", false),
+                               Attr::reference("android:networkSecurityConfig", 0x7f150000)]);
+
+        assert_eq!(flags, ApplicationFlags {
+            debuggable: FlagState::ExplicitTrue,
+            allow_backup: FlagState::ExplicitFalse,
+            uses_cleartext_traffic: FlagState::ExplicitTrue,
+            test_only: FlagState::ExplicitTrue,
+            extract_native_libs: FlagState::ExplicitFalse,
+            network_security_config: Some(0x7f150000),
+        });
+    }
+
+    #[test]
+    fn test_unset_flags() {
+        let flags = flags(vec![]);
+
+        assert_eq!(flags, ApplicationFlags::default());
+        assert_eq!(flags.allow_backup.value_or(true), Some(true));
+        assert_eq!(flags.debuggable.value_or(false), Some(false));
+    }
+
+    #[test]
+    fn test_reference_flags() {
+        let flags = flags(vec![Attr::reference("android:debuggable", 0x7f050000),
+                               Attr::string("android:allowBackup", "@7f050001")]);
+
+        assert_eq!(flags.debuggable, FlagState::Reference(0x7f050000));
+        assert_eq!(flags.allow_backup, FlagState::Reference(0x7f050001));
+        assert_eq!(flags.debuggable.value_or(false), None);
+        assert_eq!(flags.test_only, FlagState::NotSet);
+    }
+
+    #[test]
+    fn test_application_identity() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("application", vec![Attr::string("android:name", ".App"),
+                                         Attr::string("android:appComponentFactory",
+                                                      "androidx.core.app.CoreComponentFactory")])
+            .end("manifest")
+            .build()));
+
+        assert_eq!(get_application_identity(&root), ApplicationIdentity {
+            class_name: Some(String::from("com.example.App")),
+            app_component_factory: Some(String::from("androidx.core.app.CoreComponentFactory")),
+            backup_agent: None,
+        });
+    }
+
+    #[test]
+    fn test_application_identity_without_application() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .element("manifest", vec![Attr::string("package", "com.example"),
+                                      Attr::string("split", "config.arm64_v8a")])
+            .build()));
+
+        assert_eq!(get_application_identity(&root), ApplicationIdentity::default());
+    }
+
+    #[test]
+    fn test_backup_config() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("application", vec![Attr::string("android:backupAgent", ".Backup"),
+                                         Attr::reference("android:fullBackupContent", 0x7f160000),
+                                         Attr::reference("android:dataExtractionRules", 0x7f160001)])
+            .end("manifest")
+            .build()));
+
+        let config = get_backup_config(&root);
+        assert_eq!(config, BackupConfig {
+            backup_agent: Some(String::from("com.example.Backup")),
+            full_backup_content: Some(FullBackupContent::Rules(0x7f160000)),
+            full_backup_only: None,
+            data_extraction_rules: Some(0x7f160001),
+        });
+
+        // Resolving the rules reads them from the APK
+        #[cfg(feature = "apk")]
+        {
+            let rules = AxmlBuilder::new()
+                .start("data-extraction-rules", vec![])
+                .element("cloud-backup", vec![])
+                .end("data-extraction-rules")
+                .build();
+            let apk = crate::test_utils::zip_archive(&[("res/xml/rules.xml", &rules)]);
+            let path = std::env::temp_dir().join(format!("rusty-axml-backup-{}.apk", std::process::id()));
+            std::fs::write(&path, apk).unwrap();
+
+            let resolve = |id: u32| (id == 0x7f160001).then(|| String::from("res/xml/rules.xml"));
+            let resolved = resolve_backup_rules(path.to_str().unwrap(), &config, &resolve).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert!(resolved.full_backup_content.is_none());
+            assert_eq!(resolved.data_extraction_rules.unwrap().borrow().element_type, "data-extraction-rules");
+        }
+    }
+
+    #[test]
+    fn test_backup_config_unset() {
+        let config = get_backup_config(&parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![])
+            .element("application", vec![Attr::boolean("android:fullBackupContent", false),
+                                         Attr::boolean("android:fullBackupOnly", true)])
+            .end("manifest")
+            .build())));
+
+        assert_eq!(config.backup_agent, None);
+        assert_eq!(config.full_backup_content, Some(FullBackupContent::Enabled(false)));
+        assert_eq!(config.full_backup_only, Some(true));
+        assert_eq!(config.data_extraction_rules, None);
+    }
+}
@@ -0,0 +1,324 @@
+//! Arena-based tree
+//!
+//! `parse_xml` allocates an `Rc`, a `RefCell`, an attribute list, and a
+//! children list for every element, and decodes every attribute into its own
+//! `String`s. On large documents the parse time is dominated by allocations.
+//!
+//! `parse_xml_arena` builds an `XmlTree` instead: all the nodes live
+//! contiguously in a single `Vec`, they refer to each other by index, and the
+//! attributes point into the shared string table (the string pool of the
+//! document). Strings are only decoded when they are requested, and most of
+//! the time they are simply borrowed from the string table.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::io::{
+    Cursor,
+    Read,
+    Seek,
+};
+
+use crate::attributes::Attribute;
+use crate::parser::{
+    self,
+    RawAttribute,
+    XmlElement,
+};
+use crate::reader::{ AxmlReader, RawEvent };
+use crate::symbol::Symbol;
+
+/// Index of a node in an `XmlTree`
+pub type NodeId = usize;
+
+/// Value used in the binary format to indicate the absence of a string
+const NO_ENTRY: u32 = 0xffffffff;
+
+/// Attribute of an `ArenaNode`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArenaAttribute {
+    /// Index of the namespace prefix in the string table, if any
+    pub prefix: Option<u32>,
+    /// Raw attribute from the binary chunk
+    pub raw: RawAttribute,
+}
+
+/// Element stored in an `XmlTree`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArenaNode {
+    /// Index of the element type in the string table
+    pub name: u32,
+    /// Namespaces declared on this element, as `(prefix, uri)` indices in the
+    /// string table
+    pub namespaces: Vec<(u32, u32)>,
+    /// Attributes, in document order
+    pub attributes: Vec<ArenaAttribute>,
+    /// Children, in document order
+    pub children: Vec<NodeId>,
+    /// Parent, `None` for the root
+    pub parent: Option<NodeId>,
+}
+
+/// Tree of elements stored in an arena
+#[derive(Clone, Debug, Default)]
+pub struct XmlTree {
+    strings: Vec<String>,
+    nodes: Vec<ArenaNode>,
+}
+
+impl XmlTree {
+    /// Root of the tree (the first element of the document), if any
+    pub fn root(&self) -> Option<NodeId> {
+        if self.nodes.is_empty() {
+            None
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Number of elements in the tree
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check if the tree has no elements
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Shared string table
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+
+    /// Get a string from the string table. Invalid indices give an empty string.
+    pub fn string(&self, idx: u32) -> &str {
+        self.strings.get(idx as usize).map_or("", String::as_str)
+    }
+
+    /// Get a node from its ID
+    pub fn node(&self, id: NodeId) -> &ArenaNode {
+        &self.nodes[id]
+    }
+
+    /// Type of an element
+    pub fn element_type(&self, id: NodeId) -> &str {
+        self.string(self.nodes[id].name)
+    }
+
+    /// Children of an element
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id].children
+    }
+
+    /// Parent of an element
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    /// Name of an attribute, including its namespace prefix. The name is only
+    /// allocated when there is a prefix.
+    pub fn attribute_key(&self, attr: &ArenaAttribute) -> Cow<'_, str> {
+        match attr.prefix {
+            Some(prefix) => Cow::Owned(format!("{}:{}", self.string(prefix), self.string(attr.raw.name))),
+            None => Cow::Borrowed(self.string(attr.raw.name)),
+        }
+    }
+
+    /// Value of an attribute. The value is borrowed from the string table when
+    /// the attribute has a raw string value, and decoded otherwise.
+    pub fn attribute_value(&self, attr: &ArenaAttribute) -> Cow<'_, str> {
+        if attr.raw.raw_value != NO_ENTRY {
+            Cow::Borrowed(self.string(attr.raw.raw_value))
+        } else {
+            Cow::Owned(parser::decode_attribute_value(&attr.raw, &self.strings))
+        }
+    }
+
+    /// Iterate over the `(key, value)` pairs of the attributes of an element
+    pub fn attributes(&self, id: NodeId) -> impl Iterator<Item = (Cow<'_, str>, Cow<'_, str>)> {
+        self.nodes[id].attributes
+                      .iter()
+                      .map(|attr| (self.attribute_key(attr), self.attribute_value(attr)))
+    }
+
+    /// Get the value of an attribute from its local name, regardless of its
+    /// namespace prefix, see [`XmlElement::get_attr`]
+    pub fn get_attr(&self, id: NodeId, local_name: &str) -> Option<Cow<'_, str>> {
+        let attributes = &self.nodes[id].attributes;
+        let is_android = |attr: &&ArenaAttribute| {
+            attr.prefix.map(|prefix| self.string(prefix)) == Some("android")
+        };
+
+        let mut candidates = attributes.iter()
+                                       .filter(|attr| self.string(attr.raw.name) == local_name);
+        let attr = candidates.clone()
+                             .find(is_android)
+                             .or_else(|| candidates.next())?;
+
+        Some(self.attribute_value(attr))
+    }
+
+    /// Convert the tree into a `Rc<RefCell<XmlElement>>` tree
+    pub fn to_rc_tree(&self) -> Option<Rc<RefCell<XmlElement>>> {
+        self.root().map(|root| self.node_to_rc(root))
+    }
+
+    fn node_to_rc(&self, id: NodeId) -> Rc<RefCell<XmlElement>> {
+        let node = &self.nodes[id];
+        let element = Rc::new(RefCell::new(XmlElement::new(self.element_type(id))));
+
+        {
+            let mut element = element.borrow_mut();
+            for (prefix, uri) in node.namespaces.iter() {
+                element.attributes.insert(format!("xmlns:{}", self.string(*prefix)),
+                                          self.string(*uri).to_string());
+            }
+            for attr in node.attributes.iter() {
+                element.attributes.insert_first(Attribute {
+                    name: Symbol::from(self.attribute_key(attr).into_owned()),
+                    namespace: (attr.raw.namespace != NO_ENTRY).then(|| Symbol::from(self.string(attr.raw.namespace))),
+                    value: self.attribute_value(attr).into_owned(),
+                    typed_value: Some(attr.raw.typed_value),
+                });
+            }
+        }
+
+        for child in node.children.iter() {
+            XmlElement::append_child(&element, self.node_to_rc(*child));
+        }
+
+        element
+    }
+}
+
+/// Parse a whole XML document into an arena-based tree
+pub fn parse_xml_arena<R: Read + Seek>(axml_cursor: R) -> XmlTree {
+    let mut reader = AxmlReader::new(axml_cursor);
+    let mut nodes = Vec::<ArenaNode>::new();
+
+    // Map from namespace URI to the index of its prefix in the string table
+    let mut namespace_prefixes = HashMap::<String, u32>::new();
+    let mut pending_namespaces = Vec::<(u32, u32)>::new();
+    let mut stack = Vec::<NodeId>::new();
+
+    while let Some(event) = reader.next_raw_event() {
+        match event {
+            RawEvent::StartNamespace { prefix, uri } => {
+                namespace_prefixes.insert(reader.string(uri).to_string(), prefix);
+                pending_namespaces.push((prefix, uri));
+            },
+            RawEvent::StartElement(raw_element) => {
+                let attributes = raw_element.attributes
+                                            .into_iter()
+                                            .map(|raw| ArenaAttribute {
+                                                prefix: if raw.namespace == NO_ENTRY {
+                                                    None
+                                                } else {
+                                                    namespace_prefixes.get(reader.string(raw.namespace)).copied()
+                                                },
+                                                raw,
+                                            })
+                                            .collect();
+
+                let id = nodes.len();
+                let parent = stack.last().copied();
+                nodes.push(ArenaNode {
+                    name: raw_element.name,
+                    namespaces: std::mem::take(&mut pending_namespaces),
+                    attributes,
+                    children: Vec::new(),
+                    parent,
+                });
+
+                if let Some(parent) = parent {
+                    nodes[parent].children.push(id);
+                }
+                stack.push(id);
+            },
+            RawEvent::EndElement { .. } => {
+                stack.pop();
+            },
+            RawEvent::EndNamespace { .. } | RawEvent::CData { .. } => { },
+        }
+    }
+
+    XmlTree {
+        strings: reader.into_strings(),
+        nodes,
+    }
+}
+
+/// Parse a whole XML document from a byte slice into an arena-based tree,
+/// without copying it
+pub fn parse_xml_arena_from_slice(data: &[u8]) -> XmlTree {
+    parse_xml_arena(Cursor::new(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ parse_xml, tree_eq };
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn sample_manifest() -> Vec<u8> {
+        AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::int("android:versionCode", 7)])
+            .start("application", vec![Attr::boolean("android:debuggable", true)])
+            .start("activity", vec![Attr::string("android:name", ".Main")])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .element("service", vec![Attr::string("android:name", ".Sync"),
+                                     Attr::reference("android:label", 0x7f0e0001)])
+            .end("application")
+            .end("manifest")
+            .build()
+    }
+
+    #[test]
+    fn test_arena_navigation() {
+        let tree = parse_xml_arena(Cursor::new(sample_manifest()));
+
+        let root = tree.root().unwrap();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.element_type(root), "manifest");
+        assert_eq!(tree.get_attr(root, "package").as_deref(), Some("com.example"));
+        assert_eq!(tree.get_attr(root, "versionCode").as_deref(), Some("7"));
+
+        let application = tree.children(root)[0];
+        assert_eq!(tree.parent(application), Some(root));
+        assert_eq!(tree.get_attr(application, "debuggable").as_deref(), Some("true"));
+
+        let service = tree.children(application)[1];
+        let attributes: Vec<(String, String)> = tree.attributes(service)
+                                                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                                                    .collect();
+        assert_eq!(attributes, vec![
+            (String::from("android:name"), String::from(".Sync")),
+            (String::from("android:label"), String::from("type1/2131623937")),
+        ]);
+
+        // String values are borrowed from the string table
+        assert!(matches!(tree.get_attr(service, "name"), Some(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn test_arena_matches_rc_tree() {
+        let tree = parse_xml_arena(Cursor::new(sample_manifest()));
+        let rc_tree = parse_xml(Cursor::new(sample_manifest()));
+
+        assert!(tree_eq(&tree.to_rc_tree().unwrap(), &rc_tree));
+    }
+
+    #[test]
+    fn test_empty_document() {
+        let tree = parse_xml_arena(Cursor::new(Vec::new()));
+
+        assert!(tree.is_empty());
+        assert!(tree.root().is_none());
+        assert!(tree.to_rc_tree().is_none());
+    }
+}
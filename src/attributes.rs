@@ -0,0 +1,407 @@
+//! Attributes of an XML element
+//!
+//! Attributes are stored in the order in which they appear in the binary
+//! start-element chunk. Some tools (and some Android bugs) are sensitive to
+//! this order, so we keep it instead of using a `HashMap`. Elements usually
+//! only have a handful of attributes, so lookups are simple linear scans.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::res_value::ResValue;
+use crate::data_value_type::DataValueType;
+use crate::symbol::Symbol;
+
+/// A single attribute
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    /// Name of the attribute, including the namespace prefix (e.g.,
+    /// `android:name`), shared with the other attributes of the same name
+    pub name: Symbol,
+    /// URI of the namespace of the attribute, as stored in the binary chunk.
+    /// It is kept even if no `xmlns` declaration binds it to a prefix, and is
+    /// `None` for attributes without namespace or that were added without one
+    /// (see [`crate::parser::XmlElement::attr_namespace`])
+    pub namespace: Option<Symbol>,
+    /// Decoded value of the attribute, as it appears in the XML output
+    pub value: String,
+    /// Typed value from the binary chunk, if the attribute was parsed from AXML
+    pub typed_value: Option<ResValue>,
+}
+
+impl Attribute {
+    /// Local name of the attribute, without the namespace prefix
+    pub fn local_name(&self) -> &str {
+        self.name.rsplit_once(':').map_or(self.name.as_str(), |(_, local)| local)
+    }
+
+    /// URI of the namespace of the attribute: the one recorded in the binary
+    /// chunk, otherwise the URI bound to the prefix of its name, as resolved
+    /// by `namespace_uri`. `xmlns:` declarations have no namespace.
+    pub fn resolve_namespace(&self, namespace_uri: impl FnOnce(&str) -> Option<String>) -> Option<Cow<'_, str>> {
+        if let Some(namespace) = &self.namespace {
+            return Some(Cow::Borrowed(namespace.as_str()));
+        }
+
+        match self.name.split_once(':') {
+            Some(("xmlns", _)) | None => None,
+            Some((prefix, _)) => namespace_uri(prefix).map(Cow::Owned),
+        }
+    }
+
+    /// Get the value as a boolean. The typed value is used when the attribute
+    /// was parsed from AXML, otherwise the string value is parsed (`true` or
+    /// `false`). Returns `None` if the value is not a boolean (e.g., a
+    /// reference to a resource).
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.typed_value {
+            Some(typed) => match typed.data_type {
+                DataValueType::TypeIntBoolean => Some(typed.data != 0),
+                DataValueType::TypeString => parse_bool(&self.value),
+                _ => None,
+            },
+            None => parse_bool(&self.value),
+        }
+    }
+
+    /// Get the value as an integer. The typed value is used when the attribute
+    /// was parsed from AXML, otherwise the string value is parsed as a decimal
+    /// or `0x`-prefixed hexadecimal number. Returns `None` if the value is not
+    /// an integer.
+    pub fn as_int(&self) -> Option<i64> {
+        match self.typed_value {
+            Some(typed) => match typed.data_type {
+                DataValueType::TypeIntDec => Some(i64::from(typed.data as i32)),
+                DataValueType::TypeIntHex => Some(i64::from(typed.data)),
+                DataValueType::TypeString => parse_int(&self.value),
+                _ => None,
+            },
+            None => parse_int(&self.value),
+        }
+    }
+
+    /// Get the value as a floating-point number. The typed value is used when
+    /// the attribute was parsed from AXML, otherwise the string value is
+    /// parsed. Returns `None` if the value is not a number.
+    pub fn as_float(&self) -> Option<f32> {
+        match self.typed_value {
+            Some(typed) => match typed.data_type {
+                DataValueType::TypeFloat => Some(f32::from_bits(typed.data)),
+                DataValueType::TypeString => self.value.trim().parse::<f32>().ok(),
+                _ => None,
+            },
+            None => self.value.trim().parse::<f32>().ok(),
+        }
+    }
+
+    /// Get the resource ID the value refers to. The typed value is used when
+    /// the attribute was parsed from AXML, otherwise the string value is
+    /// parsed (either `@` followed by an hexadecimal ID, with or without `0x`,
+    /// or the `type1/<decimal ID>` form produced by the parser). Returns
+    /// `None` if the value is not a reference.
+    pub fn as_reference(&self) -> Option<u32> {
+        match self.typed_value {
+            Some(typed) => match typed.data_type {
+                DataValueType::TypeReference |
+                DataValueType::TypeDynamicReference => Some(typed.data),
+                DataValueType::TypeString => parse_reference(&self.value),
+                _ => None,
+            },
+            None => parse_reference(&self.value),
+        }
+    }
+}
+
+/// Order-preserving collection of attributes, with a map-like API
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+    entries: Vec<Attribute>,
+    /// Attributes decoded after another attribute of the same name, see
+    /// [`Attributes::insert_first`]
+    duplicates: Vec<Attribute>,
+}
+
+impl Attributes {
+    /// Create an empty collection of attributes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty collection of attributes with room for `capacity` entries
+    pub fn with_capacity(capacity: usize) -> Self {
+        Attributes {
+            entries: Vec::with_capacity(capacity),
+            duplicates: Vec::new(),
+        }
+    }
+
+    /// Get the value of the attribute `key`, if present
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.get_attribute(key).map(|attr| &attr.value)
+    }
+
+    /// Get a mutable reference to the value of the attribute `key`, if present
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut String> {
+        self.entries.iter_mut()
+                    .find(|attr| attr.name == key)
+                    .map(|attr| &mut attr.value)
+    }
+
+    /// Get the typed value of the attribute `key`, if present and known
+    pub fn get_typed(&self, key: &str) -> Option<&ResValue> {
+        self.get_attribute(key).and_then(|attr| attr.typed_value.as_ref())
+    }
+
+    /// Get the full attribute `key`, if present
+    pub fn get_attribute(&self, key: &str) -> Option<&Attribute> {
+        self.entries.iter().find(|attr| attr.name == key)
+    }
+
+    /// Find an attribute from its local name, regardless of the namespace
+    /// prefix it was stored with. If several attributes match,
+    /// `android:<local_name>` is preferred, otherwise the first match in
+    /// document order is returned.
+    pub fn find_local(&self, local_name: &str) -> Option<&Attribute> {
+        if let Some(attr) = self.get_attribute(&format!("android:{local_name}")) {
+            return Some(attr);
+        }
+
+        self.entries.iter().find(|attr| attr.local_name() == local_name)
+    }
+
+    /// Find an attribute from its namespace URI and local name, whatever its
+    /// prefix. The namespace of each attribute is found with
+    /// [`Attribute::resolve_namespace`].
+    pub fn find_namespaced(&self,
+                           uri: &str,
+                           local_name: &str,
+                           namespace_uri: impl Fn(&str) -> Option<String>) -> Option<&Attribute> {
+        self.entries.iter().find(|attr| {
+            attr.local_name() == local_name &&
+                attr.resolve_namespace(&namespace_uri).as_deref() == Some(uri)
+        })
+    }
+
+    /// Check if the attribute `key` is present
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Insert an attribute with no typed value. If the attribute is already
+    /// present its value is replaced in place (keeping its position) and the
+    /// old value is returned. Otherwise the attribute is added at the end.
+    pub fn insert(&mut self, key: impl Into<Symbol>, value: String) -> Option<String> {
+        self.insert_typed(key, value, None)
+    }
+
+    /// Insert an attribute along with its typed value, see [`Attributes::insert`]
+    pub fn insert_typed(&mut self, key: impl Into<Symbol>, value: String, typed_value: Option<ResValue>) -> Option<String> {
+        let key = key.into();
+        match self.entries.iter_mut().find(|attr| attr.name == key) {
+            Some(attr) => {
+                attr.typed_value = typed_value;
+                Some(std::mem::replace(&mut attr.value, value))
+            },
+            None => {
+                self.entries.push(Attribute {
+                    name: key,
+                    namespace: None,
+                    value,
+                    typed_value,
+                });
+                None
+            }
+        }
+    }
+
+    /// Insert a full attribute, including its namespace URI. If an attribute
+    /// with the same name is already present it is replaced in place and its
+    /// value is returned, see [`Attributes::insert`].
+    pub fn insert_attribute(&mut self, attribute: Attribute) -> Option<String> {
+        match self.entries.iter_mut().find(|attr| attr.name == attribute.name) {
+            Some(attr) => Some(std::mem::replace(attr, attribute).value),
+            None => {
+                self.entries.push(attribute);
+                None
+            }
+        }
+    }
+
+    /// Insert an attribute decoded from a start-element chunk. If an attribute
+    /// of the same name was already decoded, the first one is kept, like the
+    /// attribute resolution of Android which stops at the first match, and
+    /// the new one is recorded in [`Attributes::duplicates`]. Returns the
+    /// kept attribute in that case.
+    pub fn insert_first(&mut self, attribute: Attribute) -> Option<&Attribute> {
+        match self.entries.iter().position(|attr| attr.name == attribute.name) {
+            Some(idx) => {
+                self.duplicates.push(attribute);
+                Some(&self.entries[idx])
+            },
+            None => {
+                self.entries.push(attribute);
+                None
+            }
+        }
+    }
+
+    /// Attributes that were ignored because an earlier attribute of the
+    /// element has the same name, in document order, see
+    /// [`Attributes::insert_first`]. They are kept for inspection only: they
+    /// are not returned by the lookups and iterators, nor serialized.
+    pub fn duplicates(&self) -> &[Attribute] {
+        &self.duplicates
+    }
+
+    /// Remove the attribute `key` and return its value, if present
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let idx = self.entries.iter().position(|attr| attr.name == key)?;
+        Some(self.entries.remove(idx).value)
+    }
+
+    /// Iterate over the `(key, value)` pairs, in order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &String)> {
+        self.entries.iter().map(|attr| (attr.name.as_str(), &attr.value))
+    }
+
+    /// Iterate over the full attributes, in order
+    pub fn iter_attributes(&self) -> impl Iterator<Item = &Attribute> {
+        self.entries.iter()
+    }
+
+    /// Iterate over the attribute keys, in order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|attr| attr.name.as_str())
+    }
+
+    /// Iterate over the attribute values, in order
+    pub fn values(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|attr| &attr.value)
+    }
+
+    /// Number of attributes
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if there are no attributes
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Parse a boolean attribute value from its string representation
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse an integer attribute value from its string representation, either
+/// in decimal or in hexadecimal with a `0x` prefix
+fn parse_int(value: &str) -> Option<i64> {
+    let value = value.trim();
+
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => value.parse::<i64>().ok(),
+    }
+}
+
+/// Parse a resource reference from its string representation
+fn parse_reference(value: &str) -> Option<u32> {
+    if let Some(id) = value.strip_prefix("type1/") {
+        return id.parse::<u32>().ok();
+    }
+
+    let id = value.strip_prefix('@')?;
+    let id = id.strip_prefix("0x").unwrap_or(id);
+    u32::from_str_radix(id, 16).ok()
+}
+
+impl fmt::Debug for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<(String, String)> for Attributes {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        let mut attributes = Attributes::new();
+        for (k, v) in iter {
+            attributes.insert(k, v);
+        }
+        attributes
+    }
+}
+
+impl IntoIterator for Attributes {
+    type Item = Attribute;
+    type IntoIter = std::vec::IntoIter<Attribute>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insertion_order() {
+        let mut attributes = Attributes::new();
+        attributes.insert(String::from("b"), String::from("1"));
+        attributes.insert(String::from("a"), String::from("2"));
+        attributes.insert(String::from("c"), String::from("3"));
+
+        let keys: Vec<&str> = attributes.keys().collect();
+        assert_eq!(keys, vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_insert_replaces_in_place() {
+        let mut attributes = Attributes::new();
+        attributes.insert(String::from("a"), String::from("1"));
+        attributes.insert(String::from("b"), String::from("2"));
+
+        let old = attributes.insert(String::from("a"), String::from("3"));
+
+        assert_eq!(old, Some(String::from("1")));
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes.iter().next(), Some(("a", &String::from("3"))));
+    }
+
+    #[test]
+    fn test_insert_first_keeps_duplicates() {
+        let attribute = |value: &str| Attribute {
+            name: Symbol::from("android:exported"),
+            namespace: None,
+            value: String::from(value),
+            typed_value: None,
+        };
+        let mut attributes = Attributes::new();
+
+        assert_eq!(attributes.insert_first(attribute("false")), None);
+        assert_eq!(attributes.insert_first(attribute("true")).map(|attr| attr.value.as_str()), Some("false"));
+
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes.get("android:exported"), Some(&String::from("false")));
+        assert_eq!(attributes.duplicates(), &[attribute("true")]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut attributes: Attributes = vec![
+            (String::from("a"), String::from("1")),
+            (String::from("b"), String::from("2")),
+        ].into_iter().collect();
+
+        assert_eq!(attributes.remove("a"), Some(String::from("1")));
+        assert_eq!(attributes.remove("a"), None);
+        assert!(!attributes.contains_key("a"));
+        assert_eq!(attributes.get("b"), Some(&String::from("2")));
+    }
+}
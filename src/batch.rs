@@ -0,0 +1,111 @@
+//! Batch processing
+//!
+//! Parse the manifests of many APKs in parallel, using all the available
+//! cores through rayon. Only available with the `parallel` feature.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::Cursor;
+use std::panic::{ self, AssertUnwindSafe };
+use std::path::{ Path, PathBuf };
+use std::rc::Rc;
+
+use rayon::prelude::*;
+
+use crate::parser::{ self, XmlElement };
+use crate::{ read_entry_from_archive, AxmlError, MANIFEST_ENTRY };
+
+/// Message carried by a panic payload
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown error")
+    }
+}
+
+/// Read and parse the manifest of a single APK, then call `f` on it
+fn process_apk<F, T>(path: &Path, f: &F) -> Result<T, AxmlError>
+where
+    F: Fn(&Rc<RefCell<XmlElement>>) -> T,
+{
+    let zipfile = fs::File::open(path)?;
+    let mut archive = zip::ZipArchive::new(zipfile)?;
+    let axml_data = read_entry_from_archive(&mut archive, MANIFEST_ENTRY)?;
+
+    let root = parser::parse_xml(Cursor::new(axml_data));
+    panic::catch_unwind(AssertUnwindSafe(|| f(&root)))
+        .map_err(|payload| AxmlError::Parse(panic_message(payload.as_ref())))
+}
+
+/// Parse the manifest of each APK in `paths` in parallel, and call `f` on
+/// the root of each manifest.
+///
+/// Failures are isolated: an APK that cannot be read, that has no manifest,
+/// or whose manifest cannot be parsed gives an error for this APK only, and
+/// so does a panic in `f`. The results are returned in the same order as
+/// `paths`.
+///
+/// The tree of each manifest is not `Send`, so `f` runs on the thread that
+/// parsed it and should extract what is needed (e.g., the package name or a
+/// [`ManifestContents`](crate::ManifestContents)).
+pub fn process_apks<F, T>(paths: &[PathBuf], f: F) -> Vec<(PathBuf, Result<T, AxmlError>)>
+where
+    F: Fn(&Rc<RefCell<XmlElement>>) -> T + Sync,
+    T: Send,
+{
+    paths.par_iter()
+         .map(|path| (path.clone(), process_apk(path, &f)))
+         .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest_package;
+    use crate::test_utils::{ zip_archive, Attr, AxmlBuilder };
+
+    fn write_apk(dir: &Path, name: &str, package: &str) -> PathBuf {
+        let manifest = AxmlBuilder::new()
+            .start("manifest", vec![Attr::string("package", package)])
+            .end("manifest")
+            .build();
+        let path = dir.join(name);
+        fs::write(&path, zip_archive(&[("AndroidManifest.xml", &manifest)])).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_process_apks() {
+        let dir = std::env::temp_dir().join(format!("rusty-axml-batch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = write_apk(&dir, "first.apk", "com.example.first");
+        let no_manifest = dir.join("no_manifest.apk");
+        fs::write(&no_manifest, zip_archive(&[("classes.dex", b"dex\n035\0")])).unwrap();
+        let not_a_zip = dir.join("not_a_zip.apk");
+        fs::write(&not_a_zip, b"garbage").unwrap();
+        let second = write_apk(&dir, "second.apk", "com.example.second");
+        let missing = dir.join("missing.apk");
+
+        let paths = vec![first.clone(), no_manifest, not_a_zip, second.clone(), missing];
+        let results = process_apks(&paths, |root| {
+            let package = manifest_package(root);
+            if package.ends_with("second") {
+                panic!("cannot process {package}");
+            }
+            package
+        });
+        fs::remove_dir_all(&dir).unwrap();
+
+        let returned: Vec<PathBuf> = results.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(returned, paths);
+        assert_eq!(results[0].1.as_ref().unwrap(), "com.example.first");
+        assert!(matches!(results[1].1, Err(AxmlError::MissingEntry { .. })));
+        assert!(matches!(results[2].1, Err(AxmlError::Zip(_))));
+        assert!(matches!(&results[3].1, Err(AxmlError::Parse(message)) if message == "cannot process com.example.second"));
+        assert!(matches!(results[4].1, Err(AxmlError::Io(_))));
+    }
+}
@@ -1,79 +1,157 @@
+use std::ffi::OsString;
 use std::path::PathBuf;
-use clap::Parser;
-
-/// Accepted file types
-#[derive(Debug, PartialEq)]
-pub enum ArgType {
-    Apk,
-    Axml,
-    Arsc
-}
+use clap::{ CommandFactory, Parser, Subcommand };
 
 /// Basic CLI for the binary
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// Path to the file to parse. The file can be either an APK, an Android
-    /// binary-XML file, or a resource.arsc file.
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Subcommands of the binary
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Decode a binary XML document (the manifest by default) into XML
+    Xml(XmlArgs),
+    /// List the components of the app along with their exposure state
+    Components(ComponentsArgs),
+    /// List the permissions requested and declared by the app
+    Permissions(InputArgs),
+    /// Print the package name, version, and SDK levels of the app
+    Info(InputArgs),
+    /// Run the security lints on the manifest and print the findings. Exits
+    /// with code 1 if any high-severity finding is found.
+    Lint(InputArgs),
+    /// List the chunks of a binary XML file or of a resource table, stopping
+    /// at the first malformed chunk header
+    Chunks(InputArgs),
+    /// List the strings of the string pool(s), with their index and offset
+    Strings(InputArgs),
+    /// List the resource IDs of the resource map, with the name of the
+    /// attribute they stand for
+    Resmap(InputArgs),
+    /// Summarize every APK in a directory (recursively): package, version,
+    /// exposed components, and dangerous permissions. Exits with code 1 if any
+    /// APK cannot be parsed.
+    Scan(ScanArgs),
+    /// Compare two manifests (from APKs or binary XML files). Exits with code
+    /// 0 if they are identical, 1 if they differ, and 2 on errors.
+    Diff(DiffArgs),
+    /// Print the value of the attribute selected by a path query (e.g.,
+    /// `manifest@package`), one line per match, or the selected elements as
+    /// XML if the query does not end with `@attribute`. Exits with code 1 if
+    /// nothing matches.
+    Get(GetArgs),
+}
+
+/// Input file, shared by all the subcommands
+#[derive(Debug, clap::Args)]
+pub struct InputArgs {
+    /// Path to the file to parse: an APK or an Android binary XML file. The
+    /// type of the file is detected from its contents. Use `-` to read the
+    /// file from the standard input.
+    pub path: PathBuf,
+
+    /// Print the output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Arguments of the `xml` subcommand
+#[derive(Debug, clap::Args)]
+pub struct XmlArgs {
     #[clap(flatten)]
-    target: Target,
+    pub input: InputArgs,
 
-    /// Path to the output file to write the decoded content
+    /// Path to the output file to write the decoded content. Defaults to the
+    /// standard output. With `--all-xml`, path to the output directory.
     #[arg(short, long)]
     pub output: Option<PathBuf>,
+
+    /// Print the Rust representation of the parsed tree, for debugging
+    #[arg(long, conflicts_with = "json")]
+    pub debug_tree: bool,
+
+    /// Name of the binary XML entry to read from the APK (e.g.,
+    /// `res/xml/network_security_config.xml`). Defaults to the manifest.
+    #[arg(long)]
+    pub entry: Option<String>,
+
+    /// Decode every binary XML entry of the APK into the output directory,
+    /// following the layout of the APK. Plain-text XML entries are copied
+    /// as-is.
+    #[arg(long, requires = "output", conflicts_with_all = ["entry", "json", "debug_tree"])]
+    pub all_xml: bool,
 }
 
-/// Argument group to represent any file that can be parsed by AXMLParser
+/// Arguments of the `components` subcommand
 #[derive(Debug, clap::Args)]
-#[group(required = true, multiple = false)]
-struct Target {
-   /// Path to an APK
-   #[arg(short, long)]
-   apk: Option<String>,
-
-   /// Path to an Android binary XML file
-   #[arg(short, long)]
-   xml: Option<String>,
-
-   /// Path to resources.arsc file
-   #[arg(short, long)]
-   res: Option<String>,
+pub struct ComponentsArgs {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Only list the exposed components (enabled and exported)
+    #[arg(long)]
+    pub exposed: bool,
 }
 
-pub fn parse_args() -> Args {
-    Args::parse()
+/// Options that are valid without a subcommand
+const TOP_LEVEL_OPTIONS: [&str; 4] = ["-h", "--help", "-V", "--version"];
+
+/// Arguments of the `scan` subcommand
+#[derive(Debug, clap::Args)]
+pub struct ScanArgs {
+    /// Directory to scan for `*.apk` files
+    pub dir: PathBuf,
+
+    /// Print one JSON object per APK
+    #[arg(long)]
+    pub json: bool,
+
+    /// Number of APKs to process in parallel. Defaults to the number of CPUs.
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
 }
 
-impl Args {
-    pub fn get_arg_type(&self) -> ArgType {
-        if self.target.apk.is_some() {
-            return ArgType::Apk;
-        }
+/// Arguments of the `diff` subcommand
+#[derive(Debug, clap::Args)]
+pub struct DiffArgs {
+    /// Old APK or binary XML file
+    pub old: PathBuf,
 
-        if self.target.xml.is_some() {
-            return ArgType::Axml;
-        }
+    /// New APK or binary XML file
+    pub new: PathBuf,
 
-        if self.target.res.is_some() {
-            return ArgType::Arsc;
-        }
+    /// Print the changes as JSON
+    #[arg(long)]
+    pub json: bool,
+}
 
-        panic!("Will never happen");
-    }
+/// Arguments of the `get` subcommand
+#[derive(Debug, clap::Args)]
+pub struct GetArgs {
+    /// Path to the APK or Android binary XML file. Use `-` to read the file
+    /// from the standard input.
+    pub path: PathBuf,
 
-    pub fn get_arg_path(&self) -> String {
-        if self.target.apk.is_some() {
-            return self.target.apk.as_ref().unwrap().clone();
-        }
+    /// Path query, e.g., `application/activity[@android:name=".Main"]@android:exported`
+    pub query: String,
+}
 
-        if self.target.xml.is_some() {
-            return self.target.xml.as_ref().unwrap().clone();
-        }
+pub fn parse_args() -> Args {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
 
-        if self.target.res.is_some() {
-            return self.target.res.as_ref().unwrap().clone();
+    // Without a subcommand, run `xml` (e.g., `axmlparser app.apk`)
+    if let Some(first) = args.get(1).and_then(|arg| arg.to_str()) {
+        let is_subcommand = Args::command().get_subcommands()
+                                           .any(|subcommand| subcommand.get_name() == first) ||
+                            first == "help";
+        if !is_subcommand && !TOP_LEVEL_OPTIONS.contains(&first) {
+            args.insert(1, OsString::from("xml"));
         }
-
-        panic!("Will never happen");
     }
+
+    Args::parse_from(args)
 }
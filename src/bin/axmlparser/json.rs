@@ -0,0 +1,89 @@
+use std::fmt;
+
+/// Minimal JSON value, for the machine-readable output of the subcommands.
+/// Object keys are kept in insertion order.
+#[derive(Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Build an object from `(key, value)` pairs
+    pub fn object<const N: usize>(entries: [(&str, Json); N]) -> Json {
+        Json::Object(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    /// String value
+    pub fn string(value: impl Into<String>) -> Json {
+        Json::String(value.into())
+    }
+
+    /// Array of strings
+    pub fn strings<S: AsRef<str>>(values: &[S]) -> Json {
+        Json::Array(values.iter().map(|value| Json::string(value.as_ref())).collect())
+    }
+
+    /// String value, or `null`
+    pub fn opt_string(value: Option<impl Into<String>>) -> Json {
+        value.map_or(Json::Null, Json::string)
+    }
+
+    /// Integer value, or `null`
+    pub fn opt_int(value: Option<impl Into<i64>>) -> Json {
+        value.map_or(Json::Null, |value| Json::Int(value.into()))
+    }
+}
+
+/// Write `value` as a quoted and escaped JSON string
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            Json::Int(value) => write!(f, "{value}"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                f.write_str("[")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_str("]")
+            },
+            Json::Object(entries) => {
+                f.write_str("{")?;
+                for (idx, (key, value)) in entries.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            },
+        }
+    }
+}
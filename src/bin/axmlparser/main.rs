@@ -1,23 +1,807 @@
 #![cfg(feature = "cli")]
 pub mod cli;
+pub mod json;
 
-use rusty_axml::create_cursor_from_apk;
-use rusty_axml::parser;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{ self, Cursor, IsTerminal, Read, Seek, Write };
+use std::path::{ Path, PathBuf };
+use std::process;
+use std::rc::Rc;
+
+use rusty_axml::{
+    create_cursor_from_apk_bytes_entry,
+    detect_file_kind,
+    get_exposure_report,
+    AxmlError,
+    ComponentState,
+    FileKind,
+};
+use rusty_axml::chunk_types::ChunkType;
+use rusty_axml::chunks::ChunkIterator;
+use rusty_axml::parser::{ self, XmlElement };
+use rusty_axml::string_pool::{ StringPool, StringPoolHeader };
+use rusty_axml::lint::{
+    run_lints,
+    Severity,
+};
+use rusty_axml::batch::process_apks;
+use rusty_axml::permissions::{
+    get_dangerous_permissions,
+    get_declared_permissions,
+    get_requested_permissions,
+};
+use rusty_axml::sdk::get_sdk_versions;
+use rusty_axml::version::get_version_info;
+use rusty_axml::diff::{ diff_manifests, render_changes, ManifestChange };
+use rusty_axml::query::Query;
+use rusty_axml::warning::ParseWarningKind;
+use cli::{ Command, ComponentsArgs, DiffArgs, GetArgs, InputArgs, ScanArgs, XmlArgs };
+use json::Json;
+
+/// Name of the manifest file in an APK
+const MANIFEST_ENTRY: &str = "AndroidManifest.xml";
+
+/// Print an error and exit
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("Error: {message}");
+    process::exit(1);
+}
+
+/// Read the contents of the input file, or of the standard input if `path`
+/// is `-`
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    if path.as_os_str() != "-" {
+        return fs::read(path).map_err(|e| e.to_string());
+    }
+
+    let mut stdin = io::stdin().lock();
+    if stdin.is_terminal() {
+        return Err(String::from("refusing to read binary data from a terminal, pipe a file instead"));
+    }
+
+    let mut data = Vec::new();
+    stdin.read_to_end(&mut data).map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+/// Read a document from an APK (the manifest, unless `entry` is given) or
+/// from an AXML file, without decoding it. The type of the file is detected
+/// from its contents.
+fn read_document(path: &Path, entry: Option<&str>) -> Result<Vec<u8>, String> {
+    let data = read_file(path)?;
+
+    match detect_file_kind(&data) {
+        FileKind::Apk => create_cursor_from_apk_bytes_entry(&data, entry.unwrap_or(MANIFEST_ENTRY))
+                             .map(Cursor::into_inner)
+                             .map_err(|e| e.to_string()),
+        FileKind::Axml | FileKind::Xml if entry.is_some() => Err(String::from("--entry can only be used with an APK")),
+        FileKind::Axml => Ok(data),
+        FileKind::Xml => Err(String::from("plain-text XML file, there is nothing to decode")),
+        FileKind::Arsc => Err(String::from("resources.arsc files are not supported yet")),
+        FileKind::Unknown => Err(String::from("not an APK or an Android binary XML file")),
+    }
+}
+
+/// Read a binary XML document from an APK (the manifest, unless `entry` is
+/// given) or from an AXML file
+fn read_input(path: &Path, entry: Option<&str>) -> Result<Cursor<Vec<u8>>, String> {
+    let data = read_document(path, entry)?;
+
+    match detect_file_kind(&data) {
+        FileKind::Axml => Ok(Cursor::new(data)),
+        FileKind::Xml => Err(format!("{} is a plain-text XML file", entry.unwrap_or(MANIFEST_ENTRY))),
+        _ => Err(format!("{} is not an Android binary XML file", entry.unwrap_or(MANIFEST_ENTRY))),
+    }
+}
+
+/// Parse a document, printing the warnings of the parser on stderr
+fn parse_reporting_warnings<R: Read + Seek>(name: &str, axml_cursor: R) -> Rc<RefCell<XmlElement>> {
+    let result = parser::parse_xml_with_warnings(axml_cursor);
+    for warning in result.warnings.iter() {
+        eprintln!("Warning: {name}: {warning}");
+    }
+    result.root
+}
+
+/// Parse the input document, or exit with an error
+fn parse_input(path: &Path, entry: Option<&str>) -> Rc<RefCell<XmlElement>> {
+    match read_input(path, entry) {
+        Ok(axml_cursor) => parse_reporting_warnings(&path.display().to_string(), axml_cursor),
+        Err(e) => fail(format!("{}: {e}", path.display())),
+    }
+}
+
+/// Check if writing the output failed because the reader closed the pipe
+fn is_broken_pipe(error: &AxmlError) -> bool {
+    match error {
+        AxmlError::Io(e) => e.kind() == io::ErrorKind::BrokenPipe,
+        AxmlError::Xml(quick_xml::Error::Io(e)) => e.kind() == io::ErrorKind::BrokenPipe,
+        _ => false,
+    }
+}
+
+/// Read the raw contents of the input file, or of the manifest of an APK,
+/// without checking their format
+fn read_raw_input(path: &Path) -> Vec<u8> {
+    match read_file(path) {
+        Ok(data) if detect_file_kind(&data) == FileKind::Apk => {
+            match create_cursor_from_apk_bytes_entry(&data, MANIFEST_ENTRY) {
+                Ok(axml_cursor) => axml_cursor.into_inner(),
+                Err(e) => fail(format!("{}: {e}", path.display())),
+            }
+        },
+        Ok(data) => data,
+        Err(e) => fail(format!("{}: {e}", path.display())),
+    }
+}
+
+/// Find the chunks of the given type in `data` and parse them with `parse`,
+/// which gets a cursor positioned at the start of the chunk
+fn parse_chunks<T>(data: &[u8], chunk_type: ChunkType, parse: impl Fn(&mut Cursor<&[u8]>) -> T) -> Vec<(usize, T)> {
+    ChunkIterator::new(data).descend(true)
+                            .map_while(Result::ok)
+                            .filter(|(_, header)| header.chunk_type == chunk_type)
+                            .map(|(offset, _)| {
+                                let mut cursor = Cursor::new(data);
+                                cursor.set_position(offset as u64);
+                                (offset, parse(&mut cursor))
+                            })
+                            .collect()
+}
+
+/// Short name of a component state, e.g., `default-true`
+fn state_name(state: ComponentState) -> &'static str {
+    match state {
+        ComponentState::Unknown => "unknown",
+        ComponentState::DefaultTrue => "default-true",
+        ComponentState::DefaultFalse => "default-false",
+        ComponentState::ExplicitTrue => "explicit-true",
+        ComponentState::ExplicitFalse => "explicit-false",
+        ComponentState::ImplicitInvalid => "implicit-invalid",
+    }
+}
+
+/// `xml` subcommand: decode the document
+fn run_xml(args: &XmlArgs) -> Result<(), AxmlError> {
+    let path = &args.input.path;
+    let data = read_document(path, args.entry.as_deref()).unwrap_or_else(|e| fail(format!("{}: {e}", path.display())));
+    let plain_output = !args.input.json && !args.debug_tree;
+
+    match detect_file_kind(&data) {
+        FileKind::Axml => { },
+        // Some entries of the APK were not compiled, print them unchanged
+        FileKind::Xml if plain_output => { },
+        FileKind::Xml => fail(format!("{}: the entry is a plain-text XML file", path.display())),
+        _ => fail(format!("{}: the entry is not an Android binary XML file", path.display())),
+    }
+
+    // Write the decoded content to the output file, or to stdout by default
+    let mut sink: Box<dyn Write> = match &args.output {
+        Some(path) => match fs::File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => fail(format!("cannot create {}: {e}", path.display())),
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+
+    if detect_file_kind(&data) == FileKind::Xml {
+        sink.write_all(&data)?;
+        return Ok(sink.flush()?);
+    }
+
+    let elements = parse_reporting_warnings(&path.display().to_string(), Cursor::new(data));
+    if args.input.json {
+        writeln!(sink, "{}", parser::to_json(&elements))?;
+    } else if args.debug_tree {
+        writeln!(sink, "{elements:?}")?;
+    } else {
+        elements.borrow().write_to(&mut sink)?;
+        writeln!(sink)?;
+    }
+
+    Ok(sink.flush()?)
+}
+
+/// `xml --all-xml`: decode every binary XML entry of the APK into the
+/// output directory. Returns whether any entry could not be written.
+fn run_all_xml(args: &XmlArgs) -> Result<bool, AxmlError> {
+    let path = &args.input.path;
+    let out_dir = args.output.as_deref().expect("--all-xml requires --output");
+    let data = read_file(path).unwrap_or_else(|e| fail(format!("{}: {e}", path.display())));
+    if detect_file_kind(&data) != FileKind::Apk {
+        fail(format!("{}: --all-xml can only be used with an APK", path.display()));
+    }
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(&data[..]))?;
+    let mut written = 0;
+    let mut failures = Vec::new();
+
+    for idx in 0..archive.len() {
+        let mut entry = archive.by_index(idx)?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let name = entry.name().to_string();
+        // Do not let entries such as `../../.bashrc` escape the output directory
+        let Some(relative_path) = entry.enclosed_name().map(Path::to_path_buf) else {
+            failures.push((name, String::from("unsafe path")));
+            continue;
+        };
+
+        let mut entry_data = Vec::new();
+        if let Err(e) = entry.read_to_end(&mut entry_data) {
+            failures.push((name, e.to_string()));
+            continue;
+        }
+
+        let decoded = match detect_file_kind(&entry_data) {
+            FileKind::Axml => {
+                let result = parser::parse_xml_with_warnings(Cursor::new(entry_data));
+                for warning in result.warnings.iter() {
+                    eprintln!("Warning: {name}: {warning}");
+                }
+                // Do not write partial documents
+                if result.warnings.iter().any(|warning| warning.kind == ParseWarningKind::MalformedChunk) {
+                    failures.push((name, String::from("cannot parse binary XML")));
+                    continue;
+                }
+
+                let mut xml = Vec::new();
+                let written = result.root.borrow().write_to(&mut xml);
+                match written {
+                    Ok(()) => {
+                        xml.push(b'\n');
+                        xml
+                    },
+                    Err(e) => {
+                        failures.push((name, e.to_string()));
+                        continue;
+                    },
+                }
+            },
+            FileKind::Xml => entry_data,
+            _ => continue,
+        };
+
+        let out_path = out_dir.join(relative_path);
+        let result = out_path.parent().map_or(Ok(()), fs::create_dir_all)
+                                      .and_then(|_| fs::write(&out_path, decoded));
+        match result {
+            Ok(()) => written += 1,
+            Err(e) => failures.push((name, e.to_string())),
+        }
+    }
+
+    for (name, e) in failures.iter() {
+        eprintln!("Error: {name}: {e}");
+    }
+    eprintln!("{written} XML files written to {}", out_dir.display());
+
+    Ok(!failures.is_empty())
+}
+
+/// `components` subcommand: list the components and their exposure state
+fn run_components(args: &ComponentsArgs) -> Result<(), AxmlError> {
+    let elements = parse_input(&args.input.path, None);
+    let components: Vec<_> = get_exposure_report(&elements).into_iter()
+                                                           .filter(|component| !args.exposed || component.is_exposed())
+                                                           .collect();
+    let mut stdout = io::stdout().lock();
+
+    if args.input.json {
+        let components = components.iter().map(|component| Json::object([
+            ("kind", Json::string(&component.kind)),
+            ("name", Json::string(&component.name)),
+            ("target_activity", Json::opt_string(component.target_activity.as_ref())),
+            ("exported", Json::string(state_name(component.exported))),
+            ("enabled", Json::string(state_name(component.enabled))),
+            ("application_enabled", Json::string(state_name(component.application_enabled))),
+            ("exposed", Json::Bool(component.is_exposed())),
+            ("permission", Json::opt_string(component.protected_by_permission.as_ref().map(|p| &p.name))),
+            ("read_permission", Json::opt_string(component.read_permission.as_ref().map(|p| &p.name))),
+            ("write_permission", Json::opt_string(component.write_permission.as_ref().map(|p| &p.name))),
+        ]));
+        writeln!(stdout, "{}", Json::Array(components.collect()))?;
+    } else {
+        for component in components.iter() {
+            write!(stdout, "{} {} exported={} enabled={}",
+                   component.kind,
+                   component.name,
+                   state_name(component.exported),
+                   state_name(component.enabled))?;
+            if !component.application_enabled.is_true() {
+                write!(stdout, " application_enabled={}", state_name(component.application_enabled))?;
+            }
+            if let Some(permission) = &component.protected_by_permission {
+                write!(stdout, " permission={}", permission.name)?;
+            }
+            writeln!(stdout)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `permissions` subcommand: list the requested and declared permissions
+fn run_permissions(args: &InputArgs) -> Result<(), AxmlError> {
+    let elements = parse_input(&args.path, None);
+    let requested = get_requested_permissions(&elements);
+    let declared = get_declared_permissions(&elements);
+    let mut stdout = io::stdout().lock();
+
+    if args.json {
+        let requested = requested.iter().map(|permission| Json::object([
+            ("name", Json::string(&permission.name)),
+            ("max_sdk", Json::opt_int(permission.max_sdk)),
+            ("min_sdk", Json::opt_int(permission.min_sdk_via_element)),
+        ]));
+        let declared = declared.iter().map(|permission| Json::object([
+            ("name", Json::string(&permission.name)),
+            ("protection_level", Json::string(permission.protection_level.to_string())),
+            ("permission_group", Json::opt_string(permission.permission_group.as_ref())),
+        ]));
+        writeln!(stdout, "{}", Json::object([
+            ("requested", Json::Array(requested.collect())),
+            ("declared", Json::Array(declared.collect())),
+        ]))?;
+    } else {
+        for permission in requested.iter() {
+            write!(stdout, "requested {}", permission.name)?;
+            if let Some(min_sdk) = permission.min_sdk_via_element {
+                write!(stdout, " minSdkVersion={min_sdk}")?;
+            }
+            if let Some(max_sdk) = permission.max_sdk {
+                write!(stdout, " maxSdkVersion={max_sdk}")?;
+            }
+            writeln!(stdout)?;
+        }
+        for permission in declared.iter() {
+            writeln!(stdout, "declared {} {}", permission.name, permission.protection_level)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `info` subcommand: print the package name, version, and SDK levels
+fn run_info(args: &InputArgs) -> Result<(), AxmlError> {
+    let elements = parse_input(&args.path, None);
+    let package = elements.borrow().get_attr("package").map(String::from);
+    let version = get_version_info(&elements);
+    let sdk = get_sdk_versions(&elements);
+    let mut stdout = io::stdout().lock();
+
+    if args.json {
+        writeln!(stdout, "{}", Json::object([
+            ("package", Json::opt_string(package)),
+            ("version_code", Json::opt_int(version.version_code.map(|code| code as i64))),
+            ("version_name", Json::opt_string(version.version_name)),
+            ("min_sdk", Json::opt_int(sdk.min)),
+            ("target_sdk", Json::opt_int(sdk.target)),
+            ("max_sdk", Json::opt_int(sdk.max)),
+        ]))?;
+    } else {
+        let fields = [
+            ("package", package),
+            ("versionCode", version.version_code.map(|code| code.to_string())),
+            ("versionName", version.version_name),
+            ("minSdkVersion", sdk.min.map(|level| level.to_string())),
+            ("targetSdkVersion", sdk.target.map(|level| level.to_string())),
+            ("maxSdkVersion", sdk.max.map(|level| level.to_string())),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                writeln!(stdout, "{name}: {value}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `lint` subcommand: run the security lints. Returns whether any
+/// high-severity finding was found.
+fn run_lint(args: &InputArgs) -> Result<bool, AxmlError> {
+    let elements = parse_input(&args.path, None);
+    let findings = run_lints(&elements, None);
+    let mut stdout = io::stdout().lock();
+
+    if args.json {
+        let findings = findings.iter().map(|finding| Json::object([
+            ("id", Json::string(finding.id)),
+            ("severity", Json::string(finding.severity.to_string())),
+            ("message", Json::string(&finding.message)),
+            ("path", Json::string(&finding.path)),
+        ]));
+        writeln!(stdout, "{}", Json::Array(findings.collect()))?;
+    } else {
+        for finding in findings.iter() {
+            writeln!(stdout, "{finding}")?;
+        }
+    }
+
+    Ok(findings.iter().any(|finding| finding.severity == Severity::High))
+}
+
+/// `chunks` subcommand: list the chunks of the file, or of the manifest of an
+/// APK. Returns whether a malformed chunk was found.
+fn run_chunks(args: &InputArgs) -> Result<bool, AxmlError> {
+    let data = read_raw_input(&args.path);
+    let mut stdout = io::stdout().lock();
+
+    // End offsets of the chunks containing the current one, for indentation
+    let mut parents: Vec<usize> = Vec::new();
+    let mut chunks = Vec::new();
+    let mut error = None;
+
+    for chunk in ChunkIterator::new(&data).descend(true) {
+        let (offset, header) = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                error = Some(e);
+                break;
+            },
+        };
+
+        parents.retain(|end| *end > offset);
+        let depth = parents.len();
+        parents.push(offset + header.chunk_size as usize);
+
+        let pool = match header.chunk_type {
+            ChunkType::ResStringPoolType => StringPoolHeader::from_bytes(&data[offset..]),
+            _ => None,
+        };
+
+        if args.json {
+            let mut fields = vec![
+                (String::from("offset"), Json::Int(offset as i64)),
+                (String::from("depth"), Json::Int(depth as i64)),
+                (String::from("type"), Json::string(header.chunk_type.name())),
+                (String::from("header_size"), Json::Int(header.header_size.into())),
+                (String::from("chunk_size"), Json::Int(header.chunk_size.into())),
+            ];
+            if let Some(pool) = pool {
+                fields.push((String::from("string_count"), Json::Int(pool.string_count.into())));
+                fields.push((String::from("style_count"), Json::Int(pool.style_count.into())));
+                fields.push((String::from("utf8"), Json::Bool(pool.is_utf8())));
+                fields.push((String::from("sorted"), Json::Bool(pool.is_sorted())));
+            }
+            chunks.push(Json::Object(fields));
+        } else {
+            write!(stdout, "{offset:#010x}  {:indent$}{} header_size={} chunk_size={}",
+                   "", header.chunk_type, header.header_size, header.chunk_size,
+                   indent = 2 * depth)?;
+            if let Some(pool) = pool {
+                write!(stdout, " strings={} styles={} encoding={}{}",
+                       pool.string_count,
+                       pool.style_count,
+                       if pool.is_utf8() { "utf-8" } else { "utf-16" },
+                       if pool.is_sorted() { " sorted" } else { "" })?;
+            }
+            writeln!(stdout)?;
+        }
+    }
+
+    if args.json {
+        writeln!(stdout, "{}", Json::object([
+            ("chunks", Json::Array(chunks)),
+            ("error", Json::opt_string(error.as_ref().map(|e| e.to_string()))),
+        ]))?;
+    } else if let Some(e) = &error {
+        stdout.flush()?;
+        eprintln!("Error: {e}");
+    }
+
+    Ok(error.is_some())
+}
+
+/// `strings` subcommand: list the strings of the string pools
+fn run_strings(args: &InputArgs) -> Result<(), AxmlError> {
+    let data = read_raw_input(&args.path);
+    let pools: Vec<_> = parse_chunks(&data, ChunkType::ResStringPoolType,
+                                     |cursor| StringPool::from_buff(cursor, &mut Vec::new()))
+                        .into_iter()
+                        .filter_map(|(offset, pool)| match pool {
+                            Ok(pool) => Some((offset, pool)),
+                            Err(e) => {
+                                eprintln!("Error: string pool at {offset:#x}: {e}");
+                                None
+                            },
+                        })
+                        .collect();
+    let mut stdout = io::stdout().lock();
+
+    if args.json {
+        let pools = pools.iter().map(|(offset, pool)| Json::object([
+            ("offset", Json::Int(*offset as i64)),
+            ("strings", Json::Array(pool.iter().map(|string| Json::object([
+                ("index", Json::Int(string.index as i64)),
+                ("offset", Json::Int(string.offset.into())),
+                ("value", Json::string(string.value)),
+                ("lossy", Json::Bool(string.lossy)),
+            ])).collect())),
+        ]));
+        writeln!(stdout, "{}", Json::Array(pools.collect()))?;
+    } else {
+        for (offset, pool) in pools.iter() {
+            if pools.len() > 1 {
+                writeln!(stdout, "# string pool at {offset:#x}")?;
+            }
+            for string in pool.iter() {
+                writeln!(stdout, "{}\t{:#x}\t{:?}{}",
+                         string.index,
+                         string.offset,
+                         string.value,
+                         if string.lossy { "\t(lossy)" } else { "" })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `resmap` subcommand: list the resource IDs of the resource map
+fn run_resmap(args: &InputArgs) -> Result<(), AxmlError> {
+    let data = read_raw_input(&args.path);
+    let parsed = parser::parse_xml_full(Cursor::new(&data))?;
+    for warning in parsed.warnings.iter() {
+        eprintln!("Warning: {}: {warning}", args.path.display());
+    }
+    let strings: Vec<&str> = parsed.string_pool.iter().map(|string| string.value).collect();
+    let mut stdout = io::stdout().lock();
+
+    let mut entries = Vec::new();
+    if let Some(map) = parsed.resource_map.as_ref() {
+        for (idx, id) in map.resource_ids().iter().enumerate() {
+            entries.push((idx, *id, map.get_name(idx), strings.get(idx).copied()));
+        }
+    }
+
+    if args.json {
+        let entries = entries.iter().map(|(idx, id, name, string)| Json::object([
+            ("index", Json::Int(*idx as i64)),
+            ("id", Json::Int((*id).into())),
+            ("name", Json::opt_string(*name)),
+            ("string", Json::opt_string(*string)),
+        ]));
+        writeln!(stdout, "{}", Json::Array(entries.collect()))?;
+    } else {
+        for (idx, id, name, string) in entries.iter() {
+            writeln!(stdout, "{idx}\t{id:#010x}\t{}\t{:?}", name.unwrap_or("?"), string.unwrap_or(""))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Types of components, in the order of the `scan` output
+const COMPONENT_KINDS: [&str; 5] = ["activity", "activity-alias", "service", "receiver", "provider"];
+
+/// Summary of an APK, see [`run_scan`]
+struct ApkSummary {
+    package: Option<String>,
+    version_code: Option<u64>,
+    /// Number of exposed components of each type, see [`COMPONENT_KINDS`]
+    exposed: [usize; COMPONENT_KINDS.len()],
+    dangerous_permissions: Vec<String>,
+}
+
+impl ApkSummary {
+    fn new(root: &Rc<RefCell<XmlElement>>) -> Self {
+        let mut exposed = [0; COMPONENT_KINDS.len()];
+        for component in get_exposure_report(root).iter().filter(|component| component.is_exposed()) {
+            if let Some(idx) = COMPONENT_KINDS.iter().position(|kind| *kind == component.kind) {
+                exposed[idx] += 1;
+            }
+        }
+
+        ApkSummary {
+            package: root.borrow().get_attr("package").map(String::from),
+            version_code: get_version_info(root).version_code,
+            exposed,
+            dangerous_permissions: get_dangerous_permissions(root),
+        }
+    }
+}
+
+/// Find the `*.apk` files in `dir` and its subdirectories, sorted by path
+fn find_apks(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut apks = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("apk")) {
+                apks.push(path);
+            }
+        }
+    }
+
+    apks.sort();
+    Ok(apks)
+}
+
+/// `scan` subcommand: summarize every APK in a directory. Returns whether
+/// any APK could not be parsed.
+fn run_scan(args: &ScanArgs) -> Result<bool, AxmlError> {
+    let paths = find_apks(&args.dir).unwrap_or_else(|e| fail(format!("{}: {e}", args.dir.display())));
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(args.jobs.unwrap_or(0))
+                                              .build()
+                                              .unwrap_or_else(|e| fail(e));
+    let results = pool.install(|| process_apks(&paths, ApkSummary::new));
+    let mut stdout = io::stdout().lock();
+    let mut failures = Vec::new();
+
+    for (path, result) in results {
+        let summary = match result {
+            Ok(summary) => summary,
+            Err(e) => {
+                failures.push((path, e));
+                continue;
+            },
+        };
+
+        if args.json {
+            let exposed = COMPONENT_KINDS.iter()
+                                         .zip(summary.exposed)
+                                         .map(|(kind, count)| (kind.to_string(), Json::Int(count as i64)));
+            writeln!(stdout, "{}", Json::object([
+                ("path", Json::string(path.to_string_lossy())),
+                ("package", Json::opt_string(summary.package)),
+                ("version_code", Json::opt_int(summary.version_code.map(|code| code as i64))),
+                ("exposed", Json::Object(exposed.collect())),
+                ("dangerous_permissions", Json::strings(&summary.dangerous_permissions)),
+            ]))?;
+        } else {
+            write!(stdout, "{}\t{}\t{}\t",
+                   path.display(),
+                   summary.package.as_deref().unwrap_or("?"),
+                   summary.version_code.map_or(String::from("?"), |code| code.to_string()))?;
+            let exposed: Vec<String> = COMPONENT_KINDS.iter()
+                                                      .zip(summary.exposed)
+                                                      .map(|(kind, count)| format!("{kind}={count}"))
+                                                      .collect();
+            writeln!(stdout, "exposed:{}\tdangerous:{}", exposed.join(","), summary.dangerous_permissions.join(","))?;
+        }
+    }
+
+    stdout.flush()?;
+    for (path, e) in failures.iter() {
+        eprintln!("Error: {}: {e}", path.display());
+    }
+    if !failures.is_empty() {
+        eprintln!("{} of {} APKs could not be parsed", failures.len(), paths.len());
+    }
+
+    Ok(!failures.is_empty())
+}
+
+/// Exit code of `diff` on errors
+const DIFF_ERROR: i32 = 2;
+
+/// `diff` subcommand: compare two manifests. Returns whether they differ.
+fn run_diff(args: &DiffArgs) -> Result<bool, AxmlError> {
+    let parse = |path: &Path| match read_input(path, None) {
+        Ok(axml_cursor) => parse_reporting_warnings(&path.display().to_string(), axml_cursor),
+        Err(e) => {
+            eprintln!("Error: {}: {e}", path.display());
+            process::exit(DIFF_ERROR);
+        },
+    };
+    let changes = diff_manifests(&parse(&args.old), &parse(&args.new));
+    let mut stdout = io::stdout().lock();
+
+    if args.json {
+        let changes = changes.iter().map(|change| match change {
+            ManifestChange::ElementAdded { path } => Json::object([
+                ("change", Json::string("element-added")),
+                ("path", Json::string(path)),
+            ]),
+            ManifestChange::ElementRemoved { path } => Json::object([
+                ("change", Json::string("element-removed")),
+                ("path", Json::string(path)),
+            ]),
+            ManifestChange::AttributeChanged { path, key, old, new } => Json::object([
+                ("change", Json::string("attribute-changed")),
+                ("path", Json::string(path)),
+                ("attribute", Json::string(key)),
+                ("old", Json::opt_string(old.as_ref())),
+                ("new", Json::opt_string(new.as_ref())),
+            ]),
+        });
+        writeln!(stdout, "{}", Json::Array(changes.collect()))?;
+    } else if !changes.is_empty() {
+        writeln!(stdout, "--- {}", args.old.display())?;
+        writeln!(stdout, "+++ {}", args.new.display())?;
+        write!(stdout, "{}", render_changes(&changes))?;
+    }
+
+    Ok(!changes.is_empty())
+}
+
+/// `get` subcommand: print the attribute values or elements selected by a
+/// query. Returns whether nothing matched.
+fn run_get(args: &GetArgs) -> Result<bool, AxmlError> {
+    let query = Query::parse(&args.query).unwrap_or_else(|e| fail(e));
+    let root = parse_input(&args.path, None);
+    let mut stdout = io::stdout().lock();
+
+    // Bare values, one per line, for use in shell scripts
+    let found = if query.attribute().is_some() {
+        let values = query.values(&root);
+        for value in values.iter() {
+            writeln!(stdout, "{value}")?;
+        }
+        !values.is_empty()
+    } else {
+        let elements = query.select(&root);
+        for element in elements.iter() {
+            writeln!(stdout, "{}", element.borrow().to_xml_string())?;
+        }
+        !elements.is_empty()
+    };
+
+    stdout.flush()?;
+    Ok(!found)
+}
 
 fn main() {
     // Check CLI arguments
     let args = cli::parse_args();
 
-    // Check the file type
-    let arg_path = args.get_arg_path();
-
-    // Create cursor over input file contents
-    let mut axml_cursor = create_cursor_from_apk(&arg_path);
-
-    // Parse the XML
-    let elements = parser::parse_xml(axml_cursor);
-    println!("{elements:?}");
+    let result = match &args.command {
+        Command::Xml(args) if args.all_xml => match run_all_xml(args) {
+            Ok(true) => process::exit(1),
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        },
+        Command::Xml(args) => run_xml(args),
+        Command::Components(args) => run_components(args),
+        Command::Permissions(args) => run_permissions(args),
+        Command::Info(args) => run_info(args),
+        Command::Lint(args) => match run_lint(args) {
+            Ok(true) => process::exit(1),
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        },
+        Command::Strings(args) => run_strings(args),
+        Command::Resmap(args) => run_resmap(args),
+        Command::Scan(args) => match run_scan(args) {
+            Ok(true) => process::exit(1),
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        },
+        Command::Diff(args) => match run_diff(args) {
+            Ok(true) => process::exit(1),
+            Ok(false) => Ok(()),
+            Err(e) if is_broken_pipe(&e) => process::exit(1),
+            Err(e) => {
+                eprintln!("Error: cannot write output: {e}");
+                process::exit(DIFF_ERROR);
+            },
+        },
+        Command::Get(args) => match run_get(args) {
+            Ok(true) => process::exit(1),
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        },
+        Command::Chunks(args) => match run_chunks(args) {
+            Ok(true) => process::exit(1),
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        },
+    };
 
-    // TODO: convert into actual AXML and offer
-    // the possibility to write it to a file
+    match result {
+        Ok(()) => { },
+        // The output was closed early (e.g., piped into `head`), not an error
+        Err(e) if is_broken_pipe(&e) => { },
+        Err(e) => fail(format!("cannot write output: {e}")),
+    }
 }
@@ -8,7 +8,10 @@
 
 use std::io::{
     Error,
-    Cursor,
+    ErrorKind,
+    Read,
+    Seek,
+    SeekFrom,
 };
 use byteorder::{
     LittleEndian,
@@ -16,6 +19,15 @@ use byteorder::{
 };
 use crate::chunk_types::ChunkType;
 
+/// Maximum number of entries reserved from a count declared in a chunk, see
+/// [`ChunkHeader::capacity_for`]
+const MAX_RESERVED_ENTRIES: u32 = 1 << 16;
+
+/// Error for a header that cannot be valid
+fn invalid_header(reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, reason)
+}
+
 /// Header that appears at the beginning of every chunk
 #[derive(Debug)]
 pub struct ChunkHeader {
@@ -32,34 +44,33 @@ pub struct ChunkHeader {
 
 impl ChunkHeader {
     /// Parse bytes from given buffer into a `ChunkHeader`
-    pub fn from_buff(axml_buff: &mut Cursor<Vec<u8>>, expected_type: ChunkType) -> Result<Self, Error> {
+    pub fn from_buff<R: Read>(axml_buff: &mut R, expected_type: ChunkType) -> Result<Self, Error> {
         // Minimum size, for a chunk with no data
         let minimum_size = 8;
 
         // Get chunk type
-        let chunk_type = ChunkType::parse_block_type(axml_buff)
-                        .expect("Error: cannot parse block type");
+        let chunk_type = ChunkType::parse_block_type(axml_buff, expected_type.context())?;
 
         // Check if this is indeed of the expected type
         if chunk_type != expected_type {
-            panic!("Error: unexpected XML chunk type");
+            return Err(invalid_header("unexpected XML chunk type"));
         }
 
         // Get chunk header size and total size
-        let header_size = axml_buff.read_u16::<LittleEndian>().unwrap();
-        let chunk_size = axml_buff.read_u32::<LittleEndian>().unwrap();
+        let header_size = axml_buff.read_u16::<LittleEndian>()?;
+        let chunk_size = axml_buff.read_u32::<LittleEndian>()?;
 
         // Exhaustive checks on the announced sizes
         if header_size < minimum_size {
-            panic!("Error: parsed header size is smaller than the minimum");
+            return Err(invalid_header("parsed header size is smaller than the minimum"));
         }
 
         if chunk_size < minimum_size.into() {
-            panic!("Error: parsed total size is smaller than the minimum");
+            return Err(invalid_header("parsed total size is smaller than the minimum"));
         }
 
         if chunk_size < header_size.into() {
-            panic!("Error: parsed total size is smaller than parsed header size");
+            return Err(invalid_header("parsed total size is smaller than parsed header size"));
         }
 
         Ok(ChunkHeader {
@@ -69,19 +80,57 @@ impl ChunkHeader {
         })
     }
 
-    /// Debug function
+    /// Same as [`ChunkHeader::from_buff`], but also check that the whole chunk
+    /// fits in the data, see [`ChunkHeader::check_fits`]. The size of the
+    /// data is taken from the end of the stream, and the position is left
+    /// right after the header.
+    pub fn from_buff_within<R: Read + Seek>(axml_buff: &mut R, expected_type: ChunkType) -> Result<Self, Error> {
+        let offset = axml_buff.stream_position()?;
+        let header = Self::from_buff(axml_buff, expected_type)?;
+
+        let position = axml_buff.stream_position()?;
+        let data_len = axml_buff.seek(SeekFrom::End(0))?;
+        axml_buff.seek(SeekFrom::Start(position))?;
+
+        header.check_fits(offset, data_len)?;
+        Ok(header)
+    }
+
+    /// Check that the chunk starting at `offset` ends before `data_len`, so
+    /// that a corrupted size cannot make readers seek or read past the end of
+    /// the data. Fails with `ErrorKind::UnexpectedEof` otherwise.
+    pub fn check_fits(&self, offset: u64, data_len: u64) -> Result<(), Error> {
+        let left = data_len.saturating_sub(offset);
+        if u64::from(self.chunk_size) > left {
+            return Err(Error::new(ErrorKind::UnexpectedEof,
+                                  format!("chunk size {} extends past the end of the buffer ({left} bytes left)",
+                                          self.chunk_size)));
+        }
+
+        Ok(())
+    }
+
+    /// Number of entries to reserve for `count` entries of `entry_size` bytes
+    /// declared in the chunk: at most what fits in the chunk, and at most
+    /// `MAX_RESERVED_ENTRIES`, so that a corrupted count cannot trigger a
+    /// huge allocation before the data runs out. Larger collections grow as
+    /// they are filled.
+    pub(crate) fn capacity_for(&self, count: u32, entry_size: u32) -> usize {
+        let fits = (self.chunk_size - u32::from(self.header_size)) / entry_size;
+        count.min(fits).min(MAX_RESERVED_ENTRIES) as usize
+    }
+
+    /// Log the header, at the debug level
     pub fn print(&self) {
-        println!("----- Chunk header -----");
-        println!("Header chunk_type: {:02X}", self.chunk_type);
-        println!("Header header_size: {:02X}", self.header_size);
-        println!("Chunk size: {:04X}", self.chunk_size);
-        println!("----- End chunk header -----");
+        log::debug!("chunk header: type {:?}, header size {:#x}, chunk size {:#x}",
+                    self.chunk_type, self.header_size, self.chunk_size);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
     use ChunkType;
 
     #[test]
@@ -100,40 +149,42 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Error: unexpected XML chunk type")]
     fn test_unexpected_chunk_type() {
         // Prepare a buffer with a chunk type that doesn't match the expected one
         let invalid_data = vec![2, 0, 8, 0, 16, 0, 0, 0];
         let mut cursor = Cursor::new(invalid_data);
 
         let expected_type = ChunkType::ResStringPoolType;
-        let _ = ChunkHeader::from_buff(&mut cursor, expected_type);
+        let error = ChunkHeader::from_buff(&mut cursor, expected_type).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "unexpected XML chunk type");
     }
 
     #[test]
-    #[should_panic(expected = "Error: parsed header size is smaller than the minimum")]
     fn test_invalid_header_size() {
         // Prepare a buffer with a small header size (less than 8)
         let invalid_data = vec![1, 0, 4, 0, 16, 0, 0, 0];
         let mut cursor = Cursor::new(invalid_data);
 
         let expected_type = ChunkType::ResStringPoolType;
-        let _ = ChunkHeader::from_buff(&mut cursor, expected_type);
+        let error = ChunkHeader::from_buff(&mut cursor, expected_type).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "parsed header size is smaller than the minimum");
     }
 
     #[test]
-    #[should_panic(expected = "Error: parsed total size is smaller than the minimum")]
     fn test_invalid_chunk_size() {
         // Prepare a buffer with an invalid chunk size (less than 8)
         let invalid_data = vec![1, 0, 8, 0, 4, 0, 0, 0];
         let mut cursor = Cursor::new(invalid_data);
 
         let expected_type = ChunkType::ResStringPoolType;
-        let _ = ChunkHeader::from_buff(&mut cursor, expected_type);
+        let error = ChunkHeader::from_buff(&mut cursor, expected_type).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "parsed total size is smaller than the minimum");
     }
 
     #[test]
-    #[should_panic(expected = "Error: parsed total size is smaller than parsed header size")]
     fn test_invalid_chunk_size_smaller_than_header() {
         // Prepare a buffer where chunk size is smaller than header size
         // Note: the header size is constant and is always 8 bytes which is
@@ -144,6 +195,50 @@ mod tests {
         let mut cursor = Cursor::new(invalid_data);
 
         let expected_type = ChunkType::ResStringPoolType;
-        let _ = ChunkHeader::from_buff(&mut cursor, expected_type);
+        let error = ChunkHeader::from_buff(&mut cursor, expected_type).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "parsed total size is smaller than parsed header size");
+    }
+
+    #[test]
+    fn test_capacity_for() {
+        let header = ChunkHeader {
+            chunk_type: ChunkType::ResStringPoolType,
+            header_size: 28,
+            chunk_size: 28 + 4 * 10,
+        };
+        assert_eq!(header.capacity_for(3, 4), 3);
+        // Count larger than the chunk
+        assert_eq!(header.capacity_for(u32::MAX, 4), 10);
+
+        let header = ChunkHeader { chunk_size: u32::MAX, ..header };
+        assert_eq!(header.capacity_for(u32::MAX, 4), 1 << 16);
+    }
+
+    #[test]
+    fn test_chunk_within_data() {
+        // String pool header followed by 8 bytes of data, 16 bytes in total
+        let mut data = vec![1, 0, 8, 0, 16, 0, 0, 0];
+        data.extend([0; 8]);
+
+        let mut cursor = Cursor::new(&data);
+        let header = ChunkHeader::from_buff_within(&mut cursor, ChunkType::ResStringPoolType).unwrap();
+        assert_eq!(header.chunk_size, 16);
+        assert_eq!(cursor.position(), 8);
+
+        for chunk_size in [17, 16 + (1 << 20)] {
+            data[4..8].copy_from_slice(&(chunk_size as u32).to_le_bytes());
+            let error = ChunkHeader::from_buff_within(&mut Cursor::new(&data), ChunkType::ResStringPoolType)
+                .unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+            assert_eq!(error.to_string(),
+                       format!("chunk size {chunk_size} extends past the end of the buffer (16 bytes left)"));
+        }
+
+        // The size of the data is counted from the start of the chunk
+        let header = ChunkHeader { chunk_type: ChunkType::ResStringPoolType, header_size: 8, chunk_size: 16 };
+        assert!(header.check_fits(4, 20).is_ok());
+        assert!(header.check_fits(5, 20).is_err());
+        assert!(header.check_fits(30, 20).is_err());
     }
 }
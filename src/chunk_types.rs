@@ -1,7 +1,10 @@
 use std::fmt;
 use std::io::{
     Error,
-    Cursor,
+    ErrorKind,
+    Read,
+    Seek,
+    SeekFrom,
 };
 use byteorder::{
     LittleEndian,
@@ -9,102 +12,273 @@ use byteorder::{
 };
 
 /* Type identifiers for chunks. Only includes the ones related to XML */
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
 pub enum ChunkType {
-    ResNullType                 = 0x0000,
-    ResStringPoolType           = 0x0001,
-    ResTableType                = 0x0002,
-    ResXmlType                  = 0x0003,
+    ResNullType,
+    ResStringPoolType,
+    ResTableType,
+    ResXmlType,
 
     /* Chunk types in RES_XML_Type */
-    // TODO: for some reason this chunk has the same value has ResXmlStartNamespaceType which is
-    // annoying. Need to figure out a way to deal with this. In the meantime, ignore it.
-    // ResXmlFirstChunkType     = 0x0100,
-    ResXmlStartNamespaceType    = 0x0100,
-    ResXmlEndNamespaceType      = 0x0101,
-    ResXmlStartElementType      = 0x0102,
-    ResXmlEndElementType        = 0x0103,
-    ResXmlCDataType             = 0x0104,
-    ResXmlLastChunkType         = 0x017f,
+    /* Same value as ResXmlStartNamespaceType: it is only the start of the
+     * range of XML chunk types, and only appears in resource tables (see
+     * ChunkContext) */
+    ResXmlFirstChunkType,
+    ResXmlStartNamespaceType,
+    ResXmlEndNamespaceType,
+    ResXmlStartElementType,
+    ResXmlEndElementType,
+    ResXmlCDataType,
+    ResXmlLastChunkType,
 
     /* This contains a uint32_t array mapping strings in the string
      * pool back to resource identifiers.  It is optional. */
-    ResXmlResourceMapType       = 0x0180,
+    ResXmlResourceMapType,
 
     /* Chunk types in RES_TABLE_Type */
-    ResTablePackageType         = 0x0200,
-    ResTableTypeType            = 0x0201,
-    ResTableTypeSpecType        = 0x0202,
-    ResTableLibraryType         = 0x0203
+    ResTablePackageType,
+    ResTableTypeType,
+    ResTableTypeSpecType,
+    ResTableLibraryType
 }
 
+/// Kind of stream a chunk is read from, which gives the meaning of the raw
+/// chunk types shared by several chunk types
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChunkContext {
+    /// Inside a binary XML document (`RES_XML_TYPE`), or at the top level of
+    /// a file
+    Xml,
+    /// Inside a resource table (`RES_TABLE_TYPE`) or one of its packages
+    Table,
+}
+
+/// Error for a raw chunk type that does not match any [`ChunkType`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnknownChunkType(pub u16);
+
+impl fmt::Display for UnknownChunkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown chunk type {:#06x}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownChunkType {}
+
 impl ChunkType {
-    pub fn parse_block_type(buff: &mut Cursor<Vec<u8>>) -> Result<Self, Error> {
-        let raw_block_type = buff.read_u16::<LittleEndian>();
-        let raw_block_type = match raw_block_type {
-            Ok(block) => block,
-            Err(e) => return Err(e),
-        };
-
-        let block_type = match raw_block_type {
-            0x0000 => ChunkType::ResNullType,
-            0x0001 => ChunkType::ResStringPoolType,
-            0x0002 => ChunkType::ResTableType,
-            0x0003 => ChunkType::ResXmlType,
-
-            /* Chunk types in RES_XML_TYPE */
-            // TODO: see comment above.
-            // 0x0100 => ChunkType::ResXmlFirstChunkType,
-            0x0100 => ChunkType::ResXmlStartNamespaceType,
-            0x0101 => ChunkType::ResXmlEndNamespaceType,
-            0x0102 => ChunkType::ResXmlStartElementType,
-            0x0103 => ChunkType::ResXmlEndElementType,
-            0x0104 => ChunkType::ResXmlCDataType,
-            0x017f => ChunkType::ResXmlLastChunkType,
-
-            /* This contains a uint32_t array mapping strings in the string
-             * pool back to resource identifiers. It is optional. */
-            0x0180 => ChunkType::ResXmlResourceMapType,
-
-            /* Chunk types in RES_TABLE_TYPE */
-            0x0200 => ChunkType::ResTablePackageType,
-            0x0201 => ChunkType::ResTableTypeType,
-            0x0202 => ChunkType::ResTableTypeSpecType,
-            0x0203 => ChunkType::ResTableLibraryType,
-
-            /* If we find an unknown type, we stop and panic */
-            _ => panic!("Error: unknown block type {:02X}", raw_block_type)
-        };
-
-        Ok(block_type)
+    /// All the chunk types, in the order of their raw values
+    pub const ALL: [ChunkType; 16] = [
+        ChunkType::ResNullType,
+        ChunkType::ResStringPoolType,
+        ChunkType::ResTableType,
+        ChunkType::ResXmlType,
+        ChunkType::ResXmlFirstChunkType,
+        ChunkType::ResXmlStartNamespaceType,
+        ChunkType::ResXmlEndNamespaceType,
+        ChunkType::ResXmlStartElementType,
+        ChunkType::ResXmlEndElementType,
+        ChunkType::ResXmlCDataType,
+        ChunkType::ResXmlLastChunkType,
+        ChunkType::ResXmlResourceMapType,
+        ChunkType::ResTablePackageType,
+        ChunkType::ResTableTypeType,
+        ChunkType::ResTableTypeSpecType,
+        ChunkType::ResTableLibraryType,
+    ];
+
+    pub fn parse_block_type<R: Read>(buff: &mut R, context: ChunkContext) -> Result<Self, Error> {
+        let raw_block_type = buff.read_u16::<LittleEndian>()?;
+
+        /* If we find an unknown type, we stop */
+        ChunkType::from_raw(raw_block_type, context).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidData, UnknownChunkType(raw_block_type))
+        })
+    }
+
+    /// Read the raw type of the chunk at the current position, without
+    /// consuming it. Returns the offset of the chunk along with its type, so
+    /// that the chunk parsers can read the whole chunk, header included.
+    pub fn peek_raw_block_type<R: Read + Seek>(buff: &mut R) -> Result<(u64, u16), Error> {
+        let offset = buff.stream_position()?;
+        let raw_block_type = buff.read_u16::<LittleEndian>()?;
+        buff.seek(SeekFrom::Start(offset))?;
+
+        Ok((offset, raw_block_type))
+    }
+
+    /// Same as [`ChunkType::peek_raw_block_type`], but fail on unknown types
+    /// like [`ChunkType::parse_block_type`]
+    pub fn peek_block_type<R: Read + Seek>(buff: &mut R, context: ChunkContext) -> Result<(u64, Self), Error> {
+        let (offset, raw_block_type) = ChunkType::peek_raw_block_type(buff)?;
+        let block_type = ChunkType::from_raw(raw_block_type, context).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidData, UnknownChunkType(raw_block_type))
+        })?;
+
+        Ok((offset, block_type))
+    }
+
+    /// Get the chunk type from its raw value in the given context, or `None`
+    /// if it is unknown
+    pub fn from_raw(raw_block_type: u16, context: ChunkContext) -> Option<Self> {
+        // Several types may share a value, in which case the one of the
+        // context wins
+        ChunkType::ALL.into_iter()
+                      .filter(|block_type| block_type.raw() == raw_block_type)
+                      .min_by_key(|block_type| block_type.context() != context)
+    }
+
+    /// Raw value of the chunk type
+    pub fn raw(&self) -> u16 {
+        match self {
+            ChunkType::ResNullType => 0x0000,
+            ChunkType::ResStringPoolType => 0x0001,
+            ChunkType::ResTableType => 0x0002,
+            ChunkType::ResXmlType => 0x0003,
+
+            ChunkType::ResXmlFirstChunkType => 0x0100,
+            ChunkType::ResXmlStartNamespaceType => 0x0100,
+            ChunkType::ResXmlEndNamespaceType => 0x0101,
+            ChunkType::ResXmlStartElementType => 0x0102,
+            ChunkType::ResXmlEndElementType => 0x0103,
+            ChunkType::ResXmlCDataType => 0x0104,
+            ChunkType::ResXmlLastChunkType => 0x017f,
+
+            ChunkType::ResXmlResourceMapType => 0x0180,
+
+            ChunkType::ResTablePackageType => 0x0200,
+            ChunkType::ResTableTypeType => 0x0201,
+            ChunkType::ResTableTypeSpecType => 0x0202,
+            ChunkType::ResTableLibraryType => 0x0203,
+        }
+    }
+
+    /// Context in which the chunk type is read
+    pub fn context(&self) -> ChunkContext {
+        match self {
+            ChunkType::ResXmlFirstChunkType |
+            ChunkType::ResTablePackageType |
+            ChunkType::ResTableTypeType |
+            ChunkType::ResTableTypeSpecType |
+            ChunkType::ResTableLibraryType => ChunkContext::Table,
+            _ => ChunkContext::Xml,
+        }
+    }
+
+    /// Name of the chunk type, as in the Android sources (e.g., `RES_XML_TYPE`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChunkType::ResNullType => "RES_NULL_TYPE",
+            ChunkType::ResStringPoolType => "RES_STRING_POOL_TYPE",
+            ChunkType::ResTableType => "RES_TABLE_TYPE",
+            ChunkType::ResXmlType => "RES_XML_TYPE",
+            ChunkType::ResXmlFirstChunkType => "RES_XML_FIRST_CHUNK_TYPE",
+            ChunkType::ResXmlStartNamespaceType => "RES_XML_START_NAMESPACE_TYPE",
+            ChunkType::ResXmlEndNamespaceType => "RES_XML_END_NAMESPACE_TYPE",
+            ChunkType::ResXmlStartElementType => "RES_XML_START_ELEMENT_TYPE",
+            ChunkType::ResXmlEndElementType => "RES_XML_END_ELEMENT_TYPE",
+            ChunkType::ResXmlCDataType => "RES_XML_CDATA_TYPE",
+            ChunkType::ResXmlLastChunkType => "RES_XML_LAST_CHUNK_TYPE",
+            ChunkType::ResXmlResourceMapType => "RES_XML_RESOURCE_MAP_TYPE",
+            ChunkType::ResTablePackageType => "RES_TABLE_PACKAGE_TYPE",
+            ChunkType::ResTableTypeType => "RES_TABLE_TYPE_TYPE",
+            ChunkType::ResTableTypeSpecType => "RES_TABLE_TYPE_SPEC_TYPE",
+            ChunkType::ResTableLibraryType => "RES_TABLE_LIBRARY_TYPE",
+        }
+    }
+}
+
+/// Get the chunk type from its raw value, in the context of a binary XML
+/// document (see [`ChunkType::from_raw`])
+impl TryFrom<u16> for ChunkType {
+    type Error = UnknownChunkType;
+
+    fn try_from(raw_block_type: u16) -> Result<Self, Self::Error> {
+        ChunkType::from_raw(raw_block_type, ChunkContext::Xml).ok_or(UnknownChunkType(raw_block_type))
+    }
+}
+
+impl From<ChunkType> for u16 {
+    fn from(block_type: ChunkType) -> Self {
+        block_type.raw()
+    }
+}
+
+impl fmt::Display for ChunkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
     }
 }
 
 /* Implementation of the UpperHex trait for ChunkType */
 impl fmt::UpperHex for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            ChunkType::ResNullType => write!(f, "{:X}", 0x0000),
-            ChunkType::ResStringPoolType => write!(f, "{:X}", 0x0001),
-            ChunkType::ResTableType => write!(f, "{:X}", 0x0002),
-            ChunkType::ResXmlType => write!(f, "{:X}", 0x0003),
-
-            // TODO: see comment above.
-            // ChunkType::ResXmlFirstChunkType => write!(f, "{:X}", 0x0100),
-            ChunkType::ResXmlStartNamespaceType => write!(f, "{:X}", 0x0100),
-            ChunkType::ResXmlEndNamespaceType => write!(f, "{:X}", 0x0101),
-            ChunkType::ResXmlStartElementType => write!(f, "{:X}", 0x0102),
-            ChunkType::ResXmlEndElementType => write!(f, "{:X}", 0x0103),
-            ChunkType::ResXmlCDataType => write!(f, "{:X}", 0x0104),
-            ChunkType::ResXmlLastChunkType => write!(f, "{:X}", 0x017f),
-
-            ChunkType::ResXmlResourceMapType => write!(f, "{:X}", 0x0180),
-
-            ChunkType::ResTablePackageType => write!(f, "{:X}", 0x0200),
-            ChunkType::ResTableTypeType => write!(f, "{:X}", 0x0201),
-            ChunkType::ResTableTypeSpecType => write!(f, "{:X}", 0x0202),
-            ChunkType::ResTableLibraryType => write!(f, "{:X}", 0x0203),
-        }?;
-        Ok(())
+        fmt::UpperHex::fmt(&u16::from(*self), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_peek_block_type() {
+        // Start element chunk at the very beginning of the buffer
+        let mut cursor = Cursor::new(vec![0x02, 0x01, 0x10, 0x00, 0x24, 0x00, 0x00, 0x00]);
+        assert_eq!(ChunkType::peek_block_type(&mut cursor, ChunkContext::Xml).unwrap(), (0, ChunkType::ResXmlStartElementType));
+        assert_eq!(cursor.position(), 0);
+
+        cursor.set_position(4);
+        assert_eq!(ChunkType::peek_raw_block_type(&mut cursor).unwrap(), (4, 0x0024));
+        assert_eq!(ChunkType::peek_block_type(&mut cursor, ChunkContext::Xml).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(cursor.position(), 4);
+
+        cursor.set_position(7);
+        assert!(ChunkType::peek_raw_block_type(&mut cursor).is_err());
+    }
+
+    #[test]
+    fn test_from_raw_context() {
+        // 0x0100 depends on the context
+        assert_eq!(ChunkType::from_raw(0x0100, ChunkContext::Xml), Some(ChunkType::ResXmlStartNamespaceType));
+        assert_eq!(ChunkType::from_raw(0x0100, ChunkContext::Table), Some(ChunkType::ResXmlFirstChunkType));
+        assert_eq!(ChunkType::ResXmlStartNamespaceType.raw(), ChunkType::ResXmlFirstChunkType.raw());
+
+        // The other types do not
+        for context in [ChunkContext::Xml, ChunkContext::Table] {
+            assert_eq!(ChunkType::from_raw(0x0101, context), Some(ChunkType::ResXmlEndNamespaceType));
+            assert_eq!(ChunkType::from_raw(0x0200, context), Some(ChunkType::ResTablePackageType));
+            assert_eq!(ChunkType::from_raw(0x0105, context), None);
+        }
+
+        // Round trip through the raw value, in the context of the type
+        for raw in [0x0000, 0x0001, 0x0002, 0x0003, 0x0100, 0x0101, 0x0102, 0x0103, 0x0104,
+                    0x017f, 0x0180, 0x0200, 0x0201, 0x0202, 0x0203] {
+            for context in [ChunkContext::Xml, ChunkContext::Table] {
+                let chunk_type = ChunkType::from_raw(raw, context).unwrap();
+                assert_eq!(chunk_type.raw(), raw);
+                assert_eq!(ChunkType::from_raw(raw, chunk_type.context()), Some(chunk_type));
+            }
+        }
+        assert_eq!(format!("{:X}", ChunkType::ResXmlFirstChunkType), "100");
+    }
+
+    #[test]
+    fn test_conversions() {
+        for block_type in ChunkType::ALL {
+            let raw = u16::from(block_type);
+            assert_eq!(ChunkType::from_raw(raw, block_type.context()), Some(block_type));
+            if block_type != ChunkType::ResXmlFirstChunkType {
+                assert_eq!(ChunkType::try_from(raw), Ok(block_type));
+            }
+        }
+        assert_eq!(ChunkType::try_from(0x0100), Ok(ChunkType::ResXmlStartNamespaceType));
+        assert_eq!(ChunkType::try_from(0x0042), Err(UnknownChunkType(0x0042)));
+        assert_eq!(UnknownChunkType(0x0042).to_string(), "unknown chunk type 0x0042");
+
+        assert_eq!(ChunkType::ResXmlStartElementType.to_string(), "RES_XML_START_ELEMENT_TYPE");
+        assert_eq!(format!("{:X}", ChunkType::ResXmlResourceMapType), "180");
+        assert_eq!(format!("{:#06X}", ChunkType::ResStringPoolType), "0x0001");
     }
 }
@@ -0,0 +1,354 @@
+//! Chunk walking
+//!
+//! Low-level iteration over the chunks of a binary XML file or of a resource
+//! table, without interpreting their contents. This is mostly useful to
+//! understand why a file cannot be parsed. The XML reader walks chunks with
+//! the same crate-internal walker, so both agree on what a malformed chunk is.
+
+use std::io::{
+    Cursor,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use byteorder::{ ByteOrder, LittleEndian };
+
+use crate::chunk_header::ChunkHeader;
+use crate::chunk_types::{ ChunkContext, ChunkType, UnknownChunkType };
+use crate::error::AxmlError;
+
+/// Size of a chunk header without any type-specific field
+const MINIMUM_HEADER_SIZE: u64 = 8;
+
+/// Chunks that contain other chunks after their header
+const CONTAINERS: [ChunkType; 3] = [
+    ChunkType::ResXmlType,
+    ChunkType::ResTableType,
+    ChunkType::ResTablePackageType,
+];
+
+/// Chunk found by a [`ChunkWalker`], whose type may be unknown
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RawChunk {
+    pub offset: u64,
+    pub raw_type: u16,
+    /// Type of the chunk, `None` if the raw type is unknown in its context
+    pub chunk_type: Option<ChunkType>,
+    pub header_size: u16,
+    pub chunk_size: u32,
+}
+
+/// Chunk header that cannot be valid, after which the next chunks cannot be
+/// found
+#[derive(Clone, Debug)]
+pub(crate) struct ChunkError {
+    pub offset: u64,
+    pub reason: String,
+}
+
+impl From<ChunkError> for AxmlError {
+    fn from(error: ChunkError) -> Self {
+        AxmlError::MalformedChunk { offset: error.offset as usize, reason: error.reason }
+    }
+}
+
+/// Walk over the chunk headers of seekable data. This is where chunks are
+/// delimited for the whole crate: [`ChunkIterator`] and the streaming reader
+/// (and so all the parsers) are built on it.
+///
+/// The position of the next chunk only depends on the sizes announced by the
+/// headers, so the caller can read the body of a chunk (or not) without
+/// affecting the walk. Sizes are checked before they are used: a chunk must
+/// fit in its parent (or in the data) and be at least as large as its header,
+/// so that the walk always moves forward.
+pub(crate) struct ChunkWalker {
+    offset: u64,
+    /// Types of the chunks whose children are walked too
+    containers: &'static [ChunkType],
+    /// Whether zero chunk types are 2 bytes of padding rather than chunks
+    skip_padding: bool,
+    clamp_containers: bool,
+    /// End offsets of the containers the walker is in, along with the
+    /// context of their children
+    parents: Vec<(u64, ChunkContext)>,
+    data_len: Option<u64>,
+    failed: bool,
+}
+
+impl ChunkWalker {
+    pub(crate) fn new(containers: &'static [ChunkType], skip_padding: bool) -> Self {
+        ChunkWalker {
+            offset: 0,
+            containers,
+            skip_padding,
+            clamp_containers: false,
+            parents: Vec::new(),
+            data_len: None,
+            failed: false,
+        }
+    }
+
+    /// Walk the children of containers that extend past the end of their
+    /// parent up to there, instead of failing, e.g. to read what is left of
+    /// a truncated document
+    pub(crate) fn clamp_containers(mut self, clamp: bool) -> Self {
+        self.clamp_containers = clamp;
+        self
+    }
+
+    /// Offset of the next chunk header to read
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Stop the walk, e.g. after a chunk the caller cannot handle
+    pub(crate) fn stop(&mut self) {
+        self.failed = true;
+    }
+
+    fn error(&mut self, reason: String) -> Option<Result<RawChunk, ChunkError>> {
+        self.failed = true;
+        Some(Err(ChunkError { offset: self.offset, reason }))
+    }
+
+    /// Header of the next chunk of `source`, in document order, or `None` at
+    /// the end of the data. After an error, the walk is over.
+    pub(crate) fn next_chunk<R: Read + Seek>(&mut self, source: &mut R) -> Option<Result<RawChunk, ChunkError>> {
+        if self.failed {
+            return None;
+        }
+        let data_len = match self.data_len {
+            Some(data_len) => data_len,
+            None => match source.seek(SeekFrom::End(0)) {
+                Ok(data_len) => *self.data_len.insert(data_len),
+                Err(e) => return self.error(format!("cannot get the size of the data: {e}")),
+            },
+        };
+
+        loop {
+            // Leave the containers whose children were all returned
+            while self.parents.last().is_some_and(|&(end, _)| end == self.offset) {
+                self.parents.pop();
+            }
+
+            let (end, context) = self.parents.last().copied().unwrap_or((data_len, ChunkContext::Xml));
+            if self.offset >= end {
+                return None;
+            }
+
+            let mut header = [0; MINIMUM_HEADER_SIZE as usize];
+            let available = (end - self.offset).min(MINIMUM_HEADER_SIZE) as usize;
+            let read = source.seek(SeekFrom::Start(self.offset))
+                             .and_then(|_| source.read_exact(&mut header[..available]));
+            if let Err(e) = read {
+                return self.error(format!("cannot read the chunk header: {e}"));
+            }
+
+            let raw_type = LittleEndian::read_u16(&header);
+            if self.skip_padding && available >= 2 && raw_type == 0 {
+                self.offset += 2;
+                continue;
+            }
+            if available < MINIMUM_HEADER_SIZE as usize {
+                return self.error(format!("truncated chunk header ({available} bytes left)"));
+            }
+
+            let header_size = LittleEndian::read_u16(&header[2..]);
+            let chunk_size = LittleEndian::read_u32(&header[4..]);
+            if u64::from(header_size) < MINIMUM_HEADER_SIZE {
+                return self.error(format!("header size {header_size} is smaller than the minimum"));
+            }
+            if chunk_size < u32::from(header_size) {
+                return self.error(format!("chunk size {chunk_size} is smaller than the header size {header_size}"));
+            }
+            let chunk_type = ChunkType::from_raw(raw_type, context);
+            let container = chunk_type.filter(|chunk_type| self.containers.contains(chunk_type));
+            let clamped = u64::from(chunk_size) > end - self.offset;
+            if clamped && !(self.clamp_containers && container.is_some() && u64::from(header_size) <= end - self.offset) {
+                return self.error(format!("chunk size {chunk_size} extends past the end of the {} ({} bytes left)",
+                                          if self.parents.is_empty() { "buffer" } else { "parent chunk" },
+                                          end - self.offset));
+            }
+
+            let chunk = RawChunk {
+                offset: self.offset,
+                raw_type,
+                chunk_type,
+                header_size,
+                chunk_size,
+            };
+            match container {
+                Some(container) => {
+                    let context = match container {
+                        ChunkType::ResXmlType => ChunkContext::Xml,
+                        _ => ChunkContext::Table,
+                    };
+                    self.parents.push((end.min(self.offset + u64::from(chunk_size)), context));
+                    self.offset += u64::from(header_size);
+                },
+                None => self.offset += u64::from(chunk_size),
+            }
+
+            return Some(Ok(chunk));
+        }
+    }
+}
+
+/// Iterator over the `(offset, header)` pairs of the chunks in a buffer, in
+/// order.
+///
+/// By default, only the top-level chunks are returned. With
+/// [`ChunkIterator::descend`], the chunks contained in a `RES_XML_TYPE`,
+/// `RES_TABLE_TYPE`, or `RES_TABLE_PACKAGE_TYPE` chunk are returned right
+/// after it.
+///
+/// Iteration stops after the first malformed chunk header (unknown type,
+/// inconsistent sizes, or chunk extending past the end of its parent), which
+/// is returned as an [`AxmlError::MalformedChunk`].
+pub struct ChunkIterator<'a> {
+    data: Cursor<&'a [u8]>,
+    walker: ChunkWalker,
+}
+
+impl<'a> ChunkIterator<'a> {
+    /// Iterate over the top-level chunks of `data`
+    pub fn new(data: &'a [u8]) -> Self {
+        ChunkIterator {
+            data: Cursor::new(data),
+            walker: ChunkWalker::new(&[], false),
+        }
+    }
+
+    /// Also return the chunks contained in other chunks
+    pub fn descend(mut self, descend: bool) -> Self {
+        self.walker.containers = if descend { &CONTAINERS } else { &[] };
+        self
+    }
+}
+
+impl Iterator for ChunkIterator<'_> {
+    type Item = Result<(usize, ChunkHeader), AxmlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = match self.walker.next_chunk(&mut self.data)? {
+            Ok(chunk) => chunk,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        let Some(chunk_type) = chunk.chunk_type else {
+            self.walker.stop();
+            return Some(Err(AxmlError::MalformedChunk {
+                offset: chunk.offset as usize,
+                reason: UnknownChunkType(chunk.raw_type).to_string(),
+            }));
+        };
+
+        Some(Ok((chunk.offset as usize, ChunkHeader {
+            chunk_type,
+            header_size: chunk.header_size,
+            chunk_size: chunk.chunk_size,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn chunk_types(data: &[u8], descend: bool) -> Vec<(usize, ChunkType)> {
+        ChunkIterator::new(data).descend(descend)
+                                .map(|chunk| chunk.map(|(offset, header)| (offset, header.chunk_type)).unwrap())
+                                .collect()
+    }
+
+    #[test]
+    fn test_chunk_iterator() {
+        let data = AxmlBuilder::new()
+            .element("manifest", vec![Attr::string("package", "com.example")])
+            .build();
+
+        assert_eq!(chunk_types(&data, false), vec![(0, ChunkType::ResXmlType)]);
+
+        let chunks = chunk_types(&data, true);
+        let types: Vec<ChunkType> = chunks.iter().map(|(_, chunk_type)| *chunk_type).collect();
+        assert_eq!(types, vec![ChunkType::ResXmlType,
+                               ChunkType::ResStringPoolType,
+                               ChunkType::ResXmlStartElementType,
+                               ChunkType::ResXmlEndElementType]);
+        assert_eq!(chunks[1].0, 8);
+    }
+
+    /// Chunk of type `raw_type` with an empty header of `header_size` bytes
+    fn chunk(raw_type: u16, header_size: u16, body: &[u8]) -> Vec<u8> {
+        let mut chunk = raw_type.to_le_bytes().to_vec();
+        chunk.extend(header_size.to_le_bytes());
+        chunk.extend((u32::from(header_size) + body.len() as u32).to_le_bytes());
+        chunk.resize(usize::from(header_size), 0);
+        chunk.extend(body);
+        chunk
+    }
+
+    #[test]
+    fn test_nested_table_chunks() {
+        // Table > (string pool, package > (type spec, type))
+        let package = [chunk(0x0202, 16, &[]), chunk(0x0201, 20, &[0; 4])].concat();
+        let body = [chunk(0x0001, 28, &[]), chunk(0x0200, 288, &package)].concat();
+        let data = chunk(0x0002, 12, &body);
+
+        assert_eq!(chunk_types(&data, false), vec![(0, ChunkType::ResTableType)]);
+        assert_eq!(chunk_types(&data, true), vec![(0, ChunkType::ResTableType),
+                                                  (12, ChunkType::ResStringPoolType),
+                                                  (40, ChunkType::ResTablePackageType),
+                                                  (328, ChunkType::ResTableTypeSpecType),
+                                                  (344, ChunkType::ResTableTypeType)]);
+
+        // Two top-level tables
+        let data = [data.clone(), data].concat();
+        assert_eq!(chunk_types(&data, false), vec![(0, ChunkType::ResTableType), (368, ChunkType::ResTableType)]);
+    }
+
+    #[test]
+    fn test_chunk_contexts() {
+        // 0x0100 is a namespace in an XML document...
+        let data = AxmlBuilder::new().android().element("manifest", vec![]).build();
+        assert!(chunk_types(&data, true).iter().any(|&(_, chunk_type)| chunk_type == ChunkType::ResXmlStartNamespaceType));
+
+        // ... and the first XML chunk type in a resource table
+        let mut data = vec![0x02, 0x00, 0x0c, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        data.extend([0x00, 0x01, 0x08, 0x00, 0x08, 0x00, 0x00, 0x00]);
+        assert_eq!(chunk_types(&data, true), vec![(0, ChunkType::ResTableType),
+                                                  (12, ChunkType::ResXmlFirstChunkType)]);
+    }
+
+    #[test]
+    fn test_malformed_chunks() {
+        let mut data = AxmlBuilder::new().element("manifest", vec![]).build();
+
+        // Unknown type of the string pool chunk: the XML chunk is returned,
+        // then the error, then nothing
+        data[8] = 0x42;
+        let mut chunks = ChunkIterator::new(&data).descend(true);
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(matches!(chunks.next(), Some(Err(AxmlError::MalformedChunk { offset: 8, .. }))));
+        assert!(chunks.next().is_none());
+
+        // Chunk larger than the buffer
+        let mut data = AxmlBuilder::new().element("manifest", vec![]).build();
+        let size = data.len() as u32 + 1;
+        data[4..8].copy_from_slice(&size.to_le_bytes());
+        let chunks: Vec<_> = ChunkIterator::new(&data).collect();
+        assert!(matches!(chunks[..], [Err(AxmlError::MalformedChunk { offset: 0, .. })]));
+
+        // Zero-sized chunk, which would otherwise be read forever
+        let mut data = AxmlBuilder::new().element("manifest", vec![]).build();
+        data[12..16].copy_from_slice(&[0; 4]);
+        let chunks: Vec<_> = ChunkIterator::new(&data).descend(true).collect();
+        assert!(matches!(chunks[..], [Ok(_), Err(AxmlError::MalformedChunk { offset: 8, .. })]));
+
+        // Truncated header
+        let chunks: Vec<_> = ChunkIterator::new(&[0x03, 0x00, 0x08]).collect();
+        assert!(matches!(chunks[..], [Err(AxmlError::MalformedChunk { offset: 0, .. })]));
+    }
+}
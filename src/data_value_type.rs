@@ -1,7 +1,11 @@
+use std::fmt;
+
 /// Data value types
 ///
 /// Note: we ignore `TypeFirstInt`, `TypeFirstColorInt`, and `TypeLastColorInt` which hold the same values
 /// as actual data types (respectively `TypeIntDec`, `TypeIntColorArgb8`, and `TypeIntColorRgb4`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum DataValueType {
     /// The 'data' is either 0 or 1, specifying this resource is either undefined or empty,
     ///respectively
@@ -46,27 +50,116 @@ pub enum DataValueType {
     TypeIntColorRgb4		= 0x1f,
 }
 
+/// Error for a raw data value type that does not match any [`DataValueType`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnknownDataValueType(pub u8);
+
+impl fmt::Display for UnknownDataValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown data value type {:#04x}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownDataValueType {}
+
 impl DataValueType {
-    /// Convert `u8` into a `DataValueType` 
+    /// All the data value types, in the order of their raw values
+    pub const ALL: [DataValueType; 16] = [
+        DataValueType::TypeNull,
+        DataValueType::TypeReference,
+        DataValueType::TypeAttribute,
+        DataValueType::TypeString,
+        DataValueType::TypeFloat,
+        DataValueType::TypeDimension,
+        DataValueType::TypeFraction,
+        DataValueType::TypeDynamicReference,
+        DataValueType::TypeDynamicAttribute,
+        DataValueType::TypeIntDec,
+        DataValueType::TypeIntHex,
+        DataValueType::TypeIntBoolean,
+        DataValueType::TypeIntColorArgb8,
+        DataValueType::TypeIntColorRgb8,
+        DataValueType::TypeIntColorArgb4,
+        DataValueType::TypeIntColorRgb4,
+    ];
+
+    /// Convert `u8` into a `DataValueType`
+    ///
+    /// Panics if the type is unknown, see [`DataValueType::try_from_val`]
     pub fn from_val(value: u8) -> Self {
-        match value {
-            0x00 => DataValueType::TypeNull,
-            0x01 => DataValueType::TypeReference,
-            0x02 => DataValueType::TypeAttribute,
-            0x03 => DataValueType::TypeString,
-            0x04 => DataValueType::TypeFloat,
-            0x05 => DataValueType::TypeDimension,
-            0x06 => DataValueType::TypeFraction,
-            0x07 => DataValueType::TypeDynamicReference,
-            0x08 => DataValueType::TypeDynamicAttribute,
-            0x10 => DataValueType::TypeIntDec,
-            0x11 => DataValueType::TypeIntHex,
-            0x12 => DataValueType::TypeIntBoolean,
-            0x1c => DataValueType::TypeIntColorArgb8,
-            0x1d => DataValueType::TypeIntColorRgb8,
-            0x1e => DataValueType::TypeIntColorArgb4,
-            0x1f => DataValueType::TypeIntColorRgb4,
-            _ => panic!("Error: unknown data value type {:02X}", value)
+        DataValueType::try_from_val(value)
+            .unwrap_or_else(|| panic!("Error: {}", UnknownDataValueType(value)))
+    }
+
+    /// Convert `u8` into a `DataValueType`, or `None` if the type is unknown
+    pub fn try_from_val(value: u8) -> Option<Self> {
+        DataValueType::ALL.into_iter().find(|data_type| u8::from(*data_type) == value)
+    }
+
+    /// Name of the data value type, as in the Android sources (e.g.,
+    /// `TYPE_INT_DEC`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataValueType::TypeNull => "TYPE_NULL",
+            DataValueType::TypeReference => "TYPE_REFERENCE",
+            DataValueType::TypeAttribute => "TYPE_ATTRIBUTE",
+            DataValueType::TypeString => "TYPE_STRING",
+            DataValueType::TypeFloat => "TYPE_FLOAT",
+            DataValueType::TypeDimension => "TYPE_DIMENSION",
+            DataValueType::TypeFraction => "TYPE_FRACTION",
+            DataValueType::TypeDynamicReference => "TYPE_DYNAMIC_REFERENCE",
+            DataValueType::TypeDynamicAttribute => "TYPE_DYNAMIC_ATTRIBUTE",
+            DataValueType::TypeIntDec => "TYPE_INT_DEC",
+            DataValueType::TypeIntHex => "TYPE_INT_HEX",
+            DataValueType::TypeIntBoolean => "TYPE_INT_BOOLEAN",
+            DataValueType::TypeIntColorArgb8 => "TYPE_INT_COLOR_ARGB8",
+            DataValueType::TypeIntColorRgb8 => "TYPE_INT_COLOR_RGB8",
+            DataValueType::TypeIntColorArgb4 => "TYPE_INT_COLOR_ARGB4",
+            DataValueType::TypeIntColorRgb4 => "TYPE_INT_COLOR_RGB4",
         }
     }
 }
+
+impl TryFrom<u8> for DataValueType {
+    type Error = UnknownDataValueType;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        DataValueType::try_from_val(value).ok_or(UnknownDataValueType(value))
+    }
+}
+
+impl From<DataValueType> for u8 {
+    fn from(data_type: DataValueType) -> Self {
+        data_type as u8
+    }
+}
+
+impl fmt::Display for DataValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::UpperHex for DataValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&u8::from(*self), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        for data_type in DataValueType::ALL {
+            assert_eq!(DataValueType::try_from(u8::from(data_type)), Ok(data_type));
+        }
+        assert_eq!(DataValueType::try_from(0x1b), Err(UnknownDataValueType(0x1b)));
+        assert_eq!(UnknownDataValueType(0x1b).to_string(), "unknown data value type 0x1b");
+        assert_eq!(DataValueType::from_val(0x12), DataValueType::TypeIntBoolean);
+
+        assert_eq!(DataValueType::TypeIntColorArgb8.to_string(), "TYPE_INT_COLOR_ARGB8");
+        assert_eq!(format!("{:02X}", DataValueType::TypeIntColorArgb8), "1C");
+    }
+}
@@ -0,0 +1,279 @@
+//! Manifest diff
+//!
+//! Compare two parsed trees (typically the manifests of two versions of the
+//! same app) and list what changed: added or removed elements, and added,
+//! removed, or modified attributes.
+//!
+//! Elements are matched by their type and their `android:name` attribute when
+//! they have one, and by their position among the siblings of the same type
+//! otherwise. This means that reordering named components is not reported as
+//! a change, while reordering unnamed elements (e.g., intent filters) is.
+//! Class names (e.g., of components) are compared fully qualified, so `.Main`
+//! and `com.example.Main` are the same component of `com.example`.
+
+use std::fmt;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::fingerprint::CLASS_ELEMENTS;
+use crate::normalize_component_name;
+use crate::parser::XmlElement;
+
+/// A single difference between two manifests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestChange {
+    /// An element (and its whole subtree) only exists in the new manifest
+    ElementAdded {
+        path: String,
+    },
+    /// An element (and its whole subtree) only exists in the old manifest
+    ElementRemoved {
+        path: String,
+    },
+    /// An attribute was added (`old` is `None`), removed (`new` is `None`),
+    /// or modified on an element that exists in both manifests
+    AttributeChanged {
+        path: String,
+        key: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+impl fmt::Display for ManifestChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestChange::ElementAdded { path } => write!(f, "+ {path}"),
+            ManifestChange::ElementRemoved { path } => write!(f, "- {path}"),
+            ManifestChange::AttributeChanged { path, key, old, new } => {
+                match (old, new) {
+                    (None, Some(new)) => write!(f, "~ {path} +@{key}=\"{new}\""),
+                    (Some(old), None) => write!(f, "~ {path} -@{key}=\"{old}\""),
+                    (Some(old), Some(new)) => write!(f, "~ {path} @{key}: \"{old}\" -> \"{new}\""),
+                    (None, None) => write!(f, "~ {path} @{key}"),
+                }
+            },
+        }
+    }
+}
+
+/// Compute the list of changes between two trees, in document order
+pub fn diff_manifests(old: &Rc<RefCell<XmlElement>>,
+                      new: &Rc<RefCell<XmlElement>>) -> Vec<ManifestChange> {
+    let mut changes = Vec::new();
+
+    let old = old.borrow();
+    let new = new.borrow();
+
+    if old.element_type != new.element_type {
+        changes.push(ManifestChange::ElementRemoved { path: old.element_type.to_string() });
+        changes.push(ManifestChange::ElementAdded { path: new.element_type.to_string() });
+        return changes;
+    }
+
+    let packages = (old.get_attr("package").unwrap_or_default(), new.get_attr("package").unwrap_or_default());
+    diff_elements(&old, &new, &old.element_type, packages, &mut changes);
+
+    changes
+}
+
+/// Render a list of changes as text, one change per line
+pub fn render_changes(changes: &[ManifestChange]) -> String {
+    let mut output = String::new();
+    for change in changes {
+        output.push_str(&change.to_string());
+        output.push('\n');
+    }
+    output
+}
+
+/// Key used to match elements between the two trees: element type, value of
+/// `android:name` (if any, fully qualified for class names), and rank among
+/// the siblings with the same type and name.
+type MatchKey = (String, Option<String>, usize);
+
+/// Value of `android:name` of an element, fully qualified if it is a class name
+fn semantic_name(element: &XmlElement, pkg_name: &str) -> Option<String> {
+    let name = element.get_attr("name")?;
+    if CLASS_ELEMENTS.contains(&element.element_type.as_str()) {
+        Some(normalize_component_name(pkg_name, name))
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn match_keys(children: &[Rc<RefCell<XmlElement>>], pkg_name: &str) -> Vec<MatchKey> {
+    let mut keys: Vec<MatchKey> = Vec::with_capacity(children.len());
+
+    for child in children {
+        let child = child.borrow();
+        let element_type = child.element_type.to_string();
+        let name = semantic_name(&child, pkg_name);
+        let rank = keys.iter()
+                       .filter(|(t, n, _)| *t == element_type && *n == name)
+                       .count();
+        keys.push((element_type, name, rank));
+    }
+
+    keys
+}
+
+/// Path of a child, using the name as written in its manifest
+fn child_path(parent_path: &str, key: &MatchKey, child: &XmlElement) -> String {
+    let name = child.get_attr("name");
+    match (key, name) {
+        ((element_type, Some(_), 0), Some(name)) => format!("{parent_path}/{element_type}[{name}]"),
+        ((element_type, Some(_), rank), Some(name)) => format!("{parent_path}/{element_type}[{name}#{rank}]"),
+        ((element_type, _, rank), _) => format!("{parent_path}/{element_type}[{rank}]"),
+    }
+}
+
+fn diff_elements(old: &XmlElement,
+                 new: &XmlElement,
+                 path: &str,
+                 packages: (&str, &str),
+                 changes: &mut Vec<ManifestChange>) {
+    // Attributes
+    for (key, old_value) in old.attributes.iter() {
+        match new.attributes.get(key) {
+            Some(new_value) if new_value == old_value => { },
+            // Same class name, written differently
+            Some(_) if old.attributes.get_attribute(key).is_some_and(|attr| attr.local_name() == "name") &&
+                       semantic_name(old, packages.0) == semantic_name(new, packages.1) => { },
+            new_value => changes.push(ManifestChange::AttributeChanged {
+                path: path.to_string(),
+                key: key.to_string(),
+                old: Some(old_value.clone()),
+                new: new_value.cloned(),
+            }),
+        }
+    }
+    for (key, new_value) in new.attributes.iter() {
+        if !old.attributes.contains_key(key) {
+            changes.push(ManifestChange::AttributeChanged {
+                path: path.to_string(),
+                key: key.to_string(),
+                old: None,
+                new: Some(new_value.clone()),
+            });
+        }
+    }
+
+    // Children
+    let old_children: Vec<_> = old.child_elements().cloned().collect();
+    let new_children: Vec<_> = new.child_elements().cloned().collect();
+    let old_keys = match_keys(&old_children, packages.0);
+    let new_keys = match_keys(&new_children, packages.1);
+
+    for (old_key, old_child) in old_keys.iter().zip(old_children.iter()) {
+        let old_child = old_child.borrow();
+        let child_path = child_path(path, old_key, &old_child);
+        match new_keys.iter().position(|k| k == old_key) {
+            Some(idx) => diff_elements(&old_child,
+                                       &new_children[idx].borrow(),
+                                       &child_path,
+                                       packages,
+                                       changes),
+            None => changes.push(ManifestChange::ElementRemoved { path: child_path }),
+        }
+    }
+
+    for (new_key, new_child) in new_keys.iter().zip(new_children.iter()) {
+        if !old_keys.contains(new_key) {
+            changes.push(ManifestChange::ElementAdded { path: child_path(path, new_key, &new_child.borrow()) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn manifest(version: i32, components: &[(&str, &str)], permissions: &[&str]) -> Rc<RefCell<XmlElement>> {
+        let mut builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::int("android:versionCode", version)]);
+        for permission in permissions {
+            builder = builder.element("uses-permission", vec![Attr::string("android:name", permission)]);
+        }
+        builder = builder.start("application", vec![]);
+        for (kind, name) in components {
+            builder = builder.element(kind, vec![Attr::string("android:name", name)]);
+        }
+        let data = builder.end("application").end("manifest").build();
+
+        parse_xml(Cursor::new(data))
+    }
+
+    #[test]
+    fn test_identical_manifests() {
+        let old = manifest(1, &[("activity", ".Main")], &["android.permission.INTERNET"]);
+        let new = manifest(1, &[("activity", ".Main")], &["android.permission.INTERNET"]);
+
+        assert!(diff_manifests(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_added_and_removed_elements() {
+        let old = manifest(1, &[("activity", ".Main"), ("service", ".Old")], &[]);
+        let new = manifest(1, &[("service", ".New"), ("activity", ".Main")], &["android.permission.CAMERA"]);
+
+        let changes = diff_manifests(&old, &new);
+
+        assert_eq!(changes, vec![
+            ManifestChange::ElementRemoved { path: String::from("manifest/application[0]/service[.Old]") },
+            ManifestChange::ElementAdded { path: String::from("manifest/application[0]/service[.New]") },
+            ManifestChange::ElementAdded { path: String::from("manifest/uses-permission[android.permission.CAMERA]") },
+        ]);
+    }
+
+    #[test]
+    fn test_changed_attribute() {
+        let old = manifest(1, &[], &[]);
+        let new = manifest(2, &[], &[]);
+
+        let changes = diff_manifests(&old, &new);
+
+        assert_eq!(changes, vec![ManifestChange::AttributeChanged {
+            path: String::from("manifest"),
+            key: String::from("android:versionCode"),
+            old: Some(String::from("1")),
+            new: Some(String::from("2")),
+        }]);
+        assert_eq!(render_changes(&changes), "~ manifest @android:versionCode: \"1\" -> \"2\"\n");
+    }
+
+    #[test]
+    fn test_added_and_removed_attributes() {
+        let old = manifest(1, &[("activity", ".Main")], &[]);
+        let new = manifest(1, &[("activity", ".Main")], &[]);
+
+        {
+            let application = Rc::clone(old.borrow().child_elements().next().unwrap());
+            let activity = Rc::clone(application.borrow().child_elements().next().unwrap());
+            activity.borrow_mut().attributes.insert(String::from("android:exported"), String::from("false"));
+        }
+        {
+            let application = Rc::clone(new.borrow().child_elements().next().unwrap());
+            let activity = Rc::clone(application.borrow().child_elements().next().unwrap());
+            activity.borrow_mut().attributes.insert(String::from("android:label"), String::from("Main"));
+        }
+
+        let rendered = render_changes(&diff_manifests(&old, &new));
+
+        assert_eq!(rendered, "~ manifest/application[0]/activity[.Main] -@android:exported=\"false\"\n\
+                              ~ manifest/application[0]/activity[.Main] +@android:label=\"Main\"\n");
+    }
+
+    #[test]
+    fn test_class_names_are_qualified() {
+        let old = manifest(1, &[("activity", ".Main"), ("service", "com.example.Sync")], &[]);
+        let new = manifest(1, &[("service", ".Sync"), ("activity", "com.example.Main")], &[]);
+
+        assert!(diff_manifests(&old, &new).is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+//! Error type
+//!
+//! All the fallible operations of the crate that do not simply panic report
+//! their errors through the `AxmlError` enum defined here.
+
+use std::fmt;
+use std::io;
+
+/// Errors that can occur when reading or writing (A)XML data
+#[derive(Debug)]
+pub enum AxmlError {
+    /// Underlying I/O error (e.g., broken pipe or full disk when writing)
+    Io(io::Error),
+    /// Error reported by the XML writer
+    Xml(quick_xml::Error),
+    /// Error when reading a zip archive (e.g., an APK)
+    #[cfg(feature = "apk")]
+    Zip(zip::result::ZipError),
+    /// The requested entry does not exist in the archive. `candidates` lists
+    /// the entries with a similar name, if any.
+    MissingEntry {
+        name: String,
+        candidates: Vec<String>,
+    },
+    /// The requested entry of the archive is encrypted, and cannot be read
+    EncryptedEntry {
+        name: String,
+    },
+    /// Binary XML data that cannot be parsed
+    Parse(String),
+    /// Malformed chunk header at the given offset
+    MalformedChunk {
+        offset: usize,
+        reason: String,
+    },
+    /// A required attribute is missing from an element (or the element
+    /// itself is missing)
+    MissingAttribute {
+        element: String,
+        attribute: String,
+    },
+    /// Path query that cannot be parsed, see [`crate::query`]. `position` is
+    /// the byte offset of the error in the query.
+    InvalidQuery {
+        position: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for AxmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AxmlError::Io(e) => write!(f, "I/O error: {e}"),
+            AxmlError::Xml(e) => write!(f, "XML error: {e}"),
+            #[cfg(feature = "apk")]
+            AxmlError::Zip(e) => write!(f, "zip error: {e}"),
+            AxmlError::MissingEntry { name, candidates } => {
+                write!(f, "no {name} in archive")?;
+                if !candidates.is_empty() {
+                    write!(f, " (did you mean {}?)", candidates.join(", "))?;
+                }
+                Ok(())
+            },
+            AxmlError::EncryptedEntry { name } => write!(f, "{name} is encrypted in the archive"),
+            AxmlError::Parse(message) => write!(f, "cannot parse binary XML: {message}"),
+            AxmlError::MalformedChunk { offset, reason } => {
+                write!(f, "malformed chunk at offset {offset:#x}: {reason}")
+            },
+            AxmlError::MissingAttribute { element, attribute } => {
+                write!(f, "no {attribute} attribute in <{element}>")
+            },
+            AxmlError::InvalidQuery { position, reason } => {
+                write!(f, "invalid query at position {position}: {reason}")
+            },
+        }
+    }
+}
+
+impl std::error::Error for AxmlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AxmlError::Io(e) => Some(e),
+            AxmlError::Xml(e) => Some(e),
+            #[cfg(feature = "apk")]
+            AxmlError::Zip(e) => Some(e),
+            AxmlError::MissingEntry { .. } |
+            AxmlError::EncryptedEntry { .. } |
+            AxmlError::Parse(_) |
+            AxmlError::MalformedChunk { .. } |
+            AxmlError::MissingAttribute { .. } |
+            AxmlError::InvalidQuery { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for AxmlError {
+    fn from(e: io::Error) -> Self {
+        AxmlError::Io(e)
+    }
+}
+
+impl From<quick_xml::Error> for AxmlError {
+    fn from(e: quick_xml::Error) -> Self {
+        AxmlError::Xml(e)
+    }
+}
+
+#[cfg(feature = "apk")]
+impl From<zip::result::ZipError> for AxmlError {
+    fn from(e: zip::result::ZipError) -> Self {
+        AxmlError::Zip(e)
+    }
+}
@@ -0,0 +1,152 @@
+//! Hardware and software features
+//!
+//! Features declared with `<uses-feature>`, which are used by app stores to
+//! filter out incompatible devices, and features implied by the requested
+//! permissions.
+
+use crate::find_elements_by_type;
+use crate::owned::ElementRef;
+use crate::permissions::get_requested_permissions;
+
+/// Feature declared with `<uses-feature>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsesFeature {
+    /// Name of the feature (e.g., `android.hardware.camera`). `None` when the
+    /// element only declares an OpenGL ES version.
+    pub name: Option<String>,
+    /// Whether the app cannot work without the feature
+    /// (`android:required`, `true` by default)
+    pub required: bool,
+    /// Required OpenGL ES version (`android:glEsVersion`), with the major
+    /// version in the upper 16 bits and the minor version in the lower 16
+    /// bits, see [`UsesFeature::gles_version_pair`]
+    pub gles_version: Option<u32>,
+}
+
+impl UsesFeature {
+    /// Required OpenGL ES version as a `(major, minor)` pair (e.g., `(3, 1)`
+    /// for `0x00030001`)
+    pub fn gles_version_pair(&self) -> Option<(u16, u16)> {
+        self.gles_version.map(|version| ((version >> 16) as u16, version as u16))
+    }
+}
+
+/// Get the features declared with `<uses-feature>`, in document order
+pub fn get_uses_features<E: ElementRef>(root: &E) -> Vec<UsesFeature> {
+    find_elements_by_type(root, "uses-feature")
+        .iter()
+        .map(|feature| UsesFeature {
+            name: feature.get_attr("name"),
+            required: feature.get_attr_bool("required").unwrap_or(true),
+            gles_version: feature.get_attr_int("glEsVersion")
+                                 .and_then(|version| u32::try_from(version).ok()),
+        })
+        .collect()
+}
+
+/// Features implied by permissions, like `aapt dump badging` reports them
+const IMPLIED_FEATURES: [(&str, &[&str]); 20] = [
+    ("android.permission.CAMERA", &["android.hardware.camera", "android.hardware.camera.autofocus"]),
+    ("android.permission.ACCESS_FINE_LOCATION", &["android.hardware.location.gps", "android.hardware.location"]),
+    ("android.permission.ACCESS_COARSE_LOCATION", &["android.hardware.location.network", "android.hardware.location"]),
+    ("android.permission.ACCESS_MOCK_LOCATION", &["android.hardware.location"]),
+    ("android.permission.ACCESS_LOCATION_EXTRA_COMMANDS", &["android.hardware.location"]),
+    ("android.permission.INSTALL_LOCATION_PROVIDER", &["android.hardware.location"]),
+    ("android.permission.BLUETOOTH", &["android.hardware.bluetooth"]),
+    ("android.permission.BLUETOOTH_ADMIN", &["android.hardware.bluetooth"]),
+    ("android.permission.RECORD_AUDIO", &["android.hardware.microphone"]),
+    ("android.permission.ACCESS_WIFI_STATE", &["android.hardware.wifi"]),
+    ("android.permission.CHANGE_WIFI_STATE", &["android.hardware.wifi"]),
+    ("android.permission.CHANGE_WIFI_MULTICAST_STATE", &["android.hardware.wifi"]),
+    ("android.permission.CALL_PHONE", &["android.hardware.telephony"]),
+    ("android.permission.CALL_PRIVILEGED", &["android.hardware.telephony"]),
+    ("android.permission.MODIFY_PHONE_STATE", &["android.hardware.telephony"]),
+    ("android.permission.PROCESS_OUTGOING_CALLS", &["android.hardware.telephony"]),
+    ("android.permission.READ_SMS", &["android.hardware.telephony"]),
+    ("android.permission.RECEIVE_SMS", &["android.hardware.telephony"]),
+    ("android.permission.SEND_SMS", &["android.hardware.telephony"]),
+    ("android.permission.WRITE_SMS", &["android.hardware.telephony"]),
+];
+
+/// Feature implied by a requested permission, see [`get_implied_features`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImpliedFeature {
+    /// Name of the feature
+    pub name: String,
+    /// Permission implying the feature
+    pub permission: String,
+}
+
+/// Get the features implied by the requested permissions (e.g.,
+/// `android.permission.CAMERA` implies `android.hardware.camera`). Features
+/// that are explicitly declared with `<uses-feature>`, even as not required,
+/// are not implied. Each feature is only reported once, for the first
+/// permission implying it.
+pub fn get_implied_features<E: ElementRef>(root: &E) -> Vec<ImpliedFeature> {
+    let declared: Vec<String> = get_uses_features(root).into_iter()
+                                                       .filter_map(|feature| feature.name)
+                                                       .collect();
+    let mut implied: Vec<ImpliedFeature> = Vec::new();
+
+    for permission in get_requested_permissions(root) {
+        let features = IMPLIED_FEATURES.iter()
+                                       .filter(|(name, _)| *name == permission.name)
+                                       .flat_map(|(_, features)| features.iter());
+        for feature in features {
+            if declared.iter().any(|name| name == feature) ||
+               implied.iter().any(|implied| implied.name == *feature) {
+                continue;
+            }
+            implied.push(ImpliedFeature {
+                name: feature.to_string(),
+                permission: permission.name.clone(),
+            });
+        }
+    }
+
+    implied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_uses_features() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.CAMERA")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.ACCESS_FINE_LOCATION")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.ACCESS_COARSE_LOCATION")])
+            .element("uses-feature", vec![Attr::string("android:name", "android.hardware.camera"),
+                                          Attr::boolean("android:required", false)])
+            .element("uses-feature", vec![Attr::string("android:name", "android.hardware.nfc")])
+            .element("uses-feature", vec![Attr::hex("android:glEsVersion", 0x00030001),
+                                          Attr::boolean("android:required", true)])
+            .end("manifest")
+            .build()));
+
+        let features = get_uses_features(&root);
+        assert_eq!(features, vec![
+            UsesFeature { name: Some(String::from("android.hardware.camera")), required: false, gles_version: None },
+            UsesFeature { name: Some(String::from("android.hardware.nfc")), required: true, gles_version: None },
+            UsesFeature { name: None, required: true, gles_version: Some(0x00030001) },
+        ]);
+        assert_eq!(features[2].gles_version_pair(), Some((3, 1)));
+
+        let implied: Vec<(String, String)> = get_implied_features(&root)
+            .into_iter()
+            .map(|feature| (feature.name, feature.permission))
+            .collect();
+        assert_eq!(implied, vec![
+            (String::from("android.hardware.camera.autofocus"), String::from("android.permission.CAMERA")),
+            (String::from("android.hardware.location.gps"), String::from("android.permission.ACCESS_FINE_LOCATION")),
+            (String::from("android.hardware.location"), String::from("android.permission.ACCESS_FINE_LOCATION")),
+            (String::from("android.hardware.location.network"), String::from("android.permission.ACCESS_COARSE_LOCATION")),
+        ]);
+    }
+}
@@ -0,0 +1,155 @@
+//! C interface
+//!
+//! Functions callable from other languages (C, Go through cgo, etc.) through
+//! the `cdylib` build of the crate. Only available with the `ffi` feature. The
+//! matching header is generated by cbindgen at build time and checked in as
+//! `include/rusty_axml.h`.
+//!
+//! Strings returned by the library are allocated by Rust and must be released
+//! with [`rusty_axml_free_string`]. No panic crosses the FFI boundary: they are
+//! reported as errors.
+
+use std::ffi::{ c_char, c_int, CString };
+use std::panic::{ self, AssertUnwindSafe };
+use std::ptr;
+use std::slice;
+
+use crate::parser::try_parse_xml_from_slice;
+use crate::{ detect_file_kind, AxmlError, FileKind };
+
+/// Return value of the functions on success
+pub const RUSTY_AXML_OK: c_int = 0;
+
+/// Return value of the functions on error
+pub const RUSTY_AXML_ERROR: c_int = -1;
+
+/// Decode a manifest (or any binary XML document) into an XML string. With
+/// the `apk` feature, `data` can also be a whole APK, whose manifest is
+/// decoded.
+fn decode_manifest(data: &[u8]) -> Result<String, AxmlError> {
+    let root = match detect_file_kind(data) {
+        #[cfg(feature = "apk")]
+        FileKind::Apk => try_parse_xml_from_slice(crate::create_cursor_from_apk_bytes(data)?.get_ref())?,
+        _ => try_parse_xml_from_slice(data)?,
+    };
+
+    let xml = root.borrow().to_xml_string_with_declaration();
+    Ok(xml)
+}
+
+/// Convert `value` into a C string allocated by Rust. Interior NUL bytes,
+/// which C strings cannot hold, are replaced by U+FFFD.
+fn into_c_string(value: String) -> *mut c_char {
+    let value = CString::new(value).unwrap_or_else(|e| {
+        let value = String::from_utf8_lossy(&e.into_vec()).replace('\0', "\u{fffd}");
+        CString::new(value).expect("NUL bytes were replaced")
+    });
+    value.into_raw()
+}
+
+/// Decode the binary manifest in `data` (`len` bytes) into an XML string.
+///
+/// On success, returns `RUSTY_AXML_OK` and stores the XML document in
+/// `*out_xml`. On error, returns `RUSTY_AXML_ERROR` and stores an error
+/// message in `*out_err`. The other output is set to `NULL`. Both strings
+/// must be released with `rusty_axml_free_string`. `out_err` can be `NULL` if
+/// the caller does not need the error message.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes (or be `NULL` if `len` is 0),
+/// and `out_xml` and `out_err` must be `NULL` or point to writable pointers.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_axml_parse_manifest(data: *const u8,
+                                                   len: usize,
+                                                   out_xml: *mut *mut c_char,
+                                                   out_err: *mut *mut c_char) -> c_int {
+    let result = if out_xml.is_null() {
+        Err(String::from("out_xml is NULL"))
+    } else if data.is_null() && len > 0 {
+        Err(String::from("data is NULL"))
+    } else {
+        let data = if len == 0 { &[][..] } else { slice::from_raw_parts(data, len) };
+        match panic::catch_unwind(AssertUnwindSafe(|| decode_manifest(data))) {
+            Ok(Ok(xml)) => Ok(xml),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(String::from("cannot parse binary XML: the parser panicked")),
+        }
+    };
+
+    if !out_xml.is_null() {
+        *out_xml = ptr::null_mut();
+    }
+    if !out_err.is_null() {
+        *out_err = ptr::null_mut();
+    }
+
+    match result {
+        Ok(xml) => {
+            *out_xml = into_c_string(xml);
+            RUSTY_AXML_OK
+        },
+        Err(message) => {
+            if !out_err.is_null() {
+                *out_err = into_c_string(message);
+            }
+            RUSTY_AXML_ERROR
+        },
+    }
+}
+
+/// Release a string returned by the library. Does nothing if `value` is
+/// `NULL`.
+///
+/// # Safety
+///
+/// `value` must be `NULL` or a string returned by the library that was not
+/// released yet.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_axml_free_string(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    /// Call `rusty_axml_parse_manifest` and return its outputs as strings
+    fn parse(data: &[u8]) -> (c_int, Option<String>, Option<String>) {
+        let mut xml = ptr::null_mut();
+        let mut err = ptr::null_mut();
+        let ret = unsafe { rusty_axml_parse_manifest(data.as_ptr(), data.len(), &mut xml, &mut err) };
+
+        let take = |value: *mut c_char| (!value.is_null()).then(|| unsafe {
+            let string = CStr::from_ptr(value).to_string_lossy().into_owned();
+            rusty_axml_free_string(value);
+            string
+        });
+        (ret, take(xml), take(err))
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let data = AxmlBuilder::new()
+            .element("manifest", vec![Attr::string("package", "com.example")])
+            .build();
+
+        let (ret, xml, err) = parse(&data);
+        assert_eq!(ret, RUSTY_AXML_OK);
+        assert!(xml.unwrap().contains("<manifest package=\"com.example\"/>"));
+        assert!(err.is_none());
+
+        let (ret, xml, err) = parse(b"garbage");
+        assert_eq!(ret, RUSTY_AXML_ERROR);
+        assert!(xml.is_none());
+        assert_eq!(err.unwrap(), "cannot parse binary XML: not an Android binary XML document");
+
+        let ret = unsafe { rusty_axml_parse_manifest(data.as_ptr(), data.len(), ptr::null_mut(), ptr::null_mut()) };
+        assert_eq!(ret, RUSTY_AXML_ERROR);
+        unsafe { rusty_axml_free_string(ptr::null_mut()) };
+    }
+}
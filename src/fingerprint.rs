@@ -0,0 +1,174 @@
+//! Manifest fingerprint
+//!
+//! Digest of the structure of a manifest, ignoring cosmetic differences, to
+//! group apps that were repackaged from the same original.
+//!
+//! The fingerprint is the SHA-256 digest of the canonical form of the
+//! manifest returned by [`canonical_manifest`], which is built as follows:
+//!
+//!  * every element is written as `(type attributes children)`, where
+//!    `attributes` is the list of `name=value` pairs and `children` the list
+//!    of canonical children, each separated by a space;
+//!  * attributes are identified by their local name (the namespace prefix
+//!    chosen by the build tools is dropped) and sorted by name then value;
+//!  * namespace declarations (`xmlns:*`) and the attributes added by the
+//!    build tools (`platformBuildVersionCode`, `platformBuildVersionName`,
+//!    `compileSdkVersion`, `compileSdkVersionCodename`) are not included;
+//!  * values are written as Rust string literals (quoted, with escapes);
+//!  * references to resources are reduced to the type of the resource, as
+//!    `@type/0xTT` with `TT` the type ID in hexadecimal, since resource IDs
+//!    change whenever resources are added or removed;
+//!  * class names (`android:name` of the application and of the components,
+//!    `android:targetActivity`, `android:backupAgent`, etc.) are fully
+//!    qualified, see [`normalize_component_name`];
+//!  * children are sorted by their canonical form, so the order of the
+//!    elements in the manifest does not matter.
+//!
+//! Everything else, including the package name, the versions, and the
+//! values of all the other attributes, is part of the fingerprint. This
+//! canonical form is part of the API: changing it is a breaking change.
+//! Use [`canonical_manifest`] directly to hash it with another algorithm.
+
+use sha2::{ Digest, Sha256 };
+
+use crate::data_value_type::DataValueType;
+use crate::owned::{ ElementRef, OwnedXmlElement };
+use crate::{ manifest_package, normalize_component_name };
+
+/// Attributes added by the build tools, which are not part of the fingerprint
+const BUILD_ATTRIBUTES: [&str; 4] = [
+    "platformBuildVersionCode",
+    "platformBuildVersionName",
+    "compileSdkVersion",
+    "compileSdkVersionCodename",
+];
+
+/// Element types whose `android:name` is a class name
+pub(crate) const CLASS_ELEMENTS: [&str; 7] = [
+    "application",
+    "activity",
+    "activity-alias",
+    "service",
+    "receiver",
+    "provider",
+    "instrumentation",
+];
+
+/// Attributes holding a class name on any element
+const CLASS_ATTRIBUTES: [&str; 4] = [
+    "targetActivity",
+    "backupAgent",
+    "appComponentFactory",
+    "manageSpaceActivity",
+];
+
+/// Canonical form of an element and its subtree
+fn canonical_element(element: &OwnedXmlElement, pkg_name: &str) -> String {
+    let mut attributes: Vec<(String, String)> = element.attributes
+        .iter_attributes()
+        .filter(|attr| !attr.name.starts_with("xmlns:"))
+        .filter(|attr| !BUILD_ATTRIBUTES.contains(&attr.local_name()))
+        .map(|attr| {
+            let name = attr.local_name();
+            let is_class = CLASS_ATTRIBUTES.contains(&name) ||
+                           (name == "name" && CLASS_ELEMENTS.contains(&element.element_type.as_str()));
+
+            let value = match attr.typed_value {
+                Some(typed) if matches!(typed.data_type,
+                                        DataValueType::TypeReference |
+                                        DataValueType::TypeDynamicReference) => {
+                    format!("@type/{:#04x}", (typed.data >> 16) & 0xff)
+                },
+                _ if is_class => format!("{:?}", normalize_component_name(pkg_name, &attr.value)),
+                _ => format!("{:?}", attr.value),
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+    attributes.sort();
+
+    let mut children: Vec<String> = element.children
+        .iter()
+        .map(|child| canonical_element(child, pkg_name))
+        .collect();
+    children.sort();
+
+    let mut canonical = format!("({}", element.element_type);
+    for (name, value) in attributes {
+        canonical.push_str(&format!(" {name}={value}"));
+    }
+    for child in children {
+        canonical.push(' ');
+        canonical.push_str(&child);
+    }
+    canonical.push(')');
+
+    canonical
+}
+
+/// Get the canonical form of a manifest, see the [module documentation](self)
+pub fn canonical_manifest<E: ElementRef>(root: &E) -> String {
+    canonical_element(&root.to_owned_element(), &manifest_package(root))
+}
+
+/// Get the SHA-256 digest of the canonical form of a manifest, see the
+/// [module documentation](self)
+pub fn manifest_fingerprint<E: ElementRef>(root: &E) -> [u8; 32] {
+    Sha256::digest(canonical_manifest(root).as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn fingerprint(builder: AxmlBuilder) -> [u8; 32] {
+        manifest_fingerprint(&parse_xml(Cursor::new(builder.build())))
+    }
+
+    fn manifest(permission: &str, main_attrs: Vec<Attr>, icon: u32) -> AxmlBuilder {
+        AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::int("platformBuildVersionCode", 33)])
+            .element("uses-permission", vec![Attr::string("android:name", permission)])
+            .start("application", vec![Attr::reference("android:icon", icon)])
+            .element("activity", main_attrs)
+            .end("application")
+            .end("manifest")
+    }
+
+    #[test]
+    fn test_attribute_order_is_ignored() {
+        let a = manifest("android.permission.INTERNET",
+                         vec![Attr::string("android:name", ".Main"), Attr::boolean("android:exported", true)],
+                         0x7f080001);
+        let b = manifest("android.permission.INTERNET",
+                         vec![Attr::boolean("android:exported", true), Attr::string("android:name", "com.example.Main")],
+                         0x7f080042);
+
+        assert_eq!(fingerprint(a), fingerprint(b));
+    }
+
+    #[test]
+    fn test_permission_change() {
+        let a = manifest("android.permission.INTERNET", vec![Attr::string("android:name", ".Main")], 0x7f080001);
+        let b = manifest("android.permission.CAMERA", vec![Attr::string("android:name", ".Main")], 0x7f080001);
+
+        assert_ne!(fingerprint(a), fingerprint(b));
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        let root = parse_xml(Cursor::new(manifest("android.permission.INTERNET",
+                                                  vec![Attr::string("android:name", ".Main")],
+                                                  0x7f080001).build()));
+
+        assert_eq!(canonical_manifest(&root),
+                   "(manifest package=\"com.example\" \
+                    (application icon=@type/0x08 (activity name=\"com.example.Main\")) \
+                    (uses-permission name=\"android.permission.INTERNET\"))");
+    }
+}
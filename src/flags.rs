@@ -0,0 +1,74 @@
+//! Flag attributes
+//!
+//! Some attributes (e.g., `android:protectionLevel` or
+//! `android:foregroundServiceType`) are bit fields whose bits have symbolic
+//! names in the XML sources, like `signature|privileged`. Compiled manifests
+//! only contain the integer value, so the names are recovered from tables of
+//! `(name, bits)` pairs. A table can have several names for the same bits,
+//! the first one is the canonical name.
+
+/// Get the canonical names of the flags set in `value`, in table order,
+/// along with the bits that are not in the table
+pub(crate) fn flag_names(value: u32, table: &[(&'static str, u32)]) -> (Vec<&'static str>, u32) {
+    let mut names: Vec<&'static str> = Vec::new();
+    let mut seen = 0;
+    for (name, flag) in table.iter() {
+        if *flag != 0 && value & flag == *flag && seen & flag != *flag {
+            names.push(name);
+            seen |= flag;
+        }
+    }
+    (names, value & !seen)
+}
+
+/// Get the names of the flags set in `value`, with the bits that are not in
+/// the table reported in hexadecimal (e.g., `0x80000`) so that flags added
+/// in future versions of the platform are not lost
+pub fn decode_flags(value: u32, table: &[(&'static str, u32)]) -> Vec<String> {
+    let (names, unknown) = flag_names(value, table);
+    let mut names: Vec<String> = names.into_iter().map(String::from).collect();
+    if unknown != 0 {
+        names.push(format!("{unknown:#x}"));
+    }
+    names
+}
+
+/// Parse the symbolic form of a flag attribute (e.g., `location|camera`).
+/// Hexadecimal values are accepted for unknown bits. Returns `None` if a
+/// name is unknown.
+pub fn encode_flags(value: &str, table: &[(&'static str, u32)]) -> Option<u32> {
+    let mut flags = 0;
+    for name in value.split('|').map(str::trim) {
+        match table.iter().find(|(n, _)| *n == name) {
+            Some((_, flag)) => flags |= flag,
+            None => flags |= u32::from_str_radix(name.strip_prefix("0x")?, 16).ok()?,
+        }
+    }
+    Some(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: [(&str, u32); 4] = [
+        ("none", 0),
+        ("read", 0x1),
+        ("write", 0x2),
+        ("modify", 0x2),
+    ];
+
+    #[test]
+    fn test_decode_flags() {
+        assert_eq!(decode_flags(0x3, &TABLE), vec!["read", "write"]);
+        assert_eq!(decode_flags(0x12, &TABLE), vec!["write", "0x10"]);
+        assert!(decode_flags(0, &TABLE).is_empty());
+    }
+
+    #[test]
+    fn test_encode_flags() {
+        assert_eq!(encode_flags("read|modify", &TABLE), Some(0x3));
+        assert_eq!(encode_flags("read | 0x10", &TABLE), Some(0x11));
+        assert_eq!(encode_flags("execute", &TABLE), None);
+    }
+}
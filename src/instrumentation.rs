@@ -0,0 +1,79 @@
+//! Instrumentation
+//!
+//! Test APKs declare `<instrumentation>` elements to run code inside the
+//! process of the app under test (the target package).
+
+use crate::application::FlagState;
+use crate::owned::ElementRef;
+use crate::{ find_elements_by_type, manifest_package, normalize_component_name };
+
+/// Instrumentation declared by the app, see [`get_instrumentations`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instrumentation {
+    /// Fully-qualified name of the instrumentation class
+    pub name: String,
+    /// Package of the app under test (`android:targetPackage`)
+    pub target_package: Option<String>,
+    /// `android:handleProfiling`, `false` by default
+    pub handle_profiling: FlagState,
+    /// `android:functionalTest`, `false` by default
+    pub functional_test: FlagState,
+}
+
+/// Get the instrumentations declared by the app, in document order.
+/// Instrumentations without a name are ignored.
+pub fn get_instrumentations<E: ElementRef>(root: &E) -> Vec<Instrumentation> {
+    let pkg_name = manifest_package(root);
+
+    find_elements_by_type(root, "instrumentation")
+        .iter()
+        .filter_map(|instrumentation| {
+            Some(Instrumentation {
+                name: normalize_component_name(&pkg_name, &instrumentation.get_attr("name")?),
+                target_package: instrumentation.get_attr("targetPackage"),
+                handle_profiling: FlagState::from_attr(instrumentation, "handleProfiling"),
+                functional_test: FlagState::from_attr(instrumentation, "functionalTest"),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_instrumentations() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example.test")])
+            .element("instrumentation", vec![Attr::string("android:name", "androidx.test.runner.AndroidJUnitRunner"),
+                                             Attr::string("android:targetPackage", "com.example"),
+                                             Attr::boolean("android:handleProfiling", false),
+                                             Attr::boolean("android:functionalTest", false)])
+            .element("instrumentation", vec![Attr::string("android:name", ".Custom"),
+                                             Attr::string("android:targetPackage", "com.example"),
+                                             Attr::boolean("android:functionalTest", true)])
+            .element("application", vec![])
+            .end("manifest")
+            .build()));
+
+        assert_eq!(get_instrumentations(&root), vec![
+            Instrumentation {
+                name: String::from("androidx.test.runner.AndroidJUnitRunner"),
+                target_package: Some(String::from("com.example")),
+                handle_profiling: FlagState::ExplicitFalse,
+                functional_test: FlagState::ExplicitFalse,
+            },
+            Instrumentation {
+                name: String::from("com.example.test.Custom"),
+                target_package: Some(String::from("com.example")),
+                handle_profiling: FlagState::NotSet,
+                functional_test: FlagState::ExplicitTrue,
+            },
+        ]);
+    }
+}
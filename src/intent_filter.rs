@@ -0,0 +1,515 @@
+//! Intent filters
+//!
+//! Structured view of the `<intent-filter>` elements of a component, with
+//! their actions, categories, and `<data>` specifications, and of the deep
+//! links handled by an app.
+
+use crate::owned::ElementRef;
+use crate::{
+    exposure_report_with_elements,
+    find_elements_by_types,
+    manifest_package,
+    no_resolver,
+    normalize_component_name,
+    ExposedComponent,
+};
+
+/// Action of the intent filters handling deep links
+const ACTION_VIEW: &str = "android.intent.action.VIEW";
+/// Category of the intent filters that can be reached from a web browser
+const CATEGORY_BROWSABLE: &str = "android.intent.category.BROWSABLE";
+
+/// `<data>` element of an intent filter. Each attribute is optional, and
+/// the framework combines the attributes of all the `<data>` elements of a
+/// filter (e.g., the schemes of one with the hosts of another).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntentData {
+    /// URI scheme (`android:scheme`)
+    pub scheme: Option<String>,
+    /// URI authority host (`android:host`)
+    pub host: Option<String>,
+    /// URI authority port (`android:port`)
+    pub port: Option<String>,
+    /// Exact URI path (`android:path`)
+    pub path: Option<String>,
+    /// Prefix of the URI path (`android:pathPrefix`)
+    pub path_prefix: Option<String>,
+    /// Pattern matching the URI path (`android:pathPattern`)
+    pub path_pattern: Option<String>,
+    /// MIME type (`android:mimeType`)
+    pub mime_type: Option<String>,
+}
+
+impl IntentData {
+    fn from_element<E: ElementRef>(element: &E) -> Self {
+        IntentData {
+            scheme: element.get_attr("scheme"),
+            host: element.get_attr("host"),
+            port: element.get_attr("port"),
+            path: element.get_attr("path"),
+            path_prefix: element.get_attr("pathPrefix"),
+            path_pattern: element.get_attr("pathPattern"),
+            mime_type: element.get_attr("mimeType"),
+        }
+    }
+}
+
+/// Intent filter of a component
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntentFilter {
+    /// Names of the `<action>` elements, in document order
+    pub actions: Vec<String>,
+    /// Names of the `<category>` elements, in document order
+    pub categories: Vec<String>,
+    /// `<data>` elements, in document order
+    pub data: Vec<IntentData>,
+    /// Value of `android:priority`: the filters with the highest priority
+    /// handle implicit intents first, and receive ordered broadcasts first
+    pub priority: Option<i32>,
+    /// Value of `android:order`, which orders the filters of the app that
+    /// match the same intent (unlike the priority, it is not compared with
+    /// other apps)
+    pub order: Option<i32>,
+    /// Value of `android:autoVerify`, `false` if absent
+    pub auto_verify: bool,
+}
+
+impl IntentFilter {
+    /// Check if the filter handles the action `action`
+    pub fn has_action(&self, action: &str) -> bool {
+        self.actions.iter().any(|a| a == action)
+    }
+
+    /// Check if the filter has the category `category`
+    pub fn has_category(&self, category: &str) -> bool {
+        self.categories.iter().any(|c| c == category)
+    }
+
+    /// Read an `<intent-filter>` element, or an `<intent>` element of
+    /// `<queries>` which has the same children
+    pub(crate) fn from_element<E: ElementRef>(filter: &E) -> Self {
+        let children = filter.children();
+        let names = |element_type: &str| -> Vec<String> {
+            children.iter()
+                    .filter(|child| child.element_type() == element_type)
+                    .filter_map(|child| child.get_attr("name"))
+                    .collect()
+        };
+
+        IntentFilter {
+            actions: names("action"),
+            categories: names("category"),
+            data: children.iter()
+                          .filter(|child| child.element_type() == "data")
+                          .map(IntentData::from_element)
+                          .collect(),
+            priority: get_attr_i32(filter, "priority"),
+            order: get_attr_i32(filter, "order"),
+            auto_verify: filter.get_attr_bool("autoVerify") == Some(true),
+        }
+    }
+}
+
+/// Value of a 32-bit integer attribute. Hexadecimal values (typed, or
+/// `0x` strings) are the bits of the integer, so `0xffffffff` is -1.
+fn get_attr_i32<E: ElementRef>(element: &E, local_name: &str) -> Option<i32> {
+    let value = element.get_attr_int(local_name)?;
+    i32::try_from(value).ok()
+                        .or_else(|| u32::try_from(value).ok().map(|value| value as i32))
+}
+
+/// Get the intent filters declared directly in a component, in document order
+pub fn extract_intent_filters<E: ElementRef>(component: &E) -> Vec<IntentFilter> {
+    component.children()
+             .iter()
+             .filter(|child| child.element_type() == "intent-filter")
+             .map(IntentFilter::from_element)
+             .collect()
+}
+
+/// Deep link handled by an app: an intent filter of an activity (or
+/// activity-alias) with the `VIEW` action and the `BROWSABLE` category
+///
+/// Within a filter, the `<data>` attributes combine across elements: a
+/// filter with `<data android:scheme="http"/>` and
+/// `<data android:scheme="https" android:host="example.com"/>` handles both
+/// `http://example.com` and `https://example.com`. The values of all the
+/// `<data>` elements are therefore merged here, see [`DeepLink::uris`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeepLink {
+    /// Type of the component (`activity` or `activity-alias`)
+    pub kind: String,
+    /// Fully-qualified name of the component handling the link
+    pub component: String,
+    /// URI schemes, without duplicates
+    pub schemes: Vec<String>,
+    /// URI hosts, without duplicates
+    pub hosts: Vec<String>,
+    /// URI ports, without duplicates
+    pub ports: Vec<String>,
+    /// Exact URI paths, without duplicates
+    pub paths: Vec<String>,
+    /// Prefixes of the URI paths, without duplicates
+    pub path_prefixes: Vec<String>,
+    /// Patterns of the URI paths, without duplicates
+    pub path_patterns: Vec<String>,
+    /// Whether the filter has `android:autoVerify="true"`, i.e., the links
+    /// are App Links verified against the website
+    pub auto_verify: bool,
+}
+
+impl DeepLink {
+    fn from_filter(kind: &str, component: &str, filter: &IntentFilter) -> Self {
+        let mut link = DeepLink {
+            kind: kind.to_string(),
+            component: component.to_string(),
+            auto_verify: filter.auto_verify,
+            ..Default::default()
+        };
+
+        let add = |values: &mut Vec<String>, value: &Option<String>| {
+            if let Some(value) = value {
+                if !values.contains(value) {
+                    values.push(value.clone());
+                }
+            }
+        };
+        for data in filter.data.iter() {
+            add(&mut link.schemes, &data.scheme);
+            add(&mut link.hosts, &data.host);
+            add(&mut link.ports, &data.port);
+            add(&mut link.paths, &data.path);
+            add(&mut link.path_prefixes, &data.path_prefix);
+            add(&mut link.path_patterns, &data.path_pattern);
+        }
+
+        link
+    }
+
+    /// Every combination of scheme, authority, and path handled by the
+    /// filter, e.g., to build a corpus of URLs to test. Path prefixes and
+    /// patterns are given as is. As in the framework, the host is ignored
+    /// without a scheme, and the path is ignored without a host.
+    pub fn uris(&self) -> Vec<String> {
+        let mut authorities = Vec::new();
+        for host in self.hosts.iter() {
+            if self.ports.is_empty() {
+                authorities.push(host.clone());
+            }
+            for port in self.ports.iter() {
+                authorities.push(format!("{host}:{port}"));
+            }
+        }
+
+        let paths: Vec<&String> = self.paths.iter()
+                                            .chain(self.path_prefixes.iter())
+                                            .chain(self.path_patterns.iter())
+                                            .collect();
+
+        let mut uris = Vec::new();
+        for scheme in self.schemes.iter() {
+            if authorities.is_empty() {
+                uris.push(format!("{scheme}:"));
+            }
+            for authority in authorities.iter() {
+                if paths.is_empty() {
+                    uris.push(format!("{scheme}://{authority}"));
+                }
+                for path in paths.iter() {
+                    uris.push(format!("{scheme}://{authority}{path}"));
+                }
+            }
+        }
+
+        uris
+    }
+}
+
+/// Find the deep links handled by the activities and activity-aliases of an
+/// app, in document order, see [`DeepLink`]
+pub fn find_deep_links<E: ElementRef>(root: &E) -> Vec<DeepLink> {
+    let pkg_name = manifest_package(root);
+
+    find_elements_by_types(root, &["activity", "activity-alias"])
+        .iter()
+        .flat_map(|component| {
+            let kind = component.element_type();
+            let name = component.get_attr("name")
+                                .map(|name| normalize_component_name(&pkg_name, &name))
+                                .unwrap_or_default();
+
+            extract_intent_filters(component)
+                .iter()
+                .filter(|filter| filter.has_action(ACTION_VIEW) && filter.has_category(CATEGORY_BROWSABLE))
+                .map(|filter| DeepLink::from_filter(&kind, &name, filter))
+                .collect::<Vec<DeepLink>>()
+        })
+        .collect()
+}
+
+/// Component with an intent filter matching an action, see
+/// [`find_components_for_action`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentMatch {
+    /// Exposure information of the component
+    pub component: ExposedComponent,
+    /// Intent filter matching the action
+    pub filter: IntentFilter,
+}
+
+/// Receiver with an intent filter matching an action, see
+/// [`find_receivers_for_action`]
+pub type ReceiverMatch = ComponentMatch;
+
+/// Find the components of the app (of any type) with an intent filter for
+/// `action`, in document order. A component with several matching filters is
+/// reported once per filter. Use [`ExposedComponent::is_exposed`] to only
+/// keep the components other apps can reach.
+pub fn find_components_for_action<E: ElementRef>(root: &E, action: &str) -> Vec<ComponentMatch> {
+    exposure_report_with_elements(root, &no_resolver)
+        .into_iter()
+        .flat_map(|(element, component)| {
+            extract_intent_filters(&element)
+                .into_iter()
+                .filter(|filter| filter.has_action(action))
+                .map(|filter| ComponentMatch {
+                    component: component.clone(),
+                    filter,
+                })
+                .collect::<Vec<ComponentMatch>>()
+        })
+        .collect()
+}
+
+/// Find the broadcast receivers of the app with an intent filter for
+/// `action` (e.g., `android.intent.action.BOOT_COMPLETED`), see
+/// [`find_components_for_action`]
+pub fn find_receivers_for_action<E: ElementRef>(root: &E, action: &str) -> Vec<ReceiverMatch> {
+    find_components_for_action(root, action)
+        .into_iter()
+        .filter(|found| found.component.kind == "receiver")
+        .collect()
+}
+
+/// Find the broadcast receivers of the app with an intent filter whose
+/// priority is above `threshold`, in document order. A receiver with several
+/// such filters is reported once per filter.
+///
+/// Receivers with the highest priority get ordered broadcasts first and can
+/// abort them, which is how malware intercepts SMS (`SMS_RECEIVED`) or hijacks
+/// other broadcasts. Apps are supposed to stay below 1000 (priorities from
+/// 1000 are reserved to the system), so priorities like 999 or 2147483647
+/// are a strong indicator.
+pub fn find_high_priority_receivers<E: ElementRef>(root: &E, threshold: i32) -> Vec<ReceiverMatch> {
+    exposure_report_with_elements(root, &no_resolver)
+        .into_iter()
+        .filter(|(_, component)| component.kind == "receiver")
+        .flat_map(|(element, component)| {
+            extract_intent_filters(&element)
+                .into_iter()
+                .filter(|filter| filter.priority.is_some_and(|priority| priority > threshold))
+                .map(|filter| ComponentMatch {
+                    component: component.clone(),
+                    filter,
+                })
+                .collect::<Vec<ComponentMatch>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_extract_intent_filters() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("activity", vec![Attr::string("android:name", ".Links")])
+            .start("intent-filter", vec![Attr::int("android:priority", -5),
+                                         Attr::boolean("android:autoVerify", true)])
+            .element("action", vec![Attr::string("android:name", "android.intent.action.VIEW")])
+            .element("category", vec![Attr::string("android:name", "android.intent.category.DEFAULT")])
+            .element("category", vec![Attr::string("android:name", "android.intent.category.BROWSABLE")])
+            .element("data", vec![Attr::string("android:scheme", "https"),
+                                  Attr::string("android:host", "example.com"),
+                                  Attr::string("android:pathPrefix", "/app")])
+            .element("data", vec![Attr::string("android:mimeType", "image/*")])
+            .end("intent-filter")
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", "android.intent.action.SEND")])
+            .end("intent-filter")
+            .element("meta-data", vec![])
+            .end("activity")
+            .build()));
+
+        let filters = extract_intent_filters(&root);
+
+        assert_eq!(filters.len(), 2);
+        assert_eq!(filters[0].actions, vec!["android.intent.action.VIEW"]);
+        assert!(filters[0].has_category("android.intent.category.BROWSABLE"));
+        assert_eq!(filters[0].priority, Some(-5));
+        assert!(filters[0].auto_verify);
+        assert_eq!(filters[0].data, vec![
+            IntentData {
+                scheme: Some(String::from("https")),
+                host: Some(String::from("example.com")),
+                path_prefix: Some(String::from("/app")),
+                ..Default::default()
+            },
+            IntentData {
+                mime_type: Some(String::from("image/*")),
+                ..Default::default()
+            },
+        ]);
+
+        assert!(filters[1].has_action("android.intent.action.SEND"));
+        assert!(filters[1].categories.is_empty());
+        assert_eq!(filters[1].priority, None);
+        assert!(!filters[1].auto_verify);
+    }
+
+    #[test]
+    fn test_find_components_for_action() {
+        const BOOT_COMPLETED: &str = "android.intent.action.BOOT_COMPLETED";
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Main")])
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", BOOT_COMPLETED)])
+            .end("intent-filter")
+            .end("activity")
+            .start("receiver", vec![Attr::string("android:name", ".Boot"),
+                                    Attr::boolean("android:exported", true)])
+            .start("intent-filter", vec![Attr::int("android:priority", 999)])
+            .element("action", vec![Attr::string("android:name", BOOT_COMPLETED)])
+            .element("action", vec![Attr::string("android:name", "android.intent.action.QUICKBOOT_POWERON")])
+            .end("intent-filter")
+            .end("receiver")
+            .start("receiver", vec![Attr::string("android:name", ".Sms")])
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", "android.provider.Telephony.SMS_RECEIVED")])
+            .end("intent-filter")
+            .end("receiver")
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let components = find_components_for_action(&root, BOOT_COMPLETED);
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0].component.kind, "activity");
+
+        let receivers = find_receivers_for_action(&root, BOOT_COMPLETED);
+        assert_eq!(receivers.len(), 1);
+        assert_eq!(receivers[0].component.name, "com.example.Boot");
+        assert!(receivers[0].component.is_exposed());
+        assert_eq!(receivers[0].filter.priority, Some(999));
+        assert_eq!(receivers[0].filter.actions.len(), 2);
+
+        assert!(find_receivers_for_action(&root, "android.intent.action.PACKAGE_ADDED").is_empty());
+    }
+
+    #[test]
+    fn test_find_high_priority_receivers() {
+        const SMS_RECEIVED: &str = "android.provider.Telephony.SMS_RECEIVED";
+        let receiver = |name: &str, filters: Vec<Vec<Attr>>, builder: AxmlBuilder| {
+            let mut builder = builder.start("receiver", vec![Attr::string("android:name", name)]);
+            for attrs in filters {
+                builder = builder.start("intent-filter", attrs)
+                                 .element("action", vec![Attr::string("android:name", SMS_RECEIVED)])
+                                 .end("intent-filter");
+            }
+            builder.end("receiver")
+        };
+
+        let mut builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Main")])
+            .element("intent-filter", vec![Attr::int("android:priority", i32::MAX)])
+            .end("activity");
+        builder = receiver(".Max", vec![vec![Attr::int("android:priority", i32::MAX), Attr::int("android:order", 2)]], builder);
+        builder = receiver(".Hex", vec![vec![Attr::hex("android:priority", 0x3e8)]], builder);
+        builder = receiver(".String", vec![vec![Attr::string("android:priority", "999")],
+                                           vec![Attr::string("android:priority", "0x7fffffff")]], builder);
+        builder = receiver(".Negative", vec![vec![Attr::hex("android:priority", 0xfffffc18)],
+                                             vec![Attr::string("android:priority", "-1000")]], builder);
+        builder = receiver(".Default", vec![vec![]], builder);
+        let root = parse_xml(Cursor::new(builder.end("application").end("manifest").build()));
+
+        let found = |threshold: i32| -> Vec<(String, i32)> {
+            find_high_priority_receivers(&root, threshold)
+                .into_iter()
+                .map(|found| (found.component.name, found.filter.priority.unwrap()))
+                .collect()
+        };
+
+        // Activities are not receivers
+        assert_eq!(found(999), vec![(String::from("com.example.Max"), i32::MAX),
+                                    (String::from("com.example.Hex"), 1000),
+                                    (String::from("com.example.String"), i32::MAX)]);
+        assert_eq!(found(998).len(), 4);
+        assert_eq!(found(-1001).len(), 6);
+        assert!(found(i32::MAX).is_empty());
+
+        let receivers = find_receivers_for_action(&root, SMS_RECEIVED);
+        assert_eq!(receivers[0].filter.order, Some(2));
+        assert_eq!(receivers[4].filter.priority, Some(-1000));
+        assert_eq!(receivers[5].filter.priority, Some(-1000));
+        assert_eq!(receivers[6].filter.priority, None);
+    }
+
+    #[test]
+    fn test_find_deep_links() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Links")])
+            .start("intent-filter", vec![Attr::boolean("android:autoVerify", true)])
+            .element("action", vec![Attr::string("android:name", ACTION_VIEW)])
+            .element("category", vec![Attr::string("android:name", CATEGORY_BROWSABLE)])
+            .element("data", vec![Attr::string("android:scheme", "http")])
+            .element("data", vec![Attr::string("android:scheme", "https"),
+                                  Attr::string("android:host", "example.com")])
+            .element("data", vec![Attr::string("android:pathPrefix", "/item")])
+            .end("intent-filter")
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", ACTION_VIEW)])
+            .element("data", vec![Attr::string("android:scheme", "internal")])
+            .end("intent-filter")
+            .end("activity")
+            .start("activity-alias", vec![Attr::string("android:name", ".Custom")])
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", ACTION_VIEW)])
+            .element("category", vec![Attr::string("android:name", CATEGORY_BROWSABLE)])
+            .element("data", vec![Attr::string("android:scheme", "myapp")])
+            .end("intent-filter")
+            .end("activity-alias")
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let links = find_deep_links(&root);
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].component, "com.example.Links");
+        assert!(links[0].auto_verify);
+        assert_eq!(links[0].schemes, vec!["http", "https"]);
+        assert_eq!(links[0].uris(), vec![
+            "http://example.com/item",
+            "https://example.com/item",
+        ]);
+
+        assert_eq!(links[1].kind, "activity-alias");
+        assert_eq!(links[1].component, "com.example.Custom");
+        assert!(!links[1].auto_verify);
+        assert_eq!(links[1].uris(), vec!["myapp:"]);
+    }
+}
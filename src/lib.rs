@@ -1,11 +1,58 @@
 pub mod parser;
+pub mod attributes;
+pub mod symbol;
+pub mod owned;
+pub mod arena;
+pub mod reader;
+pub mod visitor;
+pub mod writer;
 pub mod chunk_types;
 pub mod chunk_header;
+pub mod chunks;
 pub mod string_pool;
 pub mod resource_map;
 pub mod data_value_type;
 pub mod res_value;
 pub mod res_table;
+pub mod error;
+pub mod warning;
+pub mod diff;
+pub mod merge;
+pub mod model;
+pub mod patch;
+pub mod permissions;
+pub mod sdk;
+pub mod intent_filter;
+pub mod meta_data;
+pub mod application;
+pub mod queries;
+pub mod features;
+pub mod libraries;
+pub mod process;
+pub mod version;
+pub mod instrumentation;
+pub mod flags;
+pub mod services;
+pub mod providers;
+pub mod tasks;
+pub mod lint;
+pub mod screens;
+pub mod fingerprint;
+pub mod query;
+pub mod zip_slice;
+#[cfg(all(feature = "parallel", feature = "apk"))]
+pub mod batch;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(test)]
+mod test_utils;
+
+/// Memory map of a file, see [`create_cursor_from_axml_mmap`]
+#[cfg(feature = "mmap")]
+pub use memmap2::Mmap;
 
 use std::{
     fs,
@@ -13,6 +60,7 @@ use std::{
 };
 use std::io::{
     Read,
+    Seek,
     Cursor,
 };
 use std::rc::Rc;
@@ -24,6 +72,18 @@ use crate::res_table::{
 };
 use crate::string_pool::StringPool;
 use crate::parser::XmlElement;
+use crate::owned::{ ElementRef, OwnedXmlElement };
+use crate::version::VersionInfo;
+use crate::sdk::SdkVersions;
+use crate::intent_filter::extract_intent_filters;
+use crate::instrumentation::Instrumentation;
+use crate::permissions::{
+    DeclaredPermission,
+    GuardPermission,
+    RequestedPermission,
+};
+
+pub use crate::error::AxmlError;
 
 /// Representation of an app's manifest contents
 #[derive(Debug, Default)]
@@ -38,10 +98,95 @@ pub struct ManifestContents {
     // TODO: does not includes permissions requested from within components
     pub created_perms: Vec<String>,
     pub requested_perms: Vec<String>,
+    /// Requested permissions along with their SDK restrictions
+    pub requested_permissions: Vec<RequestedPermission>,
+    /// Declared permissions along with their protection levels
+    pub declared_permissions: Vec<DeclaredPermission>,
+    /// Declared permission groups
+    pub permission_groups: Vec<String>,
+    /// Declared permission trees
+    pub permission_trees: Vec<String>,
+
+    /// Components declared more than once (e.g., left over by a manifest
+    /// merger). They only appear once in the component lists above.
+    pub duplicate_components: Vec<String>,
+
+    /// Declared instrumentations, only present in test APKs
+    pub instrumentations: Vec<Instrumentation>,
 
     pub main_entry_point: Option<String>,
 }
 
+impl ManifestContents {
+    /// Extract the contents of a parsed manifest. Components and permissions
+    /// are listed in document order, and component names are fully qualified
+    /// (see [`normalize_component_name`]). Components declared several times
+    /// are only listed once.
+    pub fn from_tree<E: ElementRef>(root: &E) -> ManifestContents {
+        let pkg_name = manifest_package(root);
+        let groups = group_components(root, &pkg_name);
+
+        let names = |element_type: &str| -> Vec<String> {
+            find_elements_by_type(root, element_type)
+                .iter()
+                .filter_map(|element| element.get_attr("name"))
+                .collect()
+        };
+        let component_names = |element_type: &str| -> Vec<String> {
+            groups.iter()
+                  .filter(|(kind, name, _)| kind == element_type && !name.is_empty())
+                  .map(|(_, name, _)| name.clone())
+                  .collect()
+        };
+
+        let main_entry_point = find_main_entry_points(root).into_iter().next();
+
+        ManifestContents {
+            activities: component_names("activity"),
+            services: component_names("service"),
+            providers: component_names("provider"),
+            receivers: component_names("receiver"),
+            created_perms: names("permission"),
+            requested_perms: names("uses-permission").into_iter()
+                                                       .chain(names("uses-permission-sdk-23"))
+                                                       .collect(),
+            requested_permissions: permissions::get_requested_permissions(root),
+            declared_permissions: permissions::get_declared_permissions(root),
+            permission_groups: permissions::get_permission_groups(root),
+            permission_trees: permissions::get_permission_trees(root),
+            duplicate_components: groups.iter()
+                                        .filter(|(_, _, declarations)| declarations.len() > 1)
+                                        .map(|(_, name, _)| name.clone())
+                                        .collect(),
+            instrumentations: instrumentation::get_instrumentations(root),
+            main_entry_point,
+            pkg_name,
+        }
+    }
+}
+
+/// Expand a component class name to its fully-qualified form, like Android
+/// does when reading the manifest. Names starting with a dot (`.Main`) and
+/// names without any dot (`Main`) are relative to the package `pkg`, other
+/// names (`com.example.Main`) are already fully qualified. Inner classes
+/// (`.Outer$Inner`) are handled like any other name.
+///
+/// If `pkg` is empty a relative name cannot be expanded, so it is returned
+/// without its leading dot.
+pub fn normalize_component_name(pkg: &str, name: &str) -> String {
+    let relative = match name.strip_prefix('.') {
+        Some(relative) => relative,
+        None if !name.contains('.') => name,
+        None => return name.to_string(),
+    };
+
+    if pkg.is_empty() {
+        relative.to_string()
+    } else {
+        format!("{pkg}.{relative}")
+    }
+}
+
 /// A component can be exported or enabled. Each of these feature have default values
 /// but these default values can be overriden by the developer. This means they have
 /// essentially four states:
@@ -49,37 +194,323 @@ pub struct ManifestContents {
 ///     * default to `false`,
 ///     * explicitely set to `true`,
 ///     * explicitely set to `false`
-#[derive(Debug, PartialEq)]
+///
+/// `Unknown` is used when the attribute is set to a value that cannot be
+/// evaluated statically (e.g., a reference to a boolean resource), and
+/// `ImplicitInvalid` when a component has intent filters but no explicit
+/// `android:exported` in an app targeting API 31 or above, which the platform
+/// rejects at install time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ComponentState {
     Unknown,
     DefaultTrue,
     DefaultFalse,
     ExplicitTrue,
     ExplicitFalse,
+    ImplicitInvalid,
 }
 
+impl ComponentState {
+    /// Check if the state evaluates to `true` (explicitly or by default)
+    pub fn is_true(&self) -> bool {
+        matches!(self, ComponentState::DefaultTrue | ComponentState::ExplicitTrue)
+    }
+}
+
+/// First API level where components with intent filters must explicitly set
+/// `android:exported` (Android 12)
+const EXPLICIT_EXPORT_SDK: u32 = 31;
+
+/// Last API level where content providers are exported by default (Android 4.1)
+const LEGACY_PROVIDER_SDK: u32 = 16;
+
 /// Open an APK, read the contents, and create a `Cursor` of the raw data
 /// for easier handling when parsing the XML data.
 /// This function expects `file_path` to point to an APK (or really, any valid
 /// zip file that contains a file named `AndroidManifest.xml`).
 /// To read an AXML file directly use [`create_cursor_from_axml`] instead.
 ///
+/// Returns an error if the file cannot be read as a zip archive, or if the
+/// manifest is missing or encrypted, see [`create_cursor_from_apk_entry`].
+///
 /// [`create_cursor_from_axml`]: fn.create_cursor_from_axml.html
-pub fn create_cursor_from_apk(file_path: &str) -> Cursor<Vec<u8>> {
+#[cfg(feature = "apk")]
+pub fn create_cursor_from_apk(file_path: &str) -> Result<Cursor<Vec<u8>>, AxmlError> {
+    create_cursor_from_apk_entry(file_path, MANIFEST_ENTRY)
+}
 
-    let mut axml_cursor = Vec::new();
+/// Open a zip file (e.g., an APK), read the contents of the entry
+/// `entry_name`, and create a `Cursor` of the raw data. This is useful to read
+/// other binary XML files than the manifest (e.g.,
+/// `res/xml/network_security_config.xml`) or a manifest stored under another
+/// path (e.g., `base/AndroidManifest.xml`).
+///
+/// If there is no entry with this exact name, the name is looked up
+/// case-insensitively. If this also fails, the returned
+/// [`AxmlError::MissingEntry`] lists the entries with a similar name.
+///
+/// Zip64 archives and entries whose sizes are in a data descriptor are
+/// supported. If the archive has several entries with the same name (a trick
+/// to show a decoy manifest to some tools), the last one in the central
+/// directory is read. Encrypted entries give [`AxmlError::EncryptedEntry`].
+#[cfg(feature = "apk")]
+pub fn create_cursor_from_apk_entry(file_path: &str, entry_name: &str) -> Result<Cursor<Vec<u8>>, AxmlError> {
+    let zipfile = fs::File::open(file_path)?;
+    let mut archive = zip::ZipArchive::new(zipfile)?;
+    let axml_data = read_entry_from_archive(&mut archive, entry_name)?;
 
-    let zipfile = std::fs::File::open(file_path).unwrap();
-    let mut archive = zip::ZipArchive::new(zipfile).unwrap();
-    let mut raw_file = match archive.by_name("AndroidManifest.xml") {
-        Ok(file) => file,
-        Err(..) => {
-            panic!("Error: no AndroidManifest.xml in APK");
-        }
+    Ok(Cursor::new(axml_data))
+}
+
+/// Same as [`create_cursor_from_apk`], but for an APK that is already in
+/// memory (e.g., received over the network). Returns an error if `data` is
+/// not a valid zip file or if it does not contain a manifest.
+#[cfg(feature = "apk")]
+pub fn create_cursor_from_apk_bytes(data: &[u8]) -> Result<Cursor<Vec<u8>>, AxmlError> {
+    create_cursor_from_apk_bytes_entry(data, MANIFEST_ENTRY)
+}
+
+/// Same as [`create_cursor_from_apk_entry`], but for an APK that is already
+/// in memory
+#[cfg(feature = "apk")]
+pub fn create_cursor_from_apk_bytes_entry(data: &[u8], entry_name: &str) -> Result<Cursor<Vec<u8>>, AxmlError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+    let axml_data = read_entry_from_archive(&mut archive, entry_name)?;
+
+    Ok(Cursor::new(axml_data))
+}
+
+/// Parse the manifest of an APK that is already in memory, see
+/// [`create_cursor_from_apk_bytes`]. A manifest stored without compression
+/// is parsed in place, see [`manifest_slice_from_apk`].
+#[cfg(feature = "apk")]
+pub fn parse_apk_bytes(data: &[u8]) -> Result<Rc<RefCell<XmlElement>>, AxmlError> {
+    if let Some(manifest) = manifest_slice_from_apk(data) {
+        return Ok(parser::parse_xml_from_slice(manifest));
+    }
+
+    let axml_cursor = create_cursor_from_apk_bytes(data)?;
+
+    Ok(parser::parse_xml(axml_cursor))
+}
+
+/// Get the manifest of an APK that is already in memory (e.g., mapped with
+/// `memmap2`) as a slice of the APK, without decompressing or copying it.
+/// This only works if the manifest is stored without compression, which is
+/// common in large APKs: `None` is returned otherwise, and the manifest has
+/// to be read with [`create_cursor_from_apk_bytes`]. See [`zip_slice`] for
+/// the details.
+pub fn manifest_slice_from_apk(data: &[u8]) -> Option<&[u8]> {
+    zip_slice::stored_entry(data, MANIFEST_ENTRY)
+}
+
+/// Name of the manifest file in an APK
+const MANIFEST_ENTRY: &str = "AndroidManifest.xml";
+
+/// Read the raw contents of an entry from an opened zip file, see
+/// [`create_cursor_from_apk_entry`]
+#[cfg(feature = "apk")]
+fn read_entry_from_archive<R: Read + Seek>(archive: &mut zip::ZipArchive<R>,
+                                           entry_name: &str) -> Result<Vec<u8>, AxmlError> {
+    let names: Vec<&str> = archive.file_names().collect();
+    let entry_name = match names.iter().find(|name| **name == entry_name) {
+        Some(name) => name.to_string(),
+        None => match names.iter().find(|name| name.eq_ignore_ascii_case(entry_name)) {
+            Some(name) => name.to_string(),
+            None => return Err(AxmlError::MissingEntry {
+                name: entry_name.to_string(),
+                candidates: similar_entry_names(entry_name, &names),
+            }),
+        },
     };
-    raw_file.read_to_end(&mut axml_cursor).expect("Error: cannot read manifest from app");
 
-    Cursor::new(axml_cursor)
+    // The zip crate maps each name to its last entry in the central
+    // directory, see `test_apk_duplicate_manifest`
+    let mut raw_file = match archive.by_name(&entry_name) {
+        Err(zip::result::ZipError::UnsupportedArchive(zip::result::ZipError::PASSWORD_REQUIRED)) => {
+            return Err(AxmlError::EncryptedEntry { name: entry_name });
+        },
+        result => result?,
+    };
+
+    let mut axml_data = Vec::new();
+    raw_file.read_to_end(&mut axml_data)?;
+
+    Ok(axml_data)
+}
+
+/// Maximum number of candidates reported in [`AxmlError::MissingEntry`]
+#[cfg(feature = "apk")]
+const MAX_ENTRY_CANDIDATES: usize = 5;
+
+/// Find the entries whose name is close to `entry_name`: same file name in
+/// another directory, or a small edit distance (ignoring case)
+#[cfg(feature = "apk")]
+fn similar_entry_names(entry_name: &str, names: &[&str]) -> Vec<String> {
+    let wanted = entry_name.to_lowercase();
+    let wanted_file = wanted.rsplit('/').next().unwrap_or(&wanted);
+
+    let mut candidates: Vec<(usize, &str)> = names.iter()
+        .filter_map(|name| {
+            let lower = name.to_lowercase();
+            let distance = edit_distance(&wanted, &lower);
+            let same_file = lower.rsplit('/').next() == Some(wanted_file);
+            (same_file || distance <= 3).then_some((distance, *name))
+        })
+        .collect();
+    candidates.sort();
+
+    candidates.into_iter()
+              .take(MAX_ENTRY_CANDIDATES)
+              .map(|(_, name)| name.to_string())
+              .collect()
+}
+
+/// Levenshtein distance between two strings
+#[cfg(feature = "apk")]
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Binary XML files found in an APK, see [`parse_all_axml_from_apk`]
+#[cfg(feature = "apk")]
+#[derive(Debug, Default)]
+pub struct ApkXmlFiles {
+    /// Parsed documents, indexed by their path in the APK
+    pub files: HashMap<String, Rc<RefCell<XmlElement>>>,
+    /// Entries that looked like binary XML but could not be read or parsed,
+    /// with the reason
+    pub warnings: Vec<String>,
+}
+
+/// Magic bytes at the beginning of a binary XML file: a `ResXmlType` chunk
+/// header with a header size of 8
+const AXML_MAGIC: [u8; 4] = [0x03, 0x00, 0x08, 0x00];
+
+/// Magic bytes at the beginning of a resource table: a `ResTableType` chunk
+/// header with a header size of 12
+const ARSC_MAGIC: [u8; 4] = [0x02, 0x00, 0x0c, 0x00];
+
+/// Kind of a file, see [`detect_file_kind`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    /// Zip archive (APK, AAR, etc.)
+    Apk,
+    /// Android binary XML file
+    Axml,
+    /// Resource table (`resources.arsc`)
+    Arsc,
+    /// Plain-text XML file, e.g., a resource that was not compiled
+    Xml,
+    /// Anything else
+    Unknown,
+}
+
+/// Detect the kind of a file from its first bytes, whatever its name
+pub fn detect_file_kind(data: &[u8]) -> FileKind {
+    if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        FileKind::Apk
+    } else if data.starts_with(&AXML_MAGIC) {
+        FileKind::Axml
+    } else if data.starts_with(&ARSC_MAGIC) {
+        FileKind::Arsc
+    } else if is_text_xml(data) {
+        FileKind::Xml
+    } else {
+        FileKind::Unknown
+    }
+}
+
+/// Check if `data` looks like a plain-text XML document: a `<` after an
+/// optional UTF-8 byte order mark and whitespace
+fn is_text_xml(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    data.iter().find(|c| !c.is_ascii_whitespace()) == Some(&b'<')
+}
+
+/// Parse every binary XML file in an APK: the manifest, but also layouts,
+/// `res/xml/*.xml`, `res/navigation/*.xml`, etc. Binary XML files are detected
+/// from their first bytes, not from their extension.
+///
+/// If `pattern` is given, only the entries whose path matches this glob
+/// pattern are considered (e.g., `res/xml/*`). `*` matches any sequence of
+/// characters except `/`, `**` matches any sequence of characters, and `?`
+/// matches a single character. Filtering is done before reading the entries,
+/// which keeps this fast on large APKs.
+///
+/// Entries that cannot be read or parsed are skipped and recorded in
+/// [`ApkXmlFiles::warnings`].
+#[cfg(feature = "apk")]
+pub fn parse_all_axml_from_apk(file_path: &str, pattern: Option<&str>) -> Result<ApkXmlFiles, AxmlError> {
+    let zipfile = fs::File::open(file_path)?;
+    let mut archive = zip::ZipArchive::new(zipfile)?;
+    let mut result = ApkXmlFiles::default();
+
+    for idx in 0..archive.len() {
+        let mut entry = match archive.by_index(idx) {
+            Ok(entry) => entry,
+            Err(e) => {
+                result.warnings.push(format!("entry #{idx}: {e}"));
+                continue;
+            },
+        };
+
+        let name = entry.name().to_string();
+        if entry.is_dir() || pattern.is_some_and(|p| !glob_matches(p, &name)) {
+            continue;
+        }
+
+        let mut axml_data = Vec::new();
+        if let Err(e) = entry.read_to_end(&mut axml_data) {
+            result.warnings.push(format!("{name}: {e}"));
+            continue;
+        }
+
+        if !axml_data.starts_with(&AXML_MAGIC) {
+            continue;
+        }
+
+        // Do not let a single broken resource abort the whole scan
+        match parser::try_parse_xml_from_slice(&axml_data) {
+            Ok(root) => {
+                result.files.insert(name, root);
+            },
+            Err(e) => result.warnings.push(format!("{name}: {e}")),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Check if `name` matches the glob `pattern`, see [`parse_all_axml_from_apk`]
+#[cfg(feature = "apk")]
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[u8], name: &[u8]) -> bool {
+        match pattern {
+            [] => name.is_empty(),
+            [b'*', b'*', rest @ ..] => (0..=name.len()).any(|i| matches(rest, &name[i..])),
+            [b'*', rest @ ..] => {
+                let segment_len = name.iter().position(|c| *c == b'/').unwrap_or(name.len());
+                (0..=segment_len).any(|i| matches(rest, &name[i..]))
+            },
+            [b'?', rest @ ..] => !name.is_empty() && name[0] != b'/' && matches(rest, &name[1..]),
+            [c, rest @ ..] => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+
+    matches(pattern.as_bytes(), name.as_bytes())
 }
 
 /// Open an AXML file, read the contents, and create a `Cursor` of the raw data
@@ -98,123 +529,1482 @@ pub fn create_cursor_from_axml(file_path: &str) -> Cursor<Vec<u8>> {
     Cursor::new(axml_cursor)
 }
 
-pub fn get_manifest_contents(axml_cursor: Cursor<Vec<u8>>) -> Rc<RefCell<XmlElement>> {
+/// Same as [`create_cursor_from_axml`], but map the file in memory instead
+/// of reading it, so that large files (e.g., resource tables) are only
+/// loaded as the parser reaches them, and are not copied.
+///
+/// The mapping reflects the file while it is in use: if the file is modified
+/// or truncated by another process before the cursor is dropped, the parser
+/// may see inconsistent data, or the process may be killed (`SIGBUS`) when it
+/// reads past the new end of the file. Only use this on files that do not
+/// change while they are parsed.
+#[cfg(feature = "mmap")]
+pub fn create_cursor_from_axml_mmap(file_path: &str) -> Result<Cursor<Mmap>, AxmlError> {
+    let file = fs::File::open(file_path)?;
+    // SAFETY: see the caveats above, the file must not change while mapped
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(Cursor::new(mmap))
+}
+
+pub fn get_manifest_contents<R: Read + Seek>(axml_cursor: R) -> Rc<RefCell<XmlElement>> {
     parser::parse_xml(axml_cursor)
 }
 
-/// Use BFS tree traversal to get all element of a given type
-fn find_elements_by_type(parsed_xml: &Rc<RefCell<XmlElement>>, element_type: &str) -> Vec<Rc<RefCell<XmlElement>>> {
+/// Read the root element of a binary XML document without its children,
+/// stopping as soon as it is decoded
+fn read_root_element<R: Read + Seek>(axml_cursor: R) -> Option<OwnedXmlElement> {
+    let mut reader = reader::AxmlReader::new(axml_cursor);
+
+    while let Some(event) = reader.next_raw_event() {
+        if let reader::RawEvent::StartElement(raw_element) = event {
+            return Some(OwnedXmlElement {
+                element_type: reader.string(raw_element.name).into(),
+                namespace: reader.element_namespace(&raw_element),
+                attributes: reader.decode_attributes(&raw_element),
+                children: Vec::new(),
+            });
+        }
+    }
+
+    None
+}
+
+/// Get the package name of an app from its manifest, without parsing the
+/// whole document: reading stops at the `<manifest>` element, which is much
+/// faster than [`get_manifest_contents`] when only the package name is needed
+/// (e.g., to index a large number of APKs).
+pub fn get_package_name<R: Read + Seek>(axml_cursor: R) -> Result<String, AxmlError> {
+    read_root_element(axml_cursor)
+        .and_then(|manifest| manifest.attributes.find_local("package").map(|attr| attr.value.clone()))
+        .ok_or_else(|| AxmlError::MissingAttribute {
+            element: String::from("manifest"),
+            attribute: String::from("package"),
+        })
+}
+
+/// Get the version of an app from its manifest without parsing the whole
+/// document, see [`get_package_name`] and [`version::get_version_info`]
+pub fn get_version_info_fast<R: Read + Seek>(axml_cursor: R) -> VersionInfo {
+    read_root_element(axml_cursor)
+        .map(|manifest| version::get_version_info(&&manifest))
+        .unwrap_or_default()
+}
+
+/// Use DFS tree traversal to get all element of a given type, in document order
+pub(crate) fn find_elements_by_type<E: ElementRef>(parsed_xml: &E, element_type: &str) -> Vec<E> {
+    find_elements_by_types(parsed_xml, &[element_type])
+}
+
+/// Use DFS tree traversal to get all element of any of the given types, in
+/// document order
+pub(crate) fn find_elements_by_types<E: ElementRef>(parsed_xml: &E, element_types: &[&str]) -> Vec<E> {
     let mut result = Vec::new();
-    let mut stack = vec![Rc::clone(parsed_xml)];
+    let mut stack = vec![parsed_xml.clone()];
 
     while let Some(element) = stack.pop() {
-        let borrowed = element.borrow();
-        if borrowed.element_type == element_type {
-            result.push(Rc::clone(&element));
-        }
-        for child in &borrowed.children {
-            stack.push(Rc::clone(child));
+        if element_types.contains(&element.element_type().as_str()) {
+            result.push(element.clone());
         }
+        stack.extend(element.children().into_iter().rev());
     }
 
     result
 }
 
-/// Check if a component is exposed which is the case if it is both enabled and exported
-/// Both of these properties can either be explicitely set (as parameters in the compoennt
-/// declaration in the manifest) or left to their default state.
-/// The default state depends on the presence or not of intent filters: if there is an intent
-/// filter, the assumption is that the compoennt is meants to be available to other apps, and so it
-/// is exported by default, otherwise not.
-fn is_component_exposed(component: &Rc<RefCell<XmlElement>>) -> bool {
-    let mut _enabled_state = ComponentState::DefaultTrue;
-    let mut exported_state = ComponentState::Unknown;
-
-    if let Some(enabled) = component.borrow().attributes.get("android:enabled") {
-        if enabled == "false" {
-            return false;
-        } else {
-            _enabled_state = ComponentState::ExplicitTrue;
+/// Value of the `package` attribute of the `<manifest>` element, or an empty
+/// string if it is missing
+pub(crate) fn manifest_package<E: ElementRef>(root: &E) -> String {
+    find_elements_by_type(root, "manifest")
+        .first()
+        .and_then(|manifest| manifest.get_attr("package"))
+        .unwrap_or_default()
+}
+
+/// Action of the intent filter of the launcher activity
+const ACTION_MAIN: &str = "android.intent.action.MAIN";
+/// Categories of the intent filter of the launcher activity: regular launcher
+/// and Android TV launcher
+const LAUNCHER_CATEGORIES: [&str; 2] = [
+    "android.intent.category.LAUNCHER",
+    "android.intent.category.LEANBACK_LAUNCHER",
+];
+
+/// Check if a component has an intent filter with `ACTION_MAIN` and one of
+/// the launcher categories
+pub(crate) fn is_launcher_component<E: ElementRef>(component: &E) -> bool {
+    extract_intent_filters(component)
+        .iter()
+        .any(|filter| {
+            filter.has_action(ACTION_MAIN) &&
+            LAUNCHER_CATEGORIES.iter().any(|category| filter.has_category(category))
+        })
+}
+
+/// Find the launcher entry points of an app: the activities and
+/// activity-aliases with an intent filter for `ACTION_MAIN` and either
+/// `CATEGORY_LAUNCHER` or `CATEGORY_LEANBACK_LAUNCHER`. An app can have
+/// several launcher icons, so all the candidates are returned.
+///
+/// Aliases are resolved to their `android:targetActivity`, and names are
+/// fully qualified (see [`normalize_component_name`]). Enabled components
+/// come first, followed by the ones that are explicitly disabled, each group
+/// in document order, without duplicates.
+pub fn find_main_entry_points<E: ElementRef>(root: &E) -> Vec<String> {
+    let pkg_name = manifest_package(root);
+
+    let mut enabled = Vec::new();
+    let mut disabled = Vec::new();
+
+    for component in find_elements_by_types(root, &["activity", "activity-alias"]) {
+        if !is_launcher_component(&component) {
+            continue;
         }
-    }
 
-    if let Some(exported) = component.borrow().attributes.get("android:exported") {
-        if exported == "false" {
-            return false;
+        let name = if component.element_type() == "activity-alias" {
+            component.get_attr("targetActivity").or_else(|| component.get_attr("name"))
+        } else {
+            component.get_attr("name")
+        };
+        let Some(name) = name else {
+            continue;
+        };
+
+        let name = normalize_component_name(&pkg_name, &name);
+        if component.get_android_attribute("enabled").and_then(|attr| attr.as_bool()) == Some(false) {
+            disabled.push(name);
         } else {
-            exported_state = ComponentState::ExplicitTrue;
+            enabled.push(name);
         }
     }
 
-    // If the component has intent filters then the default exported value is `true`, otherwise
-    // `false`. This is not the case for content providers though, which usually have explicit
-    // values anyway.
-    if exported_state == ComponentState::Unknown {
-        for item in component.borrow().children.iter() {
-            if item.borrow().element_type == "intent-filter" {
-                exported_state = ComponentState::DefaultTrue;
-                break;
-            }
-        }
-        if exported_state == ComponentState::Unknown {
-            exported_state = ComponentState::DefaultFalse;
+    let mut entry_points: Vec<String> = Vec::with_capacity(enabled.len() + disabled.len());
+    for name in enabled.into_iter().chain(disabled) {
+        if !entry_points.contains(&name) {
+            entry_points.push(name);
         }
     }
 
-    // At this point we know the component is enabled so we just need to check if it is also
-    // exported. Also, if the component is explicitly not exported then we return early so here we
-    // do not have to check all the cases
-    match exported_state {
-        ComponentState::DefaultFalse => false,
-        ComponentState::DefaultTrue => true,
-        ComponentState::ExplicitTrue => true,
-        _ => panic!("never going to happen")
+    entry_points
+}
+
+/// Function resolving the ID of a boolean resource (e.g., `@bool/is_exported`)
+/// to its value, if known
+pub type BoolResolver<'a> = &'a dyn Fn(u32) -> Option<bool>;
+
+/// Resolver that does not know any resource
+pub(crate) fn no_resolver(_id: u32) -> Option<bool> {
+    None
+}
+
+/// State of a boolean attribute that is explicitly set on a component, or
+/// `None` if the attribute is absent. Only the attribute of the `android`
+/// namespace counts: like PackageManager, an attribute named
+/// `android:exported` whose prefix is bound to another URI is ignored.
+fn explicit_state<E: ElementRef>(component: &E, local_name: &str, resolve: BoolResolver) -> Option<ComponentState> {
+    let attr = component.get_android_attribute(local_name)?;
+
+    // Attributes that are present but are not booleans (e.g., references to a
+    // resource) cannot be evaluated unless the resource can be resolved
+    let value = attr.as_bool().or_else(|| attr.as_reference().and_then(resolve));
+
+    Some(match value {
+        Some(true) => ComponentState::ExplicitTrue,
+        Some(false) => ComponentState::ExplicitFalse,
+        None => ComponentState::Unknown,
+    })
+}
+
+/// Get the exported state of a component.
+/// The default state depends on the presence or not of intent filters: if there is an intent
+/// filter, the assumption is that the component is meant to be available to other apps, and so
+/// it is exported by default, otherwise not. Since Android 12, apps targeting API 31 or above
+/// must set `android:exported` explicitly on such components: this is reported as
+/// `ComponentState::ImplicitInvalid` when the target SDK is at least 31.
+///
+/// Content providers do not have intent filters: they are exported by default if either the
+/// minimum or the target SDK is 16 or lower, and not exported otherwise.
+pub fn get_exported_state<E: ElementRef>(component: &E, sdk_versions: &SdkVersions) -> ComponentState {
+    exported_state(component, sdk_versions, &no_resolver)
+}
+
+fn exported_state<E: ElementRef>(component: &E,
+                                 sdk_versions: &SdkVersions,
+                                 resolve: BoolResolver) -> ComponentState {
+    if let Some(state) = explicit_state(component, "exported", resolve) {
+        return state;
+    }
+
+    if component.element_type() == "provider" {
+        let legacy = |sdk: Option<u32>| sdk.is_some_and(|sdk| sdk <= LEGACY_PROVIDER_SDK);
+        return if legacy(sdk_versions.min) || legacy(sdk_versions.target) {
+            ComponentState::DefaultTrue
+        } else {
+            ComponentState::DefaultFalse
+        };
+    }
+
+    let has_intent_filter = !extract_intent_filters(component).is_empty();
+
+    match (has_intent_filter, sdk_versions.target) {
+        (true, Some(sdk)) if sdk >= EXPLICIT_EXPORT_SDK => ComponentState::ImplicitInvalid,
+        (true, _) => ComponentState::DefaultTrue,
+        (false, _) => ComponentState::DefaultFalse,
+    }
+}
+
+/// Get the enabled state of a component. Components are enabled by default.
+pub fn get_enabled_state<E: ElementRef>(component: &E) -> ComponentState {
+    enabled_state(component, &no_resolver)
+}
+
+fn enabled_state<E: ElementRef>(component: &E, resolve: BoolResolver) -> ComponentState {
+    explicit_state(component, "enabled", resolve).unwrap_or(ComponentState::DefaultTrue)
+}
+
+/// Get the exported state of a component of a parsed manifest, see
+/// [`get_exported_state`]. The SDK versions are read from the manifest the
+/// component belongs to, found through its ancestors.
+pub fn component_export_state(component: &Rc<RefCell<XmlElement>>) -> ComponentState {
+    let manifest = component.borrow().ancestors().last().unwrap_or_else(|| Rc::clone(component));
+    get_exported_state(component, &sdk::get_sdk_versions(&manifest))
+}
+
+/// Get the enabled state of a component of a parsed manifest, see
+/// [`get_enabled_state`]
+pub fn component_enabled_state(component: &Rc<RefCell<XmlElement>>) -> ComponentState {
+    get_enabled_state(component)
+}
+
+
+/// Types of components that can be exposed to other apps
+pub(crate) const COMPONENT_TYPES: [&str; 5] = [
+    "activity",
+    "activity-alias",
+    "service",
+    "receiver",
+    "provider",
+];
+
+/// Exposure information about a component, see [`get_exposure_report`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExposedComponent {
+    /// Type of the component (`activity`, `activity-alias`, `service`,
+    /// `receiver`, or `provider`)
+    pub kind: String,
+    /// Fully-qualified name of the component
+    pub name: String,
+    /// For an `activity-alias`, fully-qualified name of the activity it
+    /// points to (`android:targetActivity`)
+    pub target_activity: Option<String>,
+    /// Exported state, see [`get_exported_state`]
+    pub exported: ComponentState,
+    /// Enabled state, see [`get_enabled_state`]. An alias is only enabled if
+    /// its target activity is enabled too, so the state of the target is
+    /// reported here if the alias is enabled but its target is not.
+    pub enabled: ComponentState,
+    /// Enabled state of the `<application>` element, see
+    /// [`get_enabled_state`]. The components of a disabled application keep
+    /// their own state in `enabled`, but none of them is exposed.
+    pub application_enabled: ComponentState,
+    /// Permission a caller needs to hold to interact with the component
+    /// (`android:permission`)
+    pub protected_by_permission: Option<GuardPermission>,
+    /// For a provider, permission needed to read from it
+    /// (`android:readPermission`), which takes precedence over
+    /// `android:permission` for reads
+    pub read_permission: Option<GuardPermission>,
+    /// For a provider, permission needed to write to it
+    /// (`android:writePermission`), which takes precedence over
+    /// `android:permission` for writes
+    pub write_permission: Option<GuardPermission>,
+    /// Whether the component declares at least one intent filter
+    pub has_intent_filter: bool,
+    /// For a provider, whether parts of it can be reached even if it is not
+    /// exported or is protected by a permission: `android:grantUriPermissions`
+    /// is set, or it has `<grant-uri-permission>` or `<path-permission>`
+    /// children
+    pub partially_exposed: bool,
+    /// Whether the component is declared more than once in the manifest, in
+    /// which case the attributes of its declarations are merged, the later
+    /// ones winning
+    pub duplicated: bool,
+}
+
+impl ExposedComponent {
+    /// Check if a component is exposed which is the case if it is both enabled and exported
+    /// Both of these properties can either be explicitely set (as parameters in the component
+    /// declaration in the manifest) or left to their default state, see [`get_exported_state`].
+    /// Components whose state cannot be evaluated (`Unknown`) or that would be rejected at
+    /// install time (`ImplicitInvalid`) are not considered as exposed, and neither are the
+    /// components of an application that is not enabled.
+    pub fn is_exposed(&self) -> bool {
+        self.application_enabled.is_true() && self.enabled.is_true() && self.exported.is_true()
     }
 }
 
-/// Parse an app's manifest and get the list of exposed components
-/// We first check if the app has the `android:enabled` component set, which would influence the
-/// state of all the components in the app
-pub fn get_exposed_components(parsed_xml: Rc<RefCell<XmlElement>>) -> Option<HashMap<String, Vec<Rc<RefCell<XmlElement>>>>> {
-    // Checking if the `<application>` tag has the `enabled` attribute set to `false`
-    let application = find_elements_by_type(&parsed_xml, "application").pop()?;
-    if let Some(enabled) = application.borrow().attributes.get("android:enabled") {
-        if enabled == "false" {
-            return None;
+/// Group the components of the app by type and fully-qualified name, in
+/// order of first declaration. Manifest mergers sometimes leave several
+/// declarations of the same component, which PackageManager treats as a
+/// single one. Components without a name are never grouped.
+fn group_components<E: ElementRef>(parsed_xml: &E, pkg_name: &str) -> Vec<(String, String, Vec<E>)> {
+    let mut groups: Vec<(String, String, Vec<E>)> = Vec::new();
+
+    for component in find_elements_by_types(parsed_xml, &COMPONENT_TYPES) {
+        let kind = component.element_type().to_string();
+        let name = component.get_attr("name")
+                            .map(|name| normalize_component_name(pkg_name, &name))
+                            .unwrap_or_default();
+
+        let existing = groups.iter_mut()
+                             .find(|(k, n, _)| !name.is_empty() && *k == kind && *n == name);
+        match existing {
+            Some((_, _, declarations)) => declarations.push(component),
+            None => groups.push((kind, name, vec![component])),
         }
     }
 
-    let mut components = HashMap::new();
+    groups
+}
 
-    components.insert(
-        String::from("activity"),
-        find_elements_by_type(&parsed_xml, "activity")
-                .into_iter()
-                .filter(is_component_exposed)
-                .collect()
-    );
-    components.insert(
-        String::from("service"),
-        find_elements_by_type(&parsed_xml, "service")
-                .into_iter()
-                .filter(is_component_exposed)
-                .collect()
-    );
-    components.insert(
-        String::from("provider"),
-        find_elements_by_type(&parsed_xml, "provider")
-                .into_iter()
-                .filter(is_component_exposed)
-                .collect()
-    );
-    components.insert(
-        String::from("receiver"),
-        find_elements_by_type(&parsed_xml, "receiver")
-                .into_iter()
-                .filter(is_component_exposed)
-                .collect()
+/// Get the fully-qualified names of the components declared more than once
+/// in the manifest, see [`ManifestContents::duplicate_components`]
+pub fn find_duplicate_components<E: ElementRef>(parsed_xml: &E) -> Vec<String> {
+    group_components(parsed_xml, &manifest_package(parsed_xml))
+        .into_iter()
+        .filter(|(_, _, declarations)| declarations.len() > 1)
+        .map(|(_, name, _)| name)
+        .collect()
+}
+
+/// Data shared by the exposure computations of all the components of an app
+struct ExposureContext<'a> {
+    pkg_name: String,
+    sdk_versions: SdkVersions,
+    declared_permissions: Vec<DeclaredPermission>,
+    application_enabled: ComponentState,
+    resolve: BoolResolver<'a>,
+}
+
+/// Compute the exposure information of a single component declaration
+fn component_report<C: ElementRef>(ctx: &ExposureContext, component: &C, kind: String, name: String) -> ExposedComponent {
+    let target_activity = if kind == "activity-alias" {
+        component.get_attr("targetActivity")
+                 .map(|target| normalize_component_name(&ctx.pkg_name, &target))
+    } else {
+        None
+    };
+
+    // Like the exported and enabled states, only the attributes of the real
+    // android namespace protect the component
+    let guard = |attr: &str| {
+        component.get_android_attribute(attr)
+                 .map(|permission| GuardPermission::new(&permission.value, &ctx.declared_permissions))
+    };
+    let children = component.children();
+    let has_child = |element_type: &str| children.iter().any(|child| child.element_type() == element_type);
+    let partially_exposed = kind == "provider" && (
+        component.get_android_attribute("grantUriPermissions").and_then(|attr| attr.as_bool()) == Some(true) ||
+        has_child("grant-uri-permission") ||
+        has_child("path-permission")
     );
 
+    ExposedComponent {
+        exported: exported_state(component, &ctx.sdk_versions, ctx.resolve),
+        enabled: enabled_state(component, ctx.resolve),
+        application_enabled: ctx.application_enabled,
+        has_intent_filter: !extract_intent_filters(component).is_empty(),
+        partially_exposed,
+        protected_by_permission: guard("permission"),
+        read_permission: guard("readPermission"),
+        write_permission: guard("writePermission"),
+        duplicated: false,
+        kind,
+        name,
+        target_activity,
+    }
+}
+
+/// Compute the exposure information of every component of the app, in
+/// document order, along with the corresponding elements. A component
+/// declared several times is reported once, with the attributes of all its
+/// declarations merged (later ones winning), along with its last declaration.
+pub(crate) fn exposure_report_with_elements<E: ElementRef>(parsed_xml: &E, resolve: BoolResolver) -> Vec<(E, ExposedComponent)> {
+    let ctx = ExposureContext {
+        pkg_name: manifest_package(parsed_xml),
+        sdk_versions: sdk::get_sdk_versions(parsed_xml),
+        declared_permissions: permissions::get_declared_permissions(parsed_xml),
+        application_enabled: find_elements_by_type(parsed_xml, "application")
+                                 .pop()
+                                 .map_or(ComponentState::DefaultTrue, |application| enabled_state(&application, resolve)),
+        resolve,
+    };
+
+    let mut reports: Vec<(E, ExposedComponent)> = group_components(parsed_xml, &ctx.pkg_name)
+        .into_iter()
+        .filter_map(|(kind, name, declarations)| {
+            let (first, rest) = declarations.split_first()?;
+            let last = declarations.last()?.clone();
+
+            let report = if rest.is_empty() {
+                component_report(&ctx, first, kind, name)
+            } else {
+                let merged = rest.iter().fold(first.to_owned_element(), |mut merged, declaration| {
+                    merged.merge(&declaration.to_owned_element());
+                    merged
+                });
+                ExposedComponent {
+                    duplicated: true,
+                    ..component_report(&ctx, &&merged, kind, name)
+                }
+            };
+
+            Some((last, report))
+        })
+        .collect();
+
+    // An alias is only enabled if its target activity is enabled too
+    let activities_enabled: HashMap<String, ComponentState> = reports
+        .iter()
+        .filter(|(_, report)| report.kind == "activity")
+        .map(|(_, report)| (report.name.clone(), report.enabled))
+        .collect();
+    for (_, report) in reports.iter_mut() {
+        let target_enabled = report.target_activity
+                                   .as_ref()
+                                   .and_then(|target| activities_enabled.get(target));
+        if let Some(&target_enabled) = target_enabled {
+            if report.enabled.is_true() && !target_enabled.is_true() {
+                report.enabled = target_enabled;
+            }
+        }
+    }
+
+    reports
+}
+
+
+/// Get the exposure information of every component of the app (activities,
+/// activity-aliases, services, receivers, and providers), in document order.
+/// Use [`ExposedComponent::is_exposed`] to only keep the exposed ones.
+///
+/// When `android:exported` or `android:enabled` refers to a boolean resource
+/// (e.g., `@bool/is_exported`), the state is reported as
+/// `ComponentState::Unknown`, see [`get_exposure_report_with_resolver`].
+pub fn get_exposure_report<E: ElementRef>(parsed_xml: &E) -> Vec<ExposedComponent> {
+    get_exposure_report_with_resolver(parsed_xml, &no_resolver)
+}
+
+/// Same as [`get_exposure_report`], but references to boolean resources in
+/// `android:exported` and `android:enabled` are resolved with `resolve`
+/// (e.g., using the values from the app's `resources.arsc`). References that
+/// cannot be resolved are still reported as `ComponentState::Unknown`.
+pub fn get_exposure_report_with_resolver<E: ElementRef>(parsed_xml: &E, resolve: BoolResolver) -> Vec<ExposedComponent> {
+    exposure_report_with_elements(parsed_xml, resolve)
+        .into_iter()
+        .map(|(_, report)| report)
+        .collect()
+}
+
+/// Parse an app's manifest and get the list of exposed components, indexed by
+/// component type (including `activity-alias`)
+/// Returns `None` if the manifest has no `<application>` element. If the
+/// application is disabled (`android:enabled` on `<application>`), or if its
+/// state cannot be evaluated, no component is exposed and the lists are empty:
+/// use [`get_exposure_report`] to still get the components, with their
+/// [`ExposedComponent::application_enabled`] state.
+/// This works on both the `Rc<RefCell<XmlElement>>` tree returned by the parser
+/// and on its owned form (`&OwnedXmlElement`), see [`owned::ElementRef`].
+/// See [`get_exposure_report`] for more details on each component.
+pub fn get_exposed_components<E: ElementRef>(parsed_xml: E) -> Option<HashMap<String, Vec<E>>> {
+    find_elements_by_type(&parsed_xml, "application").pop()?;
+
+    let mut components: HashMap<String, Vec<E>> = COMPONENT_TYPES
+        .iter()
+        .map(|kind| (kind.to_string(), Vec::new()))
+        .collect();
+
+    for (element, report) in exposure_report_with_elements(&parsed_xml, &no_resolver) {
+        if report.is_exposed() {
+            if let Some(list) = components.get_mut(&report.kind) {
+                list.push(element);
+            }
+        }
+    }
+
     Some(components)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn parse(data: Vec<u8>) -> Rc<RefCell<XmlElement>> {
+        parser::parse_xml(Cursor::new(data))
+    }
+
+    fn exposed_names(components: &HashMap<String, Vec<Rc<RefCell<XmlElement>>>>, kind: &str) -> Vec<String> {
+        components[kind].iter()
+                        .map(|c| c.borrow().get_attr("name").unwrap().to_string())
+                        .collect()
+    }
+
+    #[test]
+    fn test_exposure_with_custom_android_prefix() {
+        // The android namespace is bound to `a` instead of `android`: a lookup
+        // on "android:exported" would miss the attribute and consider the
+        // activity exported because of its intent filter.
+        let root = parse(AxmlBuilder::new()
+            .namespace("a", test_utils::ANDROID_NS)
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("a:name", ".Hidden"), Attr::boolean("a:exported", false)])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .start("activity", vec![Attr::string("a:name", ".Visible")])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let components = get_exposed_components(root).unwrap();
+        assert_eq!(exposed_names(&components, "activity"), vec![".Visible"]);
+    }
+
+    #[test]
+    fn test_exposure_ignores_fake_android_namespace() {
+        // `android` is bound to a look-alike URI, which PackageManager does not
+        // read: only `a:exported` is in the real android namespace
+        let root = parse(AxmlBuilder::new()
+            .namespace("android", "http://schemas.android.com/apk/res/androidx")
+            .namespace("a", test_utils::ANDROID_NS)
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("a:name", ".Fake"), Attr::boolean("android:exported", false)])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .start("activity", vec![Attr::string("a:name", ".Real"), Attr::boolean("a:exported", false)])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let components = get_exposed_components(root).unwrap();
+        assert_eq!(exposed_names(&components, "activity"), vec![".Fake"]);
+    }
+
+    #[test]
+    fn test_reference_exported_is_not_exposed() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![])
+            .start("application", vec![])
+            .start("receiver", vec![Attr::string("android:name", ".Ref"),
+                                    Attr::reference("android:exported", 0x7f050001)])
+            .element("intent-filter", vec![])
+            .end("receiver")
+            .start("receiver", vec![Attr::string("android:name", ".Explicit"),
+                                    Attr::boolean("android:exported", true)])
+            .end("receiver")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let components = get_exposed_components(root).unwrap();
+        assert_eq!(exposed_names(&components, "receiver"), vec![".Explicit"]);
+    }
+
+    #[test]
+    fn test_disabled_application_with_custom_prefix() {
+        let build = |enabled: Attr| parse(AxmlBuilder::new()
+            .namespace("a", test_utils::ANDROID_NS)
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![enabled])
+            .element("activity", vec![Attr::string("a:name", ".Main"), Attr::boolean("a:exported", true)])
+            .element("service", vec![Attr::string("a:name", ".Sync"),
+                                     Attr::boolean("a:exported", true),
+                                     Attr::boolean("a:enabled", false)])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        // The components are still reported, with their own state
+        let disabled = build(Attr::boolean("a:enabled", false));
+        let report = get_exposure_report(&disabled);
+        assert_eq!(report.len(), 2);
+        assert!(report.iter().all(|c| c.application_enabled == ComponentState::ExplicitFalse));
+        assert_eq!(report[0].enabled, ComponentState::DefaultTrue);
+        assert_eq!(report[1].enabled, ComponentState::ExplicitFalse);
+        assert!(report.iter().all(|c| !c.is_exposed()));
+        assert!(exposed_names(&get_exposed_components(disabled).unwrap(), "activity").is_empty());
+
+        // A reference cannot be evaluated without resolving it
+        let reference = build(Attr::reference("a:enabled", 0x7f050003));
+        let report = get_exposure_report(&reference);
+        assert_eq!(report[0].application_enabled, ComponentState::Unknown);
+        assert!(!report[0].is_exposed());
+        let resolved = get_exposure_report_with_resolver(&reference, &|id| (id == 0x7f050003).then_some(true));
+        assert_eq!(resolved[0].application_enabled, ComponentState::ExplicitTrue);
+        assert!(resolved[0].is_exposed());
+
+        let enabled = build(Attr::boolean("a:enabled", true));
+        assert_eq!(exposed_names(&get_exposed_components(enabled).unwrap(), "activity"), vec![".Main"]);
+
+        // Only a missing application is an error
+        let missing = parse(AxmlBuilder::new().start("manifest", vec![]).end("manifest").build());
+        assert!(get_exposed_components(missing).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_parse_apk_bytes() {
+        let manifest = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .end("manifest")
+            .build();
+        let apk = test_utils::zip_archive(&[("classes.dex", b"dex\n035"),
+                                            ("AndroidManifest.xml", &manifest)]);
+
+        let root = parse_apk_bytes(&apk).unwrap();
+        assert_eq!(root.borrow().get_attr("package"), Some("com.example"));
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_parse_apk_bytes_errors() {
+        let apk = test_utils::zip_archive(&[("classes.dex", b"dex\n035")]);
+
+        assert!(matches!(parse_apk_bytes(&apk), Err(AxmlError::MissingEntry { .. })));
+        assert!(matches!(parse_apk_bytes(b"not a zip"), Err(AxmlError::Zip(_))));
+    }
+
+    /// Read an APK fixture of `tests/data`, and get the package of its manifest
+    #[cfg(feature = "apk")]
+    fn apk_fixture_package(name: &str) -> Option<String> {
+        let path = format!("{}/tests/data/{name}", env!("CARGO_MANIFEST_DIR"));
+        let from_file = parser::parse_xml(create_cursor_from_apk(&path).unwrap());
+        let from_bytes = parse_apk_bytes(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(*from_file.borrow(), *from_bytes.borrow());
+
+        let package = from_file.borrow().get_attr("package").map(String::from);
+        package
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_apk_duplicate_manifest() {
+        // A decoy manifest comes first in the archive, the real one last
+        assert_eq!(apk_fixture_package("apk-duplicate-manifest.apk").as_deref(), Some("com.example.app"));
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_apk_zip64() {
+        // Zip64 end of central directory, and sizes in zip64 extra fields
+        assert_eq!(apk_fixture_package("apk-zip64.apk").as_deref(), Some("com.example.app"));
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_apk_data_descriptor() {
+        // Sizes and CRCs after the data of the entries (flag bit 3)
+        assert_eq!(apk_fixture_package("apk-data-descriptor.apk").as_deref(), Some("com.example.app"));
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_manifest_slice_from_apk() {
+        // Stored manifest, read in place
+        let stored = include_bytes!("../tests/data/apk-stored-zip64.apk");
+        let manifest = manifest_slice_from_apk(stored).unwrap();
+        assert!(stored.as_ptr_range().contains(&manifest.as_ptr()));
+        assert_eq!(apk_fixture_package("apk-stored-zip64.apk").as_deref(), Some("com.example.app"));
+
+        // Deflated manifest, decompressed by the zip crate
+        let deflated = include_bytes!("../tests/data/apk-zip64.apk");
+        assert_eq!(manifest_slice_from_apk(deflated), None);
+        assert_eq!(apk_fixture_package("apk-zip64.apk").as_deref(), Some("com.example.app"));
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_apk_encrypted_manifest() {
+        let mut apk = test_utils::zip_archive(&[("AndroidManifest.xml", b"manifest")]);
+        // Set the encryption flag in the local header and in the central
+        // directory
+        apk[6] |= 1;
+        let central = apk.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        apk[central + 8] |= 1;
+
+        match parse_apk_bytes(&apk) {
+            Err(AxmlError::EncryptedEntry { name }) => assert_eq!(name, "AndroidManifest.xml"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_apk_encrypted_fixture() {
+        // Entries encrypted with a password (ZipCrypto)
+        let path = format!("{}/tests/data/apk-encrypted-manifest.apk", env!("CARGO_MANIFEST_DIR"));
+        match create_cursor_from_apk(&path) {
+            Err(AxmlError::EncryptedEntry { name }) => assert_eq!(name, "AndroidManifest.xml"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(matches!(parse_apk_bytes(&fs::read(&path).unwrap()), Err(AxmlError::EncryptedEntry { .. })));
+        assert!(matches!(create_cursor_from_apk_entry(&path, "classes.dex"), Err(AxmlError::EncryptedEntry { .. })));
+
+        // Other errors are returned too, instead of panicking
+        let path = format!("{}/tests/data/AndroidManifest.xml", env!("CARGO_MANIFEST_DIR"));
+        assert!(create_cursor_from_apk(&path).is_err());
+    }
+
+    #[test]
+    fn test_detect_file_kind() {
+        let manifest = AxmlBuilder::new().element("manifest", vec![]).build();
+        // Local file header, and end of central directory of an empty archive
+        assert_eq!(detect_file_kind(b"PK\x03\x04\x14\x00"), FileKind::Apk);
+        assert_eq!(detect_file_kind(b"PK\x05\x06\x00\x00"), FileKind::Apk);
+        assert_eq!(detect_file_kind(&manifest), FileKind::Axml);
+        assert_eq!(detect_file_kind(&[0x02, 0x00, 0x0c, 0x00, 0x00]), FileKind::Arsc);
+        assert_eq!(detect_file_kind(b"<manifest/>"), FileKind::Xml);
+        assert_eq!(detect_file_kind(b"\xef\xbb\xbf\n  <?xml version=\"1.0\"?>"), FileKind::Xml);
+        assert_eq!(detect_file_kind(b"manifest"), FileKind::Unknown);
+        assert_eq!(detect_file_kind(b""), FileKind::Unknown);
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_glob_matches() {
+        assert!(glob_matches("res/xml/*", "res/xml/network_security_config.xml"));
+        assert!(!glob_matches("res/xml/*", "res/xml-v21/config.xml"));
+        assert!(!glob_matches("res/*.xml", "res/xml/config.xml"));
+        assert!(glob_matches("res/**.xml", "res/xml/config.xml"));
+        assert!(glob_matches("res/layout/activity_?.xml", "res/layout/activity_a.xml"));
+        assert!(glob_matches("AndroidManifest.xml", "AndroidManifest.xml"));
+        assert!(!glob_matches("AndroidManifest.xml", "base/AndroidManifest.xml"));
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_parse_all_axml_from_apk() {
+        let manifest = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .end("manifest")
+            .build();
+        let config = AxmlBuilder::new()
+            .start("network-security-config", vec![])
+            .element("base-config", vec![])
+            .end("network-security-config")
+            .build();
+        let mut broken = config.clone();
+        broken.truncate(40);
+        let apk = test_utils::zip_archive(&[("AndroidManifest.xml", &manifest),
+                                            ("res/xml/network_security_config.xml", &config),
+                                            ("res/xml/broken.xml", &broken),
+                                            ("res/raw/data.xml", b"<plain/>")]);
+
+        let path = std::env::temp_dir().join(format!("rusty-axml-test-{}.apk", std::process::id()));
+        fs::write(&path, apk).unwrap();
+        let path = path.to_str().unwrap();
+
+        let all = parse_all_axml_from_apk(path, None).unwrap();
+        let filtered = parse_all_axml_from_apk(path, Some("res/xml/*")).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let mut names: Vec<&String> = all.files.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["AndroidManifest.xml", "res/xml/network_security_config.xml"]);
+        assert_eq!(all.files["res/xml/network_security_config.xml"].borrow().element_type,
+                   "network-security-config");
+        assert_eq!(all.warnings.len(), 1);
+        assert!(all.warnings[0].starts_with("res/xml/broken.xml"));
+
+        assert_eq!(filtered.files.len(), 1);
+        assert!(filtered.files.contains_key("res/xml/network_security_config.xml"));
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_create_cursor_from_apk_entry() {
+        let config = AxmlBuilder::new()
+            .start("network-security-config", vec![])
+            .end("network-security-config")
+            .build();
+        let apk = test_utils::zip_archive(&[("base/AndroidManifest.xml", b"manifest"),
+                                            ("res/xml/Network_Security_Config.xml", &config),
+                                            ("res/xml/other.xml", b"other")]);
+
+        let path = std::env::temp_dir().join(format!("rusty-axml-entry-{}.apk", std::process::id()));
+        fs::write(&path, apk).unwrap();
+        let path = path.to_str().unwrap();
+
+        // Case-insensitive fallback
+        let cursor = create_cursor_from_apk_entry(path, "res/xml/network_security_config.xml");
+        // Near misses
+        let missing = create_cursor_from_apk_entry(path, "AndroidManifest.xml");
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(cursor.unwrap().into_inner(), config);
+        match missing {
+            Err(e @ AxmlError::MissingEntry { .. }) => {
+                assert_eq!(e.to_string(),
+                           "no AndroidManifest.xml in archive (did you mean base/AndroidManifest.xml?)");
+            },
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_create_cursor_from_axml_mmap() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/AndroidManifest.xml");
+        let mapped = create_cursor_from_axml_mmap(path).unwrap();
+        assert_eq!(mapped.get_ref()[..], create_cursor_from_axml(path).into_inner()[..]);
+        assert_eq!(get_package_name(mapped).unwrap(), "com.example.app");
+
+        assert!(matches!(create_cursor_from_axml_mmap("/nonexistent/AndroidManifest.xml"), Err(AxmlError::Io(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "apk")]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    fn launcher_filter(builder: AxmlBuilder) -> AxmlBuilder {
+        builder.start("intent-filter", vec![])
+               .element("action", vec![Attr::string("android:name", "android.intent.action.MAIN")])
+               .element("category", vec![Attr::string("android:name", "android.intent.category.LAUNCHER")])
+               .end("intent-filter")
+    }
+
+    #[test]
+    fn test_manifest_contents() {
+        let mut builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("permission", vec![Attr::string("android:name", "com.example.permission.SYNC")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.INTERNET")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.CAMERA")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", "com.example.Settings")])
+            .end("activity")
+            .start("activity", vec![Attr::string("android:name", "com.example.Main")]);
+        builder = launcher_filter(builder);
+        let root = parse(builder
+            .end("activity")
+            .element("service", vec![Attr::string("android:name", "com.example.Sync")])
+            .element("provider", vec![Attr::string("android:name", "com.example.Files")])
+            .element("receiver", vec![Attr::string("android:name", "com.example.Boot")])
+            .element("receiver", vec![Attr::string("android:name", "com.example.Alarm")])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let contents = ManifestContents::from_tree(&root);
+
+        assert_eq!(contents.pkg_name, "com.example");
+        assert_eq!(contents.activities, vec!["com.example.Settings", "com.example.Main"]);
+        assert_eq!(contents.services, vec!["com.example.Sync"]);
+        assert_eq!(contents.providers, vec!["com.example.Files"]);
+        assert_eq!(contents.receivers, vec!["com.example.Boot", "com.example.Alarm"]);
+        assert_eq!(contents.created_perms, vec!["com.example.permission.SYNC"]);
+        assert_eq!(contents.requested_perms, vec!["android.permission.INTERNET", "android.permission.CAMERA"]);
+        assert_eq!(contents.main_entry_point.as_deref(), Some("com.example.Main"));
+    }
+
+    #[test]
+    fn test_manifest_contents_minimal() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "org.minimal")])
+            .start("application", vec![])
+            .element("service", vec![Attr::string("android:name", "org.minimal.Worker")])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let contents = ManifestContents::from_tree(&root);
+
+        assert_eq!(contents.pkg_name, "org.minimal");
+        assert!(contents.activities.is_empty());
+        assert_eq!(contents.services, vec!["org.minimal.Worker"]);
+        assert!(contents.providers.is_empty());
+        assert!(contents.receivers.is_empty());
+        assert!(contents.created_perms.is_empty());
+        assert!(contents.requested_perms.is_empty());
+        assert!(contents.main_entry_point.is_none());
+    }
+
+    #[test]
+    fn test_normalize_component_name() {
+        assert_eq!(normalize_component_name("com.example", ".Main"), "com.example.Main");
+        assert_eq!(normalize_component_name("com.example", "Main"), "com.example.Main");
+        assert_eq!(normalize_component_name("com.example", "org.lib.Main"), "org.lib.Main");
+        assert_eq!(normalize_component_name("com.example", ".ui.Main"), "com.example.ui.Main");
+        assert_eq!(normalize_component_name("com.example", ".Outer$Inner"), "com.example.Outer$Inner");
+        assert_eq!(normalize_component_name("com.example", "Outer$Inner"), "com.example.Outer$Inner");
+        assert_eq!(normalize_component_name("com.example", "org.lib.Outer$Inner"), "org.lib.Outer$Inner");
+        assert_eq!(normalize_component_name("", ".Main"), "Main");
+        assert_eq!(normalize_component_name("", "Main"), "Main");
+    }
+
+    #[test]
+    fn test_manifest_contents_normalized_names() {
+        let builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Main")]);
+        let root = parse(launcher_filter(builder)
+            .end("activity")
+            .element("service", vec![Attr::string("android:name", "Sync")])
+            .element("receiver", vec![Attr::string("android:name", "org.lib.Receiver")])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let contents = ManifestContents::from_tree(&root);
+
+        assert_eq!(contents.activities, vec!["com.example.Main"]);
+        assert_eq!(contents.services, vec!["com.example.Sync"]);
+        assert_eq!(contents.receivers, vec!["org.lib.Receiver"]);
+        assert_eq!(contents.main_entry_point.as_deref(), Some("com.example.Main"));
+    }
+
+    #[test]
+    fn test_alias_launcher() {
+        let builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", ".RealMain")])
+            .start("activity-alias", vec![Attr::string("android:name", ".Launcher"),
+                                          Attr::string("android:targetActivity", ".RealMain")]);
+        let root = parse(launcher_filter(builder)
+            .end("activity-alias")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        assert_eq!(find_main_entry_points(&root), vec!["com.example.RealMain"]);
+        assert_eq!(ManifestContents::from_tree(&root).main_entry_point.as_deref(),
+                   Some("com.example.RealMain"));
+    }
+
+    #[test]
+    fn test_multiple_launchers() {
+        let mut builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Disabled"),
+                                    Attr::boolean("android:enabled", false)]);
+        builder = launcher_filter(builder)
+            .end("activity")
+            .start("activity", vec![Attr::string("android:name", ".Phone")]);
+        builder = launcher_filter(builder)
+            .end("activity")
+            .start("activity", vec![Attr::string("android:name", ".Tv")])
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", "android.intent.action.MAIN")])
+            .element("category", vec![Attr::string("android:name", "android.intent.category.LEANBACK_LAUNCHER")])
+            .end("intent-filter")
+            .end("activity")
+            .start("activity", vec![Attr::string("android:name", ".NotLauncher")])
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", "android.intent.action.MAIN")])
+            .end("intent-filter")
+            .end("activity");
+        let root = parse(builder.end("application").end("manifest").build());
+
+        assert_eq!(find_main_entry_points(&root),
+                   vec!["com.example.Phone", "com.example.Tv", "com.example.Disabled"]);
+        assert_eq!(ManifestContents::from_tree(&root).main_entry_point.as_deref(),
+                   Some("com.example.Phone"));
+    }
+
+    #[test]
+    fn test_implicit_export_with_target_sdk_31() {
+        let build = |target_sdk: i32| parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![])
+            .element("uses-sdk", vec![Attr::int("android:targetSdkVersion", target_sdk)])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Implicit")])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .start("activity", vec![Attr::string("android:name", ".Explicit"),
+                                    Attr::boolean("android:exported", true)])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let legacy = build(30);
+        let modern = build(31);
+
+        let implicit = |root: &Rc<RefCell<XmlElement>>| find_elements_by_type(root, "activity")[0].clone();
+        let sdk = |target| SdkVersions { target: Some(target), ..Default::default() };
+        assert_eq!(get_exported_state(&implicit(&legacy), &sdk(30)), ComponentState::DefaultTrue);
+        assert_eq!(get_exported_state(&implicit(&modern), &sdk(31)), ComponentState::ImplicitInvalid);
+
+        let components = get_exposed_components(legacy).unwrap();
+        assert_eq!(exposed_names(&components, "activity"), vec![".Implicit", ".Explicit"]);
+        let components = get_exposed_components(modern).unwrap();
+        assert_eq!(exposed_names(&components, "activity"), vec![".Explicit"]);
+    }
+
+    #[test]
+    fn test_duplicate_components() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", "com.example.Main"),
+                                      Attr::boolean("android:exported", true)])
+            .element("service", vec![Attr::string("android:name", ".Sync")])
+            .start("activity", vec![Attr::string("android:name", ".Main"),
+                                    Attr::boolean("android:exported", false)])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let contents = ManifestContents::from_tree(&root);
+        assert_eq!(contents.activities, vec!["com.example.Main"]);
+        assert_eq!(contents.duplicate_components, vec!["com.example.Main"]);
+        assert_eq!(find_duplicate_components(&root), vec!["com.example.Main"]);
+
+        let report = get_exposure_report(&root);
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].name, "com.example.Main");
+        assert_eq!(report[0].exported, ComponentState::ExplicitFalse);
+        assert!(report[0].has_intent_filter);
+        assert!(report[0].duplicated);
+        assert!(!report[1].duplicated);
+
+        let exposed = get_exposed_components(root).unwrap();
+        assert!(exposed["activity"].is_empty());
+    }
+
+    #[test]
+    fn test_exposure_report() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Main")])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .element("activity-alias", vec![Attr::string("android:name", ".Alias"),
+                                            Attr::string("android:targetActivity", ".Main"),
+                                            Attr::boolean("android:exported", true),
+                                            Attr::string("android:permission", "com.example.permission.ALIAS")])
+            .element("service", vec![Attr::string("android:name", "org.lib.Sync"),
+                                     Attr::boolean("android:enabled", false),
+                                     Attr::boolean("android:exported", true)])
+            .element("receiver", vec![Attr::string("android:name", ".Boot"),
+                                      Attr::reference("android:exported", 0x7f050001)])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let report = get_exposure_report(&root);
+
+        assert_eq!(report, vec![
+            ExposedComponent {
+                kind: String::from("activity"),
+                name: String::from("com.example.Main"),
+                target_activity: None,
+                exported: ComponentState::DefaultTrue,
+                enabled: ComponentState::DefaultTrue,
+                application_enabled: ComponentState::DefaultTrue,
+                protected_by_permission: None,
+                read_permission: None,
+                write_permission: None,
+                has_intent_filter: true,
+                partially_exposed: false,
+                duplicated: false,
+            },
+            ExposedComponent {
+                kind: String::from("activity-alias"),
+                name: String::from("com.example.Alias"),
+                target_activity: Some(String::from("com.example.Main")),
+                exported: ComponentState::ExplicitTrue,
+                enabled: ComponentState::DefaultTrue,
+                application_enabled: ComponentState::DefaultTrue,
+                protected_by_permission: Some(GuardPermission {
+                    name: String::from("com.example.permission.ALIAS"),
+                    protection: permissions::PermissionProtection::UnknownExternal,
+                }),
+                read_permission: None,
+                write_permission: None,
+                has_intent_filter: false,
+                partially_exposed: false,
+                duplicated: false,
+            },
+            ExposedComponent {
+                kind: String::from("service"),
+                name: String::from("org.lib.Sync"),
+                target_activity: None,
+                exported: ComponentState::ExplicitTrue,
+                enabled: ComponentState::ExplicitFalse,
+                application_enabled: ComponentState::DefaultTrue,
+                protected_by_permission: None,
+                read_permission: None,
+                write_permission: None,
+                has_intent_filter: false,
+                partially_exposed: false,
+                duplicated: false,
+            },
+            ExposedComponent {
+                kind: String::from("receiver"),
+                name: String::from("com.example.Boot"),
+                target_activity: None,
+                exported: ComponentState::Unknown,
+                enabled: ComponentState::DefaultTrue,
+                application_enabled: ComponentState::DefaultTrue,
+                protected_by_permission: None,
+                read_permission: None,
+                write_permission: None,
+                has_intent_filter: false,
+                partially_exposed: false,
+                duplicated: false,
+            },
+        ]);
+
+        let exposed: Vec<&str> = report.iter()
+                                       .filter(|c| c.is_exposed())
+                                       .map(|c| c.name.as_str())
+                                       .collect();
+        assert_eq!(exposed, vec!["com.example.Main", "com.example.Alias"]);
+    }
+
+    #[test]
+    fn test_alias_only_exposed_surface() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", ".Hidden"),
+                                      Attr::boolean("android:exported", false)])
+            .start("activity-alias", vec![Attr::string("android:name", ".Entry"),
+                                          Attr::string("android:targetActivity", ".Hidden")])
+            .element("intent-filter", vec![])
+            .end("activity-alias")
+            .element("activity", vec![Attr::string("android:name", ".Off"),
+                                      Attr::boolean("android:enabled", false)])
+            .element("activity-alias", vec![Attr::string("android:name", ".OffAlias"),
+                                            Attr::string("android:targetActivity", "com.example.Off"),
+                                            Attr::boolean("android:exported", true)])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let report = get_exposure_report(&root);
+        let exposed: Vec<(&str, Option<&str>)> = report.iter()
+                                                       .filter(|c| c.is_exposed())
+                                                       .map(|c| (c.name.as_str(), c.target_activity.as_deref()))
+                                                       .collect();
+        assert_eq!(exposed, vec![("com.example.Entry", Some("com.example.Hidden"))]);
+        assert_eq!(report[3].enabled, ComponentState::ExplicitFalse);
+
+        let components = get_exposed_components(root).unwrap();
+        assert!(exposed_names(&components, "activity").is_empty());
+        assert_eq!(exposed_names(&components, "activity-alias"), vec![".Entry"]);
+    }
+
+    #[test]
+    fn test_provider_export_defaults() {
+        let build = |min_sdk: i32, target_sdk: i32| parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-sdk", vec![Attr::int("android:minSdkVersion", min_sdk),
+                                      Attr::int("android:targetSdkVersion", target_sdk)])
+            .start("application", vec![])
+            .element("provider", vec![Attr::string("android:name", ".Files")])
+            .start("provider", vec![Attr::string("android:name", ".Shared"),
+                                    Attr::boolean("android:exported", false)])
+            .element("path-permission", vec![Attr::string("android:pathPrefix", "/public")])
+            .end("provider")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let legacy = get_exposure_report(&build(14, 16));
+        assert_eq!(legacy[0].exported, ComponentState::DefaultTrue);
+        assert!(legacy[0].is_exposed());
+        assert!(!legacy[0].partially_exposed);
+
+        let legacy_min = get_exposure_report(&build(16, 30));
+        assert_eq!(legacy_min[0].exported, ComponentState::DefaultTrue);
+
+        let modern = get_exposure_report(&build(21, 30));
+        assert_eq!(modern[0].exported, ComponentState::DefaultFalse);
+        assert!(!modern[0].is_exposed());
+        assert_eq!(modern[1].exported, ComponentState::ExplicitFalse);
+        assert!(modern[1].partially_exposed);
+    }
+
+    #[test]
+    fn test_reference_valued_flags() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("service", vec![Attr::string("android:name", ".Typed"),
+                                     Attr::reference("android:exported", 0x7f050001)])
+            .element("service", vec![Attr::string("android:name", ".Symbolic"),
+                                     Attr::string("android:exported", "@bool/flag")])
+            .element("service", vec![Attr::string("android:name", ".Disabled"),
+                                     Attr::boolean("android:exported", true),
+                                     Attr::reference("android:enabled", 0x7f050002)])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let unresolved = get_exposure_report(&root);
+        assert_eq!(unresolved[0].exported, ComponentState::Unknown);
+        assert_eq!(unresolved[1].exported, ComponentState::Unknown);
+        assert_eq!(unresolved[2].enabled, ComponentState::Unknown);
+        assert!(unresolved.iter().all(|c| !c.is_exposed()));
+        assert!(exposed_names(&get_exposed_components(Rc::clone(&root)).unwrap(), "service").is_empty());
+
+        let resources = |id: u32| match id {
+            0x7f050001 => Some(true),
+            0x7f050002 => Some(false),
+            _ => None,
+        };
+        let resolved = get_exposure_report_with_resolver(&root, &resources);
+        assert_eq!(resolved[0].exported, ComponentState::ExplicitTrue);
+        assert!(resolved[0].is_exposed());
+        assert_eq!(resolved[1].exported, ComponentState::Unknown);
+        assert_eq!(resolved[2].enabled, ComponentState::ExplicitFalse);
+        assert!(!resolved[2].is_exposed());
+    }
+
+    #[test]
+    fn test_component_state_combinations() {
+        use ComponentState::*;
+
+        // Value of the attribute, and the state it gives when it is present
+        let values = [
+            (Some(Attr::boolean("android:exported", true)), Some(ExplicitTrue)),
+            (Some(Attr::boolean("android:exported", false)), Some(ExplicitFalse)),
+            (Some(Attr::string("android:exported", "true")), Some(ExplicitTrue)),
+            (Some(Attr::reference("android:exported", 0x7f050001)), Some(Unknown)),
+            (Some(Attr::string("android:exported", "@bool/exported")), Some(Unknown)),
+            (None, None),
+        ];
+
+        let mut builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![]);
+        let mut expected = Vec::new();
+        for kind in ["activity", "activity-alias", "service", "receiver"] {
+            for (value, state) in values.iter() {
+                for has_intent_filter in [false, true] {
+                    // The enabled attribute takes the same values
+                    let attrs: Vec<Attr> = value.iter()
+                                                .flat_map(|attr| {
+                                                    let mut enabled = attr.clone();
+                                                    enabled.key = String::from("android:enabled");
+                                                    [attr.clone(), enabled]
+                                                })
+                                                .collect();
+                    builder = builder.start(kind, attrs);
+                    if has_intent_filter {
+                        builder = builder.start("intent-filter", vec![])
+                                         .element("action", vec![Attr::string("android:name", "com.example.ACTION")])
+                                         .end("intent-filter");
+                    }
+                    builder = builder.end(kind);
+                    expected.push((kind, *state, has_intent_filter));
+                }
+            }
+        }
+        let root = parse(builder.end("application").end("manifest").build());
+
+        let application = Rc::clone(root.borrow().child_elements().next().unwrap());
+        let components: Vec<_> = application.borrow().child_elements().cloned().collect();
+        assert_eq!(components.len(), expected.len());
+
+        let sdk = |target: Option<u32>| SdkVersions { target, ..Default::default() };
+        for (component, (kind, state, has_intent_filter)) in components.iter().zip(expected) {
+            let describe = format!("{kind} {state:?} with intent filter: {has_intent_filter}");
+            assert_eq!(get_enabled_state(component), state.unwrap_or(DefaultTrue), "{describe}");
+            assert_eq!(component_enabled_state(component), state.unwrap_or(DefaultTrue), "{describe}");
+            // The manifest has no target SDK
+            assert_eq!(component_export_state(component), get_exported_state(component, &sdk(None)), "{describe}");
+
+            for target in [None, Some(30), Some(31)] {
+                let exported = get_exported_state(component, &sdk(target));
+                let default = match (has_intent_filter, target) {
+                    (false, _) => DefaultFalse,
+                    (true, Some(31)) => ImplicitInvalid,
+                    (true, _) => DefaultTrue,
+                };
+                assert_eq!(exported, state.unwrap_or(default), "{describe}, target SDK {target:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_component_export_state_sdk() {
+        // The SDK versions come from the manifest of the component
+        let manifest = |target: i32| parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-sdk", vec![Attr::int("android:targetSdkVersion", target)])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Main"),
+                                    Attr::reference("android:enabled", 0x7f050001)])
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", "com.example.ACTION")])
+            .end("intent-filter")
+            .end("activity")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        for (target, exported) in [(30, ComponentState::DefaultTrue), (31, ComponentState::ImplicitInvalid)] {
+            let root = manifest(target);
+            let activity = find_elements_by_type(&root, "activity").pop().unwrap();
+            assert_eq!(component_export_state(&activity), exported);
+            assert_eq!(component_enabled_state(&activity), ComponentState::Unknown);
+        }
+
+        // A detached component has no SDK versions
+        let activity = Rc::new(RefCell::new(XmlElement::new("activity")));
+        assert_eq!(component_export_state(&activity), ComponentState::DefaultFalse);
+        assert_eq!(component_enabled_state(&activity), ComponentState::DefaultTrue);
+    }
+
+    #[test]
+    fn test_component_permissions() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("permission", vec![Attr::string("android:name", "com.example.SIGNATURE"),
+                                        Attr::hex("android:protectionLevel", 0x2)])
+            .element("permission", vec![Attr::string("android:name", "com.example.NORMAL")])
+            .start("application", vec![])
+            .element("service", vec![Attr::string("android:name", ".Open"),
+                                     Attr::boolean("android:exported", true)])
+            .element("service", vec![Attr::string("android:name", ".Signed"),
+                                     Attr::boolean("android:exported", true),
+                                     Attr::string("android:permission", "com.example.SIGNATURE")])
+            .element("receiver", vec![Attr::string("android:name", ".Weak"),
+                                      Attr::boolean("android:exported", true),
+                                      Attr::string("android:permission", "com.example.NORMAL")])
+            .element("provider", vec![Attr::string("android:name", ".Data"),
+                                      Attr::boolean("android:exported", true),
+                                      Attr::string("android:readPermission", "android.permission.READ_CONTACTS"),
+                                      Attr::string("android:writePermission", "com.example.SIGNATURE")])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let report = get_exposure_report(&root);
+        let protection = |permission: &Option<GuardPermission>| permission.as_ref().map(|p| p.protection);
+
+        assert_eq!(report[0].protected_by_permission, None);
+        assert_eq!(protection(&report[1].protected_by_permission),
+                   Some(permissions::PermissionProtection::Declared(permissions::ProtectionLevel(0x2))));
+        assert!(report[2].protected_by_permission.as_ref().unwrap().is_normal());
+        assert_eq!(report[3].protected_by_permission, None);
+        assert_eq!(report[3].read_permission.as_ref().unwrap().name, "android.permission.READ_CONTACTS");
+        assert_eq!(protection(&report[3].read_permission),
+                   Some(permissions::PermissionProtection::UnknownExternal));
+        assert_eq!(protection(&report[3].write_permission),
+                   Some(permissions::PermissionProtection::Declared(permissions::ProtectionLevel(0x2))));
+    }
+
+    #[test]
+    fn test_get_package_name() {
+        let data = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::int("android:versionCode", 42),
+                                    Attr::string("android:versionName", "1.2")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.INTERNET")])
+            .end("manifest")
+            .build();
+
+        assert_eq!(get_package_name(Cursor::new(data.clone())).unwrap(), "com.example");
+        assert_eq!(get_version_info_fast(Cursor::new(data.clone())), VersionInfo {
+            version_code: Some(42),
+            version_name: Some(String::from("1.2")),
+        });
+
+        // Reading stops at the <manifest> element: garbage after it is not
+        // read. The end of the document is the <uses-permission> start
+        // element (56 bytes) and the end chunks of the elements and of the
+        // namespace (24 bytes each).
+        let manifest_end = data.len() - 56 - 3 * 24;
+        let mut truncated = data[..manifest_end].to_vec();
+        truncated.extend_from_slice(&[0xff; 16]);
+        assert_eq!(get_package_name(Cursor::new(truncated)).unwrap(), "com.example");
+
+        let no_package = AxmlBuilder::new()
+            .element("manifest", vec![Attr::string("versionName", "1.0")])
+            .build();
+        assert!(matches!(get_package_name(Cursor::new(no_package)),
+                         Err(AxmlError::MissingAttribute { .. })));
+    }
+
+    /// Cursor remembering the furthest position it was read up to
+    struct FurthestRead<'a> {
+        cursor: Cursor<&'a [u8]>,
+        furthest: u64,
+    }
+
+    impl Read for FurthestRead<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.cursor.read(buf)?;
+            self.furthest = self.furthest.max(self.cursor.position());
+            Ok(read)
+        }
+    }
+
+    impl Seek for FurthestRead<'_> {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.cursor.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_get_package_name_stops_at_manifest() {
+        let data = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.INTERNET")])
+            .end("manifest")
+            .build();
+        // End of the <manifest> start element, followed by the
+        // <uses-permission> start element (56 bytes) and the end chunks of the
+        // elements and of the namespace (24 bytes each)
+        let manifest_end = data.len() - 56 - 3 * 24;
+
+        // The rest of the document, valid or not, is never read
+        let mut garbage = data[..manifest_end].to_vec();
+        garbage.extend_from_slice(&[0xff; 256]);
+        for data in [&data[..], &data[..manifest_end], &garbage[..]] {
+            let mut reader = FurthestRead { cursor: Cursor::new(data), furthest: 0 };
+            assert_eq!(get_package_name(&mut reader).unwrap(), "com.example");
+            assert!(reader.furthest <= manifest_end as u64, "read up to {} of {manifest_end}", reader.furthest);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Shared libraries
+//!
+//! Shared libraries the app links against: Java libraries provided by the
+//! platform or the device (`<uses-library>`), and, from API level 31, native
+//! libraries provided by the vendor (`<uses-native-library>`).
+
+use crate::find_elements_by_type;
+use crate::owned::ElementRef;
+
+/// Kind of shared library
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LibraryKind {
+    /// Java library (`<uses-library>`)
+    Java,
+    /// Native library (`<uses-native-library>`)
+    Native,
+}
+
+/// Shared library the app depends on, see [`get_library_dependencies`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LibraryDependency {
+    /// Kind of library
+    pub kind: LibraryKind,
+    /// Name of the library (e.g., `org.apache.http.legacy`)
+    pub name: String,
+    /// Whether the app cannot be installed without the library
+    /// (`android:required`, `true` by default)
+    pub required: bool,
+}
+
+/// Get the shared libraries the app depends on, in document order. Only the
+/// direct children of `<application>` are considered, since elements with
+/// the same names can appear elsewhere (e.g., in `<queries>`). Libraries
+/// without a name are ignored.
+pub fn get_library_dependencies<E: ElementRef>(root: &E) -> Vec<LibraryDependency> {
+    find_elements_by_type(root, "application")
+        .iter()
+        .flat_map(|application| application.children())
+        .filter_map(|child| {
+            let kind = match child.element_type().as_str() {
+                "uses-library" => LibraryKind::Java,
+                "uses-native-library" => LibraryKind::Native,
+                _ => return None,
+            };
+
+            Some(LibraryDependency {
+                kind,
+                name: child.get_attr("name")?,
+                required: child.get_attr_bool("required").unwrap_or(true),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_library_dependencies() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("queries", vec![])
+            .element("uses-library", vec![Attr::string("android:name", "com.bogus")])
+            .end("queries")
+            .start("application", vec![])
+            .element("uses-library", vec![Attr::string("android:name", "org.apache.http.legacy"),
+                                          Attr::boolean("android:required", false)])
+            .element("uses-native-library", vec![Attr::string("android:name", "libOpenCL.so")])
+            .element("activity", vec![Attr::string("android:name", ".Main")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        assert_eq!(get_library_dependencies(&root), vec![
+            LibraryDependency {
+                kind: LibraryKind::Java,
+                name: String::from("org.apache.http.legacy"),
+                required: false,
+            },
+            LibraryDependency {
+                kind: LibraryKind::Native,
+                name: String::from("libOpenCL.so"),
+                required: true,
+            },
+        ]);
+    }
+}
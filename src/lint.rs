@@ -0,0 +1,606 @@
+//! Security lints
+//!
+//! Checks producing security findings from a parsed manifest, so that the
+//! usual review checklist does not have to be reimplemented by every
+//! consumer of the crate. Each rule has a stable identifier that can be used
+//! to filter or suppress its findings.
+
+use std::fmt;
+
+use crate::application::{ get_application_flags, get_backup_config, FlagState, FullBackupContent };
+use crate::owned::ElementRef;
+use crate::providers::get_providers;
+use crate::res_table::ResTable;
+use crate::resource_map::ANDROID_NS;
+use crate::sdk::get_sdk_versions;
+use crate::{
+    exposure_report_with_elements,
+    is_launcher_component,
+    manifest_package,
+    no_resolver,
+    normalize_component_name,
+    ComponentState,
+    COMPONENT_TYPES,
+};
+
+/// Severity of a finding
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "INFO",
+            Severity::Low => "LOW",
+            Severity::Medium => "MEDIUM",
+            Severity::High => "HIGH",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Security finding, see [`run_lints`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// Identifier of the rule that produced the finding (e.g., `debuggable`)
+    pub id: &'static str,
+    /// Severity of the finding
+    pub severity: Severity,
+    /// Human-readable description
+    pub message: String,
+    /// Path of the offending element, e.g.,
+    /// `manifest/application/activity[com.example.Main]`
+    pub path: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {} ({})", self.severity, self.id, self.message, self.path)
+    }
+}
+
+/// Path of the `<application>` element
+const APPLICATION_PATH: &str = "manifest/application";
+
+/// Path of a component
+fn component_path(kind: &str, name: &str) -> String {
+    format!("{APPLICATION_PATH}/{kind}[{name}]")
+}
+
+/// Target API level from which cleartext traffic is disabled by default
+const CLEARTEXT_DISABLED_SDK: u32 = 28;
+
+/// Paths of a `<path-permission>` covering the whole provider
+const WIDE_PATHS: [&str; 4] = ["/", ".*", "/.*", "*"];
+
+/// `debuggable`: the app is debuggable, so anyone with ADB access can attach
+/// a debugger and run code in its process
+fn lint_debuggable<E: ElementRef>(root: &E, findings: &mut Vec<Finding>) {
+    if get_application_flags(root).debuggable == FlagState::ExplicitTrue {
+        findings.push(Finding {
+            id: "debuggable",
+            severity: Severity::High,
+            message: String::from("the application is debuggable"),
+            path: String::from(APPLICATION_PATH),
+        });
+    }
+}
+
+/// `allow-backup`: backups are allowed (the default) without rules limiting
+/// which files are backed up
+fn lint_allow_backup<E: ElementRef>(root: &E, findings: &mut Vec<Finding>) {
+    if get_application_flags(root).allow_backup.value_or(true) != Some(true) {
+        return;
+    }
+
+    let config = get_backup_config(root);
+    let has_rules = matches!(config.full_backup_content, Some(FullBackupContent::Rules(_))) ||
+                    config.data_extraction_rules.is_some();
+    if !has_rules {
+        findings.push(Finding {
+            id: "allow-backup",
+            severity: Severity::Medium,
+            message: String::from("backups are allowed without backup rules, all the app's data can be extracted"),
+            path: String::from(APPLICATION_PATH),
+        });
+    }
+}
+
+/// `cleartext-traffic`: the app can use unencrypted network connections,
+/// either explicitly or because it targets an API level below 28 without a
+/// network security configuration
+fn lint_cleartext_traffic<E: ElementRef>(root: &E, findings: &mut Vec<Finding>) {
+    let flags = get_application_flags(root);
+    let message = match flags.uses_cleartext_traffic {
+        FlagState::ExplicitTrue => "cleartext traffic is explicitly permitted",
+        FlagState::NotSet if flags.network_security_config.is_none() &&
+                             get_sdk_versions(root).target.is_none_or(|sdk| sdk < CLEARTEXT_DISABLED_SDK) => {
+            "cleartext traffic is permitted by default for target SDKs below 28"
+        },
+        _ => return,
+    };
+
+    findings.push(Finding {
+        id: "cleartext-traffic",
+        severity: Severity::Medium,
+        message: String::from(message),
+        path: String::from(APPLICATION_PATH),
+    });
+}
+
+/// Component rules, evaluated on the exposure report:
+///  * `exported-without-permission`: an exposed component (other than a
+///    launcher activity) is not protected by any permission
+///  * `normal-permission-guard`: an exposed component is protected by a
+///    permission declared with a `normal` protection level, which any app
+///    can request
+///  * `missing-exported`: a component with an intent filter does not set
+///    `android:exported` while targeting API level 31 or higher, so the app
+///    cannot be installed
+fn lint_components<E: ElementRef>(root: &E, findings: &mut Vec<Finding>) {
+    for (element, component) in exposure_report_with_elements(root, &no_resolver) {
+        let path = component_path(&component.kind, &component.name);
+
+        if component.exported == ComponentState::ImplicitInvalid {
+            findings.push(Finding {
+                id: "missing-exported",
+                severity: Severity::High,
+                message: format!("{} has an intent filter but does not set android:exported, \
+                                  which is required when targeting API level 31 or higher", component.name),
+                path: path.clone(),
+            });
+        }
+
+        if !component.is_exposed() {
+            continue;
+        }
+
+        let guards = [&component.protected_by_permission, &component.read_permission, &component.write_permission];
+        if guards.iter().all(|guard| guard.is_none()) {
+            if !is_launcher_component(&element) {
+                findings.push(Finding {
+                    id: "exported-without-permission",
+                    severity: Severity::Medium,
+                    message: format!("{} is exported without any permission", component.name),
+                    path: path.clone(),
+                });
+            }
+            continue;
+        }
+
+        for guard in guards.into_iter().flatten().filter(|guard| guard.is_normal()) {
+            findings.push(Finding {
+                id: "normal-permission-guard",
+                severity: Severity::High,
+                message: format!("{} is protected by {} which has a normal protection level",
+                                 component.name, guard.name),
+                path: path.clone(),
+            });
+        }
+    }
+}
+
+/// `provider-wide-path-permission`: a provider grants URI permissions and
+/// has a `<path-permission>` covering all of its paths
+fn lint_provider_path_permissions<E: ElementRef>(root: &E, findings: &mut Vec<Finding>) {
+    for provider in get_providers(root) {
+        if !provider.grant_uri_permissions {
+            continue;
+        }
+
+        let wide = provider.path_permissions.iter().any(|permission| {
+            [&permission.path.path, &permission.path.path_prefix, &permission.path.path_pattern]
+                .into_iter()
+                .flatten()
+                .any(|path| WIDE_PATHS.contains(&path.as_str()))
+        });
+        if wide {
+            findings.push(Finding {
+                id: "provider-wide-path-permission",
+                severity: Severity::High,
+                message: format!("{} grants URI permissions and has a path-permission covering all its paths",
+                                 provider.name),
+                path: component_path("provider", &provider.name),
+            });
+        }
+    }
+}
+
+/// Detect the namespace tricks used to fool static analysis tools, which
+/// read attributes by prefix or by local name while PackageManager only
+/// reads the attributes of the android namespace:
+///  * `fake-android-namespace`: an attribute has the `android` prefix but is
+///    not in the android namespace, e.g., because the prefix is bound to a
+///    look-alike URI
+///  * `duplicate-namespaced-attribute`: an element has several attributes
+///    with the same local name in different namespaces, one of them being
+///    (or looking like) an attribute of the android namespace (e.g.,
+///    `android:exported` and `app:exported`)
+///
+/// The exposure analysis is not fooled by these tricks, since it only reads
+/// the attributes of the android namespace, but their presence is a strong
+/// sign of a manifest crafted to evade analysis.
+pub fn detect_namespace_spoofing<E: ElementRef>(root: &E) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    visit_elements(root, &mut |element: &E, path: &str, namespaces: &[(String, String)]| {
+        let attributes = element.attributes();
+        let resolved: Vec<_> = attributes
+            .iter_attributes()
+            .filter(|attr| !attr.name.starts_with("xmlns:"))
+            .map(|attr| {
+                let namespace = attr.resolve_namespace(|prefix| {
+                    namespaces.iter().rev().find(|(p, _)| p == prefix).map(|(_, uri)| uri.clone())
+                });
+                (attr, namespace)
+            })
+            .collect();
+
+        for (attr, namespace) in resolved.iter() {
+            if attr.name.starts_with("android:") && namespace.as_deref() != Some(ANDROID_NS) {
+                findings.push(Finding {
+                    id: "fake-android-namespace",
+                    severity: Severity::High,
+                    message: format!("{} is in the namespace {} instead of the android namespace, \
+                                      so Android ignores it",
+                                     attr.name, namespace.as_deref().unwrap_or("(none)")),
+                    path: path.to_string(),
+                });
+            }
+        }
+
+        for (idx, (attr, _)) in resolved.iter().enumerate() {
+            // Report each local name once, on its first attribute
+            let local_name = attr.local_name();
+            if resolved[..idx].iter().any(|(other, _)| other.local_name() == local_name) {
+                continue;
+            }
+
+            let same_name: Vec<_> = resolved[idx..].iter()
+                                                   .filter(|(other, _)| other.local_name() == local_name)
+                                                   .collect();
+            let android = same_name.iter().any(|(other, namespace)| {
+                namespace.as_deref() == Some(ANDROID_NS) || other.name.starts_with("android:")
+            });
+            if android && same_name.iter().any(|(_, namespace)| *namespace != same_name[0].1) {
+                let names: Vec<&str> = same_name.iter().map(|(other, _)| other.name.as_str()).collect();
+                findings.push(Finding {
+                    id: "duplicate-namespaced-attribute",
+                    severity: Severity::Medium,
+                    message: format!("{local_name} is set in several namespaces ({}), \
+                                      only the one in the android namespace is used by Android",
+                                     names.join(", ")),
+                    path: path.to_string(),
+                });
+            }
+        }
+    });
+    findings
+}
+
+/// `duplicate-attribute`: an element has the same attribute several times.
+/// Android uses the first one, while naive parsers keep the last one, so
+/// this is used to show a different value (e.g., of `android:exported`) to
+/// analysis tools.
+fn lint_duplicate_attributes<E: ElementRef>(root: &E, findings: &mut Vec<Finding>) {
+    visit_elements(root, &mut |element: &E, path: &str, _: &[(String, String)]| {
+        let attributes = element.attributes();
+        for (idx, duplicate) in attributes.duplicates().iter().enumerate() {
+            // Report each name once, on its first duplicate
+            if attributes.duplicates()[..idx].iter().any(|other| other.name == duplicate.name) {
+                continue;
+            }
+
+            let values: Vec<&str> = attributes.get(&duplicate.name)
+                                              .into_iter()
+                                              .chain(attributes.duplicates()
+                                                               .iter()
+                                                               .filter(|other| other.name == duplicate.name)
+                                                               .map(|other| &other.value))
+                                              .map(String::as_str)
+                                              .collect();
+            findings.push(Finding {
+                id: "duplicate-attribute",
+                severity: Severity::High,
+                message: format!("{} is set several times ({}), Android uses the first value",
+                                 duplicate.name, values.join(", ")),
+                path: path.to_string(),
+            });
+        }
+    });
+}
+
+/// Visit every element of the tree in document order, along with its path
+/// (see [`Finding::path`]) and the `(prefix, URI)` namespace declarations in
+/// scope, innermost last
+fn visit_elements<E: ElementRef>(root: &E, visit: &mut impl FnMut(&E, &str, &[(String, String)])) {
+    fn walk<E: ElementRef>(element: &E,
+                           parent_path: &str,
+                           pkg_name: &str,
+                           namespaces: &mut Vec<(String, String)>,
+                           visit: &mut impl FnMut(&E, &str, &[(String, String)])) {
+        let element_type = element.element_type();
+        let segment = match element.get_attr("name") {
+            Some(name) if COMPONENT_TYPES.contains(&element_type.as_str()) => {
+                format!("{element_type}[{}]", normalize_component_name(pkg_name, &name))
+            },
+            _ => element_type.to_string(),
+        };
+        let path = if parent_path.is_empty() { segment } else { format!("{parent_path}/{segment}") };
+
+        let in_scope = namespaces.len();
+        namespaces.extend(element.attributes().iter().filter_map(|(key, uri)| {
+            Some((key.strip_prefix("xmlns:")?.to_string(), uri.clone()))
+        }));
+
+        visit(element, &path, namespaces);
+        for child in element.children() {
+            walk(&child, &path, pkg_name, namespaces, visit);
+        }
+        namespaces.truncate(in_scope);
+    }
+
+    walk(root, "", &manifest_package(root), &mut Vec::new(), visit);
+}
+
+/// Run all the security lints on a parsed manifest, see the individual rules
+/// for details. Findings are grouped by rule.
+///
+/// `res_table` is meant to resolve the attributes referring to resources
+/// (e.g., `android:debuggable="@bool/debug"`). The entries of
+/// `resources.arsc` are not decoded yet, so it is currently unused and such
+/// attributes are treated as unknown.
+pub fn run_lints<E: ElementRef>(root: &E, _res_table: Option<&ResTable>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    lint_debuggable(root, &mut findings);
+    lint_allow_backup(root, &mut findings);
+    lint_cleartext_traffic(root, &mut findings);
+    lint_components(root, &mut findings);
+    lint_provider_path_permissions(root, &mut findings);
+    lint_duplicate_attributes(root, &mut findings);
+    findings.extend(detect_namespace_spoofing(root));
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    /// Application attributes that do not trigger any application-level lint
+    fn safe_application() -> Vec<Attr> {
+        vec![Attr::boolean("android:allowBackup", false),
+             Attr::boolean("android:usesCleartextTraffic", false)]
+    }
+
+    /// Run the lints on a manifest with the given `<application>` attributes
+    /// and children, and get the IDs of the findings
+    fn lint_ids(target_sdk: i32, application: Vec<Attr>,
+                children: impl FnOnce(AxmlBuilder) -> AxmlBuilder) -> Vec<&'static str> {
+        let builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-sdk", vec![Attr::int("android:targetSdkVersion", target_sdk)]);
+        let builder = children(builder.start("application", application));
+        let root = parse_xml(Cursor::new(builder.end("application").end("manifest").build()));
+
+        run_lints(&root, None).into_iter().map(|finding| finding.id).collect()
+    }
+
+    #[test]
+    fn test_no_findings() {
+        assert!(lint_ids(33, safe_application(), |b| b).is_empty());
+    }
+
+    #[test]
+    fn test_debuggable() {
+        let mut application = safe_application();
+        application.push(Attr::boolean("android:debuggable", true));
+
+        assert_eq!(lint_ids(33, application, |b| b), vec!["debuggable"]);
+    }
+
+    #[test]
+    fn test_allow_backup() {
+        let default = vec![Attr::boolean("android:usesCleartextTraffic", false)];
+        assert_eq!(lint_ids(33, default, |b| b), vec!["allow-backup"]);
+
+        let with_rules = vec![Attr::boolean("android:usesCleartextTraffic", false),
+                              Attr::boolean("android:allowBackup", true),
+                              Attr::reference("android:dataExtractionRules", 0x7f160000)];
+        assert!(lint_ids(33, with_rules, |b| b).is_empty());
+    }
+
+    #[test]
+    fn test_cleartext_traffic() {
+        let explicit = vec![Attr::boolean("android:allowBackup", false),
+                            Attr::boolean("android:usesCleartextTraffic", true)];
+        assert_eq!(lint_ids(33, explicit, |b| b), vec!["cleartext-traffic"]);
+
+        let default = vec![Attr::boolean("android:allowBackup", false)];
+        assert_eq!(lint_ids(27, default.clone(), |b| b), vec!["cleartext-traffic"]);
+        assert!(lint_ids(28, default, |b| b).is_empty());
+    }
+
+    #[test]
+    fn test_exported_without_permission() {
+        let ids = lint_ids(33, safe_application(), |b| b
+            .start("activity", vec![Attr::string("android:name", ".Main"),
+                                    Attr::boolean("android:exported", true)])
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", "android.intent.action.MAIN")])
+            .element("category", vec![Attr::string("android:name", "android.intent.category.LAUNCHER")])
+            .end("intent-filter")
+            .end("activity")
+            .element("service", vec![Attr::string("android:name", ".Open"),
+                                     Attr::boolean("android:exported", true)])
+            .element("service", vec![Attr::string("android:name", ".Guarded"),
+                                     Attr::boolean("android:exported", true),
+                                     Attr::string("android:permission", "android.permission.BIND_JOB_SERVICE")]));
+
+        assert_eq!(ids, vec!["exported-without-permission"]);
+    }
+
+    #[test]
+    fn test_normal_permission_guard() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-sdk", vec![Attr::int("android:targetSdkVersion", 33)])
+            .element("permission", vec![Attr::string("android:name", "com.example.CUSTOM"),
+                                        Attr::hex("android:protectionLevel", 0)])
+            .element("permission", vec![Attr::string("android:name", "com.example.SIGNATURE"),
+                                        Attr::hex("android:protectionLevel", 2)])
+            .start("application", safe_application())
+            .element("receiver", vec![Attr::string("android:name", ".Receiver"),
+                                      Attr::boolean("android:exported", true),
+                                      Attr::string("android:permission", "com.example.CUSTOM")])
+            .element("service", vec![Attr::string("android:name", ".Service"),
+                                     Attr::boolean("android:exported", true),
+                                     Attr::string("android:permission", "com.example.SIGNATURE")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let findings = run_lints(&root, None);
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].id, "normal-permission-guard");
+        assert_eq!(findings[0].severity, Severity::High);
+        assert_eq!(findings[0].path, "manifest/application/receiver[com.example.Receiver]");
+    }
+
+    #[test]
+    fn test_missing_exported() {
+        let ids = lint_ids(31, safe_application(), |b| b
+            .start("receiver", vec![Attr::string("android:name", ".Boot")])
+            .element("intent-filter", vec![])
+            .end("receiver"));
+
+        assert_eq!(ids, vec!["missing-exported"]);
+    }
+
+    #[test]
+    fn test_provider_wide_path_permission() {
+        let ids = lint_ids(33, safe_application(), |b| b
+            .start("provider", vec![Attr::string("android:name", ".Wide"),
+                                    Attr::string("android:authorities", "com.example.wide"),
+                                    Attr::boolean("android:grantUriPermissions", true)])
+            .element("path-permission", vec![Attr::string("android:pathPrefix", "/"),
+                                             Attr::string("android:readPermission", "com.example.READ")])
+            .end("provider")
+            .start("provider", vec![Attr::string("android:name", ".Narrow"),
+                                    Attr::string("android:authorities", "com.example.narrow"),
+                                    Attr::boolean("android:grantUriPermissions", true)])
+            .element("path-permission", vec![Attr::string("android:pathPrefix", "/public/"),
+                                             Attr::string("android:readPermission", "com.example.READ")])
+            .end("provider"));
+
+        assert_eq!(ids, vec!["provider-wide-path-permission"]);
+    }
+
+    #[test]
+    fn test_namespace_spoofing() {
+        // `android` is bound to a look-alike URI, and the real android
+        // namespace to `a`
+        let root = parse_xml(Cursor::new(include_bytes!("../tests/data/AndroidManifest-spoofed.xml")));
+
+        let findings: Vec<(&str, String)> = detect_namespace_spoofing(&root)
+            .into_iter()
+            .map(|finding| (finding.id, finding.path))
+            .collect();
+        let application = String::from(APPLICATION_PATH);
+        assert_eq!(findings, vec![
+            ("fake-android-namespace", application.clone()),
+            ("fake-android-namespace", application.clone()),
+            ("fake-android-namespace", application.clone()),
+            ("duplicate-namespaced-attribute", application.clone()),
+            ("duplicate-namespaced-attribute", application.clone()),
+            ("duplicate-namespaced-attribute", application),
+            ("fake-android-namespace", component_path("activity", "com.example.spoofed.Launcher")),
+            ("fake-android-namespace", component_path("activity", "com.example.spoofed.Spoofed")),
+            ("duplicate-namespaced-attribute", component_path("service", "com.example.spoofed.Duplicated")),
+            ("fake-android-namespace", component_path("receiver", "com.example.spoofed.Guarded")),
+        ]);
+
+        // The exposure only depends on the attributes of the android
+        // namespace: the fake `android:exported="false"` and
+        // `android:permission` are ignored
+        let exposed: Vec<(String, bool)> = crate::get_exposure_report(&root)
+            .into_iter()
+            .map(|component| (component.name.clone(), component.is_exposed() && component.protected_by_permission.is_none()))
+            .collect();
+        assert_eq!(exposed, vec![
+            (String::from("com.example.spoofed.Launcher"), true),
+            (String::from("com.example.spoofed.Spoofed"), true),
+            (String::from("com.example.spoofed.Duplicated"), true),
+            (String::from("com.example.spoofed.Guarded"), true),
+            (String::from("com.example.spoofed.Hidden"), false),
+        ]);
+
+        // Same for the flags of the application: the fake `false` values do
+        // not hide the real ones
+        let flags = get_application_flags(&root);
+        assert_eq!((flags.debuggable, flags.allow_backup, flags.uses_cleartext_traffic),
+                   (FlagState::ExplicitTrue, FlagState::ExplicitTrue, FlagState::ExplicitTrue));
+        let ids: Vec<&str> = run_lints(&root, None).into_iter().map(|finding| finding.id).collect();
+        for id in ["debuggable", "allow-backup", "cleartext-traffic"] {
+            assert!(ids.contains(&id), "{id} not in {ids:?}");
+        }
+
+        // And the fake `android:enabled="false"` does not disable the
+        // launcher activity
+        assert_eq!(crate::find_main_entry_points(&root), vec![String::from("com.example.spoofed.Launcher")]);
+    }
+
+    #[test]
+    fn test_no_namespace_spoofing() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .namespace("app", "http://schemas.android.com/apk/res-auto")
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", ".Main"),
+                                      Attr::boolean("android:exported", false),
+                                      Attr::string("app:theme", "custom")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        assert!(detect_namespace_spoofing(&root).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_attribute() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", safe_application())
+            .element("service", vec![Attr::string("android:name", ".Sync"),
+                                     Attr::boolean("android:exported", false),
+                                     Attr::boolean("android:exported", true),
+                                     Attr::string("android:process", ":a"),
+                                     Attr::string("android:process", ":b"),
+                                     Attr::string("android:process", ":c")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let mut findings = Vec::new();
+        lint_duplicate_attributes(&root, &mut findings);
+        let messages: Vec<&str> = findings.iter().map(|finding| finding.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "android:exported is set several times (false, true), Android uses the first value",
+            "android:process is set several times (:a, :b, :c), Android uses the first value",
+        ]);
+        assert!(findings.iter().all(|finding| finding.path == component_path("service", "com.example.Sync")));
+    }
+}
@@ -0,0 +1,438 @@
+//! Manifest merger
+//!
+//! Combine the manifests of libraries into the manifest of an app, following
+//! a subset of the rules of the Android manifest merger:
+//!
+//! - the elements of the libraries that the app does not have are added
+//!   (permissions, features, components, etc.), with their class names
+//!   qualified with the package of the library;
+//! - elements are matched by type and `android:name` (fully qualified for
+//!   class names), `application` and `queries` by type only, and elements
+//!   without a name only match identical elements;
+//! - the attributes of matching elements are merged, and when both have a
+//!   different value the app wins and a [`MergeConflict`] is reported, unless
+//!   the app lists the attribute in `tools:replace`;
+//! - `tools:node="replace"` on an element of the app keeps it as is, and
+//!   `tools:node="remove"` removes it along with the matching elements of the
+//!   libraries;
+//! - `${applicationId}` placeholders are replaced with the package of the
+//!   app, and the `tools:` attributes are removed from the result.
+//!
+//! The libraries are merged in order, so a library given first has priority
+//! over the next ones. The `manifest` attributes and the `uses-sdk` element of
+//! the libraries are ignored.
+
+use std::fmt;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::attributes::Attribute;
+use crate::fingerprint::CLASS_ELEMENTS;
+use crate::normalize_component_name;
+use crate::parser::{ XmlElement, XmlNode };
+
+/// URI of the `tools` namespace, for the merge markers
+pub const TOOLS_NS: &str = "http://schemas.android.com/tools";
+
+/// Placeholder replaced with the package of the app
+const APPLICATION_ID: &str = "${applicationId}";
+
+/// Attribute with different values in the app and in a library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    /// Path of the element, e.g. `manifest/application/activity[.Main]`
+    pub path: String,
+    /// Key of the attribute, e.g. `android:exported`
+    pub key: String,
+    /// Value of the app, which is kept
+    pub kept: String,
+    /// Value of the library, which is discarded
+    pub discarded: String,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} @{}: kept \"{}\", discarded \"{}\"", self.path, self.key, self.kept, self.discarded)
+    }
+}
+
+/// Merged manifest, see [`merge_manifests`]
+#[derive(Debug)]
+pub struct MergeResult {
+    /// Root of the merged manifest, a new tree
+    pub root: Rc<RefCell<XmlElement>>,
+    /// Conflicting attributes, in merge order
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// State of the merge of one library
+struct Merger<'a> {
+    app_package: &'a str,
+    library_package: &'a str,
+    /// Prefixes bound to the `tools` namespace, in the app or in a library
+    tools_prefixes: &'a [String],
+    conflicts: &'a mut Vec<MergeConflict>,
+}
+
+/// Merge the manifests of `libraries` into the manifest of `app`, see the
+/// module documentation. The input trees are not modified.
+pub fn merge_manifests(app: &Rc<RefCell<XmlElement>>, libraries: &[Rc<RefCell<XmlElement>>]) -> MergeResult {
+    let root = app.borrow().clone_tree();
+    let app_package = app.borrow().get_attr("package").unwrap_or_default().to_string();
+
+    let mut tools_prefixes: Vec<String> = std::iter::once(app)
+        .chain(libraries.iter())
+        .filter_map(|manifest| manifest.borrow().namespace_prefix(TOOLS_NS))
+        .collect();
+    if tools_prefixes.is_empty() {
+        tools_prefixes.push(String::from("tools"));
+    }
+
+    let mut conflicts = Vec::new();
+    for library in libraries {
+        let library = library.borrow();
+
+        // Namespaces used by the library but not declared by the app
+        for (key, value) in library.attributes.iter().filter(|(key, _)| key.starts_with("xmlns:")) {
+            if !root.borrow().attributes.contains_key(key) {
+                root.borrow_mut().attributes.insert(key, value.clone());
+            }
+        }
+
+        let mut merger = Merger {
+            app_package: &app_package,
+            library_package: library.get_attr("package").unwrap_or_default(),
+            tools_prefixes: &tools_prefixes,
+            conflicts: &mut conflicts,
+        };
+        merger.merge_children(&root, &library, &library.element_type);
+    }
+
+    finish(&mut root.borrow_mut(), &app_package, &tools_prefixes);
+    MergeResult { root, conflicts }
+}
+
+/// Value of `android:name` of an element, fully qualified if it is a class name
+fn semantic_name(element: &XmlElement, package: &str) -> Option<String> {
+    let name = element.get_attr("name")?;
+    if CLASS_ELEMENTS.contains(&element.element_type.as_str()) {
+        Some(normalize_component_name(package, name))
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Whether `key` is the class name attribute of `element`
+fn is_class_name(element: &XmlElement, key: &str) -> bool {
+    CLASS_ELEMENTS.contains(&element.element_type.as_str()) &&
+        element.attributes.get_attribute(key).is_some_and(|attr| attr.local_name() == "name")
+}
+
+impl Merger<'_> {
+    fn is_tools_key(&self, key: &str) -> bool {
+        key.split_once(':').is_some_and(|(prefix, _)| self.tools_prefixes.iter().any(|tools| tools == prefix))
+    }
+
+    /// Value of the `tools:` attribute `name` of `element`
+    fn tools_attr<'e>(&self, element: &'e XmlElement, name: &str) -> Option<&'e str> {
+        self.tools_prefixes.iter()
+                           .find_map(|prefix| element.attributes.get(&format!("{prefix}:{name}")))
+                           .map(String::as_str)
+    }
+
+    /// Child of `target` matching `child` of the library, see the module
+    /// documentation
+    fn find_match(&self, target: &XmlElement, child: &XmlElement) -> Option<Rc<RefCell<XmlElement>>> {
+        let by_type = matches!(child.element_type.as_str(), "application" | "queries");
+        let name = semantic_name(child, self.library_package);
+
+        target.child_elements()
+              .find(|candidate| {
+                  let candidate = candidate.borrow();
+                  candidate.element_type == child.element_type &&
+                      candidate.namespace == child.namespace &&
+                      (by_type || match name.as_ref() {
+                          Some(name) => semantic_name(&candidate, self.app_package).as_ref() == Some(name),
+                          None => *candidate == *child,
+                      })
+              })
+              .cloned()
+    }
+
+    fn merge_children(&mut self, target: &Rc<RefCell<XmlElement>>, source: &XmlElement, path: &str) {
+        let is_root = target.borrow().parent().is_none();
+
+        for child in source.child_elements() {
+            let child = child.borrow();
+            if is_root && child.element_type == "uses-sdk" {
+                continue;
+            }
+
+            let existing = self.find_match(&target.borrow(), &child);
+            match existing {
+                Some(existing) => {
+                    if matches!(self.tools_attr(&existing.borrow(), "node"), Some("remove" | "replace")) {
+                        continue;
+                    }
+
+                    let child_path = match child.get_attr("name") {
+                        Some(name) => format!("{path}/{}[{name}]", child.element_type),
+                        None => format!("{path}/{}", child.element_type),
+                    };
+                    self.merge_attributes(&mut existing.borrow_mut(), &child, &child_path);
+                    self.merge_children(&existing, &child, &child_path);
+                },
+                None => {
+                    let copy = child.clone_tree();
+                    self.qualify_class_names(&mut copy.borrow_mut());
+                    XmlElement::append_child(target, copy);
+                },
+            }
+        }
+    }
+
+    fn merge_attributes(&mut self, target: &mut XmlElement, source: &XmlElement, path: &str) {
+        let replaced: Vec<String> = self.tools_attr(target, "replace")
+                                        .map(|keys| keys.split(',').map(|key| key.trim().to_string()).collect())
+                                        .unwrap_or_default();
+
+        for attr in source.attributes.iter_attributes() {
+            if self.is_tools_key(&attr.name) {
+                continue;
+            }
+
+            let class_name = is_class_name(source, &attr.name);
+            let value = if class_name {
+                normalize_component_name(self.library_package, &attr.value)
+            } else {
+                attr.value.clone()
+            };
+
+            match target.attributes.get(&attr.name) {
+                None => {
+                    target.attributes.insert_attribute(Attribute { value, ..attr.clone() });
+                },
+                Some(kept) => {
+                    let same = if class_name {
+                        normalize_component_name(self.app_package, kept) == value
+                    } else {
+                        *kept == value
+                    };
+                    if !same && !replaced.iter().any(|key| *key == attr.name) {
+                        self.conflicts.push(MergeConflict {
+                            path: path.to_string(),
+                            key: attr.name.to_string(),
+                            kept: kept.clone(),
+                            discarded: attr.value.clone(),
+                        });
+                    }
+                },
+            }
+        }
+    }
+
+    /// Qualify the relative class names of an element copied from the
+    /// library, which refer to the package of the library
+    fn qualify_class_names(&self, element: &mut XmlElement) {
+        if CLASS_ELEMENTS.contains(&element.element_type.as_str()) {
+            let key = element.attributes.find_local("name").map(|attr| attr.name.clone());
+            if let Some(value) = key.as_ref().and_then(|key| element.attributes.get_mut(key)) {
+                *value = normalize_component_name(self.library_package, value);
+            }
+        }
+
+        for child in element.child_elements() {
+            self.qualify_class_names(&mut child.borrow_mut());
+        }
+    }
+}
+
+/// Remove the elements marked with `tools:node="remove"` and all the `tools:`
+/// attributes, and replace the placeholders
+fn finish(element: &mut XmlElement, app_package: &str, tools_prefixes: &[String]) {
+    let is_tools_key = |key: &str| {
+        key.split_once(':').is_some_and(|(prefix, _)| tools_prefixes.iter().any(|tools| tools == prefix))
+    };
+
+    element.children.retain(|child| match child {
+        XmlNode::Element(child) => {
+            let child = child.borrow();
+            !tools_prefixes.iter().any(|prefix| {
+                child.attributes.get(&format!("{prefix}:node")).is_some_and(|node| node == "remove")
+            })
+        },
+        XmlNode::Text(_) => true,
+    });
+
+    let tools_keys: Vec<String> = element.attributes.keys().filter(|key| is_tools_key(key)).map(String::from).collect();
+    for key in tools_keys {
+        element.attributes.remove(&key);
+    }
+    let placeholders: Vec<String> = element.attributes
+                                           .iter()
+                                           .filter(|(_, value)| value.contains(APPLICATION_ID))
+                                           .map(|(key, _)| key.to_string())
+                                           .collect();
+    for key in placeholders {
+        if let Some(value) = element.attributes.get_mut(&key) {
+            *value = value.replace(APPLICATION_ID, app_package);
+        }
+    }
+
+    for child in element.child_elements() {
+        finish(&mut child.borrow_mut(), app_package, tools_prefixes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn app_manifest() -> Rc<RefCell<XmlElement>> {
+        parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .namespace("tools", TOOLS_NS)
+            .start("manifest", vec![Attr::string("package", "com.example.app")])
+            .element("uses-sdk", vec![Attr::int("android:minSdkVersion", 21)])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.INTERNET")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.READ_CONTACTS"),
+                                             Attr::string("tools:node", "remove")])
+            .start("application", vec![Attr::string("android:label", "App"),
+                                       Attr::string("tools:replace", "android:label")])
+            .element("activity", vec![Attr::string("android:name", ".Main"),
+                                      Attr::boolean("android:exported", true)])
+            .element("service", vec![Attr::string("android:name", "com.example.lib.Sync"),
+                                     Attr::string("tools:node", "replace")])
+            .end("application")
+            .end("manifest")
+            .build()))
+    }
+
+    fn library_manifest() -> Rc<RefCell<XmlElement>> {
+        parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example.lib")])
+            .element("uses-sdk", vec![Attr::int("android:minSdkVersion", 26)])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.INTERNET")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.READ_CONTACTS")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.CAMERA")])
+            .element("uses-feature", vec![Attr::string("android:name", "android.hardware.camera"),
+                                          Attr::boolean("android:required", false)])
+            .start("application", vec![Attr::string("android:label", "Library"),
+                                       Attr::boolean("android:allowBackup", false)])
+            .element("activity", vec![Attr::string("android:name", "com.example.app.Main"),
+                                      Attr::boolean("android:exported", false),
+                                      Attr::string("android:theme", "@style/Lib")])
+            .element("service", vec![Attr::string("android:name", ".Sync"),
+                                     Attr::boolean("android:exported", true)])
+            .start("provider", vec![Attr::string("android:name", ".LibProvider"),
+                                    Attr::string("android:authorities", "${applicationId}.lib")])
+            .element("meta-data", vec![Attr::string("android:name", "key"), Attr::string("android:value", "v")])
+            .end("provider")
+            .end("application")
+            .end("manifest")
+            .build()))
+    }
+
+    fn names(element: &Rc<RefCell<XmlElement>>, element_type: &str) -> Vec<String> {
+        element.borrow()
+               .child_elements()
+               .filter(|child| child.borrow().element_type == element_type)
+               .map(|child| child.borrow().get_attr("name").unwrap_or_default().to_string())
+               .collect()
+    }
+
+    #[test]
+    fn test_merge_library() {
+        let app = app_manifest();
+        let result = merge_manifests(&app, &[library_manifest()]);
+        let root = &result.root;
+
+        // Union of the permissions, except the one removed by the app
+        assert_eq!(names(root, "uses-permission"), vec!["android.permission.INTERNET",
+                                                        "android.permission.CAMERA"]);
+        assert_eq!(names(root, "uses-feature"), vec!["android.hardware.camera"]);
+        // The minimum SDK of the app is kept
+        assert_eq!(names(root, "uses-sdk").len(), 1);
+        let uses_sdk = root.borrow().child_elements().next().unwrap().borrow().get_attr_int("minSdkVersion");
+        assert_eq!(uses_sdk, Some(21));
+
+        // Components injected with qualified names, placeholders replaced
+        let application = Rc::clone(root.borrow().child_elements().find(|child| {
+            child.borrow().element_type == "application"
+        }).unwrap());
+        assert_eq!(names(&application, "activity"), vec![".Main"]);
+        assert_eq!(names(&application, "service"), vec!["com.example.lib.Sync"]);
+        assert_eq!(names(&application, "provider"), vec!["com.example.lib.LibProvider"]);
+        let provider = Rc::clone(application.borrow().child_elements().last().unwrap());
+        assert_eq!(provider.borrow().get_attr("authorities"), Some("com.example.app.lib"));
+        assert_eq!(provider.borrow().child_elements().count(), 1);
+
+        // Attributes: the app wins, missing ones are added
+        let application = application.borrow();
+        assert_eq!(application.get_attr("label"), Some("App"));
+        assert_eq!(application.get_attr_bool("allowBackup"), Some(false));
+        let activity = application.child_elements().next().unwrap().borrow();
+        assert_eq!(activity.get_attr_bool("exported"), Some(true));
+        assert_eq!(activity.get_attr("theme"), Some("@style/Lib"));
+        // Replaced by the app
+        let service = application.child_elements().nth(1).unwrap().borrow();
+        assert!(!service.attributes.contains_key("android:exported"));
+
+        // No markers left
+        let xml = root.borrow().to_xml_string();
+        assert!(!xml.contains("tools:node") && !xml.contains("tools:replace"), "{xml}");
+
+        assert_eq!(result.conflicts, vec![MergeConflict {
+            path: String::from("manifest/application/activity[com.example.app.Main]"),
+            key: String::from("android:exported"),
+            kept: String::from("true"),
+            discarded: String::from("false"),
+        }]);
+        assert_eq!(result.conflicts[0].to_string(),
+                   "manifest/application/activity[com.example.app.Main] @android:exported: kept \"true\", discarded \"false\"");
+
+        // The inputs are untouched
+        assert_eq!(names(&app, "uses-permission").len(), 2);
+    }
+
+    #[test]
+    fn test_merge_order() {
+        let library = |package: &str, label: &str| parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .namespace("tools", TOOLS_NS)
+            .start("manifest", vec![Attr::string("package", package)])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", "com.example.Shared"),
+                                      Attr::string("android:label", label)])
+            .element("activity", vec![Attr::string("android:name", &format!("{package}.Removed")),
+                                      Attr::string("tools:node", "remove")])
+            .end("application")
+            .end("manifest")
+            .build()));
+        let app = parse_xml(Cursor::new(AxmlBuilder::new()
+            .start("manifest", vec![Attr::string("package", "com.example.app")])
+            .end("manifest")
+            .build()));
+
+        // The first library has priority, and its marker removes the
+        // activity of the second one
+        let first = library("com.example.first", "First");
+        let second = library("com.example.second", "Second");
+        second.borrow().child_elements().next().unwrap().borrow_mut()
+              .child_elements().nth(1).unwrap().borrow_mut()
+              .attributes.insert(String::from("android:name"), String::from("com.example.first.Removed"));
+        let result = merge_manifests(&app, &[first, second]);
+
+        let application = Rc::clone(result.root.borrow().child_elements().next().unwrap());
+        assert_eq!(names(&application, "activity"), vec!["com.example.Shared"]);
+        assert_eq!(application.borrow().child_elements().next().unwrap().borrow().get_attr("label"), Some("First"));
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].discarded, "Second");
+        // Declaration of the namespace of the libraries
+        assert_eq!(result.root.borrow().attributes.get("xmlns:tools").map(String::as_str), Some(TOOLS_NS));
+    }
+}
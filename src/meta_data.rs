@@ -0,0 +1,130 @@
+//! Meta-data
+//!
+//! `<meta-data>` elements attach arbitrary name/value pairs to the
+//! application or to a component (API keys, library configuration, file
+//! provider paths, etc.). The value is either given inline with
+//! `android:value`, whose type depends on how it was compiled, or as a
+//! reference to a resource with `android:resource`.
+
+use std::collections::HashMap;
+
+use crate::attributes::Attribute;
+use crate::data_value_type::DataValueType;
+use crate::find_elements_by_type;
+use crate::owned::ElementRef;
+
+/// Value of a `<meta-data>` element
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetaDataValue {
+    /// String value
+    String(String),
+    /// Resource ID, from `android:resource` or from a reference in
+    /// `android:value`
+    Resource(u32),
+    /// Integer value (including colors)
+    Int(i64),
+    /// Boolean value
+    Bool(bool),
+    /// Floating-point value
+    Float(f32),
+}
+
+impl MetaDataValue {
+    /// Convert the `android:value` attribute based on its typed value.
+    /// Attributes without a typed value are kept as strings.
+    fn from_attribute(attr: &Attribute) -> Self {
+        let Some(typed) = attr.typed_value else {
+            return MetaDataValue::String(attr.value.clone());
+        };
+
+        match typed.data_type {
+            DataValueType::TypeIntBoolean => MetaDataValue::Bool(typed.data != 0),
+            DataValueType::TypeIntDec => MetaDataValue::Int(i64::from(typed.data as i32)),
+            DataValueType::TypeIntHex |
+            DataValueType::TypeIntColorArgb8 |
+            DataValueType::TypeIntColorRgb8 |
+            DataValueType::TypeIntColorArgb4 |
+            DataValueType::TypeIntColorRgb4 => MetaDataValue::Int(i64::from(typed.data)),
+            DataValueType::TypeFloat => MetaDataValue::Float(f32::from_bits(typed.data)),
+            DataValueType::TypeReference |
+            DataValueType::TypeDynamicReference => MetaDataValue::Resource(typed.data),
+            _ => MetaDataValue::String(attr.value.clone()),
+        }
+    }
+}
+
+/// Get the `<meta-data>` entries declared directly in an element (the
+/// application or a component), indexed by name. If a name is declared
+/// several times, the last declaration wins. Entries without a name or
+/// without a value are ignored.
+pub fn get_meta_data<E: ElementRef>(element: &E) -> HashMap<String, MetaDataValue> {
+    element.children()
+           .iter()
+           .filter(|child| child.element_type() == "meta-data")
+           .filter_map(|meta_data| {
+               let name = meta_data.get_attr("name")?;
+               let value = match meta_data.get_attr_reference("resource") {
+                   Some(id) => MetaDataValue::Resource(id),
+                   None => MetaDataValue::from_attribute(&meta_data.get_attribute("value")?),
+               };
+               Some((name, value))
+           })
+           .collect()
+}
+
+/// Get the `<meta-data>` entries of the `<application>` element, see
+/// [`get_meta_data`]
+pub fn get_application_meta_data<E: ElementRef>(root: &E) -> HashMap<String, MetaDataValue> {
+    find_elements_by_type(root, "application")
+        .first()
+        .map(get_meta_data)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_meta_data() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("meta-data", vec![Attr::string("android:name", "com.google.android.geo.API_KEY"),
+                                       Attr::string("android:value", "AIzaSy")])
+            .element("meta-data", vec![Attr::string("android:name", "com.google.android.gms.version"),
+                                       Attr::int("android:value", 12451000)])
+            .element("meta-data", vec![Attr::string("android:name", "firebase_analytics_collection_enabled"),
+                                       Attr::boolean("android:value", false)])
+            .element("meta-data", vec![Attr::string("android:name", "ratio"),
+                                       Attr::float("android:value", 1.5)])
+            .element("meta-data", vec![Attr::string("android:name", "android.support.FILE_PROVIDER_PATHS"),
+                                       Attr::reference("android:resource", 0x7f140003)])
+            .element("meta-data", vec![Attr::string("android:name", "no_value")])
+            .start("service", vec![Attr::string("android:name", ".Sync")])
+            .element("meta-data", vec![Attr::string("android:name", "config"),
+                                       Attr::reference("android:value", 0x7f0e0001)])
+            .end("service")
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let meta_data = get_application_meta_data(&root);
+
+        assert_eq!(meta_data.len(), 5);
+        assert_eq!(meta_data["com.google.android.geo.API_KEY"], MetaDataValue::String(String::from("AIzaSy")));
+        assert_eq!(meta_data["com.google.android.gms.version"], MetaDataValue::Int(12451000));
+        assert_eq!(meta_data["firebase_analytics_collection_enabled"], MetaDataValue::Bool(false));
+        assert_eq!(meta_data["ratio"], MetaDataValue::Float(1.5));
+        assert_eq!(meta_data["android.support.FILE_PROVIDER_PATHS"], MetaDataValue::Resource(0x7f140003));
+
+        let service = find_elements_by_type(&root, "service").pop().unwrap();
+        let service_meta_data = get_meta_data(&service);
+        assert_eq!(service_meta_data.len(), 1);
+        assert_eq!(service_meta_data["config"], MetaDataValue::Resource(0x7f0e0001));
+    }
+}
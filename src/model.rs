@@ -0,0 +1,235 @@
+//! Typed manifest model
+//!
+//! [`Manifest`] gathers the information most users need from a manifest
+//! (package, version, SDK versions, permissions, features, package
+//! visibility, and the application with its components) into plain structs,
+//! using the helpers of the other modules. The tree it is built from stays
+//! available for everything else.
+
+use std::collections::HashMap;
+
+use crate::application::{ get_application_flags, ApplicationFlags };
+use crate::features::{ get_uses_features, UsesFeature };
+use crate::intent_filter::{ extract_intent_filters, IntentFilter };
+use crate::meta_data::{ get_application_meta_data, get_meta_data, MetaDataValue };
+use crate::owned::ElementRef;
+use crate::permissions::{ get_requested_permissions, GuardPermission, RequestedPermission };
+use crate::queries::{ get_queries, Queries };
+use crate::sdk::{ get_sdk_versions, SdkVersions };
+use crate::version::{ get_version_info, VersionInfo };
+use crate::{
+    exposure_report_with_elements,
+    manifest_package,
+    no_resolver,
+    ComponentState,
+};
+
+/// Contents of a manifest, see [`Manifest::from_tree`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+    /// Package name of the app
+    pub package: String,
+    /// Version code and name
+    pub version: VersionInfo,
+    /// API levels from `<uses-sdk>`
+    pub sdk: SdkVersions,
+    /// The `<application>` element, empty if there is none
+    pub application: Application,
+    /// Requested permissions, in document order
+    pub permissions: Vec<RequestedPermission>,
+    /// Features declared with `<uses-feature>`, in document order
+    pub features: Vec<UsesFeature>,
+    /// Other apps the app can see
+    pub queries: Queries,
+}
+
+/// Contents of the `<application>` element
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Application {
+    /// Components of the app, in document order
+    pub components: Vec<Component>,
+    /// Security-relevant flags, as set in the manifest
+    pub flags: ApplicationFlags,
+    /// Meta-data of the application, indexed by name
+    pub meta_data: HashMap<String, MetaDataValue>,
+}
+
+/// Activity, activity-alias, service, receiver, or provider
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+    /// Type of the component (`activity`, `activity-alias`, `service`,
+    /// `receiver`, or `provider`)
+    pub kind: String,
+    /// Fully-qualified name of the component
+    pub name: String,
+    /// Exported state, see [`crate::get_exported_state`]
+    pub exported: ComponentState,
+    /// Enabled state, see [`crate::ExposedComponent::enabled`]
+    pub enabled: ComponentState,
+    /// Enabled state of the application, see
+    /// [`crate::ExposedComponent::application_enabled`]
+    pub application_enabled: ComponentState,
+    /// Permission a caller needs to hold to interact with the component
+    pub permission: Option<GuardPermission>,
+    /// Intent filters of the component
+    pub intent_filters: Vec<IntentFilter>,
+    /// Meta-data of the component, indexed by name
+    pub meta_data: HashMap<String, MetaDataValue>,
+}
+
+impl Component {
+    /// Check if the component is both enabled and exported, see
+    /// [`crate::ExposedComponent::is_exposed`]
+    pub fn is_exposed(&self) -> bool {
+        self.application_enabled.is_true() && self.enabled.is_true() && self.exported.is_true()
+    }
+}
+
+impl Manifest {
+    /// Extract the contents of a parsed manifest. Component names are fully
+    /// qualified, and components declared several times are listed once, with
+    /// the merged attributes of all their declarations and the intent filters
+    /// and meta-data of the last one (see [`crate::get_exposure_report`]).
+    pub fn from_tree<E: ElementRef>(root: &E) -> Manifest {
+        let components = exposure_report_with_elements(root, &no_resolver)
+            .into_iter()
+            .map(|(element, report)| Component {
+                intent_filters: extract_intent_filters(&element),
+                meta_data: get_meta_data(&element),
+                kind: report.kind,
+                name: report.name,
+                exported: report.exported,
+                enabled: report.enabled,
+                application_enabled: report.application_enabled,
+                permission: report.protected_by_permission,
+            })
+            .collect();
+
+        Manifest {
+            package: manifest_package(root),
+            version: get_version_info(root),
+            sdk: get_sdk_versions(root),
+            application: Application {
+                components,
+                flags: get_application_flags(root),
+                meta_data: get_application_meta_data(root),
+            },
+            permissions: get_requested_permissions(root),
+            features: get_uses_features(root),
+            queries: get_queries(root),
+        }
+    }
+
+    /// Get a component by type and fully-qualified name
+    pub fn component(&self, kind: &str, name: &str) -> Option<&Component> {
+        self.application.components
+                        .iter()
+                        .find(|component| component.kind == kind && component.name == name)
+    }
+
+    /// Get the components that are both enabled and exported, in document
+    /// order
+    pub fn exposed_components(&self) -> impl Iterator<Item = &Component> {
+        self.application.components.iter().filter(|component| component.is_exposed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::application::FlagState;
+    use crate::owned::OwnedXmlElement;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn sample_manifest() -> Vec<u8> {
+        AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::int("android:versionCode", 42),
+                                    Attr::string("android:versionName", "4.2")])
+            .element("uses-sdk", vec![Attr::int("android:minSdkVersion", 24),
+                                      Attr::int("android:targetSdkVersion", 34)])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.CAMERA")])
+            .element("uses-feature", vec![Attr::string("android:name", "android.hardware.camera"),
+                                          Attr::boolean("android:required", false)])
+            .start("queries", vec![])
+            .element("package", vec![Attr::string("android:name", "com.example.other")])
+            .end("queries")
+            .start("application", vec![Attr::boolean("android:debuggable", true)])
+            .element("meta-data", vec![Attr::string("android:name", "api_key"),
+                                       Attr::string("android:value", "secret")])
+            .start("activity", vec![Attr::string("android:name", ".Main"),
+                                    Attr::boolean("android:exported", true)])
+            .start("intent-filter", vec![])
+            .element("action", vec![Attr::string("android:name", "android.intent.action.MAIN")])
+            .element("category", vec![Attr::string("android:name", "android.intent.category.LAUNCHER")])
+            .end("intent-filter")
+            .end("activity")
+            .start("service", vec![Attr::string("android:name", ".Sync"),
+                                   Attr::boolean("android:exported", true),
+                                   Attr::string("android:permission", "android.permission.BIND_JOB_SERVICE")])
+            .element("meta-data", vec![Attr::string("android:name", "interval"), Attr::int("android:value", 60)])
+            .end("service")
+            .element("receiver", vec![Attr::string("android:name", ".Boot"),
+                                      Attr::boolean("android:enabled", false),
+                                      Attr::boolean("android:exported", true)])
+            .end("application")
+            .end("manifest")
+            .build()
+    }
+
+    #[test]
+    fn test_manifest_from_tree() {
+        let manifest = Manifest::from_tree(&parse_xml(Cursor::new(sample_manifest())));
+
+        assert_eq!(manifest.package, "com.example");
+        assert_eq!(manifest.version.version_code, Some(42));
+        assert_eq!(manifest.version.version_name.as_deref(), Some("4.2"));
+        assert_eq!(manifest.sdk.target, Some(34));
+        assert_eq!(manifest.permissions.len(), 1);
+        assert_eq!(manifest.permissions[0].name, "android.permission.CAMERA");
+        assert_eq!(manifest.features[0].name.as_deref(), Some("android.hardware.camera"));
+        assert!(!manifest.features[0].required);
+        assert_eq!(manifest.queries.packages, vec!["com.example.other"]);
+
+        let application = &manifest.application;
+        assert_eq!(application.flags.debuggable, FlagState::ExplicitTrue);
+        assert_eq!(application.meta_data["api_key"], MetaDataValue::String(String::from("secret")));
+        assert_eq!(application.components.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+                   vec!["com.example.Main", "com.example.Sync", "com.example.Boot"]);
+
+        let main = manifest.component("activity", "com.example.Main").unwrap();
+        assert_eq!(main.exported, ComponentState::ExplicitTrue);
+        assert!(main.intent_filters[0].has_category("android.intent.category.LAUNCHER"));
+        assert!(main.meta_data.is_empty());
+
+        let sync = manifest.component("service", "com.example.Sync").unwrap();
+        assert_eq!(sync.permission.as_ref().map(|p| p.name.as_str()), Some("android.permission.BIND_JOB_SERVICE"));
+        assert_eq!(sync.meta_data["interval"], MetaDataValue::Int(60));
+
+        let boot = manifest.component("receiver", "com.example.Boot").unwrap();
+        assert_eq!(boot.enabled, ComponentState::ExplicitFalse);
+        assert!(manifest.component("activity", "com.example.Boot").is_none());
+
+        assert_eq!(manifest.exposed_components().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+                   vec!["com.example.Main", "com.example.Sync"]);
+    }
+
+    #[test]
+    fn test_manifest_from_owned_tree() {
+        let root = parse_xml(Cursor::new(sample_manifest()));
+        let owned = OwnedXmlElement::from(&*root.borrow());
+        assert_eq!(Manifest::from_tree(&&owned), Manifest::from_tree(&root));
+
+        // No application
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .end("manifest")
+            .build()));
+        let manifest = Manifest::from_tree(&root);
+        assert_eq!(manifest.package, "com.example");
+        assert_eq!(manifest.application, Application::default());
+    }
+}
@@ -0,0 +1,286 @@
+//! Owned tree representation
+//!
+//! The parser produces a tree of `Rc<RefCell<XmlElement>>`, which is handy to
+//! navigate and modify but cannot cross thread boundaries. `OwnedXmlElement`
+//! holds the same data with plain owned children, so it is `Send + Sync` and
+//! can be parsed on one thread, analyzed on another, or stored in a shared
+//! cache.
+//!
+//! The `ElementRef` trait abstracts over both representations so that the
+//! analysis helpers can work on either of them.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::attributes::{
+    Attribute,
+    Attributes,
+};
+use crate::parser::XmlElement;
+use crate::resource_map::ANDROID_NS;
+use crate::symbol::Symbol;
+
+/// Owned, thread-safe version of an `XmlElement` tree
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OwnedXmlElement {
+    /// Type of element (e.g., `activity`, `service`)
+    pub element_type: Symbol,
+    /// URI of the namespace of the element, see [`XmlElement::namespace`]
+    pub namespace: Option<String>,
+    /// Attributes of the element, in document order
+    pub attributes: Attributes,
+    /// Child elements of the element. Text content is not kept.
+    pub children: Vec<OwnedXmlElement>,
+}
+
+impl OwnedXmlElement {
+    /// Get the value of an attribute from its local name, see [`XmlElement::get_attr`]
+    pub fn get_attr(&self, local_name: &str) -> Option<&str> {
+        self.attributes.find_local(local_name).map(|attr| attr.value.as_str())
+    }
+
+    /// Get an attribute of the `android` namespace from its local name, see
+    /// [`XmlElement::get_android_attribute`]. Only the namespaces declared on
+    /// this element are known to resolve prefixes.
+    pub fn get_android_attribute(&self, local_name: &str) -> Option<&Attribute> {
+        self.attributes.find_namespaced(ANDROID_NS, local_name, |prefix| {
+            self.attributes.get(&format!("xmlns:{prefix}")).cloned()
+        })
+    }
+
+    /// Get the value of an attribute as a boolean, see [`XmlElement::get_attr_bool`]
+    pub fn get_attr_bool(&self, local_name: &str) -> Option<bool> {
+        self.attributes.find_local(local_name)?.as_bool()
+    }
+
+    /// Get the value of an attribute as an integer, see [`XmlElement::get_attr_int`]
+    pub fn get_attr_int(&self, local_name: &str) -> Option<i64> {
+        self.attributes.find_local(local_name)?.as_int()
+    }
+
+    /// Get the resource ID an attribute refers to, see [`XmlElement::get_attr_reference`]
+    pub fn get_attr_reference(&self, local_name: &str) -> Option<u32> {
+        self.attributes.find_local(local_name)?.as_reference()
+    }
+
+    /// Merge a later declaration of the same element into this one: the
+    /// attributes of `other` override the existing ones and its children are
+    /// appended after the existing children
+    pub fn merge(&mut self, other: &OwnedXmlElement) {
+        for attr in other.attributes.iter_attributes() {
+            self.attributes.insert_attribute(attr.clone());
+        }
+        self.children.extend(other.children.iter().cloned());
+    }
+
+    /// Convert back into a `Rc<RefCell<XmlElement>>` tree, with parent links
+    pub fn to_rc_tree(&self) -> Rc<RefCell<XmlElement>> {
+        let element = Rc::new(RefCell::new(XmlElement::new(&self.element_type)));
+        element.borrow_mut().attributes = self.attributes.clone();
+
+        for child in self.children.iter() {
+            XmlElement::append_child(&element, child.to_rc_tree());
+        }
+
+        element
+    }
+}
+
+impl From<&XmlElement> for OwnedXmlElement {
+    fn from(element: &XmlElement) -> Self {
+        OwnedXmlElement {
+            element_type: element.element_type.clone(),
+            namespace: element.namespace.clone(),
+            attributes: element.attributes.clone(),
+            children: element.child_elements()
+                             .map(|child| OwnedXmlElement::from(&*child.borrow()))
+                             .collect(),
+        }
+    }
+}
+
+/// Read-only access to an element, implemented by both tree representations
+/// (`Rc<RefCell<XmlElement>>` and `&OwnedXmlElement`). Values are returned
+/// owned since the `RefCell` borrow cannot outlive the call.
+pub trait ElementRef: Clone {
+    /// Type of the element
+    fn element_type(&self) -> Symbol;
+
+    /// Value of an attribute from its local name, see [`XmlElement::get_attr`]
+    fn get_attr(&self, local_name: &str) -> Option<String>;
+
+    /// Value of an attribute as a boolean, see [`XmlElement::get_attr_bool`]
+    fn get_attr_bool(&self, local_name: &str) -> Option<bool>;
+
+    /// Value of an attribute as an integer, see [`XmlElement::get_attr_int`]
+    fn get_attr_int(&self, local_name: &str) -> Option<i64>;
+
+    /// Resource ID an attribute refers to, see [`XmlElement::get_attr_reference`]
+    fn get_attr_reference(&self, local_name: &str) -> Option<u32>;
+
+    /// Attribute from its local name, along with its typed value
+    fn get_attribute(&self, local_name: &str) -> Option<Attribute>;
+
+    /// Attribute of the `android` namespace, see [`XmlElement::get_android_attribute`]
+    fn get_android_attribute(&self, local_name: &str) -> Option<Attribute>;
+
+    /// All the attributes of the element, in document order
+    fn attributes(&self) -> Attributes;
+
+    /// Children of the element
+    fn children(&self) -> Vec<Self>;
+
+    /// Owned copy of the element and its subtree
+    fn to_owned_element(&self) -> OwnedXmlElement;
+}
+
+impl ElementRef for Rc<RefCell<XmlElement>> {
+    fn element_type(&self) -> Symbol {
+        self.borrow().element_type.clone()
+    }
+
+    fn get_attr(&self, local_name: &str) -> Option<String> {
+        self.borrow().get_attr(local_name).map(String::from)
+    }
+
+    fn get_attr_bool(&self, local_name: &str) -> Option<bool> {
+        self.borrow().get_attr_bool(local_name)
+    }
+
+    fn get_attr_int(&self, local_name: &str) -> Option<i64> {
+        self.borrow().get_attr_int(local_name)
+    }
+
+    fn get_attr_reference(&self, local_name: &str) -> Option<u32> {
+        self.borrow().get_attr_reference(local_name)
+    }
+
+    fn get_attribute(&self, local_name: &str) -> Option<Attribute> {
+        self.borrow().attributes.find_local(local_name).cloned()
+    }
+
+    fn get_android_attribute(&self, local_name: &str) -> Option<Attribute> {
+        self.borrow().get_android_attribute(local_name).cloned()
+    }
+
+    fn attributes(&self) -> Attributes {
+        self.borrow().attributes.clone()
+    }
+
+    fn children(&self) -> Vec<Self> {
+        self.borrow().child_elements().cloned().collect()
+    }
+
+    fn to_owned_element(&self) -> OwnedXmlElement {
+        self.borrow().to_owned_tree()
+    }
+}
+
+impl ElementRef for &OwnedXmlElement {
+    fn element_type(&self) -> Symbol {
+        self.element_type.clone()
+    }
+
+    fn get_attr(&self, local_name: &str) -> Option<String> {
+        OwnedXmlElement::get_attr(self, local_name).map(String::from)
+    }
+
+    fn get_attr_bool(&self, local_name: &str) -> Option<bool> {
+        OwnedXmlElement::get_attr_bool(self, local_name)
+    }
+
+    fn get_attr_int(&self, local_name: &str) -> Option<i64> {
+        OwnedXmlElement::get_attr_int(self, local_name)
+    }
+
+    fn get_attr_reference(&self, local_name: &str) -> Option<u32> {
+        OwnedXmlElement::get_attr_reference(self, local_name)
+    }
+
+    fn get_attribute(&self, local_name: &str) -> Option<Attribute> {
+        self.attributes.find_local(local_name).cloned()
+    }
+
+    fn get_android_attribute(&self, local_name: &str) -> Option<Attribute> {
+        OwnedXmlElement::get_android_attribute(self, local_name).cloned()
+    }
+
+    fn attributes(&self) -> Attributes {
+        self.attributes.clone()
+    }
+
+    fn children(&self) -> Vec<Self> {
+        self.children.iter().collect()
+    }
+
+    fn to_owned_element(&self) -> OwnedXmlElement {
+        (*self).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::{ parse_xml, tree_eq };
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn assert_send_sync<T: Send + Sync>() { }
+
+    fn sample_tree() -> Rc<RefCell<XmlElement>> {
+        parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Main")])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .element("service", vec![Attr::string("android:name", ".Sync"),
+                                     Attr::boolean("android:exported", false)])
+            .end("application")
+            .end("manifest")
+            .build()))
+    }
+
+    #[test]
+    fn test_owned_tree_is_send_sync() {
+        assert_send_sync::<OwnedXmlElement>();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tree = sample_tree();
+        let owned = tree.borrow().to_owned_tree();
+
+        assert_eq!(owned.children[0].children[1].get_attr_bool("exported"), Some(false));
+        assert!(tree_eq(&owned.to_rc_tree(), &tree));
+    }
+
+    #[test]
+    fn test_merge() {
+        let owned = sample_tree().borrow().to_owned_tree();
+        let mut activity = owned.children[0].children[0].clone();
+        let mut later = owned.children[0].children[1].clone();
+        later.element_type = Symbol::from("activity");
+
+        activity.merge(&later);
+
+        assert_eq!(activity.get_attr("name"), Some(".Sync"));
+        assert_eq!(activity.get_attr_bool("exported"), Some(false));
+        assert_eq!(activity.children.len(), 1);
+    }
+
+    #[test]
+    fn test_analysis_on_another_thread() {
+        let owned = sample_tree().borrow().to_owned_tree();
+
+        let exposed = std::thread::spawn(move || {
+            let components = crate::get_exposed_components(&owned).unwrap();
+            components["activity"].iter()
+                                  .map(|c| c.get_attr("name").unwrap().to_string())
+                                  .collect::<Vec<String>>()
+        }).join().unwrap();
+
+        assert_eq!(exposed, vec![".Main"]);
+    }
+}
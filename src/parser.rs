@@ -2,63 +2,400 @@
 //!
 //! This module contains the logic to parse the binary XML into a tree structure (`XmlElement`),
 //! representing the actual XML.
+//!
+//! The functions reading a single chunk (`read_*` and `parse_*`) expect the cursor at the start of
+//! the chunk, i.e., at its type, see [`ChunkType::peek_block_type`].
 
 use std::collections::HashMap;
 use std::borrow::Cow;
-use std::rc::Rc;
+use std::fmt;
+use std::rc::{ Rc, Weak };
 use std::cell::RefCell;
 use std::io::{
     Error,
     Cursor,
+    Read,
+    Seek,
     Write,
 };
 use std::fs::File;
 
 use byteorder::{
+    ByteOrder,
     LittleEndian,
     ReadBytesExt
 };
 
 use quick_xml::Writer;
-use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::events::attributes::Attribute;
 use quick_xml::name::QName;
 
 use crate::chunk_types::ChunkType;
 use crate::chunk_header::ChunkHeader;
+use crate::chunks::ChunkIterator;
 use crate::data_value_type::DataValueType;
 use crate::res_value::ResValue;
-use crate::{ ResourceMap, StringPool, ResTable };
+use crate::error::AxmlError;
+use crate::attributes::Attributes;
+use crate::symbol::Symbol;
+use crate::owned::OwnedXmlElement;
+use crate::reader::AxmlReader;
+use crate::resource_map::{ ResourceMap, ANDROID_NS };
+use crate::string_pool::StringPool;
+use crate::visitor::{ self, AxmlVisitor, ElementInfo, VisitControl };
+use crate::warning::{ ParseWarning, ParseWarningKind };
+use crate::{ detect_file_kind, FileKind };
+
+/// Child of an `XmlElement`: an element or a piece of text. Cloning a node
+/// does not copy the element it points to.
+#[derive(Clone, Debug)]
+pub enum XmlNode {
+    Element(Rc<RefCell<XmlElement>>),
+    Text(String),
+}
+
+impl XmlNode {
+    /// The element, if the node is one
+    pub fn as_element(&self) -> Option<&Rc<RefCell<XmlElement>>> {
+        match self {
+            XmlNode::Element(element) => Some(element),
+            XmlNode::Text(_) => None,
+        }
+    }
+
+    /// The text, if the node is some
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            XmlNode::Element(_) => None,
+            XmlNode::Text(text) => Some(text),
+        }
+    }
+}
+
+/// Structural equality, see the `PartialEq` implementation of `XmlElement`
+impl PartialEq for XmlNode {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (XmlNode::Element(a), XmlNode::Element(b)) => tree_eq(a, b),
+            (XmlNode::Text(a), XmlNode::Text(b)) => a == b,
+            _ => false,
+        }
+    }
+}
 
 /// Representation of an XML element with optional children
-#[derive(Debug)]
 pub struct XmlElement {
-    /// Type of element (e.g., `activity`, `service`)
-    pub element_type: String,
-    /// Attributes of the element (e.g., `exported`, `permission`)
-    pub attributes: HashMap<String, String>,
-    /// Vector of children of the XML element
-    pub children: Vec<Rc<RefCell<XmlElement>>>,
+    /// Type of element (e.g., `activity`, `service`), shared with the other
+    /// elements of the same type
+    pub element_type: Symbol,
+    /// URI of the namespace of the element, if any (e.g.,
+    /// `http://schemas.android.com/apk/distribution` for `dist:module`). The
+    /// prefix is taken from the `xmlns:` attributes of the element and its
+    /// ancestors when writing it, see [`XmlElement::qualified_name`].
+    pub namespace: Option<String>,
+    /// Attributes of the element (e.g., `exported`, `permission`), in the
+    /// order in which they appear in the binary chunk
+    pub attributes: Attributes,
+    /// Children of the XML element, elements and text interleaved in
+    /// document order. See [`XmlElement::child_elements`] to only get the
+    /// elements.
+    pub children: Vec<XmlNode>,
+    /// Back-pointer to the parent element, `None` for the root.
+    /// This is a weak reference to avoid reference cycles.
+    pub parent: Option<Weak<RefCell<XmlElement>>>,
+}
+
+/// Custom `Debug` implementation: deriving it would follow the parent link
+/// and recurse forever, so we only print the parent's element type.
+impl fmt::Debug for XmlElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parent = self.parent()
+                         .map(|parent| parent.borrow().element_type.clone());
+
+        f.debug_struct("XmlElement")
+            .field("element_type", &self.element_type)
+            .field("namespace", &self.namespace)
+            .field("attributes", &self.attributes)
+            .field("children", &self.children)
+            .field("parent", &parent)
+            .finish()
+    }
+}
+
+/// Structural equality: compares the element type, the attributes, and the
+/// children recursively. The parent links and the `Rc` pointers are ignored,
+/// so two separately parsed identical documents are equal.
+impl PartialEq for XmlElement {
+    fn eq(&self, other: &Self) -> bool {
+        self.element_type == other.element_type
+            && self.namespace == other.namespace
+            && self.attributes == other.attributes
+            && self.children == other.children
+    }
+}
+
+/// Deep copy of the element and its children. The copy has no parent, and
+/// since it is not wrapped in an `Rc` yet its direct children cannot point
+/// back to it: use [`XmlElement::clone_tree`] to get a copy with all the
+/// parent links set.
+impl Clone for XmlElement {
+    fn clone(&self) -> Self {
+        let mut element = XmlElement::new(&self.element_type);
+        element.namespace = self.namespace.clone();
+        element.attributes = self.attributes.clone();
+        element.children = self.children
+                               .iter()
+                               .map(|child| match child {
+                                   XmlNode::Element(element) => XmlNode::Element(element.borrow().clone_tree()),
+                                   XmlNode::Text(text) => XmlNode::Text(text.clone()),
+                               })
+                               .collect();
+        element
+    }
+}
+
+/// Compare two trees structurally, see the `PartialEq` implementation of
+/// `XmlElement`. Two pointers to the same element are trivially equal.
+pub fn tree_eq(a: &Rc<RefCell<XmlElement>>, b: &Rc<RefCell<XmlElement>>) -> bool {
+    Rc::ptr_eq(a, b) || *a.borrow() == *b.borrow()
+}
+
+/// Display the element as XML, see [`XmlElement::to_xml_string`]
+impl fmt::Display for XmlElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_xml_string())
+    }
+}
+
+/// Iterator over the ancestors of an element, from its parent up to the root
+pub struct Ancestors {
+    next: Option<Rc<RefCell<XmlElement>>>,
+}
+
+impl Iterator for Ancestors {
+    type Item = Rc<RefCell<XmlElement>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next.take()?;
+        self.next = current.borrow().parent();
+        Some(current)
+    }
 }
 
 impl XmlElement {
-    pub fn write_to_file(&self, file: &mut File) -> Result<(), Error> {
-        let mut writer = Writer::new_with_indent(Vec::new(), b' ', 4);
+    /// Create a new element of the given type, with no attributes, children, or parent
+    pub fn new(element_type: impl Into<Symbol>) -> Self {
+        XmlElement {
+            element_type: element_type.into(),
+            namespace: None,
+            attributes: Attributes::new(),
+            children: Vec::new(),
+            parent: None,
+        }
+    }
+
+    /// Attach `child` at the end of the children of `parent`, and set the
+    /// parent link of `child` accordingly.
+    pub fn append_child(parent: &Rc<RefCell<XmlElement>>, child: Rc<RefCell<XmlElement>>) {
+        child.borrow_mut().parent = Some(Rc::downgrade(parent));
+        parent.borrow_mut().children.push(XmlNode::Element(child));
+    }
+
+    /// Insert `child` at position `index` of the children of `parent` (text
+    /// included), and set the parent link of `child` accordingly.
+    ///
+    /// Panics if `index` is greater than the number of children.
+    pub fn insert_child(parent: &Rc<RefCell<XmlElement>>, index: usize, child: Rc<RefCell<XmlElement>>) {
+        child.borrow_mut().parent = Some(Rc::downgrade(parent));
+        parent.borrow_mut().children.insert(index, XmlNode::Element(child));
+    }
+
+    /// Append `text` at the end of the children of the element. Text right
+    /// after another text is merged into it.
+    pub fn append_text(&mut self, text: &str) {
+        match self.children.last_mut() {
+            Some(XmlNode::Text(last)) => last.push_str(text),
+            _ => self.children.push(XmlNode::Text(text.to_string())),
+        }
+    }
+
+    /// Iterate over the children that are elements, ignoring the text
+    pub fn child_elements(&self) -> impl DoubleEndedIterator<Item = &Rc<RefCell<XmlElement>>> {
+        self.children.iter().filter_map(XmlNode::as_element)
+    }
+
+    /// Text directly inside the element (not in its children), concatenated
+    pub fn text(&self) -> String {
+        self.children.iter().filter_map(XmlNode::as_text).collect()
+    }
+
+    /// Deep copy of the element and its children into fresh `Rc<RefCell<_>>`s,
+    /// with parent links pointing inside the copy. The root of the copy has no
+    /// parent, so this can be used to snapshot a subtree.
+    pub fn clone_tree(&self) -> Rc<RefCell<XmlElement>> {
+        let copy = Rc::new(RefCell::new(XmlElement::new(&self.element_type)));
+        copy.borrow_mut().namespace = self.namespace.clone();
+        copy.borrow_mut().attributes = self.attributes.clone();
+
+        for child in self.children.iter() {
+            match child {
+                XmlNode::Element(element) => XmlElement::append_child(&copy, element.borrow().clone_tree()),
+                XmlNode::Text(text) => copy.borrow_mut().append_text(text),
+            }
+        }
+
+        copy
+    }
+
+    /// Convert the tree rooted at this element into an owned, `Send + Sync`
+    /// representation, see [`OwnedXmlElement`]
+    pub fn to_owned_tree(&self) -> OwnedXmlElement {
+        OwnedXmlElement::from(self)
+    }
+
+    /// Get the parent of this element, if any.
+    /// Returns `None` for the root, or if the parent has already been dropped.
+    pub fn parent(&self) -> Option<Rc<RefCell<XmlElement>>> {
+        self.parent.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// Iterate over the ancestors of this element, starting with its direct
+    /// parent and ending with the root of the tree.
+    pub fn ancestors(&self) -> Ancestors {
+        Ancestors {
+            next: self.parent(),
+        }
+    }
+
+    /// Prefix bound to the namespace `uri` by an `xmlns:` attribute of the
+    /// element or of its closest ancestor declaring it
+    pub fn namespace_prefix(&self, uri: &str) -> Option<String> {
+        let find = |element: &XmlElement| {
+            element.attributes
+                   .iter()
+                   .find_map(|(key, value)| (value == uri).then(|| key.strip_prefix("xmlns:"))?)
+                   .map(str::to_string)
+        };
+
+        find(self).or_else(|| self.ancestors().find_map(|ancestor| find(&ancestor.borrow())))
+    }
+
+    /// URI of the namespace bound to `prefix` by an `xmlns:` attribute of the
+    /// element or of its closest ancestor declaring it
+    pub fn namespace_uri(&self, prefix: &str) -> Option<String> {
+        let key = format!("xmlns:{prefix}");
+        self.attributes.get(&key).cloned().or_else(|| {
+            self.ancestors().find_map(|ancestor| ancestor.borrow().attributes.get(&key).cloned())
+        })
+    }
+
+    /// Name of the element, prefixed if it has a namespace (e.g.,
+    /// `dist:module`). The name is left bare if no prefix is bound to the
+    /// namespace.
+    pub fn qualified_name(&self) -> Cow<'_, str> {
+        match self.namespace.as_deref().and_then(|uri| self.namespace_prefix(uri)) {
+            Some(prefix) => Cow::Owned(format!("{prefix}:{}", self.element_type)),
+            None => Cow::Borrowed(&self.element_type),
+        }
+    }
+
+    /// Get the value of an attribute from its local name, regardless of the
+    /// namespace prefix it was stored with. For example, `get_attr("exported")`
+    /// matches `android:exported`, `a:exported` (if the manifest binds the
+    /// android namespace to `a`), or a bare `exported`.
+    /// If several attributes match, `android:<local_name>` is preferred,
+    /// otherwise the first match in document order is returned.
+    pub fn get_attr(&self, local_name: &str) -> Option<&str> {
+        self.attributes.find_local(local_name).map(|attr| attr.value.as_str())
+    }
+
+    /// URI of the namespace of the attribute `key`. For parsed attributes,
+    /// this is the URI stored in the binary chunk, even when the manifest
+    /// binds the prefix to another URI or never declares it. For attributes
+    /// added without a namespace, the prefix of `key` is resolved with
+    /// [`XmlElement::namespace_uri`]. Returns `None` if the attribute is
+    /// absent or has no namespace.
+    pub fn attr_namespace(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.attributes.get_attribute(key)?.resolve_namespace(|prefix| self.namespace_uri(prefix))
+    }
+
+    /// Get an attribute of the `android` namespace from its local name. Unlike
+    /// [`XmlElement::get_attr`], the namespace URI is checked rather than the
+    /// prefix, so that an `android:exported` whose prefix is bound to another
+    /// URI is not taken for the real one.
+    pub fn get_android_attribute(&self, local_name: &str) -> Option<&crate::attributes::Attribute> {
+        self.attributes.find_namespaced(ANDROID_NS, local_name, |prefix| self.namespace_uri(prefix))
+    }
+
+    /// Get the value of an attribute as a boolean, see [`Attribute::as_bool`](crate::attributes::Attribute::as_bool)
+    pub fn get_attr_bool(&self, local_name: &str) -> Option<bool> {
+        self.attributes.find_local(local_name)?.as_bool()
+    }
+
+    /// Get the value of an attribute as an integer, see [`Attribute::as_int`](crate::attributes::Attribute::as_int)
+    pub fn get_attr_int(&self, local_name: &str) -> Option<i64> {
+        self.attributes.find_local(local_name)?.as_int()
+    }
+
+    /// Get the resource ID an attribute refers to, see [`Attribute::as_reference`](crate::attributes::Attribute::as_reference)
+    pub fn get_attr_reference(&self, local_name: &str) -> Option<u32> {
+        self.attributes.find_local(local_name)?.as_reference()
+    }
+
+    /// Write the element and its children as an XML document (including the
+    /// XML declaration) into any `Write` sink, e.g. a file or `stdout`.
+    /// Children are indented with 4 spaces, and attributes are written in the
+    /// order in which they appear in the binary XML, so the output is
+    /// deterministic.
+    pub fn write_to(&self, sink: &mut impl Write) -> Result<(), AxmlError> {
+        self.write_xml(sink, true)
+    }
+
+    /// Convenience wrapper around [`XmlElement::write_to`] for files
+    pub fn write_to_file(&self, file: &mut File) -> Result<(), AxmlError> {
+        self.write_to(file)
+    }
+
+    /// Serialize the element and its children to an XML string, without the
+    /// XML declaration. Children are indented with 4 spaces.
+    pub fn to_xml_string(&self) -> String {
+        String::from_utf8(self.to_xml_bytes(false))
+            .expect("Error: serialized XML is not valid UTF-8")
+    }
+
+    /// Serialize the element and its children to an XML string, starting with
+    /// the XML declaration (`<?xml version="1.0" encoding="utf-8"?>`).
+    pub fn to_xml_string_with_declaration(&self) -> String {
+        String::from_utf8(self.to_xml_bytes(true))
+            .expect("Error: serialized XML is not valid UTF-8")
+    }
+
+    /// Serialize the element into an in-memory buffer. Writing into a `Vec`
+    /// cannot fail, so errors from the writer are not propagated.
+    fn to_xml_bytes(&self, with_declaration: bool) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        self.write_xml(&mut buffer, with_declaration)
+            .expect("Error: cannot serialize XML into memory");
+
+        buffer
+    }
 
-        writer
-            .write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))
-            .unwrap();
+    fn write_xml<W: Write>(&self, sink: W, with_declaration: bool) -> Result<(), AxmlError> {
+        let mut writer = Writer::new_with_indent(sink, b' ', 4);
 
-        self.write_element(&mut writer).unwrap();
+        if with_declaration {
+            writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+        }
 
-        file.write_all(&writer.into_inner()[..])
-            .expect("Couldn't write to file");
+        self.write_element(&mut writer)?;
 
         Ok(())
     }
 
-    fn write_element<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), Error> {
-        let mut element = writer.create_element(&self.element_type);
+    fn write_element<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), quick_xml::Error> {
+        let name = self.qualified_name();
+        let mut element = writer.create_element(name.as_ref());
 
         element = if self.attributes.is_empty() {
             element
@@ -66,176 +403,328 @@ impl XmlElement {
             element.with_attributes(
                 self.attributes
                     .iter()
-                    .map(|(k, v)| (k.as_str(), v.as_str()))
-                    .collect::<Vec<(&str, &str)>>(),
+                    .map(|(k, v)| Attribute {
+                        key: QName(k.as_bytes()),
+                        value: escape_attribute_value(v),
+                    })
+                    .collect::<Vec<Attribute>>(),
             )
         };
 
         if self.children.is_empty() {
-            element.write_empty().unwrap();
+            element.write_empty()?;
         } else {
-            element
-                .write_inner_content(|writer| -> Result<(), quick_xml::Error> {
-                    for child in self.children.iter() {
-                        child.as_ref().borrow().write_element(writer).unwrap();
+            element.write_inner_content(|writer| -> Result<(), quick_xml::Error> {
+                for child in self.children.iter() {
+                    match child {
+                        XmlNode::Element(element) => element.borrow().write_element(writer)?,
+                        XmlNode::Text(text) => { writer.write_event(Event::Text(BytesText::new(text)))?; },
                     }
+                }
 
-                    Ok(())
-                })
-                .unwrap();
+                Ok(())
+            })?;
         }
 
         Ok(())
     }
 }
 
-/// Parse the start of a namepace
-pub fn parse_start_namespace(axml_buff: &mut Cursor<Vec<u8>>,
-                             strings: &[String],
-                             namespaces: &mut HashMap::<String, String>) {
-    // Go back 2 bytes, to account from the block type
-    let offset = axml_buff.position();
-    axml_buff.set_position(offset - 2);
+/// Escape an attribute value so that it can be written between double quotes.
+///
+/// On top of the five predefined entities (`&`, `<`, `>`, `"`, and `'`), this
+/// also escapes tabs and line breaks as character references: XML parsers
+/// normalize literal whitespace in attribute values into spaces, so they would
+/// otherwise be lost when the output is read back.
+fn escape_attribute_value(value: &str) -> Cow<'_, [u8]> {
+    let needs_escaping = |c: char| matches!(c, '&' | '<' | '>' | '"' | '\'' | '\n' | '\r' | '\t');
+
+    if !value.contains(needs_escaping) {
+        return Cow::Borrowed(value.as_bytes());
+    }
+
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    Cow::Owned(escaped.into_bytes())
+}
+
+/// Get a string from the string pool. Invalid indices give an empty string.
+fn pool_string(strings: &[String], idx: u32) -> &str {
+    strings.get(idx as usize).map_or("", String::as_str)
+}
+
+/// Read a start namespace chunk, and return the indices of the prefix and
+/// URI in the string pool without decoding them
+pub fn read_start_namespace<R: Read>(axml_buff: &mut R) -> Result<(u32, u32), Error> {
+    read_namespace(axml_buff, ChunkType::ResXmlStartNamespaceType)
+}
+
+/// Read an end namespace chunk, and return the indices of the prefix and
+/// URI in the string pool without decoding them
+pub fn read_end_namespace<R: Read>(axml_buff: &mut R) -> Result<(u32, u32), Error> {
+    read_namespace(axml_buff, ChunkType::ResXmlEndNamespaceType)
+}
 
+fn read_namespace<R: Read>(axml_buff: &mut R, chunk_type: ChunkType) -> Result<(u32, u32), Error> {
     // Parse chunk header
-    let _header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlStartNamespaceType)
-                 .expect("Error: cannot get header from start namespace chunk");
+    let _header = ChunkHeader::from_buff(axml_buff, chunk_type)?;
+
+    let _line_number = axml_buff.read_u32::<LittleEndian>()?;
+    let _comment = axml_buff.read_u32::<LittleEndian>()?;
+    let prefix = axml_buff.read_u32::<LittleEndian>()?;
+    let uri = axml_buff.read_u32::<LittleEndian>()?;
+
+    Ok((prefix, uri))
+}
 
-    let _line_number = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _comment = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let prefix = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let uri = axml_buff.read_u32::<LittleEndian>().unwrap();
+/// Parse the start of a namepace, and return the `(prefix, uri)` pair it declares
+pub fn parse_start_namespace<R: Read>(axml_buff: &mut R,
+                             strings: &[String],
+                             namespaces: &mut HashMap::<String, String>) -> Result<(String, String), Error> {
+    let (prefix, uri) = read_start_namespace(axml_buff)?;
 
-    let prefix_str = strings.get(prefix as usize).unwrap();
-    let uri_str = strings.get(uri as usize).unwrap();
+    let prefix_str = pool_string(strings, prefix);
+    let uri_str = pool_string(strings, uri);
     namespaces.insert(uri_str.to_string(), prefix_str.to_string());
+
+    Ok((prefix_str.to_string(), uri_str.to_string()))
 }
 
 /// Parse the end of a namepace
-pub fn parse_end_namespace(axml_buff: &mut Cursor<Vec<u8>>,
-                           _strings: &[String]) {
-    // Go back 2 bytes, to account from the block type
-    let offset = axml_buff.position();
-    axml_buff.set_position(offset - 2);
-
-    // Parse chunk header
-    let _header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlEndNamespaceType)
-                 .expect("Error: cannot get header from start namespace chunk");
+pub fn parse_end_namespace<R: Read>(axml_buff: &mut R,
+                           _strings: &[String]) -> Result<(), Error> {
+    read_end_namespace(axml_buff)?;
+    Ok(())
+}
 
-    let _line_number = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _comment = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _prefix = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _uri = axml_buff.read_u32::<LittleEndian>().unwrap();
+/// Attribute as stored in a start element chunk, before any decoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawAttribute {
+    /// Index of the namespace URI in the string pool, `0xffffffff` if none
+    pub namespace: u32,
+    /// Index of the attribute name in the string pool
+    pub name: u32,
+    /// Index of the raw string value in the string pool, `0xffffffff` if none
+    pub raw_value: u32,
+    /// Typed value of the attribute
+    pub typed_value: ResValue,
 }
 
-/// Parser the start of an element
-pub fn parse_start_element(axml_buff: &mut Cursor<Vec<u8>>,
-                           strings: &[String],
-                           namespace_prefixes: &HashMap::<String, String>) -> XmlElement {
-    // Go back 2 bytes, to account from the block type
-    let offset = axml_buff.position();
-    axml_buff.set_position(offset - 2);
+/// Start element chunk, before any decoding
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawStartElement {
+    /// Index of the namespace URI in the string pool, `0xffffffff` if none
+    pub namespace: u32,
+    /// Index of the element name in the string pool
+    pub name: u32,
+    /// Attributes of the element, in order
+    pub attributes: Vec<RawAttribute>,
+}
 
+/// Read a start element chunk without decoding the strings it refers to
+pub fn read_start_element<R: Read>(axml_buff: &mut R) -> Result<RawStartElement, Error> {
     // Parse chunk header
-    let _header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlStartElementType)
-                 .expect("Error: cannot get header from start namespace chunk");
-
-    let _line_number = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _comment = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _namespace = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let name = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _attribute_size = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let attribute_count = axml_buff.read_u16::<LittleEndian>().unwrap();
-    let _id_index = axml_buff.read_u16::<LittleEndian>().unwrap();
-    let _class_index = axml_buff.read_u16::<LittleEndian>().unwrap();
-    let _style_index = axml_buff.read_u16::<LittleEndian>().unwrap();
-
-    let element_type = strings.get(name as usize).unwrap().to_string();
-
-    let mut decoded_attrs = HashMap::<String, String>::new();
+    let header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlStartElementType)?;
+
+    let _line_number = axml_buff.read_u32::<LittleEndian>()?;
+    let _comment = axml_buff.read_u32::<LittleEndian>()?;
+    let namespace = axml_buff.read_u32::<LittleEndian>()?;
+    let name = axml_buff.read_u32::<LittleEndian>()?;
+    let _attribute_size = axml_buff.read_u32::<LittleEndian>()?;
+    let attribute_count = axml_buff.read_u16::<LittleEndian>()?;
+    let _id_index = axml_buff.read_u16::<LittleEndian>()?;
+    let _class_index = axml_buff.read_u16::<LittleEndian>()?;
+    let _style_index = axml_buff.read_u16::<LittleEndian>()?;
+
+    let mut attributes = Vec::with_capacity(header.capacity_for(attribute_count.into(), 20));
     for _ in 0..attribute_count {
-        let attr_namespace = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let attr_name = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let attr_raw_val = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let data_value_type = ResValue::from_buff(axml_buff).unwrap();
-
-        let mut decoded_attr_key = String::new();
-        let mut decoded_attr_val = String::new();
-
-        if attr_namespace != 0xffffffff {
-            let ns_prefix = namespace_prefixes.get(strings.get(attr_namespace as usize).unwrap()).unwrap();
-            decoded_attr_key.push_str(ns_prefix);
-            decoded_attr_key.push(':');
-        } else {
-            // TODO
+        let namespace = axml_buff.read_u32::<LittleEndian>()?;
+        let name = axml_buff.read_u32::<LittleEndian>()?;
+        let raw_value = axml_buff.read_u32::<LittleEndian>()?;
+        let typed_value = ResValue::from_buff(axml_buff)?;
+
+        attributes.push(RawAttribute {
+            namespace,
+            name,
+            raw_value,
+            typed_value,
+        });
+    }
+
+    Ok(RawStartElement {
+        namespace,
+        name,
+        attributes,
+    })
+}
+
+/// Decode the name of an attribute, including its namespace prefix if any
+pub fn decode_attribute_key(attr: &RawAttribute,
+                            strings: &[String],
+                            namespace_prefixes: &HashMap::<String, String>) -> String {
+    let mut decoded_attr_key = String::new();
+    push_attribute_key(attr, strings, namespace_prefixes, &mut decoded_attr_key);
+    decoded_attr_key
+}
+
+/// Same as [`decode_attribute_key`], but append the name to `key`
+pub(crate) fn push_attribute_key(attr: &RawAttribute,
+                                 strings: &[String],
+                                 namespace_prefixes: &HashMap::<String, String>,
+                                 key: &mut String) {
+    // Namespaces that were never declared are dropped
+    if attr.namespace != 0xffffffff {
+        if let Some(ns_prefix) = namespace_prefixes.get(pool_string(strings, attr.namespace)) {
+            key.push_str(ns_prefix);
+            key.push(':');
         }
+    }
 
-        decoded_attr_key.push_str(strings.get(attr_name as usize).unwrap());
+    key.push_str(pool_string(strings, attr.name));
+}
 
-        if attr_raw_val != 0xffffffff {
-            decoded_attr_val.push_str(&strings.get(attr_raw_val as usize).unwrap().to_string());
-        } else {
-            match data_value_type.data_type {
-                DataValueType::TypeNull => println!("TODO: DataValueType::TypeNull"),
-                DataValueType::TypeReference => {
-                    decoded_attr_val.push_str("type1/");
-                    decoded_attr_val.push_str(&data_value_type.data.to_string());
-                },
-                DataValueType::TypeAttribute => println!("TODO: DataValueType::TypeAttribute"),
-                DataValueType::TypeString => println!("TODO: DataValueType::TypeString"),
-                DataValueType::TypeFloat => println!("TODO: DataValueType::TypeFloat"),
-                DataValueType::TypeDimension => println!("TODO: DataValueType::TypeDimension"),
-                DataValueType::TypeFraction => println!("TODO: DataValueType::TypeFraction"),
-                DataValueType::TypeDynamicReference => println!("TODO: DataValueType::TypeDynamicReference"),
-                DataValueType::TypeDynamicAttribute => println!("TODO: DataValueType::TypeDynamicAttribute"),
-                DataValueType::TypeIntDec => decoded_attr_val.push_str(&data_value_type.data.to_string()),
-                DataValueType::TypeIntHex => {
-                    decoded_attr_val.push_str("0x");
-                    decoded_attr_val.push_str(&format!("{:x}", &data_value_type.data).to_string());
-                },
-                DataValueType::TypeIntBoolean => {
-                    if data_value_type.data == 0 {
-                        decoded_attr_val.push_str("false");
-                    } else {
-                        decoded_attr_val.push_str("true");
-                    }
-                },
-                DataValueType::TypeIntColorArgb8 => println!("TODO: DataValueType::TypeIntColorArgb8"),
-                DataValueType::TypeIntColorRgb8 => println!("TODO: DataValueType::TypeIntColorRgb8"),
-                DataValueType::TypeIntColorArgb4 => println!("TODO: DataValueType::TypeIntColorArgb4"),
-                DataValueType::TypeIntColorRgb4 => println!("TODO: DataValueType::TypeIntColorRgb4"),
-            }
+/// Decode the namespace URI of an element, `None` if it has none or if it was
+/// never declared
+pub fn decode_element_namespace(namespace: u32,
+                                strings: &[String],
+                                namespace_prefixes: &HashMap::<String, String>) -> Option<String> {
+    if namespace == 0xffffffff {
+        return None;
+    }
+
+    let uri = pool_string(strings, namespace);
+    namespace_prefixes.contains_key(uri).then(|| uri.to_string())
+}
+
+/// Decode the value of an attribute into its string representation
+pub fn decode_attribute_value(attr: &RawAttribute, strings: &[String]) -> String {
+    let mut decoded_attr_val = String::new();
+    let data_value_type = attr.typed_value;
+
+    if attr.raw_value != 0xffffffff {
+        decoded_attr_val.push_str(pool_string(strings, attr.raw_value));
+    } else {
+        match data_value_type.data_type {
+            DataValueType::TypeReference => {
+                decoded_attr_val.push_str("type1/");
+                decoded_attr_val.push_str(&data_value_type.data.to_string());
+            },
+            DataValueType::TypeFloat => decoded_attr_val.push_str(&f32::from_bits(data_value_type.data).to_string()),
+            DataValueType::TypeIntDec => decoded_attr_val.push_str(&data_value_type.data.to_string()),
+            DataValueType::TypeIntHex => {
+                decoded_attr_val.push_str("0x");
+                decoded_attr_val.push_str(&format!("{:x}", &data_value_type.data).to_string());
+            },
+            DataValueType::TypeIntBoolean => {
+                if data_value_type.data == 0 {
+                    decoded_attr_val.push_str("false");
+                } else {
+                    decoded_attr_val.push_str("true");
+                }
+            },
+            DataValueType::TypeNull |
+            DataValueType::TypeAttribute |
+            DataValueType::TypeString |
+            DataValueType::TypeDimension |
+            DataValueType::TypeFraction |
+            DataValueType::TypeDynamicReference |
+            DataValueType::TypeDynamicAttribute |
+            DataValueType::TypeIntColorArgb8 |
+            DataValueType::TypeIntColorRgb8 |
+            DataValueType::TypeIntColorArgb4 |
+            DataValueType::TypeIntColorRgb4 => {
+                log::debug!("cannot decode attribute values of type {:?}", data_value_type.data_type);
+            },
+        }
+    }
+
+    decoded_attr_val
+}
+
+/// Parser the start of an element
+pub fn parse_start_element<R: Read>(axml_buff: &mut R,
+                           strings: &[String],
+                           namespace_prefixes: &HashMap::<String, String>) -> Result<XmlElement, Error> {
+    let raw_element = read_start_element(axml_buff)?;
+
+    let element_type = Symbol::from(pool_string(strings, raw_element.name));
+
+    let mut decoded_attrs = Attributes::with_capacity(raw_element.attributes.len());
+    for attr in raw_element.attributes.iter() {
+        let duplicate = decoded_attrs.insert_first(crate::attributes::Attribute {
+            name: Symbol::from(decode_attribute_key(attr, strings, namespace_prefixes)),
+            namespace: decode_attribute_namespace(attr, strings),
+            value: decode_attribute_value(attr, strings),
+            typed_value: Some(attr.typed_value),
+        });
+        if let Some(kept) = duplicate {
+            log::warn!("duplicate attribute {} in <{element_type}>, keeping the first value", kept.name);
         }
-        decoded_attrs.insert(
-                decoded_attr_key.to_string(),
-                decoded_attr_val.to_string()
-        );
     }
 
-    XmlElement {
+    Ok(XmlElement {
         element_type,
+        namespace: decode_element_namespace(raw_element.namespace, strings, namespace_prefixes),
         attributes: decoded_attrs,
-        children: Vec::new()
-    }
+        children: Vec::new(),
+        parent: None,
+    })
+}
+
+/// Read an end element chunk, and return the indices of the namespace URI
+/// and of the element name in the string pool without decoding them
+pub fn read_end_element<R: Read>(axml_buff: &mut R) -> Result<(u32, u32), Error> {
+    // Parse chunk header
+    let _header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlEndElementType)?;
+
+    let _line_number = axml_buff.read_u32::<LittleEndian>()?;
+    let _comment = axml_buff.read_u32::<LittleEndian>()?;
+    let namespace = axml_buff.read_u32::<LittleEndian>()?;
+    let name = axml_buff.read_u32::<LittleEndian>()?;
+
+    Ok((namespace, name))
+}
+
+/// Decode the namespace URI of an attribute, `None` if it has none. Unlike
+/// [`decode_element_namespace`], URIs that were never declared are kept, so
+/// that the namespace of an attribute can still be checked.
+pub fn decode_attribute_namespace(attr: &RawAttribute, strings: &[String]) -> Option<Symbol> {
+    (attr.namespace != 0xffffffff).then(|| Symbol::from(pool_string(strings, attr.namespace)))
 }
 
 /// Parser the end of an element
-pub fn parse_end_element(axml_buff: &mut Cursor<Vec<u8>>,
+pub fn parse_end_element<R: Read>(axml_buff: &mut R,
                          strings: &[String]) -> Result<String, Error> {
-    // Go back 2 bytes, to account from the block type
-    let offset = axml_buff.position();
-    axml_buff.set_position(offset - 2);
+    let (_namespace, name) = read_end_element(axml_buff)?;
+
+    Ok(pool_string(strings, name).to_string())
+}
 
+/// Read a CDATA chunk, and return the index of the text in the string pool
+/// along with its typed value
+pub fn read_cdata<R: Read>(axml_buff: &mut R) -> Result<(u32, ResValue), Error> {
     // Parse chunk header
-    let _header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlEndElementType)
-                 .expect("Error: cannot get header from start namespace chunk");
+    let _header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlCDataType)?;
 
-    let _line_number = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _comment = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let _namespace = axml_buff.read_u32::<LittleEndian>().unwrap();
-    let name = axml_buff.read_u32::<LittleEndian>().unwrap();
+    let _line_number = axml_buff.read_u32::<LittleEndian>()?;
+    let _comment = axml_buff.read_u32::<LittleEndian>()?;
+    let data = axml_buff.read_u32::<LittleEndian>()?;
+    let typed_data = ResValue::from_buff(axml_buff)?;
 
-    Ok(strings.get(name as usize).unwrap().to_string())
+    Ok((data, typed_data))
 }
 
 /// Handler for XML events
@@ -257,7 +746,7 @@ pub fn handle_event<T> (writer: &mut Writer<T>,
                         key.push_str(v);
                         let attr = Attribute {
                             key: QName(key.as_bytes()),
-                            value: Cow::Borrowed(k.as_bytes())
+                            value: escape_attribute_value(k)
                         };
                         elem.push_attribute(attr);
                         break;
@@ -268,7 +757,7 @@ pub fn handle_event<T> (writer: &mut Writer<T>,
             for (attr_key, attr_val) in element_attrs {
                 let attr = Attribute {
                     key: QName(attr_key.as_bytes()),
-                    value: Cow::Borrowed(attr_val.as_bytes())
+                    value: escape_attribute_value(&attr_val)
                 };
                 elem.push_attribute(attr);
             }
@@ -279,67 +768,920 @@ pub fn handle_event<T> (writer: &mut Writer<T>,
         ChunkType::ResXmlEndElementType => {
             assert!(writer.write_event(Event::End(BytesEnd::new(element_name))).is_ok());
         },
-        _ => println!("{:02X}, other", block_type),
+        _ => log::warn!("unexpected {block_type} chunk in XML event"),
     }
 }
 
-/// Parse a whole XML document
-pub fn parse_xml(mut axml_cursor: Cursor<Vec<u8>>) -> Rc<RefCell<XmlElement>> {
-    let mut global_strings = Vec::new();
-    let mut namespace_prefixes = HashMap::<String, String>::new();
+/// Parse a whole XML document from any seekable reader (e.g., a `Cursor` over
+/// a `Vec<u8>` or a `&[u8]`, or a `File`)
+///
+/// The first element of the document is returned as the root, whatever its
+/// type (`manifest`, `network-security-config`, `LinearLayout`, etc.). An
+/// empty `manifest` element is returned if the document has no element.
+///
+/// The warnings of the parser are logged but otherwise ignored, use
+/// [`parse_xml_with_warnings`] to get them.
+pub fn parse_xml<R: Read + Seek>(axml_cursor: R) -> Rc<RefCell<XmlElement>> {
+    parse_xml_with_warnings(axml_cursor).root
+}
 
-    let root = Rc::new(RefCell::new(XmlElement {
-        element_type: "manifest".to_string(),
-        attributes: HashMap::new(),
-        children: Vec::new()
-    }));
-    let mut stack = vec![Rc::clone(&root)];
-    // let mut stack: Vec<Rc<RefCell<XmlElement>>> = Vec::new();
+/// Parsed document, with the problems the parser recovered from
+#[derive(Debug)]
+pub struct ParseResult {
+    /// Root element, see [`parse_xml`]
+    pub root: Rc<RefCell<XmlElement>>,
+    /// Skipped chunks, invalid strings, unbalanced elements, etc., in
+    /// document order
+    pub warnings: Vec<ParseWarning>,
+}
 
-    while let Ok(block_type) = ChunkType::parse_block_type(&mut axml_cursor) {
-        match block_type {
-            ChunkType::ResNullType => continue,
-            ChunkType::ResStringPoolType => {
-                let _ = StringPool::from_buff(&mut axml_cursor, &mut global_strings);
-            },
-            ChunkType::ResTableType => {
-                ResTable::parse(&mut axml_cursor);
-            },
-            ChunkType::ResXmlType => {
-                axml_cursor.set_position(axml_cursor.position() - 2);
-                let _ = ChunkHeader::from_buff(&mut axml_cursor, ChunkType::ResXmlType);
-            },
-            ChunkType::ResXmlStartNamespaceType => {
-                parse_start_namespace(&mut axml_cursor, &global_strings, &mut namespace_prefixes);
-            },
-            ChunkType::ResXmlEndNamespaceType => {
-                parse_end_namespace(&mut axml_cursor, &global_strings);
-            },
-            ChunkType::ResXmlStartElementType => {
-                // let (element_type, attrs) = parse_start_element(&mut axml_cursor, &global_strings, &namespace_prefixes).unwrap();
-                let element = parse_start_element(&mut axml_cursor, &global_strings, &namespace_prefixes);
+/// Same as [`parse_xml`], but also return the warnings about the problems the
+/// parser recovered from
+pub fn parse_xml_with_warnings<R: Read + Seek>(axml_cursor: R) -> ParseResult {
+    let mut reader = AxmlReader::new(axml_cursor);
+    let root = build_tree(&mut reader);
+    ParseResult { root, warnings: reader.take_warnings() }
+}
 
-                if element.element_type == "manifest" {
-                    stack.last().unwrap().borrow_mut().attributes = element.attributes.clone();
-                } else {
-                    let new_element = Rc::new(RefCell::new(element));
-                    stack.last().unwrap().borrow_mut().children.push(Rc::clone(&new_element));
-                    stack.push(new_element);
-                }
+/// Parsed document, along with the chunks needed to interpret or re-encode
+/// it, see [`parse_xml_full`]
+#[derive(Debug)]
+pub struct ParsedAxml {
+    /// Root element, see [`parse_xml`]
+    pub root: Rc<RefCell<XmlElement>>,
+    /// String pool of the document
+    pub string_pool: StringPool,
+    /// Resource IDs of the attribute names, if the document has a resource map
+    pub resource_map: Option<ResourceMap>,
+    /// Problems the parser recovered from, see [`ParseResult::warnings`]
+    pub warnings: Vec<ParseWarning>,
+}
 
-            },
-            ChunkType::ResXmlEndElementType => {
-                parse_end_element(&mut axml_cursor, &global_strings).unwrap();
-                stack.pop();
-            },
+/// Same as [`parse_xml_with_warnings`], but also return the string pool and
+/// the resource map of the document, which are otherwise dropped after
+/// parsing. If the document has several of them, only the first ones are
+/// returned.
+///
+/// Fails if a chunk is malformed (see [`ParseWarningKind::MalformedChunk`]),
+/// as the rest of the document is then missing, or if the document has no
+/// string pool.
+pub fn parse_xml_full<R: Read + Seek>(axml_cursor: R) -> Result<ParsedAxml, AxmlError> {
+    let mut reader = AxmlReader::new(axml_cursor);
+    let root = build_tree(&mut reader);
+    let warnings = reader.take_warnings();
 
-            ChunkType::ResXmlResourceMapType => {
-                let _ = ResourceMap::from_buff(&mut axml_cursor);
-            },
+    if let Some(warning) = warnings.iter().find(|warning| warning.kind == ParseWarningKind::MalformedChunk) {
+        return Err(AxmlError::MalformedChunk { offset: warning.offset as usize, reason: warning.message.clone() });
+    }
+    let (string_pool, resource_map) = reader.take_pools();
+    let string_pool = string_pool.ok_or_else(|| AxmlError::Parse(String::from("no string pool")))?;
 
-            _ => { },
+    Ok(ParsedAxml { root, string_pool, resource_map, warnings })
+}
+
+/// Visitor building the tree of [`parse_xml`]
+#[derive(Default)]
+struct TreeBuilder {
+    /// Namespaces declared since the last start element. They are added as
+    /// `xmlns:prefix` attributes on the next element, like in the original
+    /// XML.
+    pending_namespaces: Vec<(String, String)>,
+    root: Option<Rc<RefCell<XmlElement>>>,
+    stack: Vec<Rc<RefCell<XmlElement>>>,
+}
+
+impl AxmlVisitor for TreeBuilder {
+    fn start_element(&mut self, info: &ElementInfo) -> VisitControl {
+        let mut element = XmlElement::new(&info.name);
+        element.namespace = info.namespace.clone();
+
+        if self.pending_namespaces.is_empty() {
+            element.attributes = info.attributes.clone();
+        } else {
+            element.attributes = Attributes::with_capacity(self.pending_namespaces.len() + info.attributes.len());
+            for (prefix, uri) in self.pending_namespaces.drain(..) {
+                element.attributes.insert(format!("xmlns:{prefix}"), uri);
+            }
+            for attr in info.attributes.iter_attributes().chain(info.attributes.duplicates()) {
+                element.attributes.insert_first(attr.clone());
+            }
+        }
+
+        let new_element = Rc::new(RefCell::new(element));
+        match self.stack.last() {
+            Some(parent) => XmlElement::append_child(parent, Rc::clone(&new_element)),
+            None if self.root.is_none() => self.root = Some(Rc::clone(&new_element)),
+            // Sibling of the root element: invalid XML, ignore it
+            None => { },
+        }
+        self.stack.push(new_element);
+        VisitControl::Continue
+    }
+
+    fn end_element(&mut self, _name: &str) -> VisitControl {
+        self.stack.pop();
+        VisitControl::Continue
+    }
+
+    fn cdata(&mut self, text: &str) -> VisitControl {
+        if let Some(element) = self.stack.last() {
+            element.borrow_mut().append_text(text);
+        }
+        VisitControl::Continue
+    }
+
+    fn namespace(&mut self, prefix: &str, uri: &str) -> VisitControl {
+        self.pending_namespaces.push((prefix.to_string(), uri.to_string()));
+        VisitControl::Continue
+    }
+}
+
+/// Build the tree of the document from the events of `reader`, see
+/// [`parse_xml`]
+fn build_tree<R: Read + Seek>(reader: &mut AxmlReader<R>) -> Rc<RefCell<XmlElement>> {
+    let mut builder = TreeBuilder::default();
+    visitor::visit(reader, &mut builder);
+    builder.root.unwrap_or_else(|| Rc::new(RefCell::new(XmlElement::new("manifest"))))
+}
+
+/// Parse a whole XML document from a byte slice, without copying it
+pub fn parse_xml_from_slice(data: &[u8]) -> Rc<RefCell<XmlElement>> {
+    parse_xml(Cursor::new(data))
+}
+
+/// Same as [`parse_xml_from_slice`], but check the layout of the chunks
+/// before parsing, so that data that is not binary XML, truncated files, and
+/// corrupted chunks are reported as errors instead of partial trees. Data
+/// after the XML chunk is not checked.
+///
+/// This entry point does not touch the filesystem, which makes it suitable
+/// for targets without one (e.g., `wasm32-unknown-unknown`).
+pub fn try_parse_xml_from_slice(data: &[u8]) -> Result<Rc<RefCell<XmlElement>>, AxmlError> {
+    if detect_file_kind(data) != FileKind::Axml {
+        return Err(AxmlError::Parse(String::from("not an Android binary XML document")));
+    }
+    // Data after the XML chunk is ignored by the parser, see
+    // `ParseWarningKind::TrailingData`
+    let document = data.get(4..8)
+                       .and_then(|size| data.get(..LittleEndian::read_u32(size) as usize))
+                       .unwrap_or(data);
+    if let Some(e) = ChunkIterator::new(document).descend(true).find_map(Result::err) {
+        return Err(e);
+    }
+
+    let result = parse_xml_with_warnings(Cursor::new(data));
+    match result.warnings.into_iter().find(|warning| warning.kind == ParseWarningKind::MalformedChunk) {
+        Some(warning) => Err(AxmlError::MalformedChunk { offset: warning.offset as usize, reason: warning.message }),
+        None => Ok(result.root),
+    }
+}
+
+/// Convert a parsed tree into a JSON string, e.g. for processing with `jq`.
+///
+/// Each element is an object with the keys `name`, `attributes` (an object
+/// mapping attribute names to their string values), and `children` (an array
+/// of elements, and strings for text). The root element also has a `namespaces` object mapping
+/// prefixes to URIs, built from its `xmlns:` attributes, which are not repeated
+/// in its `attributes`. Object keys are sorted so the output is stable.
+pub fn to_json(root: &Rc<RefCell<XmlElement>>) -> String {
+    let mut json = String::new();
+    element_to_json(&root.borrow(), true, &mut json);
+    json
+}
+
+fn element_to_json(element: &XmlElement, is_root: bool, json: &mut String) {
+    let mut attributes: Vec<(&str, &String)> = element.attributes
+                                                         .iter()
+                                                         .filter(|(k, _)| !is_root || !k.starts_with("xmlns:"))
+                                                         .collect();
+    attributes.sort();
+
+    json.push_str("{\"attributes\":{");
+    for (idx, (key, value)) in attributes.iter().enumerate() {
+        if idx > 0 {
+            json.push(',');
+        }
+        push_json_string(json, key);
+        json.push(':');
+        push_json_string(json, value);
+    }
+
+    json.push_str("},\"children\":[");
+    for (idx, child) in element.children.iter().enumerate() {
+        if idx > 0 {
+            json.push(',');
+        }
+        match child {
+            XmlNode::Element(child) => element_to_json(&child.borrow(), false, json),
+            XmlNode::Text(text) => push_json_string(json, text),
+        }
+    }
+
+    json.push_str("],\"name\":");
+    push_json_string(json, &element.qualified_name());
+
+    if is_root {
+        let mut namespaces: Vec<(&str, &String)> = element.attributes
+                                                          .iter()
+                                                          .filter_map(|(k, v)| Some((k.strip_prefix("xmlns:")?, v)))
+                                                          .collect();
+        namespaces.sort();
+
+        json.push_str(",\"namespaces\":{");
+        for (idx, (prefix, uri)) in namespaces.iter().enumerate() {
+            if idx > 0 {
+                json.push(',');
+            }
+            push_json_string(json, prefix);
+            json.push(':');
+            push_json_string(json, uri);
+        }
+        json.push('}');
+    }
+
+    json.push('}');
+}
+
+/// Append `value` to `json` as a quoted and escaped JSON string
+pub(crate) fn push_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ Attr, AxmlBuilder, DIST_NS };
+
+    fn parse(data: Vec<u8>) -> Rc<RefCell<XmlElement>> {
+        parse_xml(Cursor::new(data))
+    }
+
+    #[test]
+    fn test_try_parse_xml_from_slice() {
+        let data = AxmlBuilder::new()
+            .element("manifest", vec![Attr::string("package", "com.example")])
+            .build();
+        let root = try_parse_xml_from_slice(&data).unwrap();
+        assert_eq!(root.borrow().get_attr("package"), Some("com.example"));
+
+        assert!(matches!(try_parse_xml_from_slice(b"<manifest/>"), Err(AxmlError::Parse(_))));
+        assert!(matches!(try_parse_xml_from_slice(&[]), Err(AxmlError::Parse(_))));
+        assert!(matches!(try_parse_xml_from_slice(&data[..data.len() - 4]),
+                         Err(AxmlError::MalformedChunk { offset: 0, .. })));
+
+        // Unknown type of the string pool chunk
+        let mut corrupted = data.clone();
+        corrupted[8] = 0x42;
+        assert!(matches!(try_parse_xml_from_slice(&corrupted), Err(AxmlError::MalformedChunk { offset: 8, .. })));
+    }
+
+    #[test]
+    fn test_parse_xml_with_warnings() {
+        let kinds = |data: Vec<u8>| {
+            let result = parse_xml_with_warnings(Cursor::new(data));
+            (result.root, result.warnings.iter().map(|warning| warning.kind).collect::<Vec<_>>())
+        };
+
+        let (root, warnings) = kinds(AxmlBuilder::new()
+            .element("manifest", vec![Attr::string("package", "com.example")])
+            .build());
+        assert_eq!(root.borrow().get_attr("package"), Some("com.example"));
+        assert!(warnings.is_empty());
+
+        // The name of the attribute is only in the resource map
+        let (root, warnings) = kinds(AxmlBuilder::new()
+            .resource_map(&[("", 0x01010003)])
+            .element("activity", vec![Attr::string("", ".Main")])
+            .build());
+        assert_eq!(root.borrow().get_attr("name"), Some(".Main"));
+        assert_eq!(warnings, vec![ParseWarningKind::ResolvedAttribute]);
+
+        let (_, warnings) = kinds(AxmlBuilder::new()
+            .element("activity", vec![Attr::string("", ".Main")])
+            .build());
+        assert_eq!(warnings, vec![ParseWarningKind::UnresolvedAttribute]);
+
+        let (root, warnings) = kinds(AxmlBuilder::new()
+            .start("manifest", vec![])
+            .start("application", vec![])
+            .end("activity")
+            .end("manifest")
+            .end("manifest")
+            .build());
+        assert_eq!(root.borrow().children.len(), 1);
+        assert_eq!(warnings, vec![ParseWarningKind::UnbalancedEndElement,
+                                  ParseWarningKind::UnbalancedEndElement]);
+    }
+
+    #[test]
+    fn test_duplicate_attributes() {
+        // Like Android, the first attribute wins
+        let result = parse_xml_with_warnings(Cursor::new(AxmlBuilder::new()
+            .android()
+            .element("activity", vec![
+                Attr::string("android:name", ".Main"),
+                Attr::boolean("android:exported", false),
+                Attr::boolean("android:exported", true),
+            ])
+            .build()));
+
+        let root = result.root.borrow();
+        assert_eq!(root.get_attr_bool("exported"), Some(false));
+        assert_eq!(root.attributes.keys().collect::<Vec<_>>(), vec!["xmlns:android", "android:name", "android:exported"]);
+        let duplicates: Vec<(&str, &str)> = root.attributes
+                                                .duplicates()
+                                                .iter()
+                                                .map(|attr| (attr.name.as_str(), attr.value.as_str()))
+                                                .collect();
+        assert_eq!(duplicates, vec![("android:exported", "true")]);
+
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].kind, ParseWarningKind::DuplicateAttribute);
+        assert_eq!(result.warnings[0].message,
+                   "duplicate attribute android:exported in <activity>: using \"false\", ignoring \"true\"");
+    }
+
+    #[test]
+    fn test_string_pool_past_the_end() {
+        let data = AxmlBuilder::new()
+            .android()
+            .element("manifest", vec![Attr::string("package", "com.example")])
+            .build();
+
+        // The string pool comes right after the header of the XML chunk
+        for extra in [1, 1 << 20] {
+            let mut data = data.clone();
+            let size = (data.len() - 8) as u32 + extra;
+            data[12..16].copy_from_slice(&size.to_le_bytes());
+
+            let result = parse_xml_with_warnings(Cursor::new(&data));
+            let kinds: Vec<_> = result.warnings.iter().map(|warning| warning.kind).collect();
+            assert_eq!(kinds, vec![ParseWarningKind::MalformedChunk]);
+            assert!(result.warnings[0].message.contains(&format!("chunk size {size} extends past the end")),
+                    "unexpected warning: {}", result.warnings[0]);
+            assert!(matches!(try_parse_xml_from_slice(&data), Err(AxmlError::MalformedChunk { offset: 8, .. })));
+        }
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        // The fixture, followed by the start of a second copy of it and
+        // 512 bytes of junk
+        let clean = include_bytes!("../tests/data/AndroidManifest.xml");
+        let trailing = include_bytes!("../tests/data/AndroidManifest-trailing.xml");
+        assert_eq!(trailing.len(), clean.len() + 1024);
+
+        let expected = parse_xml_with_warnings(Cursor::new(&clean[..]));
+        assert!(expected.warnings.is_empty());
+        let result = parse_xml_with_warnings(Cursor::new(&trailing[..]));
+        assert_eq!(*result.root.borrow(), *expected.root.borrow());
+        assert_eq!(result.root.borrow().to_xml_string(), expected.root.borrow().to_xml_string());
+        assert_eq!(result.warnings, vec![ParseWarning {
+            kind: ParseWarningKind::TrailingData,
+            offset: clean.len() as u64,
+            message: String::from("1024 bytes after the end of the document ignored"),
+        }]);
+
+        let root = try_parse_xml_from_slice(trailing).unwrap();
+        assert_eq!(*root.borrow(), *expected.root.borrow());
+    }
+
+    #[test]
+    fn test_reordered_chunks() {
+        // The fixture, with the string pool moved after the last element
+        let clean = include_bytes!("../tests/data/AndroidManifest.xml");
+        let reordered = include_bytes!("../tests/data/AndroidManifest-reordered.xml");
+
+        let expected = parse_xml_from_slice(clean);
+        let result = parse_xml_with_warnings(Cursor::new(&reordered[..]));
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        assert_eq!(*result.root.borrow(), *expected.borrow());
+        assert_eq!(result.root.borrow().to_xml_string(), expected.borrow().to_xml_string());
+    }
+
+    #[test]
+    fn test_parse_xml_full() {
+        let data = AxmlBuilder::new()
+            .resource_map(&[("name", 0x01010003)])
+            .element("activity", vec![Attr::string("name", ".Main")])
+            .build();
+        let parsed = parse_xml_full(Cursor::new(&data)).unwrap();
+        assert_eq!(parsed.root.borrow().get_attr("name"), Some(".Main"));
+        let strings: Vec<_> = parsed.string_pool.iter().map(|string| string.value).collect();
+        assert_eq!(strings[0], "name");
+        assert!(strings.contains(&"activity"));
+        assert_eq!(parsed.resource_map.unwrap().get_name(0), Some("name"));
+        assert!(parsed.warnings.is_empty());
+
+        let parsed = parse_xml_full(Cursor::new(AxmlBuilder::new().element("manifest", vec![]).build())).unwrap();
+        assert!(parsed.resource_map.is_none());
+
+        // Missing end of the document
+        assert!(matches!(parse_xml_full(Cursor::new(&data[..data.len() - 4])),
+                         Err(AxmlError::MalformedChunk { .. })));
+    }
+
+    #[test]
+    fn test_mixed_content() {
+        let root = parse(AxmlBuilder::new()
+            .start("foo", vec![])
+            .text("text")
+            .element("bar", vec![])
+            .text("more & more")
+            .text(" text")
+            .end("foo")
+            .build());
+
+        let root = root.borrow();
+        assert_eq!(root.children.len(), 3);
+        assert_eq!(root.children[0].as_text(), Some("text"));
+        assert_eq!(root.children[1].as_element().unwrap().borrow().element_type, "bar");
+        assert_eq!(root.children[2].as_text(), Some("more & more text"));
+        assert_eq!(root.child_elements().count(), 1);
+        assert_eq!(root.text(), "textmore & more text");
+
+        assert_eq!(root.to_xml_string(), "<foo>text<bar/>more &amp; more text</foo>");
+        assert_eq!(*root.clone_tree().borrow(), *root);
+        assert!(to_json(&root.clone_tree()).contains(r#""children":["text",{"#));
+    }
+
+    #[test]
+    fn test_element_namespace() {
+        // Manifest of a dynamic feature module of an app bundle
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .namespace("dist", DIST_NS)
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::string("split", "feature")])
+            .start("dist:module", vec![Attr::boolean("dist:instant", false),
+                                       Attr::string("dist:title", "Feature")])
+            .start("dist:delivery", vec![])
+            .element("dist:install-time", vec![])
+            .end("dist:delivery")
+            .element("dist:fusing", vec![Attr::boolean("dist:include", true)])
+            .end("dist:module")
+            .element("application", vec![Attr::boolean("android:hasCode", false)])
+            .end("manifest")
+            .build());
+
+        let module = Rc::clone(root.borrow().child_elements().next().unwrap());
+        assert_eq!(module.borrow().element_type, "module");
+        assert_eq!(module.borrow().namespace.as_deref(), Some(DIST_NS));
+        assert_eq!(module.borrow().qualified_name(), "dist:module");
+        assert_eq!(root.borrow().namespace, None);
+        assert_eq!(root.borrow().qualified_name(), "manifest");
+
+        let xml = root.borrow().to_xml_string();
+        assert!(xml.contains(r#"<dist:module dist:instant="false" dist:title="Feature">"#), "{xml}");
+        assert!(xml.contains("<dist:delivery>\n            <dist:install-time/>\n        </dist:delivery>"), "{xml}");
+        assert!(xml.contains("</dist:module>"), "{xml}");
+        assert!(xml.contains(r#"<application android:hasCode="false"/>"#), "{xml}");
+        assert!(to_json(&root).contains(r#""name":"dist:module""#));
+
+        // Without the declarations of the ancestors, the prefix is unknown
+        let detached = module.borrow().clone_tree();
+        assert_eq!(detached.borrow().namespace.as_deref(), Some(DIST_NS));
+        assert_eq!(detached.borrow().qualified_name(), "module");
+        assert_eq!(*root.borrow().clone_tree().borrow(), *root.borrow());
+    }
+
+    #[test]
+    fn test_parent_links() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Main")])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .end("application")
+            .end("manifest")
+            .build());
+
+        assert!(root.borrow().parent().is_none());
+
+        let application = Rc::clone(root.borrow().child_elements().next().unwrap());
+        let activity = Rc::clone(application.borrow().child_elements().next().unwrap());
+        let filter = Rc::clone(activity.borrow().child_elements().next().unwrap());
+
+        let parent = filter.borrow().parent().unwrap();
+        assert!(Rc::ptr_eq(&parent, &activity));
+
+        let ancestors: Vec<String> = filter.borrow()
+                                           .ancestors()
+                                           .map(|e| e.borrow().element_type.to_string())
+                                           .collect();
+        assert_eq!(ancestors, vec!["activity", "application", "manifest"]);
+    }
+
+    #[test]
+    fn test_append_child_sets_parent() {
+        let parent = Rc::new(RefCell::new(XmlElement::new("application")));
+        let child = Rc::new(RefCell::new(XmlElement::new("service")));
+
+        XmlElement::append_child(&parent, Rc::clone(&child));
+
+        assert_eq!(parent.borrow().children.len(), 1);
+        assert!(Rc::ptr_eq(&child.borrow().parent().unwrap(), &parent));
+    }
+
+    #[test]
+    fn test_to_xml_string() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![])
+            .start("application", vec![])
+            .element("activity", vec![])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let expected = "<manifest xmlns:android=\"http://schemas.android.com/apk/res/android\">\n    \
+                        <application>\n        <activity/>\n    </application>\n</manifest>";
+        assert_eq!(root.borrow().to_xml_string(), expected);
+        assert_eq!(root.borrow().to_string(), expected);
+
+        let with_decl = root.borrow().to_xml_string_with_declaration();
+        assert!(with_decl.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<manifest xmlns:android="));
+    }
+
+    /// Writer that fails on every write, to exercise the error path
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(Error::new(std::io::ErrorKind::BrokenPipe, "broken pipe"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_to_sink() {
+        let element = XmlElement::new("manifest");
+        let mut sink = Vec::new();
+
+        element.write_to(&mut sink).unwrap();
+
+        assert_eq!(String::from_utf8(sink).unwrap(),
+                   "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<manifest/>");
+    }
+
+    #[test]
+    fn test_write_to_failing_sink() {
+        let root = Rc::new(RefCell::new(XmlElement::new("manifest")));
+        XmlElement::append_child(&root, Rc::new(RefCell::new(XmlElement::new("application"))));
+
+        let result = root.borrow().write_to(&mut FailingWriter);
+
+        assert!(result.is_err());
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("broken pipe"), "unexpected error: {message}");
+    }
+
+    /// Read back the attributes of the first element of `xml` with quick-xml
+    fn read_back_attributes(xml: &str) -> Vec<(String, String)> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(e) | Event::Empty(e) => {
+                    return e.attributes()
+                        .map(|attr| {
+                            let attr = attr.unwrap();
+                            (String::from_utf8(attr.key.as_ref().to_vec()).unwrap(),
+                             attr.unescape_value().unwrap().to_string())
+                        })
+                        .collect();
+                },
+                Event::Eof => panic!("no element found"),
+                _ => { },
+            }
+        }
+    }
+
+    #[test]
+    fn test_escape_attribute_values() {
+        let values = [
+            "fish & chips",
+            "<tag>",
+            "say \"hello\"",
+            "it's",
+            "line 1\nline 2",
+            "tab\there\r\n",
+            "party 🎉",
+        ];
+
+        for value in values {
+            let mut element = XmlElement::new("meta-data");
+            element.attributes.insert(String::from("android:value"), value.to_string());
+
+            let xml = element.to_xml_string();
+            let attributes = read_back_attributes(&xml);
+
+            assert_eq!(attributes, vec![(String::from("android:value"), value.to_string())],
+                       "value did not round-trip: {xml}");
         }
     }
 
-    root
+    #[test]
+    fn test_escape_in_handle_event() {
+        let mut writer = Writer::new(Vec::new());
+        handle_event(&mut writer,
+                     String::from("data"),
+                     vec![(String::from("android:pathPattern"), String::from("/a\"b&<c>\n"))],
+                     &HashMap::new(),
+                     ChunkType::ResXmlStartElementType);
+
+        let xml = String::from_utf8(writer.into_inner()).unwrap();
+        let attributes = read_back_attributes(&xml);
+
+        assert_eq!(attributes[0].1, "/a\"b&<c>\n");
+    }
+
+    #[test]
+    fn test_deterministic_attribute_order() {
+        let data = AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![])
+            .start("application", vec![])
+            .element("activity", vec![
+                Attr::string("android:theme", "@style/Theme"),
+                Attr::boolean("android:exported", true),
+                Attr::string("android:name", ".Main"),
+                Attr::string("android:label", "Main"),
+                Attr::string("android:taskAffinity", "com.example.task"),
+                Attr::string("android:launchMode", "singleTop"),
+                Attr::string("android:process", ":remote"),
+            ])
+            .end("application")
+            .end("manifest")
+            .build();
+
+        let first = parse(data.clone()).borrow().to_xml_string();
+        let second = parse(data).borrow().to_xml_string();
+        assert_eq!(first, second);
+
+        assert!(first.contains("<activity android:theme="), "unexpected output: {first}");
+    }
+
+    #[test]
+    fn test_attribute_order_preserved() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![])
+            .element("uses-sdk", vec![
+                Attr::int("android:targetSdkVersion", 33),
+                Attr::int("android:minSdkVersion", 21),
+            ])
+            .start("application", vec![])
+            .element("service", vec![
+                Attr::string("android:process", ":remote"),
+                Attr::boolean("android:exported", false),
+                Attr::string("android:name", ".Sync"),
+                Attr::boolean("android:enabled", true),
+            ])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let application = Rc::clone(root.borrow().child_elements().nth(1).unwrap());
+        let service = Rc::clone(application.borrow().child_elements().next().unwrap());
+        let keys: Vec<String> = service.borrow().attributes.keys().map(String::from).collect();
+        assert_eq!(keys, vec!["android:process", "android:exported", "android:name", "android:enabled"]);
+
+        let xml = root.borrow().to_xml_string();
+        assert!(xml.contains("<uses-sdk android:targetSdkVersion=\"33\" android:minSdkVersion=\"21\"/>"),
+                "unexpected output: {xml}");
+        assert!(xml.contains("<service android:process=\":remote\" android:exported=\"false\" \
+                              android:name=\".Sync\" android:enabled=\"true\"/>"),
+                "unexpected output: {xml}");
+    }
+
+    #[test]
+    fn test_get_attr_ignores_prefix() {
+        let mut element = XmlElement::new("activity");
+        element.attributes.insert(String::from("a:exported"), String::from("false"));
+        element.attributes.insert(String::from("name"), String::from(".Main"));
+
+        assert_eq!(element.get_attr("exported"), Some("false"));
+        assert_eq!(element.get_attr("name"), Some(".Main"));
+        assert_eq!(element.get_attr("enabled"), None);
+        assert_eq!(element.get_attr("xported"), None);
+    }
+
+    #[test]
+    fn test_get_attr_prefers_android_prefix() {
+        let mut element = XmlElement::new("activity");
+        element.attributes.insert(String::from("foo:exported"), String::from("true"));
+        element.attributes.insert(String::from("android:exported"), String::from("false"));
+
+        assert_eq!(element.get_attr("exported"), Some("false"));
+    }
+
+    #[test]
+    fn test_attr_namespace() {
+        let root = parse(AxmlBuilder::new()
+            .namespace("android", "http://example.com/fake")
+            .namespace("a", ANDROID_NS)
+            .start("manifest", vec![])
+            .element("activity", vec![
+                Attr::boolean("android:exported", true),
+                Attr::boolean("a:exported", false),
+                Attr::string("name", ".Main"),
+            ])
+            .end("manifest")
+            .build());
+
+        let activity = Rc::clone(root.borrow().child_elements().next().unwrap());
+        let mut activity = activity.borrow_mut();
+
+        assert_eq!(activity.attr_namespace("android:exported").as_deref(), Some("http://example.com/fake"));
+        assert_eq!(activity.attr_namespace("a:exported").as_deref(), Some(ANDROID_NS));
+        assert_eq!(activity.attr_namespace("name"), None);
+        assert_eq!(activity.attr_namespace("missing"), None);
+        assert_eq!(activity.get_android_attribute("exported").map(|attr| attr.name.as_str()), Some("a:exported"));
+
+        // Attributes added without a namespace are resolved from their prefix
+        activity.attributes.insert(String::from("a:enabled"), String::from("false"));
+        assert_eq!(activity.attr_namespace("a:enabled").as_deref(), Some(ANDROID_NS));
+        assert_eq!(activity.get_android_attribute("enabled").and_then(|attr| attr.as_bool()), Some(false));
+    }
+
+    #[test]
+    fn test_typed_getters_from_axml() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![])
+            .element("activity", vec![
+                Attr::boolean("android:exported", true),
+                Attr::boolean("android:enabled", false),
+                Attr::int("android:priority", -5),
+                Attr::hex("android:glEsVersion", 0x20000),
+                Attr::reference("android:theme", 0x7f0e0001),
+                Attr::string("android:name", ".Main"),
+            ])
+            .end("manifest")
+            .build());
+
+        let activity = Rc::clone(root.borrow().child_elements().next().unwrap());
+        let activity = activity.borrow();
+
+        assert_eq!(activity.get_attr_bool("exported"), Some(true));
+        assert_eq!(activity.get_attr_bool("enabled"), Some(false));
+        assert_eq!(activity.get_attr_int("priority"), Some(-5));
+        assert_eq!(activity.get_attr_int("glEsVersion"), Some(0x20000));
+        assert_eq!(activity.get_attr_reference("theme"), Some(0x7f0e0001));
+
+        // Wrong types
+        assert_eq!(activity.get_attr_bool("theme"), None);
+        assert_eq!(activity.get_attr_int("exported"), None);
+        assert_eq!(activity.get_attr_reference("priority"), None);
+        assert_eq!(activity.get_attr_int("name"), None);
+
+        // Missing attribute
+        assert_eq!(activity.get_attr_bool("debuggable"), None);
+    }
+
+    #[test]
+    fn test_typed_getters_from_strings() {
+        let mut element = XmlElement::new("activity");
+        element.attributes.insert(String::from("android:exported"), String::from("false"));
+        element.attributes.insert(String::from("android:priority"), String::from("999"));
+        element.attributes.insert(String::from("android:order"), String::from("0x10"));
+        element.attributes.insert(String::from("android:theme"), String::from("@0x7f0e0001"));
+        element.attributes.insert(String::from("android:icon"), String::from("type1/2131034113"));
+
+        assert_eq!(element.get_attr_bool("exported"), Some(false));
+        assert_eq!(element.get_attr_int("priority"), Some(999));
+        assert_eq!(element.get_attr_int("order"), Some(16));
+        assert_eq!(element.get_attr_reference("theme"), Some(0x7f0e0001));
+        assert_eq!(element.get_attr_reference("icon"), Some(2131034113));
+        assert_eq!(element.get_attr_bool("priority"), None);
+    }
+
+    fn sample_manifest() -> Vec<u8> {
+        AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![Attr::boolean("android:debuggable", false)])
+            .start("activity", vec![Attr::string("android:name", ".Main")])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .element("service", vec![Attr::string("android:name", ".Sync")])
+            .end("application")
+            .end("manifest")
+            .build()
+    }
+
+    #[test]
+    fn test_tree_eq_separate_parses() {
+        let first = parse(sample_manifest());
+        let second = parse(sample_manifest());
+
+        assert!(!Rc::ptr_eq(&first, &second));
+        assert!(tree_eq(&first, &second));
+        assert_eq!(*first.borrow(), *second.borrow());
+    }
+
+    #[test]
+    fn test_tree_eq_after_attribute_change() {
+        let first = parse(sample_manifest());
+        let second = parse(sample_manifest());
+
+        let service = Rc::clone(second.borrow().child_elements().next().unwrap().borrow().child_elements().nth(1).unwrap());
+        service.borrow_mut().attributes.insert(String::from("android:exported"), String::from("true"));
+
+        assert!(!tree_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_clone_tree() {
+        let root = parse(sample_manifest());
+        let snapshot = root.borrow().clone_tree();
+
+        assert!(tree_eq(&root, &snapshot));
+        assert!(snapshot.borrow().parent().is_none());
+
+        // The copy has its own parent links
+        let application = Rc::clone(snapshot.borrow().child_elements().next().unwrap());
+        assert!(Rc::ptr_eq(&application.borrow().parent().unwrap(), &snapshot));
+
+        // Modifying the original does not affect the snapshot
+        root.borrow().child_elements().next().unwrap().borrow_mut().attributes.insert(String::from("android:debuggable"), String::from("true"));
+        assert!(!tree_eq(&root, &snapshot));
+        assert_eq!(application.borrow().get_attr_bool("debuggable"), Some(false));
+    }
+
+    #[test]
+    fn test_clone() {
+        let root = parse(sample_manifest());
+        let copy = root.borrow().clone();
+
+        assert_eq!(copy, *root.borrow());
+        assert!(copy.parent().is_none());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let root = parse(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::int("android:versionCode", 3)])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", ".Main"),
+                                      Attr::string("android:label", "Say \"hi\"\\o/")])
+            .end("application")
+            .end("manifest")
+            .build());
+
+        let expected = concat!(
+            r#"{"attributes":{"android:versionCode":"3","package":"com.example"},"#,
+            r#""children":[{"attributes":{},"children":["#,
+            r#"{"attributes":{"android:label":"Say \"hi\"\\o/","android:name":".Main"},"children":[],"name":"activity"}"#,
+            r#"],"name":"application"}],"name":"manifest","#,
+            r#""namespaces":{"android":"http://schemas.android.com/apk/res/android"}}"#,
+        );
+        assert_eq!(to_json(&root), expected);
+    }
+
+    #[test]
+    fn test_namespace_declared_on_root() {
+        let root = parse(sample_manifest());
+
+        assert_eq!(root.borrow().attributes.keys().next().unwrap(), "xmlns:android");
+        assert_eq!(root.borrow().attributes.get("xmlns:android").unwrap(),
+                   "http://schemas.android.com/apk/res/android");
+    }
+
+    #[test]
+    fn test_parse_from_slice() {
+        let data = sample_manifest();
+
+        assert!(tree_eq(&parse_xml_from_slice(&data), &parse(data.clone())));
+    }
+
+    #[test]
+    fn test_debug_does_not_recurse() {
+        let parent = Rc::new(RefCell::new(XmlElement::new("application")));
+        let child = Rc::new(RefCell::new(XmlElement::new("service")));
+        XmlElement::append_child(&parent, Rc::clone(&child));
+
+        let output = format!("{:?}", parent.borrow());
+        assert!(output.contains("service"));
+        assert!(format!("{:?}", child.borrow()).contains("Some(\"application\")"));
+    }
 }
@@ -0,0 +1,664 @@
+//! Manifest patching
+//!
+//! Helpers to modify a parsed manifest in place, e.g. before writing it back
+//! to binary XML with [`crate::writer::write_axml`].
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::data_value_type::DataValueType;
+use crate::error::AxmlError;
+use crate::merge::TOOLS_NS;
+use crate::parser::{ XmlElement, XmlNode };
+use crate::providers::split_authorities;
+use crate::res_value::ResValue;
+use crate::resource_map::ANDROID_NS;
+use crate::{
+    find_elements_by_type,
+    find_elements_by_types,
+    manifest_package,
+    normalize_component_name,
+    COMPONENT_TYPES,
+};
+
+/// Attributes holding class names, which can be relative to the package,
+/// by element type. See [`rename_package`].
+const CLASS_NAME_ATTRIBUTES: [(&str, &[&str]); 7] = [
+    ("application", &["name", "backupAgent", "manageSpaceActivity", "appComponentFactory"]),
+    ("activity", &["name", "parentActivityName"]),
+    ("activity-alias", &["name", "targetActivity", "parentActivityName"]),
+    ("service", &["name"]),
+    ("receiver", &["name"]),
+    ("provider", &["name"]),
+    ("instrumentation", &["name"]),
+];
+
+/// Elements requesting a permission
+const PERMISSION_ELEMENTS: [&str; 2] = ["uses-permission", "uses-permission-sdk-23"];
+
+/// Prefix bound to the `android` namespace in the manifest, declared on the
+/// root element as `android` if needed
+fn android_prefix(root: &Rc<RefCell<XmlElement>>) -> String {
+    let prefix = root.borrow().namespace_prefix(ANDROID_NS);
+    prefix.unwrap_or_else(|| {
+        root.borrow_mut().attributes.insert(String::from("xmlns:android"), String::from(ANDROID_NS));
+        String::from("android")
+    })
+}
+
+/// Request the permission `name` by adding a `<uses-permission>` element to
+/// the manifest, after the existing ones, or before `<application>` if there
+/// are none. Returns `false` without changing anything if the permission is
+/// already requested.
+pub fn add_permission(root: &Rc<RefCell<XmlElement>>, name: &str) -> bool {
+    let requested = root.borrow()
+                        .child_elements()
+                        .any(|child| {
+                            let child = child.borrow();
+                            PERMISSION_ELEMENTS.contains(&child.element_type.as_str()) &&
+                                child.get_attr("name") == Some(name)
+                        });
+    if requested {
+        return false;
+    }
+
+    let mut permission = XmlElement::new("uses-permission");
+    permission.attributes.insert(format!("{}:name", android_prefix(root)), name.to_string());
+
+    let position = {
+        let root = root.borrow();
+        let position_of = |predicate: &dyn Fn(&XmlElement) -> bool| {
+            root.children.iter().rposition(|child| match child {
+                XmlNode::Element(element) => predicate(&element.borrow()),
+                XmlNode::Text(_) => false,
+            })
+        };
+
+        position_of(&|element| PERMISSION_ELEMENTS.contains(&element.element_type.as_str()))
+            .map(|last| last + 1)
+            .or_else(|| position_of(&|element| element.element_type == "application"))
+            .unwrap_or(root.children.len())
+    };
+    XmlElement::insert_child(root, position, Rc::new(RefCell::new(permission)));
+    true
+}
+
+/// Set the boolean attribute `android:<local_name>` of `element`, as a typed
+/// boolean so that PackageManager reads it. An existing attribute is updated
+/// in place, keeping its position.
+fn set_bool_attribute(element: &mut XmlElement, prefix: &str, local_name: &str, value: bool) {
+    element.attributes.insert_typed(format!("{prefix}:{local_name}"),
+                                    value.to_string(),
+                                    Some(ResValue::boolean(value)));
+}
+
+/// Set the boolean attribute `android:<flag>` of the `<application>` element
+/// (e.g., `debuggable` or `allowBackup`). Returns `false` if there is no
+/// `<application>` element.
+pub fn set_application_flag(root: &Rc<RefCell<XmlElement>>, flag: &str, value: bool) -> bool {
+    let Some(application) = find_elements_by_type(root, "application").into_iter().next() else {
+        return false;
+    };
+
+    let prefix = android_prefix(root);
+    set_bool_attribute(&mut application.borrow_mut(), &prefix, flag, value);
+    true
+}
+
+/// Elements of the given types whose attribute `local_name` designates the
+/// class `class_name`, compared in their fully-qualified form
+fn find_components(root: &Rc<RefCell<XmlElement>>,
+                   element_types: &[&str],
+                   local_name: &str,
+                   class_name: &str) -> Vec<Rc<RefCell<XmlElement>>> {
+    let pkg_name = manifest_package(root);
+    let class_name = normalize_component_name(&pkg_name, class_name);
+
+    find_elements_by_types(root, element_types)
+        .into_iter()
+        .filter(|element| {
+            element.borrow()
+                   .get_attr(local_name)
+                   .is_some_and(|declared| normalize_component_name(&pkg_name, declared) == class_name)
+        })
+        .collect()
+}
+
+/// Set `android:exported` on every declaration of the component named
+/// `component_name` (activity, activity-alias, service, receiver, or
+/// provider). Names are compared in their fully-qualified form, so `.Main`
+/// and `com.example.Main` designate the same component. Returns `false` if
+/// there is no such component.
+pub fn set_component_exported(root: &Rc<RefCell<XmlElement>>, component_name: &str, exported: bool) -> bool {
+    let components = find_components(root, &COMPONENT_TYPES, "name", component_name);
+    if components.is_empty() {
+        return false;
+    }
+
+    let prefix = android_prefix(root);
+    for component in components.iter() {
+        set_bool_attribute(&mut component.borrow_mut(), &prefix, "exported", exported);
+    }
+    true
+}
+
+/// Remove every declaration of the component of type `kind` (e.g.,
+/// `activity`) named `name`, relative or fully qualified. Removing an
+/// activity also removes the activity-aliases that target it, which would
+/// be invalid otherwise. Returns whether anything was removed.
+pub fn remove_component(root: &Rc<RefCell<XmlElement>>, kind: &str, name: &str) -> bool {
+    let mut removed = find_components(root, &[kind], "name", name);
+    if kind == "activity" {
+        removed.extend(find_components(root, &["activity-alias"], "targetActivity", name));
+    }
+
+    for element in removed.iter() {
+        let parent = element.borrow_mut().parent.take().and_then(|parent| parent.upgrade());
+        if let Some(parent) = parent {
+            parent.borrow_mut().children.retain(|child| {
+                child.as_element().is_none_or(|child| !Rc::ptr_eq(child, element))
+            });
+        }
+    }
+    !removed.is_empty()
+}
+
+/// Disable every declaration of the component of type `kind` named `name`,
+/// by setting `android:enabled` to `false` as a typed boolean. Returns
+/// whether anything changed, i.e., `false` if there is no such component or
+/// if it is already disabled.
+pub fn disable_component(root: &Rc<RefCell<XmlElement>>, kind: &str, name: &str) -> bool {
+    let disabled = Some(ResValue::boolean(false));
+    let components: Vec<Rc<RefCell<XmlElement>>> = find_components(root, &[kind], "name", name)
+        .into_iter()
+        .filter(|component| {
+            component.borrow()
+                     .attributes
+                     .find_local("enabled")
+                     .is_none_or(|attr| attr.typed_value != disabled)
+        })
+        .collect();
+    if components.is_empty() {
+        return false;
+    }
+
+    let prefix = android_prefix(root);
+    for component in components.iter() {
+        set_bool_attribute(&mut component.borrow_mut(), &prefix, "enabled", false);
+    }
+    true
+}
+
+/// Remove everything in the `tools` namespace: the attributes
+/// (`tools:ignore`, `tools:targetApi`, `tools:node`, etc.), whatever their
+/// prefix, the elements, and the `xmlns:` declarations. These are only meant
+/// for the build tools, and the manifests of built APKs do not carry them.
+/// Returns whether anything was removed.
+pub fn strip_tools_attributes(root: &Rc<RefCell<XmlElement>>) -> bool {
+    // Find everything first: the prefixes are resolved with the declarations
+    // of the ancestors, which are removed too
+    let mut removals = Vec::new();
+    find_namespace_attributes(root, TOOLS_NS, &mut removals);
+
+    let mut removed = false;
+    for (element, keys) in removals.iter() {
+        let mut element = element.borrow_mut();
+        for key in keys.iter() {
+            element.attributes.remove(key);
+        }
+        let children = element.children.len();
+        element.children.retain(|child| {
+            child.as_element().is_none_or(|child| child.borrow().namespace.as_deref() != Some(TOOLS_NS))
+        });
+        removed |= !keys.is_empty() || element.children.len() != children;
+    }
+    removed
+}
+
+/// Collect the keys of the attributes of `element` and its descendants that
+/// are in the namespace `uri` or declare it
+fn find_namespace_attributes(element: &Rc<RefCell<XmlElement>>, uri: &str, found: &mut Vec<(Rc<RefCell<XmlElement>>, Vec<String>)>) {
+    let borrowed = element.borrow();
+    let keys = borrowed.attributes
+                       .iter()
+                       .filter(|(key, value)| match key.split_once(':') {
+                           Some(("xmlns", _)) => *value == uri,
+                           Some((prefix, _)) => borrowed.namespace_uri(prefix).as_deref() == Some(uri),
+                           None => false,
+                       })
+                       .map(|(key, _)| key.to_string())
+                       .collect();
+    found.push((Rc::clone(element), keys));
+
+    for child in borrowed.child_elements() {
+        find_namespace_attributes(child, uri, found);
+    }
+}
+
+/// Replace the string value of the attribute with the local name
+/// `local_name` by `rewrite(value)`, if it returns something. Values that are
+/// not strings (e.g., references to resources) are left as is.
+fn rewrite_attribute(element: &mut XmlElement, local_name: &str, rewrite: impl Fn(&str) -> Option<String>) {
+    let Some(attr) = element.attributes.find_local(local_name) else {
+        return;
+    };
+    if attr.typed_value.is_some_and(|typed| typed.data_type != DataValueType::TypeString) {
+        return;
+    }
+
+    let key = attr.name.clone();
+    if let Some(value) = element.attributes.get_mut(&key) {
+        if let Some(rewritten) = rewrite(value) {
+            *value = rewritten;
+        }
+    }
+}
+
+/// Rename the package of the app to `new_package`, keeping the classes and
+/// the other settings that depend on the package consistent:
+///
+/// - `package` on `<manifest>` is set to `new_package`;
+/// - relative class names (`.Main` or `Main`) are expanded with the old
+///   package, since they designate classes that do not move: `android:name`
+///   of the application, components, and instrumentations,
+///   `android:backupAgent`, `android:manageSpaceActivity`, and
+///   `android:appComponentFactory` of the application,
+///   `android:targetActivity` of activity-aliases, and
+///   `android:parentActivityName` of activities and activity-aliases;
+/// - the authorities of providers (`android:authorities`) that are the old
+///   package or start with it followed by a dot (e.g.,
+///   `com.example.fileprovider` from `${applicationId}.fileprovider`) get the
+///   new package instead, so that they do not clash with the original app;
+/// - task affinities (`android:taskAffinity` of the application and of
+///   activities) are rewritten the same way, since the default affinity is
+///   the package name.
+///
+/// Other attributes, such as the names of the permissions declared by the
+/// app, are left unchanged. Returns an error if the manifest has no package,
+/// in which case nothing is changed.
+pub fn rename_package(root: &Rc<RefCell<XmlElement>>, new_package: &str) -> Result<(), AxmlError> {
+    let old_package = root.borrow().attributes.get("package").cloned()
+        .filter(|package| !package.is_empty())
+        .ok_or_else(|| AxmlError::MissingAttribute {
+            element: String::from("manifest"),
+            attribute: String::from("package"),
+        })?;
+
+    // Old package or one of its sub-names, with the new package instead
+    let move_prefix = |value: &str| -> Option<String> {
+        let rest = value.strip_prefix(old_package.as_str())?;
+        (rest.is_empty() || rest.starts_with('.')).then(|| format!("{new_package}{rest}"))
+    };
+
+    let element_types: Vec<&str> = CLASS_NAME_ATTRIBUTES.iter().map(|(element_type, _)| *element_type).collect();
+    for element in find_elements_by_types(root, &element_types) {
+        let mut element = element.borrow_mut();
+        let attributes = CLASS_NAME_ATTRIBUTES.iter()
+                                              .find(|(element_type, _)| *element_type == element.element_type)
+                                              .map_or(&[][..], |(_, attributes)| *attributes);
+        for local_name in attributes {
+            rewrite_attribute(&mut element, local_name, |name| {
+                let expanded = normalize_component_name(&old_package, name);
+                (expanded != name).then_some(expanded)
+            });
+        }
+
+        match element.element_type.as_str() {
+            "provider" => rewrite_attribute(&mut element, "authorities", |authorities| {
+                let authorities = split_authorities(authorities);
+                authorities.iter()
+                           .any(|authority| move_prefix(authority).is_some())
+                           .then(|| authorities.iter()
+                                               .map(|authority| move_prefix(authority).unwrap_or_else(|| authority.clone()))
+                                               .collect::<Vec<String>>()
+                                               .join(";"))
+            }),
+            "application" | "activity" => rewrite_attribute(&mut element, "taskAffinity", move_prefix),
+            _ => { },
+        }
+    }
+
+    root.borrow_mut().attributes.insert(String::from("package"), new_package.to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::{ parse_xml, parse_xml_with_warnings };
+    use crate::permissions::has_requested_permission;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+    use crate::writer::write_axml;
+
+    const FIXTURE: &[u8] = include_bytes!("../tests/data/AndroidManifest.xml");
+
+    fn child_types(root: &Rc<RefCell<XmlElement>>) -> Vec<String> {
+        root.borrow()
+            .child_elements()
+            .map(|child| {
+                let child = child.borrow();
+                match child.get_attr("name") {
+                    Some(name) => format!("{}[{name}]", child.element_type),
+                    None => child.element_type.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_add_permission_and_write() {
+        let root = parse_xml(Cursor::new(FIXTURE));
+        assert!(add_permission(&root, "android.permission.CAMERA"));
+        assert!(!add_permission(&root, "android.permission.INTERNET"));
+        assert!(!add_permission(&root, "android.permission.CAMERA"));
+
+        let rewritten = parse_xml_with_warnings(Cursor::new(write_axml(&root)));
+        assert!(rewritten.warnings.is_empty(), "{:?}", rewritten.warnings);
+        assert!(has_requested_permission(&rewritten.root, "android.permission.CAMERA"));
+        assert_eq!(child_types(&rewritten.root), vec!["uses-sdk",
+                                                      "uses-permission[android.permission.INTERNET]",
+                                                      "uses-permission[android.permission.CAMERA]",
+                                                      "application"]);
+        assert_eq!(rewritten.root.borrow().child_elements().nth(2).unwrap().borrow().to_xml_string(),
+                   r#"<uses-permission android:name="android.permission.CAMERA"/>"#);
+    }
+
+    #[test]
+    fn test_set_flags_and_write() {
+        let root = parse_xml(Cursor::new(FIXTURE));
+        assert!(set_application_flag(&root, "debuggable", true));
+        assert!(set_application_flag(&root, "allowBackup", true));
+        assert!(set_component_exported(&root, "com.example.app.MainActivity", false));
+        assert!(set_component_exported(&root, ".SyncService", true));
+        assert!(!set_component_exported(&root, ".Missing", true));
+
+        let data = write_axml(&root);
+        let parsed = crate::parser::parse_xml_full(Cursor::new(&data)).unwrap();
+        assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+
+        let application = find_elements_by_type(&parsed.root, "application").pop().unwrap();
+        let application = application.borrow();
+        // Written in resource ID order, so that the platform finds them
+        assert_eq!(application.to_string().lines().next().unwrap(),
+                   r#"<application android:label="Example" android:debuggable="true" android:allowBackup="true">"#);
+        for (local_name, id) in [("debuggable", 0x0101000f), ("allowBackup", 0x01010280)] {
+            let attr = application.attributes.find_local(local_name).unwrap();
+            assert_eq!(attr.typed_value, Some(ResValue::boolean(true)));
+            assert_eq!(crate::resource_map::android_attribute_id(local_name), Some(id));
+        }
+
+        let exported: Vec<Option<bool>> = find_elements_by_types(&parsed.root, &COMPONENT_TYPES)
+            .iter()
+            .map(|component| component.borrow().get_attr_bool("exported"))
+            .collect();
+        assert_eq!(exported, vec![Some(false), Some(true)]);
+        let attr = application.child_elements().next().unwrap().borrow()
+                              .attributes.find_local("exported").cloned().unwrap();
+        assert_eq!(attr.typed_value.map(|typed| typed.data_type),
+                   Some(crate::data_value_type::DataValueType::TypeIntBoolean));
+
+        // The resource map gives the IDs of the new attributes
+        let resource_ids = parsed.resource_map.unwrap().resource_ids().to_vec();
+        assert!(resource_ids.contains(&0x0101000f));
+        assert!(resource_ids.contains(&0x01010010));
+
+        // Without an application
+        let root = Rc::new(RefCell::new(XmlElement::new("manifest")));
+        assert!(!set_application_flag(&root, "debuggable", true));
+        assert!(root.borrow().attributes.is_empty());
+    }
+
+    #[test]
+    fn test_set_exported_on_duplicates() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("receiver", vec![Attr::string("android:name", ".Boot"), Attr::string("android:exported", "true")])
+            .element("receiver", vec![Attr::string("android:name", "com.example.Boot")])
+            .element("activity", vec![Attr::string("android:name", ".Main")])
+            .end("application")
+            .end("manifest")
+            .build()));
+        assert!(set_component_exported(&root, "Boot", false));
+
+        let receivers = find_elements_by_type(&root, "receiver");
+        for receiver in receivers.iter() {
+            assert_eq!(receiver.borrow().get_attr_bool("exported"), Some(false));
+            assert_eq!(receiver.borrow().attributes.get_typed("android:exported"), Some(&ResValue::boolean(false)));
+        }
+        // Updated in place
+        assert_eq!(receivers[0].borrow().attributes.keys().collect::<Vec<_>>(),
+                   vec!["android:name", "android:exported"]);
+        let activity = find_elements_by_type(&root, "activity").pop().unwrap();
+        assert_eq!(activity.borrow().get_attr("exported"), None);
+    }
+
+    #[test]
+    fn test_rename_package() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example.app")])
+            .start("application", vec![Attr::string("android:name", ".App"),
+                                       Attr::string("android:backupAgent", "Backup"),
+                                       Attr::string("android:taskAffinity", "com.example.app")])
+            .element("activity", vec![Attr::string("android:name", ".Main"),
+                                      Attr::string("android:taskAffinity", "com.example.app.secondary"),
+                                      Attr::string("android:parentActivityName", "com.example.app.Home")])
+            .element("activity", vec![Attr::string("android:name", "Other"),
+                                      Attr::string("android:taskAffinity", "com.example.apps")])
+            .element("activity-alias", vec![Attr::string("android:name", ".Alias"),
+                                            Attr::string("android:targetActivity", ".Main")])
+            .element("service", vec![Attr::string("android:name", "com.google.Service")])
+            .element("provider", vec![Attr::string("android:name", "androidx.core.content.FileProvider"),
+                                      Attr::string("android:authorities", "com.example.app.fileprovider")])
+            .element("provider", vec![Attr::string("android:name", ".Data"),
+                                      Attr::string("android:authorities", "com.example.app;com.other.data")])
+            .element("receiver", vec![Attr::reference("android:name", 0x7f130000)])
+            .end("application")
+            .element("permission", vec![Attr::string("android:name", "com.example.app.PERMISSION")])
+            .end("manifest")
+            .build()));
+
+        rename_package(&root, "com.clone.app").unwrap();
+        let root = root.borrow();
+        assert_eq!(root.attributes.get("package").map(String::as_str), Some("com.clone.app"));
+
+        let application = root.child_elements().next().unwrap().borrow();
+        assert_eq!(application.get_attr("name"), Some("com.example.app.App"));
+        assert_eq!(application.get_attr("backupAgent"), Some("com.example.app.Backup"));
+        assert_eq!(application.get_attr("taskAffinity"), Some("com.clone.app"));
+
+        let attrs = |idx: usize, local_names: &[&str]| -> Vec<Option<String>> {
+            let element = application.child_elements().nth(idx).unwrap().borrow();
+            local_names.iter().map(|local_name| element.get_attr(local_name).map(String::from)).collect()
+        };
+        let some = |value: &str| Some(String::from(value));
+        assert_eq!(attrs(0, &["name", "taskAffinity", "parentActivityName"]),
+                   vec![some("com.example.app.Main"), some("com.clone.app.secondary"), some("com.example.app.Home")]);
+        assert_eq!(attrs(1, &["name", "taskAffinity"]),
+                   vec![some("com.example.app.Other"), some("com.example.apps")]);
+        assert_eq!(attrs(2, &["name", "targetActivity"]),
+                   vec![some("com.example.app.Alias"), some("com.example.app.Main")]);
+        assert_eq!(attrs(3, &["name"]), vec![some("com.google.Service")]);
+        // Authority expanded by aapt from `${applicationId}.fileprovider`
+        assert_eq!(attrs(4, &["authorities"]), vec![some("com.clone.app.fileprovider")]);
+        assert_eq!(attrs(5, &["name", "authorities"]),
+                   vec![some("com.example.app.Data"), some("com.clone.app;com.other.data")]);
+        // References are left as is
+        assert_eq!(application.child_elements().nth(6).unwrap().borrow().get_attr_reference("name"),
+                   Some(0x7f130000));
+        assert_eq!(root.child_elements().nth(1).unwrap().borrow().get_attr("name"),
+                   Some("com.example.app.PERMISSION"));
+    }
+
+    #[test]
+    fn test_rename_package_write() {
+        let root = parse_xml(Cursor::new(FIXTURE));
+        rename_package(&root, "com.clone.app").unwrap();
+
+        let parsed = parse_xml_with_warnings(Cursor::new(write_axml(&root)));
+        assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+        let contents = crate::ManifestContents::from_tree(&parsed.root);
+        assert_eq!(contents.pkg_name, "com.clone.app");
+        assert_eq!(contents.activities, vec!["com.example.app.MainActivity"]);
+        assert_eq!(contents.services, vec!["com.example.app.SyncService"]);
+
+        // No package
+        let root = Rc::new(RefCell::new(XmlElement::new("manifest")));
+        assert!(matches!(rename_package(&root, "com.clone.app"), Err(AxmlError::MissingAttribute { .. })));
+        assert!(root.borrow().attributes.is_empty());
+    }
+
+    fn exposed(root: &Rc<RefCell<XmlElement>>) -> Vec<String> {
+        crate::get_exposure_report(root)
+            .into_iter()
+            .filter(|report| report.is_exposed())
+            .map(|report| format!("{} {}", report.kind, report.name))
+            .collect()
+    }
+
+    fn exposed_manifest() -> Rc<RefCell<XmlElement>> {
+        parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", ".Main"), Attr::boolean("android:exported", true)])
+            .element("activity-alias", vec![Attr::string("android:name", ".Launcher"),
+                                            Attr::string("android:targetActivity", "com.example.Main"),
+                                            Attr::boolean("android:exported", true)])
+            .element("service", vec![Attr::string("android:name", "Sync"), Attr::boolean("android:exported", true)])
+            .element("receiver", vec![Attr::string("android:name", ".Main"), Attr::boolean("android:exported", true)])
+            .element("service", vec![Attr::string("android:name", ".Sync"), Attr::string("android:enabled", "true")])
+            .end("application")
+            .end("manifest")
+            .build()))
+    }
+
+    #[test]
+    fn test_remove_component() {
+        let root = exposed_manifest();
+        assert_eq!(exposed(&root), vec!["activity com.example.Main", "activity-alias com.example.Launcher",
+                                        "service com.example.Sync", "receiver com.example.Main"]);
+
+        assert!(remove_component(&root, "activity", "com.example.Main"));
+        assert!(!remove_component(&root, "activity", ".Main"));
+        assert!(!remove_component(&root, "provider", ".Sync"));
+        assert_eq!(exposed(&root), vec!["service com.example.Sync", "receiver com.example.Main"]);
+
+        assert!(remove_component(&root, "service", ".Sync"));
+        assert_eq!(exposed(&root), vec!["receiver com.example.Main"]);
+        let application = find_elements_by_type(&root, "application").pop().unwrap();
+        assert_eq!(application.borrow().children.len(), 1);
+
+        let parsed = parse_xml_with_warnings(Cursor::new(write_axml(&root)));
+        assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+        assert_eq!(exposed(&parsed.root), vec!["receiver com.example.Main"]);
+    }
+
+    #[test]
+    fn test_disable_component() {
+        let root = exposed_manifest();
+        assert!(disable_component(&root, "activity", "Main"));
+        assert!(!disable_component(&root, "activity", ".Main"));
+        assert!(!disable_component(&root, "activity", ".Missing"));
+        // The alias follows its target activity
+        assert_eq!(exposed(&root), vec!["service com.example.Sync", "receiver com.example.Main"]);
+
+        // Both declarations, one of them updated in place
+        assert!(disable_component(&root, "service", "com.example.Sync"));
+        let services = find_elements_by_type(&root, "service");
+        assert_eq!(services[1].borrow().attributes.keys().collect::<Vec<_>>(),
+                   vec!["android:name", "android:enabled"]);
+
+        let parsed = parse_xml_with_warnings(Cursor::new(write_axml(&root)));
+        assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+        assert_eq!(exposed(&parsed.root), vec!["receiver com.example.Main"]);
+        let report = crate::get_exposure_report(&parsed.root);
+        assert_eq!(report[0].enabled, crate::ComponentState::ExplicitFalse);
+        for service in find_elements_by_type(&parsed.root, "service") {
+            assert_eq!(service.borrow().attributes.get_typed("android:enabled"), Some(&ResValue::boolean(false)));
+        }
+    }
+
+    #[test]
+    fn test_strip_tools_attributes() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .namespace("tools", TOOLS_NS)
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::string("tools:ignore", "GoogleAppIndexingWarning")])
+            .start("application", vec![Attr::string("android:label", "Example"),
+                                       Attr::string("tools:targetApi", "31"),
+                                       Attr::string("tools:replace", "android:label")])
+            .element("activity", vec![Attr::string("android:name", ".Main")])
+            .element("service", vec![Attr::string("android:name", ".Sync"),
+                                     Attr::string("tools:node", "merge")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        // The attributes are parsed with their prefix, and written back
+        let rewritten = parse_xml(Cursor::new(write_axml(&root)));
+        let service = find_elements_by_type(&rewritten, "service").pop().unwrap();
+        assert_eq!(service.borrow().attributes.get("tools:node").map(String::as_str), Some("merge"));
+        assert_eq!(service.borrow().attributes.find_local("node").map(|attr| attr.name.as_str()), Some("tools:node"));
+        assert_eq!(rewritten.borrow().to_xml_string(), root.borrow().to_xml_string());
+
+        // Another prefix can be bound to the namespace on a nested element
+        let activity = find_elements_by_type(&root, "activity").pop().unwrap();
+        activity.borrow_mut().attributes.insert("xmlns:t", String::from(TOOLS_NS));
+        activity.borrow_mut().attributes.insert("t:ignore", String::from("ExportedActivity"));
+        let mut tools_element = XmlElement::new("tools-only");
+        tools_element.namespace = Some(String::from(TOOLS_NS));
+        XmlElement::append_child(&activity, Rc::new(RefCell::new(tools_element)));
+
+        assert!(strip_tools_attributes(&root));
+        assert!(!strip_tools_attributes(&root));
+        assert_eq!(root.borrow().to_xml_string(),
+                   concat!(r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example">"#, "\n",
+                           r#"    <application android:label="Example">"#, "\n",
+                           r#"        <activity android:name=".Main"/>"#, "\n",
+                           r#"        <service android:name=".Sync"/>"#, "\n",
+                           "    </application>\n",
+                           "</manifest>"));
+
+        let parsed = crate::parser::parse_xml_full(Cursor::new(write_axml(&root))).unwrap();
+        assert!(parsed.string_pool.iter().all(|string| string.value != TOOLS_NS));
+    }
+
+    #[test]
+    fn test_add_permission_position() {
+        // Before the application, with the prefix of the manifest
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .namespace("a", crate::test_utils::ANDROID_NS)
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-sdk", vec![])
+            .element("application", vec![])
+            .element("uses-feature", vec![])
+            .end("manifest")
+            .build()));
+        assert!(add_permission(&root, "android.permission.CAMERA"));
+        assert_eq!(child_types(&root), vec!["uses-sdk", "uses-permission[android.permission.CAMERA]",
+                                            "application", "uses-feature"]);
+        let permission = Rc::clone(root.borrow().child_elements().nth(1).unwrap());
+        assert!(permission.borrow().attributes.contains_key("a:name"));
+        assert!(Rc::ptr_eq(&permission.borrow().parent().unwrap(), &root));
+
+        // Already requested for API 23 and above
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![])
+            .element("uses-permission-sdk-23", vec![Attr::string("android:name", "android.permission.CAMERA")])
+            .end("manifest")
+            .build()));
+        assert!(!add_permission(&root, "android.permission.CAMERA"));
+
+        // Empty manifest without the android namespace
+        let root = Rc::new(RefCell::new(XmlElement::new("manifest")));
+        assert!(add_permission(&root, "android.permission.CAMERA"));
+        assert_eq!(root.borrow().attributes.get("xmlns:android").map(String::as_str), Some(ANDROID_NS));
+        assert_eq!(child_types(&root), vec!["uses-permission[android.permission.CAMERA]"]);
+    }
+}
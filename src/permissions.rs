@@ -0,0 +1,467 @@
+//! Permissions
+//!
+//! Structured view of the permissions requested by an app (`<uses-permission>`
+//! and `<uses-permission-sdk-23>`) and of the permissions it declares
+//! (`<permission>`, `<permission-group>`, and `<permission-tree>`).
+
+use std::fmt;
+
+use crate::flags::flag_names;
+use crate::owned::ElementRef;
+use crate::{ exposure_report_with_elements, find_elements_by_type, find_elements_by_types, no_resolver };
+
+/// Permission requested by the app
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestedPermission {
+    /// Name of the permission (e.g., `android.permission.CAMERA`)
+    pub name: String,
+    /// Value of `android:maxSdkVersion`: the permission is not requested on
+    /// devices running a more recent API level
+    pub max_sdk: Option<u32>,
+    /// Minimum API level implied by the element: `Some(23)` for
+    /// `<uses-permission-sdk-23>`, `None` for `<uses-permission>`
+    pub min_sdk_via_element: Option<u32>,
+}
+
+impl RequestedPermission {
+    /// Check if the permission is requested on a device running `api_level`
+    pub fn is_requested_on(&self, api_level: u32) -> bool {
+        self.max_sdk.is_none_or(|max| api_level <= max) &&
+        self.min_sdk_via_element.is_none_or(|min| api_level >= min)
+    }
+}
+
+/// Protection level of a declared permission: a base level (`normal`,
+/// `dangerous`, `signature`, etc.) and optional flags (`privileged`,
+/// `development`, etc.), stored as a bit field like in the framework
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProtectionLevel(pub u32);
+
+/// Mask of the base protection level
+const PROTECTION_MASK_BASE: u32 = 0xf;
+
+/// Names of the base protection levels, indexed by value
+const PROTECTION_BASES: [&str; 5] = [
+    "normal",
+    "dangerous",
+    "signature",
+    "signatureOrSystem",
+    "internal",
+];
+
+/// Names and values of the protection flags. Some flags have several names,
+/// the first one is the canonical name.
+const PROTECTION_FLAGS: [(&str, u32); 26] = [
+    ("privileged", 0x10),
+    ("system", 0x10),
+    ("development", 0x20),
+    ("appop", 0x40),
+    ("pre23", 0x80),
+    ("installer", 0x100),
+    ("verifier", 0x200),
+    ("preinstalled", 0x400),
+    ("setup", 0x800),
+    ("instant", 0x1000),
+    ("ephemeral", 0x1000),
+    ("runtime", 0x2000),
+    ("oem", 0x4000),
+    ("vendorPrivileged", 0x8000),
+    ("textClassifier", 0x10000),
+    ("wellbeing", 0x20000),
+    ("documenter", 0x40000),
+    ("configurator", 0x80000),
+    ("incidentReportApprover", 0x100000),
+    ("appPredictor", 0x200000),
+    ("module", 0x400000),
+    ("companion", 0x800000),
+    ("retailDemo", 0x1000000),
+    ("recents", 0x2000000),
+    ("role", 0x4000000),
+    ("knownSigner", 0x8000000),
+];
+
+impl ProtectionLevel {
+    /// Parse a protection level from its symbolic form, e.g.
+    /// `signature|privileged`. Returns `None` if a name is unknown.
+    pub fn from_names(value: &str) -> Option<Self> {
+        let mut level = 0;
+        for name in value.split('|').map(str::trim) {
+            if let Some(base) = PROTECTION_BASES.iter().position(|b| *b == name) {
+                level |= base as u32;
+            } else {
+                let (_, flag) = PROTECTION_FLAGS.iter().find(|(n, _)| *n == name)?;
+                level |= flag;
+            }
+        }
+        Some(ProtectionLevel(level))
+    }
+
+    /// Name of the base protection level
+    pub fn base(&self) -> &'static str {
+        PROTECTION_BASES.get((self.0 & PROTECTION_MASK_BASE) as usize)
+                        .copied()
+                        .unwrap_or("unknown")
+    }
+
+    /// Names of the protection flags that are set
+    pub fn flags(&self) -> Vec<&'static str> {
+        flag_names(self.0 & !PROTECTION_MASK_BASE, &PROTECTION_FLAGS).0
+    }
+
+    /// Check if the base level is `normal`, i.e., any app can be granted
+    /// the permission
+    pub fn is_normal(&self) -> bool {
+        self.0 & PROTECTION_MASK_BASE == 0
+    }
+}
+
+impl fmt::Display for ProtectionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.base())?;
+        for flag in self.flags() {
+            write!(f, "|{flag}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Permission declared by the app with a `<permission>` element
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeclaredPermission {
+    /// Name of the permission
+    pub name: String,
+    /// Protection level, `normal` if not specified
+    pub protection_level: ProtectionLevel,
+    /// Group the permission belongs to, if any
+    pub permission_group: Option<String>,
+    /// Resource ID of the description of the permission, if any
+    pub description_ref: Option<u32>,
+}
+
+/// Protection of a permission guarding a component
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PermissionProtection {
+    /// The permission is declared in the manifest with this protection level
+    Declared(ProtectionLevel),
+    /// The permission is not declared in the manifest (platform permission or
+    /// permission declared by another app), its protection level is unknown
+    UnknownExternal,
+}
+
+/// Permission guarding a component, along with its protection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuardPermission {
+    /// Name of the permission
+    pub name: String,
+    /// Protection of the permission
+    pub protection: PermissionProtection,
+}
+
+impl GuardPermission {
+    /// Look up the protection of the permission `name` among the permissions
+    /// declared in the manifest
+    pub fn new(name: &str, declared: &[DeclaredPermission]) -> Self {
+        let protection = match declared.iter().find(|permission| permission.name == name) {
+            Some(permission) => PermissionProtection::Declared(permission.protection_level),
+            None => PermissionProtection::UnknownExternal,
+        };
+
+        GuardPermission {
+            name: name.to_string(),
+            protection,
+        }
+    }
+
+    /// Check if the permission is declared with a `normal` protection level,
+    /// which any app can be granted, so it does not really protect anything
+    pub fn is_normal(&self) -> bool {
+        matches!(self.protection, PermissionProtection::Declared(level) if level.is_normal())
+    }
+}
+
+/// Get the permissions declared by the app, in document order.
+///
+/// `android:protectionLevel` is decoded whether it is stored as an integer
+/// (the usual case in compiled manifests) or as a string of symbolic names.
+/// An unknown symbolic name is treated as `normal`.
+pub fn get_declared_permissions<E: ElementRef>(root: &E) -> Vec<DeclaredPermission> {
+    find_elements_by_type(root, "permission")
+        .iter()
+        .filter_map(|element| {
+            let protection_level = match element.get_attr_int("protectionLevel") {
+                Some(level) => ProtectionLevel(level as u32),
+                None => element.get_attr("protectionLevel")
+                               .and_then(|level| ProtectionLevel::from_names(&level))
+                               .unwrap_or_default(),
+            };
+
+            Some(DeclaredPermission {
+                name: element.get_attr("name")?,
+                protection_level,
+                permission_group: element.get_attr("permissionGroup"),
+                description_ref: element.get_attr_reference("description"),
+            })
+        })
+        .collect()
+}
+
+/// Get the names of the permission groups declared by the app
+/// (`<permission-group>`), in document order
+pub fn get_permission_groups<E: ElementRef>(root: &E) -> Vec<String> {
+    find_elements_by_type(root, "permission-group")
+        .iter()
+        .filter_map(|element| element.get_attr("name"))
+        .collect()
+}
+
+/// Get the base names of the permission trees declared by the app
+/// (`<permission-tree>`), in document order
+pub fn get_permission_trees<E: ElementRef>(root: &E) -> Vec<String> {
+    find_elements_by_type(root, "permission-tree")
+        .iter()
+        .filter_map(|element| element.get_attr("name"))
+        .collect()
+}
+
+/// Get the permissions requested by the app, in document order
+pub fn get_requested_permissions<E: ElementRef>(root: &E) -> Vec<RequestedPermission> {
+    find_elements_by_types(root, &["uses-permission", "uses-permission-sdk-23"])
+        .iter()
+        .filter_map(|element| {
+            Some(RequestedPermission {
+                name: element.get_attr("name")?,
+                max_sdk: element.get_attr_int("maxSdkVersion")
+                                .and_then(|sdk| u32::try_from(sdk).ok()),
+                min_sdk_via_element: if element.element_type() == "uses-permission-sdk-23" {
+                    Some(23)
+                } else {
+                    None
+                },
+            })
+        })
+        .collect()
+}
+
+/// Check if the app requests the permission `name`, with
+/// `<uses-permission>` or `<uses-permission-sdk-23>`. The name must be fully
+/// qualified (e.g., `android.permission.CAMERA`) and is compared
+/// case-sensitively, like the platform does.
+pub fn has_requested_permission<E: ElementRef>(root: &E, name: &str) -> bool {
+    get_requested_permissions(root).iter().any(|permission| permission.name == name)
+}
+
+/// Runtime permissions of the platform, with a `dangerous` protection level,
+/// as of Android 14
+pub const DANGEROUS_PERMISSIONS: [&str; 41] = [
+    "android.permission.READ_CALENDAR",
+    "android.permission.WRITE_CALENDAR",
+    "android.permission.READ_CALL_LOG",
+    "android.permission.WRITE_CALL_LOG",
+    "android.permission.PROCESS_OUTGOING_CALLS",
+    "android.permission.CAMERA",
+    "android.permission.READ_CONTACTS",
+    "android.permission.WRITE_CONTACTS",
+    "android.permission.GET_ACCOUNTS",
+    "android.permission.ACCESS_FINE_LOCATION",
+    "android.permission.ACCESS_COARSE_LOCATION",
+    "android.permission.ACCESS_BACKGROUND_LOCATION",
+    "android.permission.ACCESS_MEDIA_LOCATION",
+    "android.permission.RECORD_AUDIO",
+    "android.permission.READ_PHONE_STATE",
+    "android.permission.READ_PHONE_NUMBERS",
+    "android.permission.CALL_PHONE",
+    "android.permission.ANSWER_PHONE_CALLS",
+    "com.android.voicemail.permission.ADD_VOICEMAIL",
+    "android.permission.USE_SIP",
+    "android.permission.ACCEPT_HANDOVER",
+    "android.permission.BODY_SENSORS",
+    "android.permission.BODY_SENSORS_BACKGROUND",
+    "android.permission.ACTIVITY_RECOGNITION",
+    "android.permission.SEND_SMS",
+    "android.permission.RECEIVE_SMS",
+    "android.permission.READ_SMS",
+    "android.permission.RECEIVE_WAP_PUSH",
+    "android.permission.RECEIVE_MMS",
+    "android.permission.READ_EXTERNAL_STORAGE",
+    "android.permission.WRITE_EXTERNAL_STORAGE",
+    "android.permission.READ_MEDIA_AUDIO",
+    "android.permission.READ_MEDIA_IMAGES",
+    "android.permission.READ_MEDIA_VIDEO",
+    "android.permission.READ_MEDIA_VISUAL_USER_SELECTED",
+    "android.permission.BLUETOOTH_ADVERTISE",
+    "android.permission.BLUETOOTH_CONNECT",
+    "android.permission.BLUETOOTH_SCAN",
+    "android.permission.NEARBY_WIFI_DEVICES",
+    "android.permission.UWB_RANGING",
+    "android.permission.POST_NOTIFICATIONS",
+];
+
+/// Get the requested permissions that are dangerous platform permissions
+/// (see [`DANGEROUS_PERMISSIONS`]), in document order and without duplicates
+pub fn get_dangerous_permissions<E: ElementRef>(root: &E) -> Vec<String> {
+    let mut dangerous: Vec<String> = Vec::new();
+
+    for permission in get_requested_permissions(root) {
+        if DANGEROUS_PERMISSIONS.contains(&permission.name.as_str()) && !dangerous.contains(&permission.name) {
+            dangerous.push(permission.name);
+        }
+    }
+
+    dangerous
+}
+
+/// Get the fully-qualified names of the components protected by the
+/// permission `permission_name`, in document order: through
+/// `android:permission` (which also covers receivers without intent filters
+/// that can only be reached explicitly), or, for providers, through
+/// `android:readPermission` or `android:writePermission`. The name must be
+/// fully qualified and is compared case-sensitively, like the platform does.
+pub fn components_guarded_by<E: ElementRef>(root: &E, permission_name: &str) -> Vec<String> {
+    exposure_report_with_elements(root, &no_resolver)
+        .into_iter()
+        .map(|(_, component)| component)
+        .filter(|component| {
+            [&component.protected_by_permission, &component.read_permission, &component.write_permission]
+                .into_iter()
+                .flatten()
+                .any(|guard| guard.name == permission_name)
+        })
+        .map(|component| component.name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_requested_permissions() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.INTERNET")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.WRITE_EXTERNAL_STORAGE"),
+                                             Attr::int("android:maxSdkVersion", 28)])
+            .element("uses-permission-sdk-23", vec![Attr::string("android:name", "android.permission.CAMERA")])
+            .end("manifest")
+            .build()));
+
+        let permissions = get_requested_permissions(&root);
+
+        assert_eq!(permissions, vec![
+            RequestedPermission {
+                name: String::from("android.permission.INTERNET"),
+                max_sdk: None,
+                min_sdk_via_element: None,
+            },
+            RequestedPermission {
+                name: String::from("android.permission.WRITE_EXTERNAL_STORAGE"),
+                max_sdk: Some(28),
+                min_sdk_via_element: None,
+            },
+            RequestedPermission {
+                name: String::from("android.permission.CAMERA"),
+                max_sdk: None,
+                min_sdk_via_element: Some(23),
+            },
+        ]);
+
+        assert!(permissions[1].is_requested_on(28));
+        assert!(!permissions[1].is_requested_on(30));
+        assert!(!permissions[2].is_requested_on(22));
+        assert!(permissions[2].is_requested_on(23));
+
+        let contents = crate::ManifestContents::from_tree(&root);
+        assert_eq!(contents.requested_perms.len(), 3);
+        assert_eq!(contents.requested_permissions, permissions);
+    }
+
+    #[test]
+    fn test_protection_level() {
+        assert_eq!(ProtectionLevel(0).to_string(), "normal");
+        assert_eq!(ProtectionLevel(0x12).to_string(), "signature|privileged");
+        assert_eq!(ProtectionLevel(0x21).to_string(), "dangerous|development");
+        assert_eq!(ProtectionLevel::from_names("signature|privileged"), Some(ProtectionLevel(0x12)));
+        assert_eq!(ProtectionLevel::from_names("signatureOrSystem"), Some(ProtectionLevel(3)));
+        assert_eq!(ProtectionLevel::from_names("signature|system").unwrap().to_string(),
+                   "signature|privileged");
+        assert_eq!(ProtectionLevel::from_names("bogus"), None);
+        assert!(ProtectionLevel(0x20).is_normal());
+    }
+
+    #[test]
+    fn test_declared_permissions() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("permission-group", vec![Attr::string("android:name", "com.example.group.SYNC")])
+            .element("permission-tree", vec![Attr::string("android:name", "com.example.dynamic")])
+            .element("permission", vec![Attr::string("android:name", "com.example.permission.READ"),
+                                        Attr::hex("android:protectionLevel", 0x12),
+                                        Attr::string("android:permissionGroup", "com.example.group.SYNC"),
+                                        Attr::reference("android:description", 0x7f0e0002)])
+            .element("permission", vec![Attr::string("android:name", "com.example.permission.WRITE"),
+                                        Attr::string("android:protectionLevel", "dangerous|development")])
+            .element("permission", vec![Attr::string("android:name", "com.example.permission.PING")])
+            .end("manifest")
+            .build()));
+
+        let permissions = get_declared_permissions(&root);
+
+        assert_eq!(permissions, vec![
+            DeclaredPermission {
+                name: String::from("com.example.permission.READ"),
+                protection_level: ProtectionLevel(0x12),
+                permission_group: Some(String::from("com.example.group.SYNC")),
+                description_ref: Some(0x7f0e0002),
+            },
+            DeclaredPermission {
+                name: String::from("com.example.permission.WRITE"),
+                protection_level: ProtectionLevel(0x21),
+                permission_group: None,
+                description_ref: None,
+            },
+            DeclaredPermission {
+                name: String::from("com.example.permission.PING"),
+                protection_level: ProtectionLevel(0),
+                permission_group: None,
+                description_ref: None,
+            },
+        ]);
+        assert_eq!(get_permission_groups(&root), vec!["com.example.group.SYNC"]);
+        assert_eq!(get_permission_trees(&root), vec!["com.example.dynamic"]);
+    }
+
+    #[test]
+    fn test_permission_helpers() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.INTERNET")])
+            .element("uses-permission-sdk-23", vec![Attr::string("android:name", "android.permission.CAMERA")])
+            .start("application", vec![])
+            .element("receiver", vec![Attr::string("android:name", ".Push"),
+                                      Attr::string("android:permission", "com.example.PUSH")])
+            .element("provider", vec![Attr::string("android:name", ".Data"),
+                                      Attr::string("android:authorities", "com.example.data"),
+                                      Attr::string("android:writePermission", "com.example.PUSH")])
+            .element("service", vec![Attr::string("android:name", ".Sync"),
+                                     Attr::string("android:permission", "com.example.SYNC")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        assert!(has_requested_permission(&root, "android.permission.INTERNET"));
+        assert!(has_requested_permission(&root, "android.permission.CAMERA"));
+        assert!(!has_requested_permission(&root, "android.permission.camera"));
+        assert_eq!(get_dangerous_permissions(&root), vec!["android.permission.CAMERA"]);
+
+        assert_eq!(components_guarded_by(&root, "com.example.PUSH"),
+                   vec!["com.example.Push", "com.example.Data"]);
+        assert_eq!(components_guarded_by(&root, "com.example.SYNC"), vec!["com.example.Sync"]);
+        assert!(components_guarded_by(&root, "com.example.push").is_empty());
+    }
+}
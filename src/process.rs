@@ -0,0 +1,161 @@
+//! Process model
+//!
+//! Processes the components of an app run in: by default every component
+//! runs in a process named after the package, but the application and each
+//! component can choose another one with `android:process`. Apps signed with
+//! the same key can also share a Linux user ID (`android:sharedUserId`).
+
+use crate::owned::ElementRef;
+use crate::{ find_elements_by_type, find_elements_by_types, manifest_package, normalize_component_name };
+
+/// Process a component runs in, see [`get_process_model`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentProcess {
+    /// Type of the component (`activity`, `service`, `receiver`, or
+    /// `provider`)
+    pub kind: String,
+    /// Fully-qualified name of the component
+    pub name: String,
+    /// Effective process name: the component's `android:process`, else the
+    /// application's `android:process`, else the package name. Private
+    /// process names (`:remote`) are prefixed with the package name.
+    pub process: String,
+    /// Whether the process is private to the app, i.e., its name starts
+    /// with `:`
+    pub private_process: bool,
+    /// Whether an instance of the component can be created in the process of
+    /// each client (`android:multiprocess`, activities and providers only)
+    pub multiprocess: bool,
+}
+
+/// Process model of the app, see [`get_process_model`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessModel {
+    /// Shared user ID (`android:sharedUserId` on `<manifest>`)
+    pub shared_user_id: Option<String>,
+    /// Default process of the components, as declared on `<application>`
+    pub application_process: Option<String>,
+    /// Processes of the components, in document order
+    pub components: Vec<ComponentProcess>,
+}
+
+/// Expand a process name: private names (`:remote`) are relative to the
+/// package. Returns the expanded name and whether the process is private.
+fn expand_process_name(pkg: &str, process: &str) -> (String, bool) {
+    if process.starts_with(':') {
+        (format!("{pkg}{process}"), true)
+    } else {
+        (process.to_string(), false)
+    }
+}
+
+/// Get the process model of the app: shared user ID, default process, and
+/// the effective process of each component
+pub fn get_process_model<E: ElementRef>(root: &E) -> ProcessModel {
+    let pkg_name = manifest_package(root);
+    let shared_user_id = find_elements_by_type(root, "manifest")
+        .first()
+        .and_then(|manifest| manifest.get_attr("sharedUserId"));
+    let application_process = find_elements_by_type(root, "application")
+        .first()
+        .and_then(|application| application.get_attr("process"));
+
+    let components = find_elements_by_types(root, &["activity", "service", "receiver", "provider"])
+        .iter()
+        .map(|component| {
+            let process = component.get_attr("process")
+                                   .or_else(|| application_process.clone())
+                                   .unwrap_or_else(|| pkg_name.clone());
+            let (process, private_process) = expand_process_name(&pkg_name, &process);
+
+            ComponentProcess {
+                kind: component.element_type().to_string(),
+                name: component.get_attr("name")
+                               .map(|name| normalize_component_name(&pkg_name, &name))
+                               .unwrap_or_default(),
+                process,
+                private_process,
+                multiprocess: component.get_attr_bool("multiprocess").unwrap_or(false),
+            }
+        })
+        .collect();
+
+    ProcessModel {
+        shared_user_id,
+        application_process,
+        components,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_process_model() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::string("android:sharedUserId", "com.example.shared")])
+            .start("application", vec![Attr::string("android:process", "com.example.main")])
+            .element("activity", vec![Attr::string("android:name", ".Main")])
+            .element("service", vec![Attr::string("android:name", ".Remote"),
+                                     Attr::string("android:process", ":remote")])
+            .element("provider", vec![Attr::string("android:name", ".Data"),
+                                      Attr::string("android:process", "com.other.global"),
+                                      Attr::boolean("android:multiprocess", true)])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let model = get_process_model(&root);
+
+        assert_eq!(model.shared_user_id.as_deref(), Some("com.example.shared"));
+        assert_eq!(model.application_process.as_deref(), Some("com.example.main"));
+        assert_eq!(model.components, vec![
+            ComponentProcess {
+                kind: String::from("activity"),
+                name: String::from("com.example.Main"),
+                process: String::from("com.example.main"),
+                private_process: false,
+                multiprocess: false,
+            },
+            ComponentProcess {
+                kind: String::from("service"),
+                name: String::from("com.example.Remote"),
+                process: String::from("com.example:remote"),
+                private_process: true,
+                multiprocess: false,
+            },
+            ComponentProcess {
+                kind: String::from("provider"),
+                name: String::from("com.example.Data"),
+                process: String::from("com.other.global"),
+                private_process: false,
+                multiprocess: true,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_default_process() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("receiver", vec![Attr::string("android:name", ".Boot")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let model = get_process_model(&root);
+
+        assert_eq!(model.shared_user_id, None);
+        assert_eq!(model.application_process, None);
+        assert_eq!(model.components[0].process, "com.example");
+        assert!(!model.components[0].private_process);
+    }
+}
@@ -0,0 +1,209 @@
+//! Content providers
+//!
+//! Structured view of the `<provider>` elements: authorities, permissions,
+//! and the URI paths that can be reached through permission grants or
+//! per-path permissions.
+
+use std::collections::HashMap;
+
+use crate::meta_data::{ get_meta_data, MetaDataValue };
+use crate::owned::ElementRef;
+use crate::sdk::get_sdk_versions;
+use crate::{
+    find_elements_by_type,
+    get_enabled_state,
+    get_exported_state,
+    manifest_package,
+    normalize_component_name,
+    ComponentState,
+};
+
+/// URI path specification, as used by `<grant-uri-permission>` and
+/// `<path-permission>`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UriPath {
+    /// Exact path (`android:path`)
+    pub path: Option<String>,
+    /// Path prefix (`android:pathPrefix`)
+    pub path_prefix: Option<String>,
+    /// Path pattern (`android:pathPattern`)
+    pub path_pattern: Option<String>,
+}
+
+impl UriPath {
+    fn from_element<E: ElementRef>(element: &E) -> Self {
+        UriPath {
+            path: element.get_attr("path"),
+            path_prefix: element.get_attr("pathPrefix"),
+            path_pattern: element.get_attr("pathPattern"),
+        }
+    }
+}
+
+/// `<path-permission>` element: permissions guarding a subset of the
+/// provider's URIs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathPermission {
+    /// Paths the permissions apply to
+    pub path: UriPath,
+    /// Permission needed to read from and write to the paths
+    pub permission: Option<String>,
+    /// Permission needed to read from the paths
+    pub read_permission: Option<String>,
+    /// Permission needed to write to the paths
+    pub write_permission: Option<String>,
+}
+
+/// Content provider declared by the app, see [`get_providers`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderInfo {
+    /// Fully-qualified name of the provider
+    pub name: String,
+    /// Authorities of the provider (`android:authorities`, separated by
+    /// semicolons in the manifest)
+    pub authorities: Vec<String>,
+    /// Exported state, see [`get_exported_state`]
+    pub exported: ComponentState,
+    /// Enabled state, see [`get_enabled_state`]
+    pub enabled: ComponentState,
+    /// Whether permissions to access any URI of the provider can be granted
+    /// (`android:grantUriPermissions`, `false` by default)
+    pub grant_uri_permissions: bool,
+    /// Permission needed to read from and write to the provider
+    pub permission: Option<String>,
+    /// Permission needed to read from the provider
+    pub read_permission: Option<String>,
+    /// Permission needed to write to the provider
+    pub write_permission: Option<String>,
+    /// Paths for which permissions can be granted even if
+    /// `android:grantUriPermissions` is `false` (`<grant-uri-permission>`)
+    pub grant_uri_paths: Vec<UriPath>,
+    /// Per-path permissions (`<path-permission>`)
+    pub path_permissions: Vec<PathPermission>,
+    /// Meta-data of the provider (e.g., the file paths of a `FileProvider`)
+    pub meta_data: HashMap<String, MetaDataValue>,
+}
+
+/// Split the value of `android:authorities` into a list of authorities
+pub(crate) fn split_authorities(authorities: &str) -> Vec<String> {
+    authorities.split(';')
+               .map(str::trim)
+               .filter(|authority| !authority.is_empty())
+               .map(String::from)
+               .collect()
+}
+
+/// Get the content providers declared by the app, in document order
+pub fn get_providers<E: ElementRef>(root: &E) -> Vec<ProviderInfo> {
+    let pkg_name = manifest_package(root);
+    let sdk_versions = get_sdk_versions(root);
+
+    find_elements_by_type(root, "provider")
+        .iter()
+        .map(|provider| {
+            let children = provider.children();
+            let children_of_type = |element_type: &str| -> Vec<E> {
+                children.iter()
+                        .filter(|child| child.element_type() == element_type)
+                        .cloned()
+                        .collect()
+            };
+
+            ProviderInfo {
+                name: provider.get_attr("name")
+                              .map(|name| normalize_component_name(&pkg_name, &name))
+                              .unwrap_or_default(),
+                authorities: provider.get_attr("authorities")
+                                     .map(|authorities| split_authorities(&authorities))
+                                     .unwrap_or_default(),
+                exported: get_exported_state(provider, &sdk_versions),
+                enabled: get_enabled_state(provider),
+                grant_uri_permissions: provider.get_attr_bool("grantUriPermissions").unwrap_or(false),
+                permission: provider.get_attr("permission"),
+                read_permission: provider.get_attr("readPermission"),
+                write_permission: provider.get_attr("writePermission"),
+                grant_uri_paths: children_of_type("grant-uri-permission")
+                    .iter()
+                    .map(UriPath::from_element)
+                    .collect(),
+                path_permissions: children_of_type("path-permission")
+                    .iter()
+                    .map(|element| PathPermission {
+                        path: UriPath::from_element(element),
+                        permission: element.get_attr("permission"),
+                        read_permission: element.get_attr("readPermission"),
+                        write_permission: element.get_attr("writePermission"),
+                    })
+                    .collect(),
+                meta_data: get_meta_data(provider),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_get_providers() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-sdk", vec![Attr::int("android:minSdkVersion", 21),
+                                      Attr::int("android:targetSdkVersion", 33)])
+            .start("application", vec![])
+            .start("provider", vec![Attr::string("android:name", "androidx.core.content.FileProvider"),
+                                    Attr::string("android:authorities", "com.example.fileprovider"),
+                                    Attr::boolean("android:exported", false),
+                                    Attr::boolean("android:grantUriPermissions", true)])
+            .element("meta-data", vec![Attr::string("android:name", "android.support.FILE_PROVIDER_PATHS"),
+                                       Attr::reference("android:resource", 0x7f140000)])
+            .end("provider")
+            .start("provider", vec![Attr::string("android:name", ".Data"),
+                                    Attr::string("android:authorities", "com.example.data; com.example.legacy"),
+                                    Attr::string("android:readPermission", "com.example.READ")])
+            .element("grant-uri-permission", vec![Attr::string("android:pathPrefix", "/shared/")])
+            .element("path-permission", vec![Attr::string("android:pathPattern", "/public/.*"),
+                                             Attr::string("android:readPermission", "com.example.READ_PUBLIC")])
+            .end("provider")
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let providers = get_providers(&root);
+        assert_eq!(providers.len(), 2);
+
+        let file_provider = &providers[0];
+        assert_eq!(file_provider.name, "androidx.core.content.FileProvider");
+        assert_eq!(file_provider.authorities, vec!["com.example.fileprovider"]);
+        assert_eq!(file_provider.exported, ComponentState::ExplicitFalse);
+        assert_eq!(file_provider.enabled, ComponentState::DefaultTrue);
+        assert!(file_provider.grant_uri_permissions);
+        assert_eq!(file_provider.meta_data["android.support.FILE_PROVIDER_PATHS"],
+                   MetaDataValue::Resource(0x7f140000));
+
+        let data = &providers[1];
+        assert_eq!(data.name, "com.example.Data");
+        assert_eq!(data.authorities, vec!["com.example.data", "com.example.legacy"]);
+        assert_eq!(data.exported, ComponentState::DefaultFalse);
+        assert!(!data.grant_uri_permissions);
+        assert_eq!(data.read_permission.as_deref(), Some("com.example.READ"));
+        assert_eq!(data.grant_uri_paths, vec![UriPath {
+            path_prefix: Some(String::from("/shared/")),
+            ..Default::default()
+        }]);
+        assert_eq!(data.path_permissions, vec![PathPermission {
+            path: UriPath {
+                path_pattern: Some(String::from("/public/.*")),
+                ..Default::default()
+            },
+            read_permission: Some(String::from("com.example.READ_PUBLIC")),
+            ..Default::default()
+        }]);
+        assert!(data.meta_data.is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+//! Package visibility
+//!
+//! From API level 30, an app only sees the other apps it declares in the
+//! `<queries>` element of its manifest: by package name, by the intents they
+//! handle, or by the authorities of their content providers.
+
+use crate::find_elements_by_type;
+use crate::intent_filter::IntentFilter;
+use crate::owned::ElementRef;
+use crate::providers::split_authorities;
+
+/// Package visibility declarations, see [`get_queries`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Queries {
+    /// Names of the packages declared with `<package>`
+    pub packages: Vec<String>,
+    /// Intents declared with `<intent>`. An `<intent>` has the same children
+    /// as an `<intent-filter>` but only describes a single intent, so it has
+    /// at most one action and one `<data>` element.
+    pub intents: Vec<IntentFilter>,
+    /// Authorities declared with `<provider>`, one per entry even if they
+    /// are declared in a single attribute separated by semicolons
+    pub providers: Vec<String>,
+}
+
+/// Get the package visibility declarations of the app, merged across all
+/// the `<queries>` elements, in document order
+pub fn get_queries<E: ElementRef>(root: &E) -> Queries {
+    let mut queries = Queries::default();
+
+    for element in find_elements_by_type(root, "queries") {
+        for child in element.children() {
+            match child.element_type().as_str() {
+                "package" => queries.packages.extend(child.get_attr("name")),
+                "intent" => queries.intents.push(IntentFilter::from_element(&child)),
+                "provider" => {
+                    if let Some(authorities) = child.get_attr("authorities") {
+                        queries.providers.extend(split_authorities(&authorities));
+                    }
+                },
+                _ => { },
+            }
+        }
+    }
+
+    queries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_get_queries() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("queries", vec![])
+            .element("package", vec![Attr::string("android:name", "com.whatsapp")])
+            .start("intent", vec![])
+            .element("action", vec![Attr::string("android:name", "android.intent.action.SEND")])
+            .element("data", vec![Attr::string("android:mimeType", "image/jpeg")])
+            .end("intent")
+            .element("provider", vec![Attr::string("android:authorities", "com.other.files;com.other.data")])
+            .end("queries")
+            .start("queries", vec![])
+            .element("package", vec![Attr::string("android:name", "org.telegram.messenger")])
+            .end("queries")
+            .element("application", vec![])
+            .end("manifest")
+            .build()));
+
+        let queries = get_queries(&root);
+
+        assert_eq!(queries.packages, vec!["com.whatsapp", "org.telegram.messenger"]);
+        assert_eq!(queries.providers, vec!["com.other.files", "com.other.data"]);
+        assert_eq!(queries.intents.len(), 1);
+        assert!(queries.intents[0].has_action("android.intent.action.SEND"));
+        assert_eq!(queries.intents[0].data[0].mime_type.as_deref(), Some("image/jpeg"));
+    }
+}
@@ -0,0 +1,371 @@
+//! Path queries
+//!
+//! Select elements, or the value of one of their attributes, with a small
+//! path syntax inspired by XPath:
+//!
+//! ```text
+//! query     := step ('/' step)* ('@' attribute)?
+//!            | '@' attribute
+//! step      := (name | '*') ('[' predicate ']')*
+//! predicate := '@' attribute ('=' value)?
+//!            | index
+//! ```
+//!
+//! For instance, `manifest@package` is the package name, and
+//! `application/activity[@android:name=".Main"]@android:exported` is the
+//! exported state of the `.Main` activity.
+//!
+//! The first step is matched against the root element. If it does not match,
+//! the path is relative to the root element, so `manifest/application` and
+//! `application` select the same element. A query made of an attribute only
+//! (e.g., `@package`) refers to the root element.
+//!
+//! Attribute names with a namespace prefix (`android:exported`) must match
+//! exactly, while names without a prefix (`exported`) match any attribute
+//! with this local name, like [`ElementRef::get_attr`]. Values in predicates
+//! are compared with the decoded value of the attribute, as it appears in the
+//! XML output, and are quoted with `"` or `'` unless they only contain name
+//! characters. Indices start at 0 and apply to the elements matched so far in
+//! the step, e.g., `activity[@exported="true"][0]` is the first exported
+//! activity.
+
+use std::str::FromStr;
+
+use crate::error::AxmlError;
+use crate::owned::ElementRef;
+
+/// Condition on the elements matched by a step
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Predicate {
+    /// The element has the attribute
+    HasAttribute(String),
+    /// The attribute of the element has the given value
+    AttributeEquals(String, String),
+    /// Position among the elements matched so far
+    Index(usize),
+}
+
+/// One step of a path: element type and conditions
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Step {
+    /// Element type, or `None` for `*`
+    name: Option<String>,
+    predicates: Vec<Predicate>,
+}
+
+/// Parsed path query, see the [module documentation](self) for the syntax
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Query {
+    steps: Vec<Step>,
+    attribute: Option<String>,
+}
+
+/// Value of an attribute, matched by full name if `name` has a namespace
+/// prefix or by local name otherwise
+fn attribute_value<E: ElementRef>(element: &E, name: &str) -> Option<String> {
+    if name.contains(':') {
+        let local_name = name.rsplit_once(':').map_or(name, |(_, local)| local);
+        element.get_attribute(local_name)
+               .filter(|attr| attr.name == name)
+               .map(|attr| attr.value)
+    } else {
+        element.get_attr(name)
+    }
+}
+
+impl Step {
+    fn matches_name<E: ElementRef>(&self, element: &E) -> bool {
+        self.name.as_ref().is_none_or(|name| element.element_type() == *name)
+    }
+
+    /// Filter `candidates`, which all matched the name of the step, with the
+    /// predicates of the step
+    fn filter<E: ElementRef>(&self, mut candidates: Vec<E>) -> Vec<E> {
+        for predicate in self.predicates.iter() {
+            candidates = match predicate {
+                Predicate::HasAttribute(name) => {
+                    candidates.into_iter()
+                              .filter(|element| attribute_value(element, name).is_some())
+                              .collect()
+                },
+                Predicate::AttributeEquals(name, value) => {
+                    candidates.into_iter()
+                              .filter(|element| attribute_value(element, name).as_ref() == Some(value))
+                              .collect()
+                },
+                Predicate::Index(idx) => candidates.into_iter().nth(*idx).into_iter().collect(),
+            };
+        }
+        candidates
+    }
+}
+
+impl Query {
+    /// Parse a query, see the [module documentation](self) for the syntax
+    pub fn parse(query: &str) -> Result<Query, AxmlError> {
+        QueryParser { query, pos: 0 }.parse()
+    }
+
+    /// Attribute selected by the query (the part after `@`), if any
+    pub fn attribute(&self) -> Option<&str> {
+        self.attribute.as_deref()
+    }
+
+    /// Elements matched by the path of the query, in document order
+    pub fn select<E: ElementRef>(&self, root: &E) -> Vec<E> {
+        let (mut matched, steps) = match self.steps.split_first() {
+            None => return vec![root.clone()],
+            // Rooted path, e.g., `manifest/application`
+            Some((first, rest)) if first.matches_name(root) && !first.filter(vec![root.clone()]).is_empty() => {
+                (vec![root.clone()], rest)
+            },
+            // Path relative to the root, e.g., `application`
+            Some(_) => (vec![root.clone()], &self.steps[..]),
+        };
+
+        for step in steps {
+            matched = matched.iter()
+                             .flat_map(|parent| {
+                                 let children = parent.children()
+                                                      .into_iter()
+                                                      .filter(|child| step.matches_name(child))
+                                                      .collect();
+                                 step.filter(children)
+                             })
+                             .collect();
+        }
+
+        matched
+    }
+
+    /// Values of the attribute selected by the query, for each matched
+    /// element that has it. Empty if the query does not select an attribute.
+    pub fn values<E: ElementRef>(&self, root: &E) -> Vec<String> {
+        let Some(attribute) = self.attribute.as_deref() else {
+            return Vec::new();
+        };
+
+        self.select(root)
+            .iter()
+            .filter_map(|element| attribute_value(element, attribute))
+            .collect()
+    }
+}
+
+impl FromStr for Query {
+    type Err = AxmlError;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        Query::parse(query)
+    }
+}
+
+/// Get the value of the first attribute matched by `query` (e.g.,
+/// `application@android:debuggable`), if any
+pub fn query_attr<E: ElementRef>(root: &E, query: &str) -> Result<Option<String>, AxmlError> {
+    let query = Query::parse(query)?;
+    if query.attribute.is_none() {
+        return Err(AxmlError::InvalidQuery {
+            position: 0,
+            reason: String::from("the query does not select an attribute"),
+        });
+    }
+
+    Ok(query.values(root).into_iter().next())
+}
+
+/// Check if `c` can be part of an element or attribute name
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')
+}
+
+/// Recursive-descent parser for the query syntax
+struct QueryParser<'a> {
+    query: &'a str,
+    /// Byte offset of the next character
+    pos: usize,
+}
+
+impl QueryParser<'_> {
+    fn error<T>(&self, reason: impl Into<String>) -> Result<T, AxmlError> {
+        Err(AxmlError::InvalidQuery { position: self.pos, reason: reason.into() })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.query[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), AxmlError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            },
+            Some(c) => self.error(format!("expected '{expected}', found '{c}'")),
+            None => self.error(format!("expected '{expected}', found the end of the query")),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, AxmlError> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_name_char) {
+            self.bump();
+        }
+
+        if start == self.pos {
+            return match self.peek() {
+                Some(c) => self.error(format!("expected a name, found '{c}'")),
+                None => self.error("expected a name, found the end of the query"),
+            };
+        }
+        Ok(self.query[start..self.pos].to_string())
+    }
+
+    /// Quoted or bare value in a predicate
+    fn value(&mut self) -> Result<String, AxmlError> {
+        let Some(quote @ ('"' | '\'')) = self.peek() else {
+            return self.name();
+        };
+
+        self.bump();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != quote) {
+            self.bump();
+        }
+        let value = self.query[start..self.pos].to_string();
+        self.expect(quote)?;
+        Ok(value)
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, AxmlError> {
+        if self.peek() == Some('@') {
+            self.bump();
+            let name = self.name()?;
+            if self.peek() != Some('=') {
+                return Ok(Predicate::HasAttribute(name));
+            }
+            self.bump();
+            return Ok(Predicate::AttributeEquals(name, self.value()?));
+        }
+
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+        match self.query[start..self.pos].parse() {
+            Ok(idx) => Ok(Predicate::Index(idx)),
+            Err(_) => {
+                self.pos = start;
+                self.error("expected an attribute ('@name') or an index")
+            },
+        }
+    }
+
+    fn step(&mut self) -> Result<Step, AxmlError> {
+        let name = if self.peek() == Some('*') {
+            self.bump();
+            None
+        } else {
+            Some(self.name()?)
+        };
+
+        let mut predicates = Vec::new();
+        while self.peek() == Some('[') {
+            self.bump();
+            predicates.push(self.predicate()?);
+            self.expect(']')?;
+        }
+
+        Ok(Step { name, predicates })
+    }
+
+    fn parse(mut self) -> Result<Query, AxmlError> {
+        let mut steps = Vec::new();
+
+        if self.peek() != Some('@') {
+            steps.push(self.step()?);
+            while self.peek() == Some('/') {
+                self.bump();
+                steps.push(self.step()?);
+            }
+        }
+
+        let attribute = match self.peek() {
+            Some('@') => {
+                self.bump();
+                Some(self.name()?)
+            },
+            _ => None,
+        };
+
+        match self.peek() {
+            None => Ok(Query { steps, attribute }),
+            Some(c) => self.error(format!("unexpected '{c}'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::owned::OwnedXmlElement;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_query() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![Attr::boolean("android:debuggable", true)])
+            .element("activity", vec![Attr::string("android:name", ".Main"),
+                                      Attr::boolean("android:exported", true)])
+            .element("activity", vec![Attr::string("android:name", ".Settings")])
+            .element("service", vec![Attr::string("android:name", ".Sync"),
+                                     Attr::boolean("android:exported", false)])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let get = |query: &str| query_attr(&root, query).unwrap();
+        assert_eq!(get("manifest@package").as_deref(), Some("com.example"));
+        assert_eq!(get("@package").as_deref(), Some("com.example"));
+        assert_eq!(get("application@android:debuggable").as_deref(), Some("true"));
+        assert_eq!(get("manifest/application@debuggable").as_deref(), Some("true"));
+        assert_eq!(get("application/activity[@android:name=\".Main\"]@android:exported").as_deref(), Some("true"));
+        assert_eq!(get("application/activity[@name='.Settings']@exported"), None);
+        assert_eq!(get("application/activity[1]@name").as_deref(), Some(".Settings"));
+        assert_eq!(get("application/*[@exported][1]@name").as_deref(), Some(".Sync"));
+        assert_eq!(get("application/activity@tools:name"), None);
+        assert_eq!(get("receiver@name"), None);
+
+        let query = Query::parse("application/*[@android:exported=true]").unwrap();
+        assert_eq!(query.attribute(), None);
+        assert_eq!(query.select(&root).len(), 1);
+        assert_eq!("application/activity@name".parse::<Query>().unwrap().values(&root),
+                   vec![".Main", ".Settings"]);
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        let position = |query: &str| match Query::parse(query) {
+            Err(AxmlError::InvalidQuery { position, .. }) => position,
+            other => panic!("{query} should be invalid, got {other:?}"),
+        };
+
+        assert_eq!(position(""), 0);
+        assert_eq!(position("manifest/"), 9);
+        assert_eq!(position("activity[@name=\".Main]"), 22);
+        assert_eq!(position("activity[name]"), 9);
+        assert_eq!(position("manifest@package/application"), 16);
+        assert!(matches!(query_attr(&OwnedXmlElement::default().to_rc_tree(), "manifest"),
+                         Err(AxmlError::InvalidQuery { .. })));
+    }
+}
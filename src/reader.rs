@@ -0,0 +1,794 @@
+//! Streaming reader
+//!
+//! `AxmlReader` is a pull-based reader over a binary XML document, similar to
+//! quick-xml's `Reader`: each call to `next_event` decodes the next chunk and
+//! returns the corresponding event, without building a tree. This allows
+//! constant-memory scanning of very large documents, and stopping as soon as
+//! the required data has been found.
+//!
+//! The string pool, resource map, and other non-XML chunks are handled
+//! internally. Unknown chunks are skipped, and recorded along with other
+//! recoverable problems as [`ParseWarning`]s. The tree builders (`parse_xml` and `parse_xml_arena`) are
+//! implemented on top of this reader so there is a single chunk-decoding path.
+
+use std::collections::HashMap;
+use std::io::{
+    Error,
+    Read,
+    Seek,
+    SeekFrom,
+};
+
+use crate::attributes::{ Attribute, Attributes };
+use crate::chunk_types::{ ChunkType, UnknownChunkType };
+use crate::chunk_header::ChunkHeader;
+use crate::chunks::{ ChunkError, ChunkWalker, RawChunk };
+use crate::parser::{
+    self,
+    RawAttribute,
+    RawStartElement,
+};
+use crate::res_value::ResValue;
+use crate::resource_map::android_attribute_name;
+use crate::symbol::{ Interner, Symbol };
+use crate::warning::{ ParseWarning, ParseWarningKind };
+use crate::{ ResourceMap, StringPool, ResTable };
+
+/// Event from the binary XML document, with indices into the string pool
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RawEvent {
+    StartNamespace {
+        prefix: u32,
+        uri: u32,
+    },
+    EndNamespace {
+        prefix: u32,
+        uri: u32,
+    },
+    StartElement(RawStartElement),
+    EndElement {
+        namespace: u32,
+        name: u32,
+    },
+    CData {
+        data: u32,
+        typed_data: ResValue,
+    },
+}
+
+/// Decoded event from the binary XML document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AxmlEvent {
+    /// Start of a namespace declaration
+    StartNamespace {
+        prefix: String,
+        uri: String,
+    },
+    /// End of a namespace declaration
+    EndNamespace {
+        prefix: String,
+        uri: String,
+    },
+    /// Start of an element, with its namespace URI (see
+    /// [`AxmlReader::element_namespace`]) and its decoded attributes
+    StartElement {
+        name: Symbol,
+        namespace: Option<String>,
+        attributes: Attributes,
+    },
+    /// End of an element
+    EndElement {
+        name: Symbol,
+    },
+    /// Text content
+    CData {
+        text: String,
+    },
+}
+
+/// Pull-based reader over a binary XML document
+pub struct AxmlReader<R> {
+    cursor: R,
+    /// Walk over the chunks, through the XML chunk
+    walker: ChunkWalker,
+    strings: Vec<String>,
+    resource_ids: Vec<u32>,
+    /// First string pool and resource map of the document
+    string_pool: Option<StringPool>,
+    resource_map: Option<ResourceMap>,
+    namespace_prefixes: HashMap<String, String>,
+    /// Element and attribute names decoded so far, shared by the events
+    names: Interner,
+    /// Buffer to build attribute names before interning them
+    key_buffer: String,
+    /// Offsets of the string pools and resource maps read before the walk,
+    /// `None` until they are read, see [`AxmlReader::read_pools`]
+    preloaded: Option<Vec<u64>>,
+    /// End of the XML chunk of the document, as announced by its header,
+    /// after which the data is not read
+    document_end: Option<u64>,
+    /// Offset of the chunk of the last event
+    chunk_offset: u64,
+    warnings: Vec<ParseWarning>,
+    /// Set after a malformed chunk, after which the next chunks cannot be found
+    stopped: bool,
+}
+
+impl<R: Read + Seek> AxmlReader<R> {
+    /// Create a reader over the given binary XML data
+    pub fn new(cursor: R) -> Self {
+        AxmlReader {
+            cursor,
+            walker: ChunkWalker::new(&[ChunkType::ResXmlType], true).clamp_containers(true),
+            strings: Vec::new(),
+            resource_ids: Vec::new(),
+            string_pool: None,
+            resource_map: None,
+            namespace_prefixes: HashMap::new(),
+            names: Interner::default(),
+            key_buffer: String::new(),
+            preloaded: None,
+            document_end: None,
+            chunk_offset: 0,
+            warnings: Vec::new(),
+            stopped: false,
+        }
+    }
+
+    /// Warnings about the data read so far
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Take the warnings recorded so far, leaving the list empty
+    pub fn take_warnings(&mut self) -> Vec<ParseWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Offset of the chunk of the last event
+    pub(crate) fn chunk_offset(&self) -> u64 {
+        self.chunk_offset
+    }
+
+    /// Record and log a warning
+    pub(crate) fn warn(&mut self, kind: ParseWarningKind, offset: u64, message: String) {
+        let warning = ParseWarning { kind, offset, message };
+        log::warn!("{warning}");
+        self.warnings.push(warning);
+    }
+
+    /// Name of the Android attribute with the resource ID that the resource
+    /// map gives for the string `name`, if any
+    fn resource_attribute_name(&self, name: u32) -> Option<&'static str> {
+        self.resource_ids.get(name as usize).and_then(|id| android_attribute_name(*id))
+    }
+
+    /// Strings from the string pool(s) read so far
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+
+    /// Get a string from the string pool. Invalid indices give an empty string.
+    pub fn string(&self, idx: u32) -> &str {
+        self.strings.get(idx as usize).map_or("", String::as_str)
+    }
+
+    /// Consume the reader and return the strings read so far
+    pub fn into_strings(self) -> Vec<String> {
+        self.strings
+    }
+
+    /// Resource IDs of the attribute names, from the resource map. The first
+    /// strings of the pool are the names of these attributes.
+    pub fn resource_ids(&self) -> &[u32] {
+        &self.resource_ids
+    }
+
+    /// Decode the name of an attribute, including its namespace prefix.
+    /// Obfuscators sometimes strip or scramble the attribute names in the
+    /// string pool, so like Android, the name of an attribute with a known
+    /// resource ID in the resource map is taken from that ID.
+    pub fn attribute_key(&self, attr: &RawAttribute) -> String {
+        let mut key = String::new();
+        self.push_attribute_key(attr, &mut key);
+        key
+    }
+
+    /// Same as [`AxmlReader::attribute_key`], but append the name to `key`
+    fn push_attribute_key(&self, attr: &RawAttribute, key: &mut String) {
+        let start = key.len();
+        parser::push_attribute_key(attr, &self.strings, &self.namespace_prefixes, key);
+        if let Some(name) = self.resource_attribute_name(attr.name) {
+            // Keep the prefix
+            key.truncate(key[start..].rfind(':').map_or(start, |idx| start + idx + 1));
+            key.push_str(name);
+        }
+    }
+
+    /// Same as [`AxmlReader::attribute_key`], without allocating names that
+    /// were already seen
+    fn interned_attribute_key(&mut self, attr: &RawAttribute) -> Symbol {
+        let mut key = std::mem::take(&mut self.key_buffer);
+        key.clear();
+        self.push_attribute_key(attr, &mut key);
+        let symbol = self.names.intern(&key);
+        self.key_buffer = key;
+        symbol
+    }
+
+    /// Interned string `idx` of the string pool, see [`AxmlReader::string`]
+    fn interned_string(&mut self, idx: u32) -> Symbol {
+        let string = self.strings.get(idx as usize).map_or("", String::as_str);
+        self.names.intern(string)
+    }
+
+    /// Decode the namespace URI of a start element, `None` if it has none or
+    /// if it was never declared
+    pub fn element_namespace(&self, raw_element: &RawStartElement) -> Option<String> {
+        parser::decode_element_namespace(raw_element.namespace, &self.strings, &self.namespace_prefixes)
+    }
+
+    /// Decode the attributes of a start element, see [`AxmlReader::attribute_key`].
+    /// Attribute names are shared with the other elements decoded by this
+    /// reader. When the element has the same attribute several times, the
+    /// first one is used and the others are kept in
+    /// [`Attributes::duplicates`], with a warning.
+    pub fn decode_attributes(&mut self, raw_element: &RawStartElement) -> Attributes {
+        let mut attributes = Attributes::with_capacity(raw_element.attributes.len());
+        for attr in raw_element.attributes.iter() {
+            let namespace = (attr.namespace != 0xffffffff).then(|| self.interned_string(attr.namespace));
+            let attr = Attribute {
+                name: self.interned_attribute_key(attr),
+                namespace,
+                value: parser::decode_attribute_value(attr, &self.strings),
+                typed_value: Some(attr.typed_value),
+            };
+            if attributes.insert_first(attr).is_none() {
+                continue;
+            }
+
+            if let Some(ignored) = attributes.duplicates().last() {
+                let kept = attributes.get(&ignored.name).map_or("", String::as_str);
+                let message = format!("duplicate attribute {} in <{}>: using \"{kept}\", ignoring \"{}\"",
+                                      ignored.name, self.string(raw_element.name), ignored.value);
+                self.warn(ParseWarningKind::DuplicateAttribute, self.chunk_offset, message);
+            }
+        }
+        attributes
+    }
+
+    /// Map from namespace URI to prefix, for the namespaces seen so far
+    pub fn namespace_prefixes(&self) -> &HashMap<String, String> {
+        &self.namespace_prefixes
+    }
+
+    /// Same as [`AxmlReader::read_chunk`], but warn if the parser did not
+    /// read the whole chunk announced by its header (padding, fields added by
+    /// newer versions of the format, etc.). The walk continues after the
+    /// announced end of the chunk anyway.
+    fn read_bounded_chunk(&mut self, chunk: &RawChunk, block_type: ChunkType) -> Result<Option<RawEvent>, Error> {
+        self.cursor.seek(SeekFrom::Start(chunk.offset))?;
+        let event = self.read_chunk(chunk.offset, block_type)?;
+
+        // The other chunks of the document are inside the XML chunk, after
+        // its header
+        let end = chunk.offset + match block_type {
+            ChunkType::ResXmlType => u64::from(chunk.header_size),
+            _ => u64::from(chunk.chunk_size),
+        };
+        let position = self.cursor.stream_position()?;
+        if position != end {
+            self.warn(ParseWarningKind::ChunkSizeMismatch, chunk.offset,
+                      format!("{} bytes read in a {} chunk of {} bytes",
+                              position.saturating_sub(chunk.offset), block_type, end - chunk.offset));
+        }
+
+        Ok(event)
+    }
+
+    /// First string pool of the document, once read. The strings of all the
+    /// pools are available through [`AxmlReader::strings`].
+    pub fn string_pool(&self) -> Option<&StringPool> {
+        self.string_pool.as_ref()
+    }
+
+    /// First resource map of the document, once read. The resource IDs of
+    /// all the maps are available through [`AxmlReader::resource_ids`].
+    pub fn resource_map(&self) -> Option<&ResourceMap> {
+        self.resource_map.as_ref()
+    }
+
+    /// Take the string pool and resource map read so far
+    pub(crate) fn take_pools(&mut self) -> (Option<StringPool>, Option<ResourceMap>) {
+        (self.string_pool.take(), self.resource_map.take())
+    }
+
+    /// Read the chunk of type `block_type` starting at `offset`, the current
+    /// position. Returns `None` for chunks that do not produce events.
+    fn read_chunk(&mut self, offset: u64, block_type: ChunkType) -> Result<Option<RawEvent>, Error> {
+        let event = match block_type {
+            ChunkType::ResStringPoolType => {
+                let pool = StringPool::from_buff(&mut self.cursor, &mut self.strings)?;
+                let strings_start = offset + u64::from(pool.strings_start());
+                let lossy: Vec<_> = pool.iter()
+                                        .filter(|string| string.lossy)
+                                        .map(|string| (string.index, strings_start + u64::from(string.offset)))
+                                        .collect();
+                for (index, string_offset) in lossy {
+                    self.warn(ParseWarningKind::LossyString, string_offset,
+                              format!("string #{index} of the string pool contains invalid data, replaced with U+FFFD"));
+                }
+                self.string_pool.get_or_insert(pool);
+                None
+            },
+            ChunkType::ResTableType => {
+                ResTable::parse(&mut self.cursor)?;
+                None
+            },
+            ChunkType::ResXmlType => {
+                ChunkHeader::from_buff(&mut self.cursor, ChunkType::ResXmlType)?;
+                None
+            },
+            ChunkType::ResXmlStartNamespaceType => {
+                let (prefix, uri) = parser::read_start_namespace(&mut self.cursor)?;
+                self.namespace_prefixes.insert(self.string(uri).to_string(),
+                                               self.string(prefix).to_string());
+                Some(RawEvent::StartNamespace { prefix, uri })
+            },
+            ChunkType::ResXmlEndNamespaceType => {
+                let (prefix, uri) = parser::read_end_namespace(&mut self.cursor)?;
+                Some(RawEvent::EndNamespace { prefix, uri })
+            },
+            ChunkType::ResXmlStartElementType => {
+                Some(RawEvent::StartElement(parser::read_start_element(&mut self.cursor)?))
+            },
+            ChunkType::ResXmlEndElementType => {
+                let (namespace, name) = parser::read_end_element(&mut self.cursor)?;
+                Some(RawEvent::EndElement { namespace, name })
+            },
+            ChunkType::ResXmlCDataType => {
+                let (data, typed_data) = parser::read_cdata(&mut self.cursor)?;
+                Some(RawEvent::CData { data, typed_data })
+            },
+            ChunkType::ResXmlResourceMapType => {
+                let resource_map = ResourceMap::from_buff(&mut self.cursor)?;
+                self.resource_ids.extend_from_slice(resource_map.resource_ids());
+                self.resource_map.get_or_insert(resource_map);
+                None
+            },
+            _ => None,
+        };
+
+        Ok(event)
+    }
+
+    /// Read the string pools and resource maps that come before the first
+    /// element, whatever their order (some tools write the resource map first,
+    /// or several pools), so that readers stopping early (e.g.,
+    /// [`crate::get_package_name`]) do not walk the rest of the document. If
+    /// there is no string pool before the first element, the walk goes on
+    /// until one is found, so that the elements before it can be decoded.
+    /// The pools and maps after that are read when the walk gets there. A
+    /// pool that cannot be read is left to the walk, which reports it when it
+    /// gets there.
+    fn read_pools(&mut self) {
+        let mut preloaded = Vec::new();
+        let mut walker = ChunkWalker::new(&[ChunkType::ResXmlType], true).clamp_containers(true);
+        let mut document_end = None;
+
+        while let Some(Ok(chunk)) = walker.next_chunk(&mut self.cursor) {
+            if document_end.is_some_and(|end| chunk.offset >= end) {
+                break;
+            }
+            match chunk.chunk_type {
+                Some(ChunkType::ResXmlType) => {
+                    document_end.get_or_insert(chunk.offset + u64::from(chunk.chunk_size));
+                },
+                Some(ChunkType::ResXmlStartElementType) if self.string_pool.is_some() => break,
+                Some(block_type @ (ChunkType::ResStringPoolType | ChunkType::ResXmlResourceMapType)) => {
+                    let strings = self.strings.len();
+                    if self.read_bounded_chunk(&chunk, block_type).is_err() {
+                        self.strings.truncate(strings);
+                        break;
+                    }
+                    preloaded.push(chunk.offset);
+                },
+                _ => { },
+            }
+        }
+
+        self.preloaded = Some(preloaded);
+    }
+
+    /// Header of the next chunk of the document, or `None` once the XML
+    /// chunk has been read entirely, warning about the data left after it
+    fn next_chunk(&mut self) -> Option<Result<RawChunk, ChunkError>> {
+        if let Some(end) = self.document_end.filter(|&end| self.walker.offset() >= end) {
+            let data_len = self.cursor.seek(SeekFrom::End(0)).unwrap_or(end);
+            if data_len > end {
+                self.warn(ParseWarningKind::TrailingData, end,
+                          format!("{} bytes after the end of the document ignored", data_len - end));
+            }
+            // Only warn once
+            self.document_end = None;
+            self.walker.stop();
+            return None;
+        }
+
+        let chunk = self.walker.next_chunk(&mut self.cursor)?;
+        if let Ok(chunk) = &chunk {
+            if chunk.chunk_type == Some(ChunkType::ResXmlType) && self.document_end.is_none() {
+                self.document_end = Some(chunk.offset + u64::from(chunk.chunk_size));
+            }
+        }
+        Some(chunk)
+    }
+
+    /// Get the next event without decoding strings, or `None` at the end of
+    /// the document. Non-XML chunks (string pool, resource map, etc.) are
+    /// consumed internally.
+    pub fn next_raw_event(&mut self) -> Option<RawEvent> {
+        if self.preloaded.is_none() {
+            self.read_pools();
+        }
+
+        while !self.stopped {
+            let chunk = match self.next_chunk()? {
+                Ok(chunk) => chunk,
+                // The following chunks cannot be found, stop there
+                Err(e) => {
+                    self.warn(ParseWarningKind::MalformedChunk, e.offset,
+                              format!("malformed chunk: {}, stopping", e.reason));
+                    self.stopped = true;
+                    break;
+                },
+            };
+            let offset = chunk.offset;
+            self.chunk_offset = offset;
+
+            let Some(block_type) = chunk.chunk_type else {
+                self.warn(ParseWarningKind::UnknownChunk, offset,
+                          format!("{} skipped", UnknownChunkType(chunk.raw_type)));
+                continue;
+            };
+
+            let preloaded = self.preloaded.as_ref().is_some_and(|preloaded| preloaded.contains(&offset));
+            let event = match block_type {
+                ChunkType::ResStringPoolType |
+                ChunkType::ResXmlResourceMapType if preloaded => continue,
+                ChunkType::ResStringPoolType |
+                ChunkType::ResTableType |
+                ChunkType::ResXmlType |
+                ChunkType::ResXmlStartNamespaceType |
+                ChunkType::ResXmlEndNamespaceType |
+                ChunkType::ResXmlStartElementType |
+                ChunkType::ResXmlEndElementType |
+                ChunkType::ResXmlCDataType |
+                ChunkType::ResXmlResourceMapType => self.read_bounded_chunk(&chunk, block_type),
+                _ => {
+                    self.warn(ParseWarningKind::UnknownChunk, offset,
+                              format!("unexpected {block_type} chunk skipped"));
+                    continue;
+                },
+            };
+
+            match event {
+                Ok(Some(event)) => return Some(event),
+                Ok(None) => { },
+                // The contents of the chunk cannot be trusted, stop there
+                Err(e) => {
+                    self.warn(ParseWarningKind::MalformedChunk, offset,
+                              format!("malformed {block_type} chunk: {e}, stopping"));
+                    self.walker.stop();
+                    self.stopped = true;
+                },
+            }
+        }
+
+        None
+    }
+
+    /// Skip the rest of the element of the last `StartElement` event, up to
+    /// and including its end element, without decoding the chunks: their
+    /// sizes are enough to find the next one. Namespaces declared in the
+    /// skipped subtree are ignored, as they are out of scope after it.
+    pub fn skip_subtree(&mut self) {
+        let mut depth = 1usize;
+        while depth > 0 && !self.stopped {
+            match self.next_chunk() {
+                Some(Ok(chunk)) => {
+                    self.chunk_offset = chunk.offset;
+                    match chunk.chunk_type {
+                        Some(ChunkType::ResXmlStartElementType) => depth += 1,
+                        Some(ChunkType::ResXmlEndElementType) => depth -= 1,
+                        _ => { },
+                    }
+                },
+                Some(Err(e)) => {
+                    self.warn(ParseWarningKind::MalformedChunk, e.offset,
+                              format!("malformed chunk in a skipped element: {}, stopping", e.reason));
+                    self.stopped = true;
+                },
+                None => return,
+            }
+        }
+    }
+
+    /// Get the next decoded event, or `None` at the end of the document
+    pub fn next_event(&mut self) -> Option<AxmlEvent> {
+        let event = match self.next_raw_event()? {
+            RawEvent::StartNamespace { prefix, uri } => AxmlEvent::StartNamespace {
+                prefix: self.string(prefix).to_string(),
+                uri: self.string(uri).to_string(),
+            },
+            RawEvent::EndNamespace { prefix, uri } => AxmlEvent::EndNamespace {
+                prefix: self.string(prefix).to_string(),
+                uri: self.string(uri).to_string(),
+            },
+            RawEvent::StartElement(raw_element) => {
+                for attr in raw_element.attributes.iter() {
+                    if !self.string(attr.name).is_empty() {
+                        continue;
+                    }
+
+                    let element = self.string(raw_element.name);
+                    let (kind, message) = match self.resource_attribute_name(attr.name) {
+                        Some(name) => (ParseWarningKind::ResolvedAttribute,
+                                       format!("attribute of <{element}> without a name, resolved to {name} from the resource map")),
+                        None => (ParseWarningKind::UnresolvedAttribute,
+                                 format!("attribute of <{element}> without a name")),
+                    };
+                    self.warn(kind, self.chunk_offset, message);
+                }
+
+                AxmlEvent::StartElement {
+                    name: self.interned_string(raw_element.name),
+                    namespace: self.element_namespace(&raw_element),
+                    attributes: self.decode_attributes(&raw_element),
+                }
+            },
+            RawEvent::EndElement { name, .. } => AxmlEvent::EndElement {
+                name: self.interned_string(name),
+            },
+            RawEvent::CData { data, .. } => AxmlEvent::CData {
+                text: self.string(data).to_string(),
+            },
+        };
+
+        Some(event)
+    }
+}
+
+impl<R: Read + Seek> Iterator for AxmlReader<R> {
+    type Item = AxmlEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::chunks::ChunkIterator;
+    use crate::test_utils::{ Attr, AxmlBuilder, ANDROID_NS };
+
+    fn sample_manifest() -> Vec<u8> {
+        AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", ".Main")])
+            .element("activity", vec![Attr::string("android:name", ".Settings")])
+            .end("application")
+            .end("manifest")
+            .build()
+    }
+
+    #[test]
+    fn test_event_sequence() {
+        let reader = AxmlReader::new(Cursor::new(sample_manifest()));
+        let events: Vec<AxmlEvent> = reader.collect();
+
+        assert_eq!(events.len(), 10);
+        assert_eq!(events[0], AxmlEvent::StartNamespace {
+            prefix: String::from("android"),
+            uri: String::from(ANDROID_NS),
+        });
+        assert!(matches!(&events[1], AxmlEvent::StartElement { name, .. } if name == "manifest"));
+        assert_eq!(events[8], AxmlEvent::EndElement { name: Symbol::from("manifest") });
+        assert!(matches!(events[9], AxmlEvent::EndNamespace { .. }));
+
+        if let AxmlEvent::StartElement { attributes, .. } = &events[3] {
+            assert_eq!(attributes.get("android:name").unwrap(), ".Main");
+        } else {
+            panic!("expected a start element, got {:?}", events[3]);
+        }
+    }
+
+    #[test]
+    fn test_early_exit() {
+        let mut reader = AxmlReader::new(Cursor::new(sample_manifest()));
+
+        let package = loop {
+            match reader.next_event() {
+                Some(AxmlEvent::StartElement { attributes, .. }) => {
+                    break attributes.get("package").cloned();
+                },
+                Some(_) => continue,
+                None => break None,
+            }
+        };
+
+        assert_eq!(package.as_deref(), Some("com.example"));
+    }
+
+    #[test]
+    fn test_count_activities() {
+        let reader = AxmlReader::new(Cursor::new(sample_manifest()));
+        let count = reader.filter(|event| matches!(event, AxmlEvent::StartElement { name, .. } if name == "activity"))
+                          .count();
+
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_chunk_order() {
+        let data = AxmlBuilder::new()
+            .resource_map(&[("a", 0x0101021b), ("b", 0x01010003)])
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::int("android:a", 7)])
+            .element("activity", vec![Attr::string("android:b", ".Main")])
+            .end("manifest")
+            .build();
+        let chunks: Vec<&[u8]> = ChunkIterator::new(&data).descend(true)
+                                                          .skip(1)
+                                                          .map(|chunk| {
+                                                              let (offset, header) = chunk.unwrap();
+                                                              &data[offset..offset + header.chunk_size as usize]
+                                                          })
+                                                          .collect();
+        assert_eq!(&chunks[0][..2], &[0x01, 0x00]);
+        assert_eq!(&chunks[1][..2], &[0x80, 0x01]);
+
+        let document = |chunks: &[&[u8]]| {
+            let body = chunks.concat();
+            let mut data = data[..8].to_vec();
+            data[4..8].copy_from_slice(&(8 + body.len() as u32).to_le_bytes());
+            data.extend(body);
+            let result = parser::parse_xml_with_warnings(Cursor::new(data));
+            assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+            let xml = result.root.borrow().to_xml_string();
+            xml
+        };
+        let expected = document(&chunks);
+        assert!(expected.contains("android:name=\".Main\""));
+
+        // Resource map first, and string pool after the namespace
+        assert_eq!(document(&[&[chunks[1], chunks[2], chunks[0]], &chunks[3..]].concat()), expected);
+        // String pool at the end
+        assert_eq!(document(&[&chunks[1..], &[chunks[0]]].concat()), expected);
+        // Second copy of the string pool
+        assert_eq!(document(&[&chunks[..], &[chunks[0]]].concat()), expected);
+    }
+
+    #[test]
+    fn test_attribute_names_from_resource_map() {
+        // Obfuscated names in the string pool, recovered from the resource IDs
+        let data = AxmlBuilder::new()
+            .resource_map(&[("a", 0x0101021b), ("b", 0x01010003)])
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example"),
+                                    Attr::int("android:a", 7)])
+            .element("activity", vec![Attr::string("android:b", ".Main")])
+            .end("manifest")
+            .build();
+
+        let mut reader = AxmlReader::new(Cursor::new(data));
+        let mut attributes = Vec::new();
+        while let Some(event) = reader.next_event() {
+            if let AxmlEvent::StartElement { attributes: attrs, .. } = event {
+                attributes.extend(attrs.iter_attributes().map(|attr| attr.name.clone()));
+            }
+        }
+
+        assert_eq!(reader.resource_ids(), &[0x0101021b, 0x01010003]);
+        assert_eq!(attributes, vec!["package", "android:versionCode", "android:name"]);
+    }
+
+    #[test]
+    fn test_warnings() {
+        let mut data = AxmlBuilder::new()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("application", vec![Attr::int("", 1)])
+            .end("manifest")
+            .build();
+
+        // Replace the `e` of `example` by a lone surrogate
+        let idx = data.windows(4).position(|w| w == b"x\0a\0").unwrap() - 2;
+        data[idx..idx + 2].copy_from_slice(&0xd800u16.to_le_bytes());
+
+        // Unknown chunk right after the string pool
+        let pool_end = 8 + u32::from_le_bytes(data[12..16].try_into().unwrap()) as usize;
+        data.splice(pool_end..pool_end, [0x42, 0x42, 0x08, 0x00, 0x08, 0x00, 0x00, 0x00]);
+        let size = data.len() as u32;
+        data[4..8].copy_from_slice(&size.to_le_bytes());
+
+        let mut reader = AxmlReader::new(Cursor::new(data));
+        let mut package = None;
+        while let Some(event) = reader.next_event() {
+            if let AxmlEvent::StartElement { attributes, .. } = event {
+                package = package.or(attributes.get("package").cloned());
+            }
+        }
+
+        let warnings = reader.take_warnings();
+        let kinds: Vec<_> = warnings.iter().map(|warning| warning.kind).collect();
+        assert_eq!(kinds, vec![ParseWarningKind::LossyString,
+                               ParseWarningKind::UnknownChunk,
+                               ParseWarningKind::UnresolvedAttribute]);
+        assert_eq!(warnings[1].offset, pool_end as u64);
+        assert_eq!(package.as_deref(), Some("com.\u{fffd}xample"));
+        assert!(reader.warnings().is_empty());
+    }
+
+    /// Insert `bytes` at the end of the chunk starting at `offset`, updating
+    /// its size and the size of the document
+    fn grow_chunk(data: &mut Vec<u8>, offset: usize, bytes: &[u8]) {
+        let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap());
+        let end = offset + size as usize;
+        data.splice(end..end, bytes.iter().copied());
+        data[offset + 4..offset + 8].copy_from_slice(&(size + bytes.len() as u32).to_le_bytes());
+        let total = data.len() as u32;
+        data[4..8].copy_from_slice(&total.to_le_bytes());
+    }
+
+    fn read_all(reader: &mut AxmlReader<Cursor<Vec<u8>>>) -> Vec<(String, Option<String>)> {
+        let mut elements = Vec::new();
+        while let Some(event) = reader.next_event() {
+            if let AxmlEvent::StartElement { name, attributes, .. } = event {
+                elements.push((name.to_string(), attributes.get("package").cloned()));
+            }
+        }
+        elements
+    }
+
+    #[test]
+    fn test_chunk_size_bounds() {
+        let data = AxmlBuilder::new()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("application", vec![])
+            .end("manifest")
+            .build();
+        let expected = vec![("manifest".to_string(), Some("com.example".to_string())),
+                            ("application".to_string(), None)];
+
+        // Padding after the strings is part of the string pool
+        let mut padded = data.clone();
+        grow_chunk(&mut padded, 8, &[0xaa; 6]);
+        let mut reader = AxmlReader::new(Cursor::new(padded));
+        assert_eq!(read_all(&mut reader), expected);
+        assert!(reader.warnings().is_empty());
+
+        // Data after the attributes of an element is skipped with a warning
+        let mut extended = data.clone();
+        let start = extended.windows(4).position(|w| w == [0x02, 0x01, 0x10, 0x00]).unwrap();
+        grow_chunk(&mut extended, start, &[0xaa; 4]);
+        let mut reader = AxmlReader::new(Cursor::new(extended));
+        assert_eq!(read_all(&mut reader), expected);
+        let warnings = reader.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, ParseWarningKind::ChunkSizeMismatch);
+        assert_eq!(warnings[0].offset, start as u64);
+
+        // A chunk cannot end past the end of the data
+        let mut truncated = data.clone();
+        let size = u32::from_le_bytes(truncated[start + 4..start + 8].try_into().unwrap());
+        truncated[start + 4..start + 8].copy_from_slice(&(size + 0x1000).to_le_bytes());
+        let mut reader = AxmlReader::new(Cursor::new(truncated));
+        assert!(read_all(&mut reader).is_empty());
+        assert_eq!(reader.warnings()[0].kind, ParseWarningKind::MalformedChunk);
+    }
+}
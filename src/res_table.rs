@@ -2,11 +2,13 @@
 
 use crate::chunk_header::ChunkHeader;
 use crate::string_pool::StringPool;
-use crate::chunk_types::ChunkType;
+use crate::chunk_types::{ ChunkContext, ChunkType };
 
 use std::io::{
     Error,
-    Cursor,
+    ErrorKind,
+    Read,
+    Seek,
 };
 use byteorder::{
     LittleEndian,
@@ -34,34 +36,33 @@ pub struct ResTable {
 }
 
 impl ResTable {
-    pub fn parse(axml_buff: &mut Cursor<Vec<u8>>) {
-
-        /* Go back 2 bytes, to account from the block type */
-        let initial_offset = axml_buff.position();
-        axml_buff.set_position(initial_offset - 2);
+    /* Parse the resource table, starting at the beginning of the chunk */
+    pub fn parse<R: Read + Seek>(axml_buff: &mut R) -> Result<(), Error> {
 
         /* Parse chunk header */
-        let _header = ChunkHeader::from_buff(axml_buff, ChunkType::ResTableType)
-                     .expect("Error: cannot get chunk header from string pool");
+        let _header = ChunkHeader::from_buff_within(axml_buff, ChunkType::ResTableType)?;
 
         /* Get package count */
-        let package_count = axml_buff.read_u32::<LittleEndian>().unwrap();
+        let package_count = axml_buff.read_u32::<LittleEndian>()?;
 
         let mut strings = Vec::<String>::new();
         for _ in 0..package_count {
-            let block_type = ChunkType::parse_block_type(axml_buff)
-                            .expect("Error: cannot parse block type");
+            let (_, block_type) = ChunkType::peek_block_type(axml_buff, ChunkContext::Table)?;
             match block_type {
                 ChunkType::ResStringPoolType => {
-                    StringPool::from_buff(axml_buff, &mut strings);
+                    StringPool::from_buff(axml_buff, &mut strings)?;
                 },
                 ChunkType::ResTablePackageType => {
-                    ResTablePackage::parse(axml_buff)
-                                    .expect("Error: cannot parse table package");
+                    ResTablePackage::parse(axml_buff)?;
+                },
+                _ => {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                                          format!("unexpected {block_type} chunk in resource table")));
                 },
-                _ => { panic!("######## Unexpected block type: {:02X}", block_type); }
             };
         }
+
+        Ok(())
     }
 }
 
@@ -104,35 +105,30 @@ pub struct ResTablePackage {
 }
 
 impl ResTablePackage {
-    pub fn parse(axml_buff: &mut Cursor<Vec<u8>>) -> Result<Self, Error> {
-
-        /* Go back 2 bytes, to account from the block type */
-        let initial_offset = axml_buff.position();
-        axml_buff.set_position(initial_offset - 2);
+    /* Parse the package, starting at the beginning of the chunk */
+    pub fn parse<R: Read>(axml_buff: &mut R) -> Result<Self, Error> {
 
         /* Parse chunk header */
         // let header = ResTable::from_buff(axml_buff)
         //              .expect("Error: cannot parse resource table header from string pool");
-        let header = ChunkHeader::from_buff(axml_buff, ChunkType::ResTablePackageType)
-                     .expect("Error: cannot get chunk header for ResTablePackage");
+        let header = ChunkHeader::from_buff(axml_buff, ChunkType::ResTablePackageType)?;
         // let header = ChunkHeader { chunk_type: 0x0, header_size: 0x0, size: 0x0 };
 
         /* Get other members */
-        let id = axml_buff.read_u32::<LittleEndian>().unwrap();
+        let id = axml_buff.read_u32::<LittleEndian>()?;
 
-        // TODO: this could be simpler with an iterator
         let mut name: [u16; 128] = [0; 128];
-        for i in 0..128 {
-            name[i] = axml_buff.read_u16::<LittleEndian>().unwrap();
-            if name[i] == 0x00 {
+        for c in name.iter_mut() {
+            *c = axml_buff.read_u16::<LittleEndian>()?;
+            if *c == 0x00 {
                 break;
             }
         }
-        let type_strings = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let last_public_type = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let key_strings = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let last_public_key = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let type_id_offset = axml_buff.read_u32::<LittleEndian>().unwrap();
+        let type_strings = axml_buff.read_u32::<LittleEndian>()?;
+        let last_public_type = axml_buff.read_u32::<LittleEndian>()?;
+        let key_strings = axml_buff.read_u32::<LittleEndian>()?;
+        let last_public_key = axml_buff.read_u32::<LittleEndian>()?;
+        let type_id_offset = axml_buff.read_u32::<LittleEndian>()?;
 
         /* Build and return the object */
         Ok(ResTablePackage {
@@ -4,7 +4,8 @@ use crate::data_value_type::DataValueType;
 
 use std::io::{
     Error,
-    Cursor,
+    ErrorKind,
+    Read,
 };
 use byteorder::{
     LittleEndian,
@@ -14,6 +15,7 @@ use byteorder::{
 /* Representation of a value in a resource, supplying type
  * information.
  */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ResValue {
     /* Number of bytes in this structure */
     pub size: u16,
@@ -26,16 +28,34 @@ pub struct ResValue {
 }
 
 impl ResValue {
-    pub fn from_buff(axml_buff: &mut Cursor<Vec<u8>>) -> Result<Self, Error> {
-        let size = axml_buff.read_u16::<LittleEndian>().unwrap();
-        let res0 = axml_buff.read_u8().unwrap();
+    /// Typed value with the given type and data, e.g. to add an attribute
+    pub fn new(data_type: DataValueType, data: u32) -> Self {
+        ResValue {
+            size: 8,
+            res0: 0,
+            data_type,
+            data,
+        }
+    }
+
+    /// Boolean value, encoded like aapt does (`0xffffffff` for `true`)
+    pub fn boolean(value: bool) -> Self {
+        ResValue::new(DataValueType::TypeIntBoolean, if value { 0xffffffff } else { 0 })
+    }
+
+    pub fn from_buff<R: Read>(axml_buff: &mut R) -> Result<Self, Error> {
+        let size = axml_buff.read_u16::<LittleEndian>()?;
+        let res0 = axml_buff.read_u8()?;
 
         if res0 != 0 {
-            panic!("res0 is not 0");
+            return Err(Error::new(ErrorKind::InvalidData, "res0 is not 0"));
         }
 
-        let data_type = DataValueType::from_val(axml_buff.read_u8().unwrap());
-        let data = axml_buff.read_u32::<LittleEndian>().unwrap();
+        let raw_data_type = axml_buff.read_u8()?;
+        let data_type = DataValueType::try_from(raw_data_type).map_err(|e| {
+            Error::new(ErrorKind::InvalidData, e)
+        })?;
+        let data = axml_buff.read_u32::<LittleEndian>()?;
 
         Ok(ResValue {
             size,
@@ -7,7 +7,7 @@ use crate::chunk_types::ChunkType;
 
 use std::io::{
     Error,
-    Cursor,
+    Read,
 };
 use byteorder::{
     LittleEndian,
@@ -17,6 +17,7 @@ use byteorder::{
 /* Header of a chunk representing a resrouce map.
  * TODO: documentation
  */
+#[derive(Debug)]
 pub struct ResourceMap {
     /* Chunk header */
     header: ChunkHeader,
@@ -27,20 +28,16 @@ pub struct ResourceMap {
 
 impl ResourceMap {
 
-    pub fn from_buff(axml_buff: &mut Cursor<Vec<u8>>) -> Result<Self, Error> {
-        /* Go back 2 bytes, to account from the block type */
-        let offset = axml_buff.position();
-        axml_buff.set_position(offset - 2);
-
+    /* Parse the resource map, starting at the beginning of the chunk */
+    pub fn from_buff<R: Read>(axml_buff: &mut R) -> Result<Self, Error> {
         /* Parse chunk header */
-        let header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlResourceMapType)
-                     .expect("Error: cannot get chunk header from string pool");
+        let header = ChunkHeader::from_buff(axml_buff, ChunkType::ResXmlResourceMapType)?;
 
         /* Get resources IDs */
-        let mut resources_id = Vec::new();
         let nb_resources = (header.chunk_size / 4) - 2;
+        let mut resources_id = Vec::with_capacity(header.capacity_for(nb_resources, 4));
         for _ in 0..nb_resources {
-            let id = axml_buff.read_u32::<LittleEndian>().unwrap();
+            let id = axml_buff.read_u32::<LittleEndian>()?;
             resources_id.push(id);
         }
 
@@ -49,10 +46,30 @@ impl ResourceMap {
             resources_id
         })
     }
+
+    /// Resource IDs of the attribute names, indexed like the string pool
+    pub fn resource_ids(&self) -> &[u32] {
+        &self.resources_id
+    }
+
+    /// Resource ID of the attribute name at index `idx` of the string pool
+    pub fn get_id(&self, idx: usize) -> Option<u32> {
+        self.resources_id.get(idx).copied()
+    }
+
+    /// Name of the attribute at index `idx` of the string pool, from its
+    /// resource ID, see [`android_attribute_name`]
+    pub fn get_name(&self, idx: usize) -> Option<&'static str> {
+        self.get_id(idx).and_then(android_attribute_name)
+    }
 }
 
-fn get_resource_string(mut id: u32) -> Result<String, Error> {
-    let attr_names = vec![
+/// URI of the `android` namespace
+pub const ANDROID_NS: &str = "http://schemas.android.com/apk/res/android";
+
+/// Names of the attributes of the `android` namespace, indexed by resource ID
+/// (starting at `0x01010000`)
+const ANDROID_ATTRIBUTE_NAMES: &[&str] = &[
         "theme",
         "label",
         "icon",
@@ -1382,10 +1399,59 @@ fn get_resource_string(mut id: u32) -> Result<String, Error> {
         "contextDescription",
         "showMetadataInPreview",
         "colorSecondary"
-    ];
+];
 
-    // For now, we only care about the attribute names.
-    id -= 0x1010000;
+/// Get the name of an attribute of the `android` namespace from its resource
+/// ID (e.g., `0x01010003` is `name`), if known
+pub fn android_attribute_name(id: u32) -> Option<&'static str> {
+    let idx = id.checked_sub(0x1010000)?;
+    ANDROID_ATTRIBUTE_NAMES.get(idx as usize)
+                           .copied()
+                           .filter(|name| *name != "UNKNOWN")
+}
 
-    Ok(attr_names[id as usize].to_string())
+/// Get the resource ID of an attribute of the `android` namespace from its
+/// name (e.g., `name` is `0x01010003`), the reverse of
+/// [`android_attribute_name`]
+pub fn android_attribute_id(name: &str) -> Option<u32> {
+    if name == "UNKNOWN" {
+        return None;
+    }
+    ANDROID_ATTRIBUTE_NAMES.iter()
+                           .position(|known| *known == name)
+                           .map(|idx| 0x1010000 + idx as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_android_attribute_name() {
+        assert_eq!(android_attribute_name(0x01010003), Some("name"));
+        assert_eq!(android_attribute_name(0x0101021b), Some("versionCode"));
+        assert_eq!(android_attribute_name(0x7f010000), None);
+        assert_eq!(android_attribute_name(0x00000001), None);
+
+        assert_eq!(android_attribute_id("name"), Some(0x01010003));
+        assert_eq!(android_attribute_id("versionCode"), Some(0x0101021b));
+        assert_eq!(android_attribute_id("UNKNOWN"), None);
+        assert_eq!(android_attribute_id("notAnAttribute"), None);
+    }
+
+    #[test]
+    fn test_resource_map() {
+        let mut buf = Vec::new();
+        for value in [0x00080180u32, 20, 0x01010003, 0x7f010000, 0x0101021b] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut cursor = std::io::Cursor::new(buf);
+        let resource_map = ResourceMap::from_buff(&mut cursor).unwrap();
+
+        assert_eq!(resource_map.get_id(1), Some(0x7f010000));
+        assert_eq!(resource_map.get_id(3), None);
+        assert_eq!(resource_map.get_name(0), Some("name"));
+        assert_eq!(resource_map.get_name(1), None);
+        assert_eq!(resource_map.get_name(2), Some("versionCode"));
+    }
 }
@@ -0,0 +1,169 @@
+//! Screen compatibility
+//!
+//! Legacy apps can restrict the screens they support with
+//! `<supports-screens>` and, more strictly, with `<compatible-screens>`,
+//! which app stores use to filter out the devices whose screen is not listed.
+
+use crate::application::FlagState;
+use crate::owned::ElementRef;
+use crate::find_elements_by_type;
+
+/// Names and values of the `android:screenSize` enum
+const SCREEN_SIZES: [(&str, i64); 4] = [
+    ("small", 200),
+    ("normal", 300),
+    ("large", 400),
+    ("xlarge", 500),
+];
+
+/// Names and values of the `android:screenDensity` enum. Other densities
+/// (e.g., 420) are given as numbers.
+const SCREEN_DENSITIES: [(&str, i64); 6] = [
+    ("ldpi", 120),
+    ("mdpi", 160),
+    ("hdpi", 240),
+    ("xhdpi", 320),
+    ("xxhdpi", 480),
+    ("xxxhdpi", 640),
+];
+
+/// Screen declared in `<compatible-screens>`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompatibleScreen {
+    /// Screen size (`small`, `normal`, `large`, or `xlarge`)
+    pub size: Option<String>,
+    /// Screen density (`ldpi` to `xxxhdpi`, or a number of dots per inch)
+    pub density: Option<String>,
+}
+
+/// Attributes of `<supports-screens>`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SupportsScreens {
+    /// `android:smallScreens`
+    pub small_screens: FlagState,
+    /// `android:normalScreens`
+    pub normal_screens: FlagState,
+    /// `android:largeScreens`
+    pub large_screens: FlagState,
+    /// `android:xlargeScreens`
+    pub xlarge_screens: FlagState,
+    /// `android:resizeable`
+    pub resizeable: FlagState,
+    /// `android:anyDensity`
+    pub any_density: FlagState,
+    /// `android:requiresSmallestWidthDp`
+    pub requires_smallest_width_dp: Option<u32>,
+    /// `android:compatibleWidthLimitDp`
+    pub compatible_width_limit_dp: Option<u32>,
+    /// `android:largestWidthLimitDp`
+    pub largest_width_limit_dp: Option<u32>,
+}
+
+/// Screen compatibility of the app, see [`get_screen_compatibility`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScreenCompatibility {
+    /// `<supports-screens>`, if present
+    pub supports_screens: Option<SupportsScreens>,
+    /// Screens listed in `<compatible-screens>`, in document order. If the
+    /// list is not empty, the app can only be installed on these screens.
+    pub compatible_screens: Vec<CompatibleScreen>,
+}
+
+/// Decode an enum attribute from its integer value, keeping unknown values
+/// as numbers. String values are returned as is.
+fn decode_enum<E: ElementRef>(element: &E, local_name: &str, table: &[(&str, i64)]) -> Option<String> {
+    match element.get_attr_int(local_name) {
+        Some(value) => Some(table.iter()
+                                 .find(|(_, v)| *v == value)
+                                 .map_or_else(|| value.to_string(), |(name, _)| name.to_string())),
+        None => element.get_attr(local_name),
+    }
+}
+
+/// Get the screen compatibility declarations of the app. Enum values are
+/// decoded into their symbolic names, and the boolean attributes of
+/// `<supports-screens>` are reported without applying their defaults, which
+/// depend on the SDK versions.
+pub fn get_screen_compatibility<E: ElementRef>(root: &E) -> ScreenCompatibility {
+    let supports_screens = find_elements_by_type(root, "supports-screens")
+        .first()
+        .map(|element| {
+            let dp = |name: &str| element.get_attr_int(name).and_then(|value| u32::try_from(value).ok());
+
+            SupportsScreens {
+                small_screens: FlagState::from_attr(element, "smallScreens"),
+                normal_screens: FlagState::from_attr(element, "normalScreens"),
+                large_screens: FlagState::from_attr(element, "largeScreens"),
+                xlarge_screens: FlagState::from_attr(element, "xlargeScreens"),
+                resizeable: FlagState::from_attr(element, "resizeable"),
+                any_density: FlagState::from_attr(element, "anyDensity"),
+                requires_smallest_width_dp: dp("requiresSmallestWidthDp"),
+                compatible_width_limit_dp: dp("compatibleWidthLimitDp"),
+                largest_width_limit_dp: dp("largestWidthLimitDp"),
+            }
+        });
+
+    let compatible_screens = find_elements_by_type(root, "compatible-screens")
+        .iter()
+        .flat_map(|element| element.children())
+        .filter(|child| child.element_type() == "screen")
+        .map(|screen| CompatibleScreen {
+            size: decode_enum(&screen, "screenSize", &SCREEN_SIZES),
+            density: decode_enum(&screen, "screenDensity", &SCREEN_DENSITIES),
+        })
+        .collect();
+
+    ScreenCompatibility {
+        supports_screens,
+        compatible_screens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_screen_compatibility() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("supports-screens", vec![Attr::boolean("android:smallScreens", false),
+                                              Attr::boolean("android:xlargeScreens", true),
+                                              Attr::int("android:requiresSmallestWidthDp", 600)])
+            .start("compatible-screens", vec![])
+            .element("screen", vec![Attr::int("android:screenSize", 400),
+                                    Attr::int("android:screenDensity", 240)])
+            .element("screen", vec![Attr::int("android:screenSize", 500),
+                                    Attr::int("android:screenDensity", 420)])
+            .end("compatible-screens")
+            .end("manifest")
+            .build()));
+
+        let compatibility = get_screen_compatibility(&root);
+
+        assert_eq!(compatibility.supports_screens, Some(SupportsScreens {
+            small_screens: FlagState::ExplicitFalse,
+            xlarge_screens: FlagState::ExplicitTrue,
+            requires_smallest_width_dp: Some(600),
+            ..Default::default()
+        }));
+        assert_eq!(compatibility.compatible_screens, vec![
+            CompatibleScreen { size: Some(String::from("large")), density: Some(String::from("hdpi")) },
+            CompatibleScreen { size: Some(String::from("xlarge")), density: Some(String::from("420")) },
+        ]);
+    }
+
+    #[test]
+    fn test_no_screen_restrictions() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .element("manifest", vec![Attr::string("package", "com.example")])
+            .build()));
+
+        assert_eq!(get_screen_compatibility(&root), ScreenCompatibility::default());
+    }
+}
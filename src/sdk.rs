@@ -0,0 +1,113 @@
+//! SDK versions
+//!
+//! Most of the security-relevant defaults of the platform depend on the API
+//! levels declared by the app, so they are exposed as integers here.
+
+use crate::owned::ElementRef;
+use crate::find_elements_by_type;
+
+/// API levels declared by the app
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SdkVersions {
+    /// Minimum API level required to install the app
+    pub min: Option<u32>,
+    /// API level the app targets
+    pub target: Option<u32>,
+    /// Maximum API level the app can be installed on (ignored by the
+    /// platform since Android 2.0.1, but still present in some manifests)
+    pub max: Option<u32>,
+}
+
+/// Attributes of `<manifest>` that can be used as the target SDK when
+/// `<uses-sdk>` does not provide it, in order of preference. aapt2 records the
+/// SDK the app was compiled against in these attributes.
+const TARGET_SDK_FALLBACKS: [&str; 3] = [
+    "targetSdkVersion",
+    "compileSdkVersion",
+    "platformBuildVersionCode",
+];
+
+/// Get the API levels declared by the app.
+///
+/// The values are read from `<uses-sdk>`, whether they are stored as integers
+/// or as decimal or hexadecimal strings. Values that are not numbers (e.g.,
+/// preview codenames) are ignored. When `<uses-sdk>` is absent or lacks a
+/// value, the attributes of `<manifest>` are used instead: `minSdkVersion`
+/// for the minimum, and `targetSdkVersion`, `compileSdkVersion`, or
+/// `platformBuildVersionCode` for the target.
+pub fn get_sdk_versions<E: ElementRef>(root: &E) -> SdkVersions {
+    let read = |element: &E, name: &str| {
+        element.get_attr_int(name).and_then(|value| u32::try_from(value).ok())
+    };
+
+    let mut versions = SdkVersions::default();
+
+    if let Some(uses_sdk) = find_elements_by_type(root, "uses-sdk").first() {
+        versions.min = read(uses_sdk, "minSdkVersion");
+        versions.target = read(uses_sdk, "targetSdkVersion");
+        versions.max = read(uses_sdk, "maxSdkVersion");
+    }
+
+    if let Some(manifest) = find_elements_by_type(root, "manifest").first() {
+        if versions.min.is_none() {
+            versions.min = read(manifest, "minSdkVersion");
+        }
+        if versions.target.is_none() {
+            versions.target = TARGET_SDK_FALLBACKS.iter().find_map(|name| read(manifest, name));
+        }
+    }
+
+    versions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn versions(manifest_attrs: Vec<Attr>, uses_sdk_attrs: Option<Vec<Attr>>) -> SdkVersions {
+        let mut builder = AxmlBuilder::new()
+            .android()
+            .start("manifest", manifest_attrs);
+        if let Some(attrs) = uses_sdk_attrs {
+            builder = builder.element("uses-sdk", attrs);
+        }
+        let root = parse_xml(Cursor::new(builder.end("manifest").build()));
+
+        get_sdk_versions(&root)
+    }
+
+    #[test]
+    fn test_int_typed_values() {
+        let versions = versions(vec![], Some(vec![Attr::int("android:minSdkVersion", 21),
+                                                  Attr::int("android:targetSdkVersion", 33),
+                                                  Attr::int("android:maxSdkVersion", 34)]));
+
+        assert_eq!(versions, SdkVersions { min: Some(21), target: Some(33), max: Some(34) });
+    }
+
+    #[test]
+    fn test_string_values() {
+        let decimal = versions(vec![], Some(vec![Attr::string("android:minSdkVersion", "19"),
+                                                 Attr::string("android:targetSdkVersion", "30")]));
+        let hex = versions(vec![], Some(vec![Attr::hex("android:minSdkVersion", 0x17),
+                                             Attr::string("android:targetSdkVersion", "0x1f")]));
+        let codename = versions(vec![], Some(vec![Attr::string("android:minSdkVersion", "S")]));
+
+        assert_eq!(decimal, SdkVersions { min: Some(19), target: Some(30), max: None });
+        assert_eq!(hex, SdkVersions { min: Some(23), target: Some(31), max: None });
+        assert_eq!(codename, SdkVersions::default());
+    }
+
+    #[test]
+    fn test_manifest_fallback() {
+        let versions = versions(vec![Attr::string("package", "com.example"),
+                                     Attr::int("android:compileSdkVersion", 34),
+                                     Attr::string("platformBuildVersionCode", "33")],
+                                None);
+
+        assert_eq!(versions, SdkVersions { min: None, target: Some(34), max: None });
+    }
+}
@@ -0,0 +1,84 @@
+//! Services
+//!
+//! Service-specific attributes, such as the types of foreground services
+//! (`android:foregroundServiceType`, from API level 29).
+
+use std::collections::HashMap;
+
+use crate::flags::{ decode_flags, encode_flags };
+use crate::owned::ElementRef;
+use crate::{ find_elements_by_type, manifest_package, normalize_component_name };
+
+/// Names and values of the foreground service types
+pub const FOREGROUND_SERVICE_TYPES: [(&str, u32); 15] = [
+    ("none", 0x0),
+    ("dataSync", 0x1),
+    ("mediaPlayback", 0x2),
+    ("phoneCall", 0x4),
+    ("location", 0x8),
+    ("connectedDevice", 0x10),
+    ("mediaProjection", 0x20),
+    ("camera", 0x40),
+    ("microphone", 0x80),
+    ("health", 0x100),
+    ("remoteMessaging", 0x200),
+    ("systemExempted", 0x400),
+    ("shortService", 0x800),
+    ("mediaProcessing", 0x2000),
+    ("specialUse", 0x40000000),
+];
+
+/// Get the foreground service types of the services of the app, indexed by
+/// the fully-qualified name of the service. The types are decoded from the
+/// integer value of `android:foregroundServiceType` (or from its symbolic
+/// form if it is stored as a string), unknown bits being reported in
+/// hexadecimal (e.g., `0x4000`). Services without the attribute are omitted.
+pub fn get_foreground_service_types<E: ElementRef>(root: &E) -> HashMap<String, Vec<String>> {
+    let pkg_name = manifest_package(root);
+
+    find_elements_by_type(root, "service")
+        .iter()
+        .filter_map(|service| {
+            let name = normalize_component_name(&pkg_name, &service.get_attr("name")?);
+            let types = match service.get_attr_int("foregroundServiceType") {
+                Some(value) => value as u32,
+                None => encode_flags(&service.get_attr("foregroundServiceType")?, &FOREGROUND_SERVICE_TYPES)?,
+            };
+
+            Some((name, decode_flags(types, &FOREGROUND_SERVICE_TYPES)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_foreground_service_types() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("service", vec![Attr::string("android:name", ".Tracker"),
+                                     Attr::hex("android:foregroundServiceType", 0x48)])
+            .element("service", vec![Attr::string("android:name", ".Future"),
+                                     Attr::hex("android:foregroundServiceType", 0x80004)])
+            .element("service", vec![Attr::string("android:name", ".Source"),
+                                     Attr::string("android:foregroundServiceType", "microphone|dataSync")])
+            .element("service", vec![Attr::string("android:name", ".Plain")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let types = get_foreground_service_types(&root);
+
+        assert_eq!(types.len(), 3);
+        assert_eq!(types["com.example.Tracker"], vec!["location", "camera"]);
+        assert_eq!(types["com.example.Future"], vec!["phoneCall", "0x80000"]);
+        assert_eq!(types["com.example.Source"], vec!["dataSync", "microphone"]);
+    }
+}
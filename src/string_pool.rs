@@ -11,8 +11,10 @@ use crate::chunk_header::ChunkHeader;
 use crate::chunk_types::ChunkType;
 
 use std::io::{
+    Error,
     Read,
-    Cursor,
+    Seek,
+    SeekFrom,
 };
 use byteorder::{
     LittleEndian,
@@ -78,53 +80,116 @@ pub struct StringPool {
 
     /// The strings from the pool
     strings: Vec<String>,
+
+    /// Whether each string contained invalid UTF-8 or UTF-16 data, replaced
+    /// with `U+FFFD`
+    lossy: Vec<bool>,
+}
+
+/// String of a string pool, see [`StringPool::iter`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolString<'a> {
+    /// Index of the string in the pool
+    pub index: usize,
+    /// Offset of the string, from the start of the string data
+    pub offset: u32,
+    /// Decoded string
+    pub value: &'a str,
+    /// Whether the string contained invalid data, replaced with `U+FFFD`
+    pub lossy: bool,
+}
+
+/// Fields of the header of a string pool, see [`StringPoolHeader::from_bytes`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StringPoolHeader {
+    /// Number of strings in the pool
+    pub string_count: u32,
+    /// Number of style span arrays in the pool
+    pub style_count: u32,
+    /// Raw flags, see [`StringPoolHeader::is_sorted`] and
+    /// [`StringPoolHeader::is_utf8`]
+    pub flags: u32,
+    /// Offset from the header to the string data
+    pub strings_start: u32,
+    /// Offset from the header to the style data
+    pub styles_start: u32,
+}
+
+impl StringPoolHeader {
+    /// Read the header of the string pool chunk at the beginning of `chunk`,
+    /// without decoding the strings. Returns `None` if `chunk` is too short.
+    pub fn from_bytes(chunk: &[u8]) -> Option<Self> {
+        let field = |idx: usize| -> Option<u32> {
+            let bytes = chunk.get(8 + 4 * idx..12 + 4 * idx)?;
+            Some(u32::from_le_bytes(bytes.try_into().ok()?))
+        };
+
+        Some(StringPoolHeader {
+            string_count: field(0)?,
+            style_count: field(1)?,
+            flags: field(2)?,
+            strings_start: field(3)?,
+            styles_start: field(4)?,
+        })
+    }
+
+    /// Whether the pool is sorted by UTF-16 string values
+    pub fn is_sorted(&self) -> bool {
+        (self.flags & (1<<0)) != 0
+    }
+
+    /// Whether the strings are encoded in UTF-8 rather than UTF-16
+    pub fn is_utf8(&self) -> bool {
+        (self.flags & (1<<8)) != 0
+    }
 }
 
 impl StringPool {
-    /// Parse the string pool from the raw data
-    pub fn from_buff(axml_buff: &mut Cursor<Vec<u8>>,
-                 global_strings: &mut Vec<String>) -> Self {
+    /// Parse the string pool from the raw data, starting at the beginning of
+    /// the chunk (i.e., at its type). The cursor is left at the end of the
+    /// chunk.
+    pub fn from_buff<R: Read + Seek>(axml_buff: &mut R,
+                 global_strings: &mut Vec<String>) -> Result<Self, Error> {
 
-        // Go back 2 bytes, to account from the block type
-        let initial_offset = axml_buff.position() - 2;
-        axml_buff.set_position(initial_offset);
-        let initial_offset = initial_offset as u32;
+        let initial_offset = axml_buff.stream_position()?;
 
         // Parse chunk header
-        let header = ChunkHeader::from_buff(axml_buff, ChunkType::ResStringPoolType)
-                     .expect("Error: cannot get chunk header from string pool");
+        let header = ChunkHeader::from_buff_within(axml_buff, ChunkType::ResStringPoolType)?;
 
         // Get remaining members
-        let string_count = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let style_count = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let flags = axml_buff.read_u32::<LittleEndian>().unwrap();
+        let string_count = axml_buff.read_u32::<LittleEndian>()?;
+        let style_count = axml_buff.read_u32::<LittleEndian>()?;
+        let flags = axml_buff.read_u32::<LittleEndian>()?;
         let is_sorted = (flags & (1<<0)) != 0;
         let is_utf8 = (flags & (1<<8)) != 0;
-        let strings_start = axml_buff.read_u32::<LittleEndian>().unwrap();
-        let styles_start = axml_buff.read_u32::<LittleEndian>().unwrap();
+        let strings_start = axml_buff.read_u32::<LittleEndian>()?;
+        let styles_start = axml_buff.read_u32::<LittleEndian>()?;
 
         // Get strings offsets
-        let mut strings_offsets = Vec::new();
+        let mut strings_offsets = Vec::with_capacity(header.capacity_for(string_count, 4));
         for _ in 0..string_count {
-            let offset = axml_buff.read_u32::<LittleEndian>().unwrap();
+            let offset = axml_buff.read_u32::<LittleEndian>()?;
             strings_offsets.push(offset);
         }
 
         // Get styles offsets
-        let mut styles_offsets = Vec::new();
+        let mut styles_offsets = Vec::with_capacity(header.capacity_for(style_count, 4));
         for _ in 0..style_count {
-            let offset = axml_buff.read_u32::<LittleEndian>().unwrap();
+            let offset = axml_buff.read_u32::<LittleEndian>()?;
             styles_offsets.push(offset);
         }
 
         // Strings
+        global_strings.reserve(strings_offsets.len());
+        let mut strings = Vec::with_capacity(strings_offsets.len());
+        let mut lossy = Vec::with_capacity(strings_offsets.len());
         for offset in strings_offsets.iter() {
-            // let current_start = (strings_start + offset + 8) as u64;
-            let current_start = (initial_offset + strings_start + offset) as u64;
-            axml_buff.set_position(current_start);
+            let current_start = initial_offset + u64::from(strings_start) + u64::from(*offset);
+            axml_buff.seek(SeekFrom::Start(current_start))?;
 
             let str_size;
             let decoded_string;
+            let is_lossy;
 
             if is_utf8 {
                 // NOTE for resources.arsc files
@@ -134,35 +199,39 @@ impl StringPool {
                 // Actual string sometime contains 00, which need to be ignored
                 // Ending zero might be  2 byte or 4 byte
                 //
-                // TODO: Consider both Length bytes and String length > 32767 characters
-                //
-                // Actually, there are two length if the file is in UTF-8: the encoded and decoded lengths
+                // Actually, there are two length if the file is in UTF-8: the decoded (UTF-16)
+                // and encoded lengths, each taking one or two bytes
                 //
 
-                let _encoded_size = axml_buff.read_u8().unwrap() as u32;
-                str_size = axml_buff.read_u8().unwrap() as u32;
+                let _decoded_size = read_utf8_length(axml_buff)?;
+                str_size = read_utf8_length(axml_buff)?;
                 let mut str_buff = Vec::with_capacity(str_size as usize);
                 let mut chunk = axml_buff.take(str_size.into());
 
-                chunk.read_to_end(&mut str_buff).unwrap();
-                // decoded_string = String::from_utf8(str_buff).unwrap();
-                decoded_string = String::from_utf8(str_buff)
-                                 .expect("Error: cannot decode string, using raw");
+                chunk.read_to_end(&mut str_buff)?;
+                is_lossy = std::str::from_utf8(&str_buff).is_err();
+                decoded_string = String::from_utf8_lossy(&str_buff).into_owned();
             } else {
-                str_size = axml_buff.read_u16::<LittleEndian>().unwrap() as u32;
-                let iter = (0..str_size as usize)
-                        .map(|_| axml_buff.read_u16::<LittleEndian>().unwrap());
-                decoded_string = std::char::decode_utf16(iter).collect::<Result<String, _>>().unwrap();
+                str_size = read_utf16_length(axml_buff)?;
+                let units = (0..str_size as usize)
+                        .map(|_| axml_buff.read_u16::<LittleEndian>())
+                        .collect::<Result<Vec<u16>, Error>>()?;
+                is_lossy = std::char::decode_utf16(units.iter().copied()).any(|c| c.is_err());
+                decoded_string = String::from_utf16_lossy(&units);
             }
 
-            if str_size > 0 {
-                global_strings.push(decoded_string);
-            }
+            // Empty strings are kept too, the chunks refer to the strings by
+            // their index in the pool
+            global_strings.push(decoded_string.clone());
+            strings.push(decoded_string);
+            lossy.push(is_lossy);
         }
 
-        let strings = global_strings.to_vec();
+        // The strings are not necessarily in order, and may be followed by
+        // padding or by the styles, so continue after the whole chunk
+        axml_buff.seek(SeekFrom::Start(initial_offset + u64::from(header.chunk_size)))?;
 
-        StringPool {
+        Ok(StringPool {
             header,
             string_count,
             style_count,
@@ -172,9 +241,49 @@ impl StringPool {
             styles_start,
             strings_offsets,
             styles_offsets,
-            strings
-        }
+            strings,
+            lossy,
+        })
     }
+
+    /// Offset of the string data, from the start of the chunk
+    pub fn strings_start(&self) -> u32 {
+        self.strings_start
+    }
+
+    /// Iterate over the strings of this pool, in order
+    pub fn iter(&self) -> impl Iterator<Item = PoolString<'_>> {
+        self.strings.iter()
+                    .zip(self.strings_offsets.iter())
+                    .zip(self.lossy.iter())
+                    .enumerate()
+                    .map(|(index, ((value, offset), lossy))| PoolString {
+                        index,
+                        offset: *offset,
+                        value,
+                        lossy: *lossy,
+                    })
+    }
+}
+
+/// Read the length of a UTF-8 string: one byte, or two if the high bit of the
+/// first one is set, which then holds the high bits of the length
+fn read_utf8_length<R: Read>(axml_buff: &mut R) -> Result<u32, Error> {
+    let first = u32::from(axml_buff.read_u8()?);
+    if first & 0x80 == 0 {
+        return Ok(first);
+    }
+    Ok(((first & 0x7f) << 8) | u32::from(axml_buff.read_u8()?))
+}
+
+/// Read the length of a UTF-16 string: one unit, or two if the high bit of the
+/// first one is set, which then holds the high bits of the length
+fn read_utf16_length<R: Read>(axml_buff: &mut R) -> Result<u32, Error> {
+    let first = u32::from(axml_buff.read_u16::<LittleEndian>()?);
+    if first & 0x8000 == 0 {
+        return Ok(first);
+    }
+    Ok(((first & 0x7fff) << 16) | u32::from(axml_buff.read_u16::<LittleEndian>()?))
 }
 
 #[cfg(test)]
@@ -183,6 +292,13 @@ mod tests {
     use std::io::{ Cursor, Write };
     use byteorder::{LittleEndian, WriteBytesExt};
 
+    /// Set the chunk size of a string pool to the size of the whole buffer
+    fn with_chunk_size(mut buf: Vec<u8>) -> Cursor<Vec<u8>> {
+        let size = buf.len() as u32;
+        buf[4..8].copy_from_slice(&size.to_le_bytes());
+        Cursor::new(buf)
+    }
+
     // Helper function to create a simple buffer for testing
     fn create_test_buffer() -> Cursor<Vec<u8>> {
         let mut buf = Vec::new();
@@ -190,7 +306,7 @@ mod tests {
         // Chunk header
         buf.write_u16::<LittleEndian>(0x0001).unwrap(); // ChunkType::ResStringPoolType
         buf.write_u16::<LittleEndian>(8).unwrap();      // Chunk header size
-        buf.write_u32::<LittleEndian>(128).unwrap();    // Chunk data size
+        buf.write_u32::<LittleEndian>(0).unwrap();      // Chunk data size, see `with_chunk_size`
 
         // String pool header
         buf.write_u32::<LittleEndian>(2).unwrap();      // string_count
@@ -218,7 +334,7 @@ mod tests {
         buf.write_u16::<LittleEndian>(0x0064).unwrap(); // 'd'
         buf.write_u16::<LittleEndian>(0x0000).unwrap(); // Null terminator
 
-        Cursor::new(buf)
+        with_chunk_size(buf)
     }
 
     #[test]
@@ -226,13 +342,10 @@ mod tests {
         // Create a test buffer
         let mut buffer = create_test_buffer();
 
-        // The `from_buff` function assumes we have read the chunk type already
-        buffer.read_u16::<LittleEndian>().unwrap();
-
         let mut global_strings = Vec::new();
 
         // Parse string pool from buffer
-        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings);
+        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings).unwrap();
 
         // Validate that the string pool is parsed correctly
         assert_eq!(string_pool.strings.len(), 2);
@@ -240,23 +353,46 @@ mod tests {
         assert_eq!(string_pool.strings[1], "World");
     }
 
+    #[test]
+    fn test_string_pool_parse_from_slice() {
+        let buf = create_test_buffer().into_inner();
+        let mut buffer = Cursor::new(buf.as_slice());
+
+        let mut global_strings = Vec::new();
+        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings).unwrap();
+
+        assert_eq!(string_pool.strings, vec!["Hello", "World"]);
+    }
+
     #[test]
     fn test_string_pool_flags() {
         let mut buffer = create_test_buffer();
 
-        // The `from_buff` function assumes we have read the chunk type already
-        buffer.read_u16::<LittleEndian>().unwrap();
-
         let mut global_strings = Vec::new();
 
         // Parse string pool from buffer
-        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings);
+        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings).unwrap();
 
         // Validate the flags
         assert!(string_pool.is_sorted);
         assert!(!string_pool.is_utf8);
     }
 
+    #[test]
+    fn test_chunk_size_past_the_end() {
+        // Pools announcing one more byte, or a megabyte more, than the data
+        for extra in [1, 1 << 20] {
+            let mut buf = with_chunk_size(create_test_buffer().into_inner()).into_inner();
+            let size = buf.len() as u32 + extra;
+            buf[4..8].copy_from_slice(&size.to_le_bytes());
+
+            let mut global_strings = Vec::new();
+            let error = StringPool::from_buff(&mut Cursor::new(buf), &mut global_strings).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+            assert!(global_strings.is_empty());
+        }
+    }
+
     #[test]
     fn test_empty_pool() {
         // Test case with no strings in the pool
@@ -264,7 +400,7 @@ mod tests {
 
         buf.write_u16::<LittleEndian>(0x0001).unwrap(); // ChunkType::ResStringPoolType
         buf.write_u16::<LittleEndian>(8).unwrap();      // Chunk header size
-        buf.write_u32::<LittleEndian>(128).unwrap();    // Chunk data size
+        buf.write_u32::<LittleEndian>(0).unwrap();      // Chunk data size, see `with_chunk_size`
 
         buf.write_u32::<LittleEndian>(0).unwrap(); // string_count = 0
         buf.write_u32::<LittleEndian>(0).unwrap(); // style_count = 0
@@ -272,14 +408,11 @@ mod tests {
         buf.write_u32::<LittleEndian>(32).unwrap(); // strings_start
         buf.write_u32::<LittleEndian>(20).unwrap(); // styles_start
 
-        let mut buffer = Cursor::new(buf);
-
-        // The `from_buff` function assumes we have read the chunk type already
-        buffer.read_u16::<LittleEndian>().unwrap();
+        let mut buffer = with_chunk_size(buf);
 
         let mut global_strings = Vec::new();
 
-        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings);
+        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings).unwrap();
 
         // Check that the string pool is correctly parsed and contains no strings
         assert_eq!(string_pool.strings.len(), 0);
@@ -292,7 +425,7 @@ mod tests {
 
         buf.write_u16::<LittleEndian>(0x0001).unwrap(); // ChunkType::ResStringPoolType
         buf.write_u16::<LittleEndian>(8).unwrap();      // Chunk header size
-        buf.write_u32::<LittleEndian>(128).unwrap();    // Chunk data size
+        buf.write_u32::<LittleEndian>(0).unwrap();      // Chunk data size, see `with_chunk_size`
 
         buf.write_u32::<LittleEndian>(1).unwrap();      // string_count = 1
         buf.write_u32::<LittleEndian>(0).unwrap();      // style_count = 0
@@ -306,17 +439,83 @@ mod tests {
         buf.write_all(b"Hello").unwrap();          // UTF-8 string data
         buf.write_u8(0x00).unwrap();                    // Null terminator
 
-        let mut buffer = Cursor::new(buf);
-
-        // The `from_buff` function assumes we have read the chunk type already
-        buffer.read_u16::<LittleEndian>().unwrap();
+        let mut buffer = with_chunk_size(buf);
 
         let mut global_strings = Vec::new();
 
-        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings);
+        let string_pool = StringPool::from_buff(&mut buffer, &mut global_strings).unwrap();
 
         // Validate that the string pool has correctly decoded the UTF-8 string
         assert_eq!(string_pool.strings.len(), 1);
         assert_eq!(string_pool.strings[0], "Hello");
     }
+
+    /// Pool with a single string, whose data (length prefix included) is
+    /// `entry`
+    fn single_string_pool(flags: u32, entry: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.write_u16::<LittleEndian>(0x0001).unwrap();
+        buf.write_u16::<LittleEndian>(28).unwrap();
+        buf.write_u32::<LittleEndian>(32 + entry.len() as u32).unwrap();
+        buf.write_u32::<LittleEndian>(1).unwrap();      // string_count
+        buf.write_u32::<LittleEndian>(0).unwrap();      // style_count
+        buf.write_u32::<LittleEndian>(flags).unwrap();
+        buf.write_u32::<LittleEndian>(32).unwrap();     // strings_start
+        buf.write_u32::<LittleEndian>(0).unwrap();      // styles_start
+        buf.write_u32::<LittleEndian>(0).unwrap();      // Offset of the string
+        buf.write_all(entry).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_long_string_lengths() {
+        // Lengths from 0x80 (UTF-8) and 0x8000 (UTF-16) take two bytes or
+        // units, the first one with its high bit set
+        let long = "é".repeat(200);
+        let mut entry = vec![0x80, 200, 0x81, 144];
+        entry.extend(long.as_bytes());
+        entry.push(0);
+        let pool = StringPool::from_buff(&mut Cursor::new(single_string_pool(256, &entry)), &mut Vec::new()).unwrap();
+        assert_eq!(pool.strings, vec![long]);
+
+        let long = "a".repeat(0x12345);
+        let mut entry = Vec::new();
+        entry.write_u16::<LittleEndian>(0x8001).unwrap();
+        entry.write_u16::<LittleEndian>(0x2345).unwrap();
+        for unit in long.encode_utf16() {
+            entry.write_u16::<LittleEndian>(unit).unwrap();
+        }
+        entry.write_u16::<LittleEndian>(0).unwrap();
+        let pool = StringPool::from_buff(&mut Cursor::new(single_string_pool(0, &entry)), &mut Vec::new()).unwrap();
+        assert_eq!(pool.strings, vec![long]);
+    }
+
+    #[test]
+    fn test_string_pool_header() {
+        let buf = create_test_buffer().into_inner();
+        let header = StringPoolHeader::from_bytes(&buf).unwrap();
+
+        assert_eq!(header.string_count, 2);
+        assert_eq!(header.style_count, 0);
+        assert_eq!(header.strings_start, 36);
+        assert!(header.is_sorted());
+        assert!(!header.is_utf8());
+        assert_eq!(StringPoolHeader::from_bytes(&buf[..20]), None);
+    }
+
+    #[test]
+    fn test_iter_flags_lossy_strings() {
+        let mut buf = create_test_buffer().into_inner();
+        // Replace 'W' in the second string with an unpaired surrogate
+        let idx = buf.iter().position(|b| *b == 0x57).unwrap();
+        buf[idx..idx + 2].copy_from_slice(&0xd800u16.to_le_bytes());
+
+        let mut buffer = Cursor::new(buf);
+        let string_pool = StringPool::from_buff(&mut buffer, &mut Vec::new()).unwrap();
+
+        let strings: Vec<PoolString> = string_pool.iter().collect();
+        assert_eq!(strings.len(), 2);
+        assert_eq!(strings[0], PoolString { index: 0, offset: 0, value: "Hello", lossy: false });
+        assert_eq!(strings[1], PoolString { index: 1, offset: 14, value: "\u{fffd}orld", lossy: true });
+    }
 }
@@ -0,0 +1,176 @@
+//! Interned names
+//!
+//! The same element and attribute names come back again and again in a
+//! manifest (every component has an `android:name`), so the parser allocates
+//! each distinct name once and shares it between all the elements that use
+//! it. A [`Symbol`] is cheap to clone, and derefs and compares like a `str`.
+
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Immutable, shared string used for element and attribute names
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    /// Get the name as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Check if two symbols share the same allocation, e.g. because they
+    /// were interned by the same parser
+    pub fn ptr_eq(&self, other: &Symbol) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol(Arc::from(name))
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol(Arc::from(name))
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::from(name.as_str())
+    }
+}
+
+impl From<&Symbol> for Symbol {
+    fn from(name: &Symbol) -> Self {
+        name.clone()
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(name: Symbol) -> Self {
+        name.0.to_string()
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<Symbol> for str {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == &*other.0
+    }
+}
+
+impl PartialEq<Symbol> for &str {
+    fn eq(&self, other: &Symbol) -> bool {
+        *self == &*other.0
+    }
+}
+
+impl PartialEq<Symbol> for String {
+    fn eq(&self, other: &Symbol) -> bool {
+        **self == *other.0
+    }
+}
+
+/// Set of the names seen during a parse, to share their allocations
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    symbols: HashSet<Symbol>,
+}
+
+impl Interner {
+    /// Get the symbol for `name`, allocating it the first time only
+    pub(crate) fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return symbol.clone();
+        }
+
+        let symbol = Symbol::from(name);
+        self.symbols.insert(symbol.clone());
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_comparisons() {
+        let symbol = Symbol::from("android:name");
+        assert_eq!(symbol, "android:name");
+        assert_eq!("android:name", symbol);
+        assert_eq!(symbol, String::from("android:name"));
+        assert_ne!(symbol, "name");
+        assert_eq!(symbol.rsplit_once(':'), Some(("android", "name")));
+        assert_eq!(format!("{symbol} {symbol:?}"), "android:name \"android:name\"");
+        assert_eq!(String::from(symbol), "android:name");
+    }
+
+    #[test]
+    fn test_interner() {
+        let mut interner = Interner::default();
+        let first = interner.intern("activity");
+        let second = interner.intern("activity");
+        let other = interner.intern("service");
+
+        assert!(first.ptr_eq(&second));
+        assert!(!first.ptr_eq(&Symbol::from("activity")));
+        assert_eq!(first, Symbol::from("activity"));
+        assert_eq!(other, "service");
+    }
+}
@@ -0,0 +1,159 @@
+//! Task model
+//!
+//! Heuristics to detect task hijacking (e.g., StrandHogg): activities that
+//! start in their own task (`singleTask`, `singleInstance`), that have an
+//! affinity for another task (`android:taskAffinity`), or that can move to
+//! another task (`android:allowTaskReparenting`) can be used by a malicious
+//! app to slip its own activities into the victim's task, or the other way
+//! around.
+
+use crate::owned::ElementRef;
+use crate::{ find_elements_by_type, manifest_package, normalize_component_name };
+
+/// Names of the launch modes, indexed by value
+const LAUNCH_MODES: [&str; 5] = [
+    "standard",
+    "singleTop",
+    "singleTask",
+    "singleInstance",
+    "singleInstancePerTask",
+];
+
+/// Launch modes in which the activity is the root of its own task
+const TASK_ROOT_LAUNCH_MODES: [&str; 3] = [
+    "singleTask",
+    "singleInstance",
+    "singleInstancePerTask",
+];
+
+/// Activity with task-related attributes, see [`analyze_task_model`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskFinding {
+    /// Fully-qualified name of the activity
+    pub activity: String,
+    /// Launch mode (`android:launchMode`, `standard` by default)
+    pub launch_mode: String,
+    /// Effective task affinity: the activity's `android:taskAffinity`, else
+    /// the application's, else the package name. An empty string means the
+    /// activity has no affinity for any task.
+    pub task_affinity: String,
+    /// Whether the task affinity differs from the package name
+    pub custom_task_affinity: bool,
+    /// Whether the activity can move to the task it has an affinity for
+    /// (`android:allowTaskReparenting`, inherited from `<application>`)
+    pub allow_task_reparenting: bool,
+}
+
+/// Decode `android:launchMode`, stored as an integer in compiled manifests
+fn launch_mode<E: ElementRef>(activity: &E) -> String {
+    match activity.get_attr_int("launchMode") {
+        Some(mode) => usize::try_from(mode).ok()
+                                           .and_then(|mode| LAUNCH_MODES.get(mode))
+                                           .map_or_else(|| mode.to_string(), |mode| mode.to_string()),
+        None => activity.get_attr("launchMode").unwrap_or_else(|| String::from(LAUNCH_MODES[0])),
+    }
+}
+
+/// Find the activities whose task-related attributes can be abused for task
+/// hijacking, in document order: activities with a launch mode making them
+/// the root of their own task, with a task affinity other than the package
+/// name, or allowing task reparenting
+pub fn analyze_task_model<E: ElementRef>(root: &E) -> Vec<TaskFinding> {
+    let pkg_name = manifest_package(root);
+    let application = find_elements_by_type(root, "application").into_iter().next();
+    let application_attr = |name: &str| application.as_ref().and_then(|application| application.get_attr(name));
+    let application_reparenting = application.as_ref()
+                                             .and_then(|application| application.get_attr_bool("allowTaskReparenting"))
+                                             .unwrap_or(false);
+
+    find_elements_by_type(root, "activity")
+        .iter()
+        .filter_map(|activity| {
+            let launch_mode = launch_mode(activity);
+            let task_affinity = activity.get_attr("taskAffinity")
+                                        .or_else(|| application_attr("taskAffinity"))
+                                        .unwrap_or_else(|| pkg_name.clone());
+            let custom_task_affinity = task_affinity != pkg_name;
+            let allow_task_reparenting = activity.get_attr_bool("allowTaskReparenting")
+                                                 .unwrap_or(application_reparenting);
+
+            if !TASK_ROOT_LAUNCH_MODES.contains(&launch_mode.as_str()) &&
+               !custom_task_affinity &&
+               !allow_task_reparenting {
+                return None;
+            }
+
+            Some(TaskFinding {
+                activity: activity.get_attr("name")
+                                  .map(|name| normalize_component_name(&pkg_name, &name))
+                                  .unwrap_or_default(),
+                launch_mode,
+                task_affinity,
+                custom_task_affinity,
+                allow_task_reparenting,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    #[test]
+    fn test_analyze_task_model() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("android:name", ".Main"),
+                                      Attr::int("android:launchMode", 1)])
+            .element("activity", vec![Attr::string("android:name", ".Login"),
+                                      Attr::int("android:launchMode", 2)])
+            .element("activity", vec![Attr::string("android:name", ".Hijack"),
+                                      Attr::string("android:taskAffinity", "com.victim"),
+                                      Attr::boolean("android:allowTaskReparenting", true)])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        assert_eq!(analyze_task_model(&root), vec![
+            TaskFinding {
+                activity: String::from("com.example.Login"),
+                launch_mode: String::from("singleTask"),
+                task_affinity: String::from("com.example"),
+                custom_task_affinity: false,
+                allow_task_reparenting: false,
+            },
+            TaskFinding {
+                activity: String::from("com.example.Hijack"),
+                launch_mode: String::from("standard"),
+                task_affinity: String::from("com.victim"),
+                custom_task_affinity: true,
+                allow_task_reparenting: true,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_application_defaults() {
+        let root = parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("application", vec![Attr::string("android:taskAffinity", "com.example.tasks")])
+            .element("activity", vec![Attr::string("android:name", ".Main"),
+                                      Attr::string("android:launchMode", "singleInstance")])
+            .end("application")
+            .end("manifest")
+            .build()));
+
+        let findings = analyze_task_model(&root);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].launch_mode, "singleInstance");
+        assert_eq!(findings[0].task_affinity, "com.example.tasks");
+        assert!(findings[0].custom_task_affinity);
+    }
+}
@@ -0,0 +1,290 @@
+#![allow(dead_code)]
+
+//! Helpers shared by the unit tests
+//!
+//! The main helper is `AxmlBuilder`, which produces a minimal but valid binary
+//! XML document (string pool, namespaces, elements, and attributes) so that
+//! the parser can be tested without shipping binary fixtures.
+
+use byteorder::{
+    LittleEndian,
+    WriteBytesExt,
+};
+
+/// URI of the `android` namespace
+pub const ANDROID_NS: &str = "http://schemas.android.com/apk/res/android";
+
+/// URI of the `dist` namespace of app bundles
+pub const DIST_NS: &str = "http://schemas.android.com/apk/distribution";
+
+/// Value of an attribute in a test document
+#[derive(Clone)]
+pub enum AttrValue {
+    /// Raw string value, stored in the string pool
+    Str(String),
+    /// Typed value: data type and data
+    Typed(u8, u32),
+}
+
+/// Attribute in a test document. The key can be prefixed (e.g. `android:name`)
+/// in which case the prefix is resolved against the declared namespaces.
+#[derive(Clone)]
+pub struct Attr {
+    pub key: String,
+    pub value: AttrValue,
+}
+
+impl Attr {
+    pub fn string(key: &str, value: &str) -> Self {
+        Attr { key: key.to_string(), value: AttrValue::Str(value.to_string()) }
+    }
+
+    pub fn boolean(key: &str, value: bool) -> Self {
+        Attr { key: key.to_string(), value: AttrValue::Typed(0x12, if value { 0xffffffff } else { 0 }) }
+    }
+
+    pub fn int(key: &str, value: i32) -> Self {
+        Attr { key: key.to_string(), value: AttrValue::Typed(0x10, value as u32) }
+    }
+
+    pub fn hex(key: &str, value: u32) -> Self {
+        Attr { key: key.to_string(), value: AttrValue::Typed(0x11, value) }
+    }
+
+    pub fn float(key: &str, value: f32) -> Self {
+        Attr { key: key.to_string(), value: AttrValue::Typed(0x04, value.to_bits()) }
+    }
+
+    pub fn reference(key: &str, id: u32) -> Self {
+        Attr { key: key.to_string(), value: AttrValue::Typed(0x01, id) }
+    }
+}
+
+enum Node {
+    Start { name: String, attrs: Vec<Attr> },
+    End { name: String },
+    Text(String),
+}
+
+/// Builder for binary XML documents. Element names and attribute keys can be
+/// prefixed with a declared namespace (e.g. `dist:module`).
+#[derive(Default)]
+pub struct AxmlBuilder {
+    namespaces: Vec<(String, String)>,
+    resource_map: Vec<(String, u32)>,
+    nodes: Vec<Node>,
+}
+
+impl AxmlBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a namespace. All namespaces wrap the whole document.
+    pub fn namespace(mut self, prefix: &str, uri: &str) -> Self {
+        self.namespaces.push((prefix.to_string(), uri.to_string()));
+        self
+    }
+
+    /// Declare the `android` namespace
+    pub fn android(self) -> Self {
+        self.namespace("android", ANDROID_NS)
+    }
+
+    pub fn start(mut self, name: &str, attrs: Vec<Attr>) -> Self {
+        self.nodes.push(Node::Start { name: name.to_string(), attrs });
+        self
+    }
+
+    pub fn end(mut self, name: &str) -> Self {
+        self.nodes.push(Node::End { name: name.to_string() });
+        self
+    }
+
+    /// Add a resource map: the given attribute names are placed first in the
+    /// string pool, associated with the given resource IDs
+    pub fn resource_map(mut self, entries: &[(&str, u32)]) -> Self {
+        self.resource_map = entries.iter().map(|(name, id)| (name.to_string(), *id)).collect();
+        self
+    }
+
+    /// Add text content (CDATA chunk) at the current position
+    pub fn text(mut self, text: &str) -> Self {
+        self.nodes.push(Node::Text(text.to_string()));
+        self
+    }
+
+    /// Start and immediately end an element
+    pub fn element(self, name: &str, attrs: Vec<Attr>) -> Self {
+        self.start(name, attrs).end(name)
+    }
+
+    /// Produce the binary XML document
+    pub fn build(self) -> Vec<u8> {
+        let mut strings: Vec<String> = self.resource_map.iter().map(|(name, _)| name.clone()).collect();
+        let mut intern = |s: &str| -> u32 {
+            match strings.iter().position(|x| x == s) {
+                Some(idx) => idx as u32,
+                None => {
+                    strings.push(s.to_string());
+                    (strings.len() - 1) as u32
+                }
+            }
+        };
+
+        let resolve_ns = |prefix: &str| -> String {
+            self.namespaces.iter()
+                .find(|(p, _)| p == prefix)
+                .map(|(_, uri)| uri.clone())
+                .expect("undeclared namespace prefix in test document")
+        };
+
+        let mut body = Vec::new();
+
+        for (prefix, uri) in self.namespaces.iter() {
+            write_header(&mut body, 0x0100, 16, 24);
+            body.write_u32::<LittleEndian>(1).unwrap();
+            body.write_u32::<LittleEndian>(0xffffffff).unwrap();
+            body.write_u32::<LittleEndian>(intern(prefix)).unwrap();
+            body.write_u32::<LittleEndian>(intern(uri)).unwrap();
+        }
+
+        for node in self.nodes.iter() {
+            match node {
+                Node::Start { name, attrs } => {
+                    let (ns, local) = match name.split_once(':') {
+                        Some((prefix, local)) => (intern(&resolve_ns(prefix)), local),
+                        None => (0xffffffff, name.as_str()),
+                    };
+                    write_header(&mut body, 0x0102, 16, 36 + 20 * attrs.len() as u32);
+                    body.write_u32::<LittleEndian>(1).unwrap();
+                    body.write_u32::<LittleEndian>(0xffffffff).unwrap();
+                    body.write_u32::<LittleEndian>(ns).unwrap();
+                    body.write_u32::<LittleEndian>(intern(local)).unwrap();
+                    body.write_u16::<LittleEndian>(0x14).unwrap();
+                    body.write_u16::<LittleEndian>(0x14).unwrap();
+                    body.write_u16::<LittleEndian>(attrs.len() as u16).unwrap();
+                    body.write_u16::<LittleEndian>(0).unwrap();
+                    body.write_u16::<LittleEndian>(0).unwrap();
+                    body.write_u16::<LittleEndian>(0).unwrap();
+
+                    for attr in attrs {
+                        let (ns, local) = match attr.key.split_once(':') {
+                            Some((prefix, local)) => (intern(&resolve_ns(prefix)), local),
+                            None => (0xffffffff, attr.key.as_str()),
+                        };
+                        body.write_u32::<LittleEndian>(ns).unwrap();
+                        body.write_u32::<LittleEndian>(intern(local)).unwrap();
+                        let (raw, data_type, data) = match &attr.value {
+                            AttrValue::Str(s) => {
+                                let idx = intern(s);
+                                (idx, 0x03, idx)
+                            },
+                            AttrValue::Typed(data_type, data) => (0xffffffff, *data_type, *data),
+                        };
+                        body.write_u32::<LittleEndian>(raw).unwrap();
+                        body.write_u16::<LittleEndian>(8).unwrap();
+                        body.write_u8(0).unwrap();
+                        body.write_u8(data_type).unwrap();
+                        body.write_u32::<LittleEndian>(data).unwrap();
+                    }
+                },
+                Node::End { name } => {
+                    let (ns, local) = match name.split_once(':') {
+                        Some((prefix, local)) => (intern(&resolve_ns(prefix)), local),
+                        None => (0xffffffff, name.as_str()),
+                    };
+                    write_header(&mut body, 0x0103, 16, 24);
+                    body.write_u32::<LittleEndian>(1).unwrap();
+                    body.write_u32::<LittleEndian>(0xffffffff).unwrap();
+                    body.write_u32::<LittleEndian>(ns).unwrap();
+                    body.write_u32::<LittleEndian>(intern(local)).unwrap();
+                },
+                Node::Text(text) => {
+                    let idx = intern(text);
+                    write_header(&mut body, 0x0104, 16, 28);
+                    body.write_u32::<LittleEndian>(1).unwrap();
+                    body.write_u32::<LittleEndian>(0xffffffff).unwrap();
+                    body.write_u32::<LittleEndian>(idx).unwrap();
+                    body.write_u16::<LittleEndian>(8).unwrap();
+                    body.write_u8(0).unwrap();
+                    body.write_u8(0x03).unwrap();
+                    body.write_u32::<LittleEndian>(idx).unwrap();
+                },
+            }
+        }
+
+        for (prefix, uri) in self.namespaces.iter().rev() {
+            write_header(&mut body, 0x0101, 16, 24);
+            body.write_u32::<LittleEndian>(1).unwrap();
+            body.write_u32::<LittleEndian>(0xffffffff).unwrap();
+            body.write_u32::<LittleEndian>(intern(prefix)).unwrap();
+            body.write_u32::<LittleEndian>(intern(uri)).unwrap();
+        }
+
+        let mut pool = build_string_pool(&strings);
+        if !self.resource_map.is_empty() {
+            write_header(&mut pool, 0x0180, 8, 8 + 4 * self.resource_map.len() as u32);
+            for (_, id) in self.resource_map.iter() {
+                pool.write_u32::<LittleEndian>(*id).unwrap();
+            }
+        }
+
+        let mut doc = Vec::new();
+        write_header(&mut doc, 0x0003, 8, (8 + pool.len() + body.len()) as u32);
+        doc.extend(pool);
+        doc.extend(body);
+        doc
+    }
+}
+
+fn write_header(buf: &mut Vec<u8>, chunk_type: u16, header_size: u16, chunk_size: u32) {
+    buf.write_u16::<LittleEndian>(chunk_type).unwrap();
+    buf.write_u16::<LittleEndian>(header_size).unwrap();
+    buf.write_u32::<LittleEndian>(chunk_size).unwrap();
+}
+
+/// Build a UTF-16 string pool chunk
+fn build_string_pool(strings: &[String]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut offsets = Vec::new();
+    for s in strings {
+        offsets.push(data.len() as u32);
+        let units: Vec<u16> = s.encode_utf16().collect();
+        data.write_u16::<LittleEndian>(units.len() as u16).unwrap();
+        for unit in units {
+            data.write_u16::<LittleEndian>(unit).unwrap();
+        }
+        data.write_u16::<LittleEndian>(0).unwrap();
+    }
+    while data.len() % 4 != 0 {
+        data.push(0);
+    }
+
+    let strings_start = 28 + 4 * strings.len() as u32;
+    let mut pool = Vec::new();
+    write_header(&mut pool, 0x0001, 28, strings_start + data.len() as u32);
+    pool.write_u32::<LittleEndian>(strings.len() as u32).unwrap();
+    pool.write_u32::<LittleEndian>(0).unwrap();
+    pool.write_u32::<LittleEndian>(0).unwrap();
+    pool.write_u32::<LittleEndian>(strings_start).unwrap();
+    pool.write_u32::<LittleEndian>(0).unwrap();
+    for offset in offsets {
+        pool.write_u32::<LittleEndian>(offset).unwrap();
+    }
+    pool.extend(data);
+    pool
+}
+
+/// Build an in-memory zip archive (e.g., an APK) with the given entries
+#[cfg(feature = "apk")]
+pub fn zip_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    use std::io::{ Cursor, Write };
+
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in entries {
+        writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
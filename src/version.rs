@@ -0,0 +1,100 @@
+//! App version
+//!
+//! Version of the app as declared on the `<manifest>` element.
+
+use crate::owned::ElementRef;
+use crate::find_elements_by_type;
+
+/// Version of the app, see [`get_version_info`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VersionInfo {
+    /// Version code, including `android:versionCodeMajor` in the upper 32
+    /// bits for apps using long version codes
+    pub version_code: Option<u64>,
+    /// User-visible version name
+    pub version_name: Option<String>,
+}
+
+/// Get the version of the app from the `<manifest>` element.
+///
+/// `android:versionCode` is read whether it is stored as an integer or as a
+/// decimal or hexadecimal string. It is an unsigned 32-bit value, and
+/// `android:versionCodeMajor`, if present, makes up its upper 32 bits.
+/// A version name referring to a string resource is not resolved and
+/// reported as `None`. The attributes added by aapt2
+/// (`platformBuildVersionCode` and `platformBuildVersionName`) describe the
+/// platform the app was built against and are not used.
+pub fn get_version_info<E: ElementRef>(root: &E) -> VersionInfo {
+    let Some(manifest) = find_elements_by_type(root, "manifest").into_iter().next() else {
+        return VersionInfo::default();
+    };
+
+    // Integer attributes are stored as signed values, keep their 32 bits
+    let read = |name: &str| manifest.get_attr_int(name).map(|value| u64::from(value as u32));
+
+    let version_code = match (read("versionCode"), read("versionCodeMajor")) {
+        (Some(minor), Some(major)) => Some((major << 32) | minor),
+        (Some(minor), None) => Some(minor),
+        (None, Some(major)) => Some(major << 32),
+        (None, None) => None,
+    };
+
+    let version_name = if manifest.get_attr_reference("versionName").is_some() {
+        None
+    } else {
+        manifest.get_attr("versionName")
+    };
+
+    VersionInfo {
+        version_code,
+        version_name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::parse_xml;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn version(manifest_attrs: Vec<Attr>) -> VersionInfo {
+        get_version_info(&parse_xml(Cursor::new(AxmlBuilder::new()
+            .android()
+            .start("manifest", manifest_attrs)
+            .end("manifest")
+            .build())))
+    }
+
+    #[test]
+    fn test_typed_version() {
+        let info = version(vec![Attr::string("package", "com.example"),
+                                Attr::int("android:versionCode", 42),
+                                Attr::string("android:versionName", "1.2.3"),
+                                Attr::string("platformBuildVersionName", "14")]);
+
+        assert_eq!(info, VersionInfo {
+            version_code: Some(42),
+            version_name: Some(String::from("1.2.3")),
+        });
+    }
+
+    #[test]
+    fn test_long_version_code() {
+        let info = version(vec![Attr::hex("android:versionCode", 0xfffffffe),
+                                Attr::int("android:versionCodeMajor", 2)]);
+
+        assert_eq!(info.version_code, Some(0x2_ffff_fffe));
+        assert_eq!(info.version_name, None);
+    }
+
+    #[test]
+    fn test_string_version() {
+        let info = version(vec![Attr::string("android:versionCode", "0x10"),
+                                Attr::reference("android:versionName", 0x7f120000),
+                                Attr::string("platformBuildVersionName", "14")]);
+
+        assert_eq!(info.version_code, Some(16));
+        assert_eq!(info.version_name, None);
+    }
+}
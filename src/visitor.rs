@@ -0,0 +1,254 @@
+//! Visitor API
+//!
+//! `parse_with_visitor` walks a binary XML document in a single pass and calls
+//! the methods of an [`AxmlVisitor`] for each element, text, and namespace,
+//! without building a tree. Each method tells the walker how to continue: a
+//! visitor can skip the subtree of an element it is not interested in, which
+//! does not decode anything in it, or stop as soon as it has what it needs.
+//!
+//! Unbalanced end elements are handled by the walker, so visitors always see
+//! matching `start_element` and `end_element` calls. `parse_xml` is
+//! implemented on top of this walker.
+
+use std::io::{
+    Read,
+    Seek,
+};
+
+use crate::attributes::Attributes;
+use crate::reader::{ AxmlEvent, AxmlReader };
+use crate::symbol::Symbol;
+use crate::warning::{ ParseWarning, ParseWarningKind };
+
+/// How the walk should continue after a call to an [`AxmlVisitor`] method
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisitControl {
+    /// Go on with the next event
+    Continue,
+    /// Skip the children of the element, whose `end_element` is called right
+    /// away. Same as `Continue` when returned by other methods than
+    /// `start_element`.
+    SkipSubtree,
+    /// Stop the walk
+    Stop,
+}
+
+/// Element given to [`AxmlVisitor::start_element`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElementInfo {
+    /// Name of the element, without its prefix (e.g., `uses-permission`)
+    pub name: Symbol,
+    /// URI of the namespace of the element, if any
+    pub namespace: Option<String>,
+    /// Decoded attributes of the element
+    pub attributes: Attributes,
+    /// Number of elements this element is in, 0 for the root
+    pub depth: usize,
+}
+
+/// Callbacks of [`parse_with_visitor`]. All of them continue the walk by
+/// default.
+pub trait AxmlVisitor {
+    /// Start of an element
+    fn start_element(&mut self, _element: &ElementInfo) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// End of the element named `name`
+    fn end_element(&mut self, _name: &str) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// Text content
+    fn cdata(&mut self, _text: &str) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// Namespace declaration, before the element that declares it
+    fn namespace(&mut self, _prefix: &str, _uri: &str) -> VisitControl {
+        VisitControl::Continue
+    }
+}
+
+/// Walk the document and call the methods of `visitor` for each event, see
+/// the module documentation. Returns the warnings about the problems the
+/// parser recovered from.
+pub fn parse_with_visitor<R: Read + Seek, V: AxmlVisitor>(axml_cursor: R, visitor: &mut V) -> Vec<ParseWarning> {
+    let mut reader = AxmlReader::new(axml_cursor);
+    visit(&mut reader, visitor);
+    reader.take_warnings()
+}
+
+/// Same as [`parse_with_visitor`], with an existing reader
+pub(crate) fn visit<R: Read + Seek, V: AxmlVisitor>(reader: &mut AxmlReader<R>, visitor: &mut V) {
+    // Names of the open elements
+    let mut open = Vec::<Symbol>::new();
+
+    while let Some(event) = reader.next_event() {
+        let control = match event {
+            AxmlEvent::StartNamespace { prefix, uri } => visitor.namespace(&prefix, &uri),
+            AxmlEvent::StartElement { name, namespace, attributes } => {
+                let element = ElementInfo { name, namespace, attributes, depth: open.len() };
+                match visitor.start_element(&element) {
+                    VisitControl::SkipSubtree => {
+                        reader.skip_subtree();
+                        visitor.end_element(&element.name)
+                    },
+                    control => {
+                        open.push(element.name);
+                        control
+                    },
+                }
+            },
+            AxmlEvent::EndElement { name } => {
+                let offset = reader.chunk_offset();
+                match open.last() {
+                    Some(last) if *last == name => { },
+                    Some(last) => reader.warn(ParseWarningKind::UnbalancedEndElement, offset,
+                                              format!("</{name}> closes <{last}>")),
+                    None => {
+                        reader.warn(ParseWarningKind::UnbalancedEndElement, offset,
+                                    format!("</{name}> without a start element, ignored"));
+                        continue;
+                    },
+                }
+                open.pop();
+                visitor.end_element(&name)
+            },
+            AxmlEvent::CData { text } => visitor.cdata(&text),
+            AxmlEvent::EndNamespace { .. } => VisitControl::Continue,
+        };
+
+        if control == VisitControl::Stop {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::test_utils::{ Attr, AxmlBuilder };
+
+    fn sample_manifest() -> Vec<u8> {
+        AxmlBuilder::new()
+            .android()
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.INTERNET")])
+            .start("application", vec![])
+            .start("activity", vec![Attr::string("android:name", ".Main")])
+            .element("intent-filter", vec![])
+            .end("activity")
+            .end("application")
+            .element("uses-permission", vec![Attr::string("android:name", "android.permission.CAMERA")])
+            .end("manifest")
+            .build()
+    }
+
+    /// Record the calls, skipping the elements named `skip` and stopping at
+    /// the element named `stop`
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+        skip: &'static str,
+        stop: &'static str,
+    }
+
+    impl AxmlVisitor for Recorder {
+        fn start_element(&mut self, element: &ElementInfo) -> VisitControl {
+            self.calls.push(format!("{}<{}>", element.depth, element.name));
+            if element.name == self.skip {
+                VisitControl::SkipSubtree
+            } else if element.name == self.stop {
+                VisitControl::Stop
+            } else {
+                VisitControl::Continue
+            }
+        }
+
+        fn end_element(&mut self, name: &str) -> VisitControl {
+            self.calls.push(format!("</{name}>"));
+            VisitControl::Continue
+        }
+
+        fn namespace(&mut self, prefix: &str, _uri: &str) -> VisitControl {
+            self.calls.push(format!("xmlns:{prefix}"));
+            VisitControl::Continue
+        }
+    }
+
+    #[test]
+    fn test_visit_all() {
+        let mut recorder = Recorder::default();
+        let warnings = parse_with_visitor(Cursor::new(sample_manifest()), &mut recorder);
+        assert!(warnings.is_empty());
+        assert_eq!(recorder.calls, vec!["xmlns:android", "0<manifest>",
+                                        "1<uses-permission>", "</uses-permission>",
+                                        "1<application>", "2<activity>",
+                                        "3<intent-filter>", "</intent-filter>",
+                                        "</activity>", "</application>",
+                                        "1<uses-permission>", "</uses-permission>",
+                                        "</manifest>"]);
+    }
+
+    #[test]
+    fn test_skip_and_stop() {
+        let mut recorder = Recorder { skip: "application", ..Default::default() };
+        parse_with_visitor(Cursor::new(sample_manifest()), &mut recorder);
+        assert_eq!(recorder.calls, vec!["xmlns:android", "0<manifest>",
+                                        "1<uses-permission>", "</uses-permission>",
+                                        "1<application>", "</application>",
+                                        "1<uses-permission>", "</uses-permission>",
+                                        "</manifest>"]);
+
+        let mut recorder = Recorder { stop: "application", ..Default::default() };
+        parse_with_visitor(Cursor::new(sample_manifest()), &mut recorder);
+        assert_eq!(recorder.calls.last().unwrap(), "1<application>");
+    }
+
+    #[test]
+    fn test_skipped_subtree_is_not_decoded() {
+        // The attribute without a name would give a warning if decoded
+        let data = AxmlBuilder::new()
+            .start("manifest", vec![])
+            .start("application", vec![])
+            .element("activity", vec![Attr::string("", ".Main")])
+            .end("application")
+            .element("uses-permission", vec![Attr::string("name", "android.permission.CAMERA")])
+            .end("manifest")
+            .build();
+
+        #[derive(Default)]
+        struct Permissions(Vec<String>);
+
+        impl AxmlVisitor for Permissions {
+            fn start_element(&mut self, element: &ElementInfo) -> VisitControl {
+                match element.name.as_str() {
+                    "manifest" => VisitControl::Continue,
+                    "uses-permission" => {
+                        self.0.extend(element.attributes.get("name").cloned());
+                        VisitControl::SkipSubtree
+                    },
+                    _ => VisitControl::SkipSubtree,
+                }
+            }
+        }
+
+        let mut permissions = Permissions::default();
+        let warnings = parse_with_visitor(Cursor::new(&data), &mut permissions);
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(permissions.0, vec!["android.permission.CAMERA"]);
+
+        // Chunk size pointing past the end of the data
+        let mut truncated = data.clone();
+        let start = truncated.windows(4).enumerate()
+                             .filter(|(_, w)| *w == [0x02, 0x01, 0x10, 0x00])
+                             .nth(2).unwrap().0;
+        truncated[start + 4..start + 8].copy_from_slice(&0x1000u32.to_le_bytes());
+        let mut permissions = Permissions::default();
+        let warnings = parse_with_visitor(Cursor::new(truncated), &mut permissions);
+        assert_eq!(warnings[0].kind, ParseWarningKind::MalformedChunk);
+        assert!(permissions.0.is_empty());
+    }
+}
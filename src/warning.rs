@@ -0,0 +1,57 @@
+//! Parse warnings
+//!
+//! The parser recovers from some oddities in the binary XML data (unknown
+//! chunks, strings that are not valid UTF-8, etc.). Each recovery is recorded
+//! as a `ParseWarning`, so that callers can decide whether to trust the
+//! result, and logged with the `log` crate.
+
+use std::fmt;
+
+/// Kind of a [`ParseWarning`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParseWarningKind {
+    /// A chunk of an unknown type, or of a type that is not expected in a
+    /// binary XML document, was skipped
+    UnknownChunk,
+    /// A string of the string pool contained invalid data, which was replaced
+    /// with `U+FFFD`
+    LossyString,
+    /// The name of an attribute is empty, and cannot be resolved from the
+    /// resource map either
+    UnresolvedAttribute,
+    /// The name of an attribute is empty, and was taken from the resource map
+    ResolvedAttribute,
+    /// An end element does not match the open elements, and was ignored if
+    /// no element is open
+    UnbalancedEndElement,
+    /// A chunk could not be parsed (truncated data, invalid header, etc.),
+    /// and the rest of the document was ignored
+    MalformedChunk,
+    /// The parser of a chunk did not read exactly the size announced in its
+    /// header, and the next chunk was read from the announced end
+    ChunkSizeMismatch,
+    /// An element has the same attribute several times, and only the first
+    /// one is used, see [`Attributes::insert_first`](crate::attributes::Attributes::insert_first)
+    DuplicateAttribute,
+    /// Data was found after the end of the XML chunk of the document (e.g.,
+    /// padding or junk appended by a packer), and was ignored
+    TrailingData,
+}
+
+/// Non-fatal problem found while parsing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// What the parser recovered from
+    pub kind: ParseWarningKind,
+    /// Offset, in bytes from the start of the document, of the chunk (or
+    /// string) the warning is about
+    pub offset: u64,
+    /// Human-readable description
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at offset {:#x}: {}", self.offset, self.message)
+    }
+}
@@ -0,0 +1,39 @@
+//! WebAssembly bindings
+//!
+//! JavaScript entry points to decode binary XML documents in the browser
+//! (e.g., a manifest dropped on a web page), built with `wasm-bindgen`. Only
+//! available with the `wasm` feature. The `apk` feature is not needed, and its
+//! compression backends do not build for `wasm32-unknown-unknown`, so the
+//! manifest has to be extracted from the APK on the JavaScript side.
+//!
+//! ```text
+//! wasm-pack build --target web --no-default-features --features wasm
+//! ```
+//!
+//! ```js
+//! import init, { decodeXml } from "./pkg/rusty_axml.js";
+//!
+//! await init();
+//! const bytes = new Uint8Array(await file.arrayBuffer());
+//! console.log(decodeXml(bytes));
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::parser::try_parse_xml_from_slice;
+
+/// Decode a binary XML document into an XML string
+#[wasm_bindgen(js_name = decodeXml)]
+pub fn decode_xml(data: &[u8]) -> Result<String, JsError> {
+    let root = try_parse_xml_from_slice(data)?;
+    let xml = root.borrow().to_xml_string_with_declaration();
+    Ok(xml)
+}
+
+/// Decode a binary XML document into a JSON string, see
+/// [`to_json`](crate::parser::to_json)
+#[wasm_bindgen(js_name = decodeJson)]
+pub fn decode_json(data: &[u8]) -> Result<String, JsError> {
+    let root = try_parse_xml_from_slice(data)?;
+    Ok(crate::parser::to_json(&root))
+}
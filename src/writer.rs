@@ -0,0 +1,414 @@
+//! Binary XML writer
+//!
+//! `write_axml` serializes a tree back to binary XML, the format Android
+//! expects in APKs: a string pool, a resource map giving the resource IDs of
+//! the attributes of the `android` namespace, and the namespace, element, and
+//! text chunks of the document.
+//!
+//! Typed values (booleans, integers, references, etc.) are written as they
+//! were parsed, and attributes without a typed value (e.g., added with
+//! [`crate::attributes::Attributes::insert`]) are written as strings. Only
+//! the attributes of the `android` namespace get a resource ID: the IDs of
+//! other attributes (e.g., from libraries) are not kept by the parser.
+//!
+//! As in the output of aapt2, the attributes of each element are sorted by
+//! resource ID, followed by the attributes without an ID in their order in the
+//! tree, since the platform only finds attributes that are in ID order.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::attributes::Attribute;
+use crate::chunk_types::ChunkType;
+use crate::data_value_type::DataValueType;
+use crate::parser::{ XmlElement, XmlNode };
+use crate::resource_map::{ android_attribute_id, android_attribute_name, ANDROID_NS };
+
+/// Index used for absent strings (no namespace, no comment, no raw value)
+const NO_INDEX: u32 = 0xffffffff;
+
+/// Size of the header of namespace, element, and text chunks
+const NODE_HEADER_SIZE: u16 = 16;
+
+/// Size of an attribute in a start-element chunk
+const ATTRIBUTE_SIZE: u16 = 20;
+
+/// Size of a typed value
+const RES_VALUE_SIZE: u16 = 8;
+
+/// Serialize the tree rooted at `root` to binary XML. The namespaces are
+/// declared where the `xmlns:` attributes are, and text is written as text
+/// chunks.
+pub fn write_axml(root: &Rc<RefCell<XmlElement>>) -> Vec<u8> {
+    let root = root.borrow();
+
+    // Attribute names with a resource ID go first in the string pool, in the
+    // order of their IDs, so that the resource map is indexed like the pool
+    let mut resource_ids = Vec::new();
+    collect_resource_ids(&root, &mut resource_ids);
+    resource_ids.sort_unstable();
+    resource_ids.dedup();
+
+    let mut writer = AxmlWriter::new(&resource_ids);
+    writer.write_element(&root);
+
+    let mut doc = Vec::new();
+    let pool = writer.string_pool();
+    let resource_map_size = 8 + 4 * resource_ids.len();
+    write_header(&mut doc, ChunkType::ResXmlType, 8,
+                 8 + pool.len() + resource_map_size + writer.body.len());
+    doc.extend(pool);
+    write_header(&mut doc, ChunkType::ResXmlResourceMapType, 8, resource_map_size);
+    for id in resource_ids.iter() {
+        write_u32(&mut doc, *id);
+    }
+    doc.extend(writer.body);
+    doc
+}
+
+/// Split an attribute key into the URI of its namespace and its local name,
+/// see [`XmlElement::attr_namespace`]. Keys without a known namespace are
+/// kept whole.
+fn split_key<'a>(element: &XmlElement, attr: &'a Attribute) -> (Option<String>, &'a str) {
+    match attr.resolve_namespace(|prefix| element.namespace_uri(prefix)) {
+        Some(uri) => (Some(uri.into_owned()), attr.local_name()),
+        None => (None, &attr.name),
+    }
+}
+
+/// Resource ID of an attribute, if it is a known attribute of the `android`
+/// namespace
+fn resource_id(namespace: Option<&str>, local: &str) -> Option<u32> {
+    (namespace == Some(ANDROID_NS)).then(|| android_attribute_id(local))?
+}
+
+fn collect_resource_ids(element: &XmlElement, ids: &mut Vec<u32>) {
+    for attr in element.attributes.iter_attributes().filter(|attr| !attr.name.starts_with("xmlns:")) {
+        let (namespace, local) = split_key(element, attr);
+        ids.extend(resource_id(namespace.as_deref(), local));
+    }
+    for child in element.child_elements() {
+        collect_resource_ids(&child.borrow(), ids);
+    }
+}
+
+/// State of the serialization: string pool being built and chunks written
+/// so far
+struct AxmlWriter<'a> {
+    /// Resource IDs of the first strings of the pool
+    resource_ids: &'a [u32],
+    strings: Vec<String>,
+    /// Index of the strings without a resource ID
+    indices: HashMap<String, u32>,
+    body: Vec<u8>,
+    line: u32,
+}
+
+impl<'a> AxmlWriter<'a> {
+    fn new(resource_ids: &'a [u32]) -> Self {
+        let strings = resource_ids.iter()
+                                  .map(|id| android_attribute_name(*id)
+                                                .unwrap_or_default()
+                                                .to_string())
+                                  .collect();
+        AxmlWriter {
+            resource_ids,
+            strings,
+            indices: HashMap::new(),
+            body: Vec::new(),
+            line: 0,
+        }
+    }
+
+    /// Index of `value` in the string pool, added if needed
+    fn string(&mut self, value: &str) -> u32 {
+        if let Some(idx) = self.indices.get(value) {
+            return *idx;
+        }
+        let idx = self.strings.len() as u32;
+        self.strings.push(value.to_string());
+        self.indices.insert(value.to_string(), idx);
+        idx
+    }
+
+    /// Index of the name of an attribute: the entry of its resource ID if it
+    /// has one, so that it is not shared with other strings
+    fn attribute_name(&mut self, namespace: Option<&str>, local: &str) -> u32 {
+        resource_id(namespace, local)
+            .and_then(|id| self.resource_ids.binary_search(&id).ok())
+            .map(|idx| idx as u32)
+            .unwrap_or_else(|| self.string(local))
+    }
+
+    fn optional_string(&mut self, value: Option<&str>) -> u32 {
+        value.map_or(NO_INDEX, |value| self.string(value))
+    }
+
+    /// Header of a namespace, element, or text chunk
+    fn node_header(&mut self, chunk_type: ChunkType, chunk_size: usize) {
+        write_header(&mut self.body, chunk_type, NODE_HEADER_SIZE, chunk_size);
+        write_u32(&mut self.body, self.line);
+        write_u32(&mut self.body, NO_INDEX);
+    }
+
+    fn namespace(&mut self, chunk_type: ChunkType, prefix: &str, uri: &str) {
+        let (prefix, uri) = (self.string(prefix), self.string(uri));
+        self.node_header(chunk_type, 24);
+        write_u32(&mut self.body, prefix);
+        write_u32(&mut self.body, uri);
+    }
+
+    fn write_element(&mut self, element: &XmlElement) {
+        self.line += 1;
+
+        let (namespaces, attributes): (Vec<&Attribute>, Vec<&Attribute>) =
+            element.attributes.iter_attributes().partition(|attr| attr.name.starts_with("xmlns:"));
+        let namespaces: Vec<(&str, &str)> = namespaces.iter()
+                                                     .map(|attr| (&attr.name["xmlns:".len()..], attr.value.as_str()))
+                                                     .collect();
+        for (prefix, uri) in namespaces.iter() {
+            self.namespace(ChunkType::ResXmlStartNamespaceType, prefix, uri);
+        }
+
+        // Like aapt2, put the attributes with a resource ID first, sorted by
+        // ID: the platform looks them up by walking both lists in order, and
+        // misses the ones that are out of order
+        let mut attributes: Vec<(&Attribute, Option<String>, &str)> =
+            attributes.into_iter()
+                      .map(|attr| {
+                          let (namespace, local) = split_key(element, attr);
+                          (attr, namespace, local)
+                      })
+                      .collect();
+        attributes.sort_by_key(|(_, namespace, local)| {
+            let id = resource_id(namespace.as_deref(), local);
+            (id.is_none(), id)
+        });
+
+        // Encode the attributes first, since they add strings to the pool
+        let mut encoded = Vec::with_capacity(attributes.len() * ATTRIBUTE_SIZE as usize);
+        let (mut id_index, mut class_index, mut style_index) = (0, 0, 0);
+        for (position, (attr, namespace, local)) in attributes.iter().enumerate() {
+            let local = *local;
+            match (namespace.as_deref(), local) {
+                (Some(ANDROID_NS), "id") => id_index = position as u16 + 1,
+                (None, "class") => class_index = position as u16 + 1,
+                (None, "style") => style_index = position as u16 + 1,
+                _ => { },
+            }
+
+            let namespace_idx = self.optional_string(namespace.as_deref());
+            let name_idx = self.attribute_name(namespace.as_deref(), local);
+            let (raw, data_type, data) = match attr.typed_value {
+                Some(typed) if typed.data_type != DataValueType::TypeString => (NO_INDEX, typed.data_type, typed.data),
+                _ => {
+                    let idx = self.string(&attr.value);
+                    (idx, DataValueType::TypeString, idx)
+                },
+            };
+            write_u32(&mut encoded, namespace_idx);
+            write_u32(&mut encoded, name_idx);
+            write_u32(&mut encoded, raw);
+            write_res_value(&mut encoded, data_type, data);
+        }
+
+        let namespace = self.optional_string(element.namespace.as_deref());
+        let name = self.string(&element.element_type);
+        self.node_header(ChunkType::ResXmlStartElementType, 36 + encoded.len());
+        write_u32(&mut self.body, namespace);
+        write_u32(&mut self.body, name);
+        write_u16(&mut self.body, 0x14);
+        write_u16(&mut self.body, ATTRIBUTE_SIZE);
+        write_u16(&mut self.body, attributes.len() as u16);
+        write_u16(&mut self.body, id_index);
+        write_u16(&mut self.body, class_index);
+        write_u16(&mut self.body, style_index);
+        self.body.extend(encoded);
+
+        for child in element.children.iter() {
+            match child {
+                XmlNode::Element(child) => self.write_element(&child.borrow()),
+                XmlNode::Text(text) => {
+                    let idx = self.string(text);
+                    self.node_header(ChunkType::ResXmlCDataType, 28);
+                    write_u32(&mut self.body, idx);
+                    write_res_value(&mut self.body, DataValueType::TypeString, idx);
+                },
+            }
+        }
+
+        self.node_header(ChunkType::ResXmlEndElementType, 24);
+        write_u32(&mut self.body, namespace);
+        write_u32(&mut self.body, name);
+
+        for (prefix, uri) in namespaces.iter().rev() {
+            self.namespace(ChunkType::ResXmlEndNamespaceType, prefix, uri);
+        }
+    }
+
+    /// String pool chunk, in UTF-16
+    fn string_pool(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut offsets = Vec::with_capacity(self.strings.len());
+        for string in self.strings.iter() {
+            offsets.push(data.len() as u32);
+            let units: Vec<u16> = string.encode_utf16().collect();
+            // Lengths above 0x7fff take two units, the high bit of the first
+            // one telling so
+            if units.len() > 0x7fff {
+                write_u16(&mut data, 0x8000 | (units.len() >> 16) as u16);
+            }
+            write_u16(&mut data, units.len() as u16);
+            for unit in units {
+                write_u16(&mut data, unit);
+            }
+            write_u16(&mut data, 0);
+        }
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+
+        let strings_start = 28 + 4 * offsets.len();
+        let mut pool = Vec::with_capacity(strings_start + data.len());
+        write_header(&mut pool, ChunkType::ResStringPoolType, 28, strings_start + data.len());
+        write_u32(&mut pool, offsets.len() as u32);
+        write_u32(&mut pool, 0);                        // style count
+        write_u32(&mut pool, 0);                        // flags (not sorted, UTF-16)
+        write_u32(&mut pool, strings_start as u32);
+        write_u32(&mut pool, 0);                        // styles start
+        for offset in offsets {
+            write_u32(&mut pool, offset);
+        }
+        pool.extend(data);
+        pool
+    }
+}
+
+fn write_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_header(buf: &mut Vec<u8>, chunk_type: ChunkType, header_size: u16, chunk_size: usize) {
+    write_u16(buf, u16::from(chunk_type));
+    write_u16(buf, header_size);
+    write_u32(buf, chunk_size as u32);
+}
+
+fn write_res_value(buf: &mut Vec<u8>, data_type: DataValueType, data: u32) {
+    write_u16(buf, RES_VALUE_SIZE);
+    buf.push(0);
+    buf.push(u8::from(data_type));
+    write_u32(buf, data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::parser::{ parse_xml_full, parse_xml_with_warnings };
+    use crate::test_utils::{ Attr, AxmlBuilder, DIST_NS };
+
+    const FIXTURE: &[u8] = include_bytes!("../tests/data/AndroidManifest.xml");
+
+    #[test]
+    fn test_write_round_trip() {
+        let parsed = parse_xml_full(Cursor::new(FIXTURE)).unwrap();
+        let data = write_axml(&parsed.root);
+
+        let rewritten = parse_xml_full(Cursor::new(&data)).unwrap();
+        assert!(rewritten.warnings.is_empty(), "{:?}", rewritten.warnings);
+        assert_eq!(crate::diff::diff_manifests(&parsed.root, &rewritten.root), vec![]);
+        // Only the order of the attributes changes, sorted by resource ID
+        assert!(parsed.root.borrow().to_xml_string().starts_with(&format!(
+            r#"<manifest xmlns:android="{ANDROID_NS}" package="com.example.app" android:versionCode="42""#)));
+        assert!(rewritten.root.borrow().to_xml_string().starts_with(&format!(
+            r#"<manifest xmlns:android="{ANDROID_NS}" android:versionCode="42" android:versionName="1.2.0" package="com.example.app">"#)));
+
+        // The resource map covers the android attributes, sorted by ID
+        let resource_map = rewritten.resource_map.unwrap();
+        let ids = resource_map.resource_ids();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ids.contains(&0x01010003));
+        assert!(ids.contains(&0x0101021b));
+        assert_eq!(resource_map.get_name(0), rewritten.string_pool.iter().next().map(|string| string.value));
+
+        // Writing again gives the same data
+        assert_eq!(write_axml(&rewritten.root), data);
+    }
+
+    #[test]
+    fn test_write_namespaces_and_text() {
+        let root = parse_xml_with_warnings(Cursor::new(AxmlBuilder::new()
+            .android()
+            .namespace("dist", DIST_NS)
+            .start("manifest", vec![Attr::string("package", "com.example")])
+            .start("dist:module", vec![Attr::boolean("dist:instant", true)])
+            .element("dist:fusing", vec![Attr::boolean("dist:include", true)])
+            .end("dist:module")
+            .start("meta", vec![Attr::string("android:value", "name"),
+                                Attr::reference("android:resource", 0x7f010000),
+                                Attr::float("android:scaleX", 1.5)])
+            .text("some & text")
+            .end("meta")
+            .end("manifest")
+            .build())).root;
+        let data = write_axml(&root);
+        let rewritten = parse_xml_full(Cursor::new(&data)).unwrap();
+
+        assert_eq!(rewritten.root.borrow().to_xml_string(), root.borrow().to_xml_string());
+        let module = Rc::clone(rewritten.root.borrow().child_elements().next().unwrap());
+        assert_eq!(module.borrow().namespace.as_deref(), Some(DIST_NS));
+        assert_eq!(module.borrow().qualified_name(), "dist:module");
+
+        // The `name` value does not share the entry of the `name` attribute,
+        // which is not used here, and the typed values are kept
+        let meta = Rc::clone(rewritten.root.borrow().child_elements().last().unwrap());
+        let meta = meta.borrow();
+        assert_eq!(meta.get_attr("value"), Some("name"));
+        assert_eq!(meta.get_attr_reference("resource"), Some(0x7f010000));
+        assert_eq!(meta.attributes.find_local("scaleX").unwrap().as_float(), Some(1.5));
+        assert_eq!(meta.text(), "some & text");
+        assert_eq!(rewritten.resource_map.unwrap().resource_ids().len(), 3);
+    }
+
+    #[test]
+    fn test_write_attribute_without_typed_value() {
+        let root = Rc::new(RefCell::new(XmlElement::new("manifest")));
+        root.borrow_mut().attributes.insert(String::from("xmlns:a"), String::from(ANDROID_NS));
+        root.borrow_mut().attributes.insert(String::from("a:label"), String::from("App"));
+        root.borrow_mut().attributes.insert(String::from("undeclared:key"), String::from("value"));
+
+        let rewritten = parse_xml_full(Cursor::new(write_axml(&root))).unwrap();
+        let rewritten = rewritten.root.borrow();
+        assert_eq!(rewritten.attributes.get("a:label").map(String::as_str), Some("App"));
+        assert_eq!(rewritten.attributes.get("undeclared:key").map(String::as_str), Some("value"));
+    }
+
+    #[test]
+    fn test_write_attributes_sorted_by_id() {
+        let root = parse_xml_with_warnings(Cursor::new(AxmlBuilder::new()
+            .android()
+            .element("application", vec![Attr::string("class", "Main"),
+                                         Attr::boolean("android:debuggable", false),
+                                         Attr::string("android:id", "@+id/app")])
+            .build())).root;
+        // Added after attributes with higher IDs
+        root.borrow_mut().attributes.insert(String::from("android:label"), String::from("App"));
+        root.borrow_mut().attributes.insert(String::from("android:allowBackup"), String::from("true"));
+
+        let data = write_axml(&root);
+        let rewritten = parse_xml_full(Cursor::new(&data)).unwrap();
+        let names: Vec<String> = rewritten.root.borrow()
+                                               .attributes
+                                               .iter_attributes()
+                                               .filter(|attr| !attr.name.starts_with("xmlns:"))
+                                               .map(|attr| attr.name.to_string())
+                                               .collect();
+        assert_eq!(names, ["android:label", "android:debuggable", "android:id", "android:allowBackup", "class"]);
+    }
+}
@@ -0,0 +1,233 @@
+//! Zero-copy access to stored zip entries
+//!
+//! Reading an entry through `zip::ZipArchive` parses the whole central
+//! directory into a map and copies the data of the entry, even when it is
+//! stored without compression. For large APKs where only the manifest is
+//! needed, this module walks the central directory of an archive that is
+//! already in memory (e.g., a file mapped with `memmap2`) and returns the
+//! data of a stored entry as a slice of the archive.
+//!
+//! Only what is needed to locate stored entries is read: compressed or
+//! encrypted entries, archives spanning several disks, and malformed
+//! structures all give `None`, and the caller is expected to fall back to
+//! the zip crate. The CRC of the data is not checked.
+
+use byteorder::{ ByteOrder, LittleEndian };
+
+/// Signature of the end of central directory record
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+/// Signature of the zip64 end of central directory locator
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+/// Signature of the zip64 end of central directory record
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+/// Signature of a central directory file header
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+/// Signature of a local file header
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+
+/// Size of the end of central directory record, without the comment
+const EOCD_SIZE: usize = 22;
+/// Size of the zip64 end of central directory locator
+const ZIP64_LOCATOR_SIZE: usize = 20;
+/// Size of a central directory file header, without the variable fields
+const CENTRAL_HEADER_SIZE: usize = 46;
+/// Size of a local file header, without the variable fields
+const LOCAL_HEADER_SIZE: usize = 30;
+/// ID of the zip64 extended information extra field
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// Compression method of entries stored as is
+const METHOD_STORED: u16 = 0;
+/// General purpose flag of encrypted entries
+const FLAG_ENCRYPTED: u16 = 1;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset.checked_add(2)?).map(LittleEndian::read_u16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset.checked_add(4)?).map(LittleEndian::read_u32)
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset.checked_add(8)?).map(LittleEndian::read_u64)
+}
+
+/// Offset and number of entries of the central directory
+fn find_central_directory(data: &[u8]) -> Option<(usize, u64)> {
+    // The record is followed by a comment of at most 65535 bytes
+    let last = data.len().checked_sub(EOCD_SIZE)?;
+    let first = last.saturating_sub(usize::from(u16::MAX));
+    let eocd = (first..=last).rev().find(|&offset| {
+        u32_at(data, offset) == Some(EOCD_SIGNATURE) &&
+            u16_at(data, offset + 20).is_some_and(|len| offset + EOCD_SIZE + usize::from(len) == data.len())
+    })?;
+
+    // Archives spanning several disks are not supported
+    if u16_at(data, eocd + 4)? != 0 || u16_at(data, eocd + 6)? != 0 {
+        return None;
+    }
+
+    let entries = u16_at(data, eocd + 10)?;
+    let offset = u32_at(data, eocd + 16)?;
+    if entries != u16::MAX && offset != u32::MAX {
+        return Some((offset as usize, u64::from(entries)));
+    }
+
+    // Zip64 archive: the values are in the zip64 record, found through the
+    // locator right before the end of central directory record
+    let locator = eocd.checked_sub(ZIP64_LOCATOR_SIZE)?;
+    if u32_at(data, locator)? != ZIP64_LOCATOR_SIGNATURE {
+        return None;
+    }
+    let record = usize::try_from(u64_at(data, locator + 8)?).ok()?;
+    if u32_at(data, record)? != ZIP64_EOCD_SIGNATURE {
+        return None;
+    }
+
+    let entries = u64_at(data, record + 32)?;
+    let offset = usize::try_from(u64_at(data, record + 48)?).ok()?;
+    Some((offset, entries))
+}
+
+/// Values of a central directory header that may be in the zip64 extra field
+struct EntryLocation {
+    compressed_size: u64,
+    uncompressed_size: u64,
+    local_header: u64,
+}
+
+/// Replace the values of `location` that are set to their maximum by the
+/// ones of the zip64 extra field, which only holds these values, in order
+fn apply_zip64_extra(extra: &[u8], location: &mut EntryLocation) -> Option<()> {
+    let mut offset = 0;
+    while offset + 4 <= extra.len() {
+        let id = u16_at(extra, offset)?;
+        let size = usize::from(u16_at(extra, offset + 2)?);
+        let field = extra.get(offset + 4..offset + 4 + size)?;
+
+        if id == ZIP64_EXTRA_ID {
+            let mut position = 0;
+            for value in [&mut location.uncompressed_size, &mut location.compressed_size, &mut location.local_header] {
+                if *value == u64::from(u32::MAX) {
+                    *value = u64_at(field, position)?;
+                    position += 8;
+                }
+            }
+            return Some(());
+        }
+
+        offset += 4 + size;
+    }
+
+    Some(())
+}
+
+/// Get the data of the entry `name` of the zip archive `data`, without
+/// copying it, if the entry is stored without compression. If several
+/// entries have this name, the last one in the central directory is used,
+/// like [`crate::create_cursor_from_apk_entry`].
+///
+/// Returns `None` if the entry is missing, compressed, or encrypted, or if
+/// the archive cannot be read this way: use the zip crate in that case.
+pub fn stored_entry<'a>(data: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let (mut offset, entries) = find_central_directory(data)?;
+
+    let mut found = None;
+    for _ in 0..entries {
+        if u32_at(data, offset)? != CENTRAL_HEADER_SIGNATURE {
+            return None;
+        }
+
+        let name_len = usize::from(u16_at(data, offset + 28)?);
+        let extra_len = usize::from(u16_at(data, offset + 30)?);
+        let comment_len = usize::from(u16_at(data, offset + 32)?);
+        let name_start = offset + CENTRAL_HEADER_SIZE;
+        let extra_start = name_start + name_len;
+
+        if data.get(name_start..extra_start)? == name.as_bytes() {
+            found = Some(offset);
+        }
+        offset = extra_start + extra_len + comment_len;
+    }
+
+    let header = found?;
+    let flags = u16_at(data, header + 8)?;
+    let method = u16_at(data, header + 10)?;
+    if method != METHOD_STORED || flags & FLAG_ENCRYPTED != 0 {
+        return None;
+    }
+
+    let mut location = EntryLocation {
+        compressed_size: u64::from(u32_at(data, header + 20)?),
+        uncompressed_size: u64::from(u32_at(data, header + 24)?),
+        local_header: u64::from(u32_at(data, header + 42)?),
+    };
+    let extra_start = header + CENTRAL_HEADER_SIZE + usize::from(u16_at(data, header + 28)?);
+    let extra = data.get(extra_start..extra_start + usize::from(u16_at(data, header + 30)?))?;
+    apply_zip64_extra(extra, &mut location)?;
+    if location.compressed_size != location.uncompressed_size {
+        return None;
+    }
+
+    // The data starts after the local header, whose variable fields may
+    // differ from the ones of the central directory
+    let local = usize::try_from(location.local_header).ok()?;
+    if u32_at(data, local)? != LOCAL_HEADER_SIGNATURE {
+        return None;
+    }
+    let start = local + LOCAL_HEADER_SIZE +
+                usize::from(u16_at(data, local + 26)?) +
+                usize::from(u16_at(data, local + 28)?);
+    let end = start.checked_add(usize::try_from(location.compressed_size).ok()?)?;
+
+    data.get(start..end)
+}
+
+#[cfg(all(test, feature = "apk"))]
+mod tests {
+    use super::*;
+    use std::io::{ Cursor, Write };
+    use zip::write::FileOptions;
+    use zip::CompressionMethod;
+
+    fn archive(entries: &[(&str, &[u8], CompressionMethod)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data, method) in entries {
+            writer.start_file(*name, FileOptions::default().compression_method(*method)).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_stored_entry() {
+        let data = archive(&[("classes.dex", b"dex\n035", CompressionMethod::Stored),
+                             ("AndroidManifest.xml", b"manifest", CompressionMethod::Stored),
+                             ("res/layout/main.xml", b"layout", CompressionMethod::Deflated)]);
+
+        let manifest = stored_entry(&data, "AndroidManifest.xml").unwrap();
+        assert_eq!(manifest, b"manifest");
+        // Borrowed from the archive
+        assert!(data.as_ptr_range().contains(&manifest.as_ptr()));
+
+        assert_eq!(stored_entry(&data, "classes.dex"), Some(&b"dex\n035"[..]));
+        assert_eq!(stored_entry(&data, "res/layout/main.xml"), None);
+        assert_eq!(stored_entry(&data, "missing.xml"), None);
+        assert_eq!(stored_entry(&data[..data.len() - 1], "AndroidManifest.xml"), None);
+        assert_eq!(stored_entry(b"not a zip", "AndroidManifest.xml"), None);
+    }
+
+    #[test]
+    fn test_stored_entry_zip64() {
+        // Zip64 records and extra fields, and a decoy manifest before the
+        // real one
+        let data = include_bytes!("../tests/data/apk-stored-zip64.apk");
+        let manifest = include_bytes!("../tests/data/AndroidManifest.xml");
+        assert_eq!(stored_entry(data, "AndroidManifest.xml"), Some(&manifest[..]));
+
+        // Deflated entries
+        let deflated = include_bytes!("../tests/data/apk-duplicate-manifest.apk");
+        assert_eq!(stored_entry(deflated, "AndroidManifest.xml"), None);
+    }
+}
@@ -0,0 +1,62 @@
+//! Build a small C program against the `cdylib` and the generated header, and
+//! check that it can decode a manifest
+
+#![cfg(all(feature = "ffi", unix))]
+
+use std::path::{ Path, PathBuf };
+use std::process::Command;
+
+/// Directory containing the shared library built for this test run
+fn library_dir() -> PathBuf {
+    let deps = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    [deps.clone(), deps.parent().unwrap().to_path_buf()]
+        .into_iter()
+        .find(|dir| ["librusty_axml.so", "librusty_axml.dylib"].iter().any(|lib| dir.join(lib).exists()))
+        .expect("cannot find the rusty_axml shared library")
+}
+
+#[test]
+fn test_checked_in_header_is_up_to_date() {
+    let generated = std::fs::read_to_string(Path::new(env!("OUT_DIR")).join("rusty_axml.h")).unwrap();
+    let checked_in = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/include/rusty_axml.h")).unwrap();
+    assert!(generated == checked_in, "include/rusty_axml.h is outdated, copy it from {}", env!("OUT_DIR"));
+}
+
+#[test]
+fn test_c_smoke() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = library_dir();
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi_smoke");
+
+    let compiled = Command::new(std::env::var("CC").unwrap_or_else(|_| String::from("cc")))
+        .arg(manifest_dir.join("tests/ffi/smoke.c"))
+        .arg("-I").arg(env!("OUT_DIR"))
+        .arg("-L").arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lrusty_axml")
+        .arg("-o").arg(&program)
+        .status();
+    match compiled {
+        Ok(status) => assert!(status.success(), "cannot compile the C smoke test"),
+        // No C compiler available, nothing to test
+        Err(e) => {
+            eprintln!("skipping the C smoke test: {e}");
+            return;
+        },
+    }
+
+    // Cargo adds its own directories to the search path of the dynamic
+    // loader, which may contain a build of the library without the `ffi`
+    // feature
+    let output = Command::new(&program)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .env("DYLD_LIBRARY_PATH", &lib_dir)
+        .arg(manifest_dir.join("tests/data/AndroidManifest.xml"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "smoke test failed: {stderr}");
+    assert!(stdout.contains("package=\"com.example.app\""), "unexpected output: {stdout}");
+    assert!(stderr.contains("malformed chunk"), "unexpected error: {stderr}");
+}
@@ -0,0 +1,62 @@
+/* Smoke test of the C interface, built and run by tests/ffi.rs */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "rusty_axml.h"
+
+static unsigned char *read_file(const char *path, size_t *len) {
+    FILE *file = fopen(path, "rb");
+    if (file == NULL) {
+        return NULL;
+    }
+
+    fseek(file, 0, SEEK_END);
+    *len = (size_t) ftell(file);
+    fseek(file, 0, SEEK_SET);
+
+    unsigned char *data = malloc(*len);
+    if (data != NULL && fread(data, 1, *len, file) != *len) {
+        free(data);
+        data = NULL;
+    }
+    fclose(file);
+    return data;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <manifest>\n", argv[0]);
+        return 2;
+    }
+
+    size_t len;
+    unsigned char *data = read_file(argv[1], &len);
+    if (data == NULL) {
+        fprintf(stderr, "cannot read %s\n", argv[1]);
+        return 2;
+    }
+
+    char *xml = NULL;
+    char *err = NULL;
+
+    /* Valid manifest */
+    if (rusty_axml_parse_manifest(data, len, &xml, &err) != RUSTY_AXML_OK || xml == NULL || err != NULL) {
+        fprintf(stderr, "cannot parse %s: %s\n", argv[1], err != NULL ? err : "(no message)");
+        return 1;
+    }
+    printf("%s\n", xml);
+    rusty_axml_free_string(xml);
+
+    /* Truncated manifest */
+    if (rusty_axml_parse_manifest(data, len / 2, &xml, &err) != RUSTY_AXML_ERROR || xml != NULL || err == NULL) {
+        fprintf(stderr, "truncated manifest was not rejected\n");
+        return 1;
+    }
+    fprintf(stderr, "%s\n", err);
+    rusty_axml_free_string(err);
+
+    free(data);
+    return 0;
+}
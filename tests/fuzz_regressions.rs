@@ -0,0 +1,59 @@
+//! Regression tests for the crashes found by the fuzz targets (see `fuzz/`)
+//!
+//! Each file in `tests/data/fuzz/<target>/` is a minimized input that used
+//! to make the parser panic. Malformed data must be reported as an error or
+//! a warning instead.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use rusty_axml::parser::{ parse_xml_with_warnings, try_parse_xml_from_slice };
+use rusty_axml::string_pool::StringPool;
+use rusty_axml::warning::ParseWarningKind;
+
+/// Inputs of the corpus of `target`, sorted by name
+fn corpus(target: &str) -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/fuzz").join(target);
+    let mut inputs: Vec<_> = fs::read_dir(&dir).unwrap()
+                                               .map(|entry| {
+                                                   let path = entry.unwrap().path();
+                                                   let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                                                   (name, fs::read(&path).unwrap())
+                                               })
+                                               .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "empty corpus in {}", dir.display());
+    inputs
+}
+
+#[test]
+fn test_parse_xml_corpus() {
+    // Inputs whose chunks can all be read, but refer to strings or
+    // namespaces that do not exist
+    let readable = ["missing-attribute-name.bin", "missing-attribute-value.bin", "undeclared-namespace.bin"];
+    // Inputs with a chunk size that does not match its contents, whose
+    // parsing continues after the announced end of the chunk (up to the
+    // truncated chunk that follows)
+    let resynchronized = ["truncated-end-element.bin"];
+
+    for (name, data) in corpus("parse_xml") {
+        let result = parse_xml_with_warnings(Cursor::new(&data));
+        let _ = result.root.borrow().to_xml_string();
+
+        let malformed = result.warnings.iter().any(|warning| warning.kind == ParseWarningKind::MalformedChunk);
+        let mismatch = result.warnings.iter().any(|warning| warning.kind == ParseWarningKind::ChunkSizeMismatch);
+        assert_eq!(malformed, !readable.contains(&name.as_str()),
+                   "{name}: {:?}", result.warnings);
+        assert_eq!(mismatch, resynchronized.contains(&name.as_str()), "{name}: {:?}", result.warnings);
+        // All of them are truncated, or have an invalid chunk layout
+        assert!(try_parse_xml_from_slice(&data).is_err(), "{name}");
+    }
+}
+
+#[test]
+fn test_string_pool_corpus() {
+    for (name, data) in corpus("string_pool") {
+        assert!(StringPool::from_buff(&mut Cursor::new(&data[..]), &mut Vec::new()).is_err(), "{name}");
+    }
+}
@@ -0,0 +1,432 @@
+//! Round-trip tests of the binary XML writer
+//!
+//! Each fixture is parsed, written back with `write_axml`, and parsed again:
+//! both trees must be the same, and both the fixture and the written document
+//! must pass [`assert_valid_axml`], which checks the layout of every chunk.
+//!
+//! The `aapt2-*.xml` fixtures are laid out like the output of aapt2: UTF-8
+//! string pool, resource map of the `android` attributes, and attributes
+//! sorted by resource ID. They cover non-ASCII and long strings, several
+//! namespaces (declared on the root and on nested elements, and used by
+//! elements), and references and other typed values.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::rc::Rc;
+
+use rusty_axml::chunk_header::ChunkHeader;
+use rusty_axml::chunk_types::ChunkType;
+use rusty_axml::chunks::ChunkIterator;
+use rusty_axml::data_value_type::DataValueType;
+use rusty_axml::parser::{ parse_xml_full, XmlElement, XmlNode };
+use rusty_axml::resource_map::android_attribute_name;
+use rusty_axml::string_pool::{ StringPool, StringPoolHeader };
+use rusty_axml::writer::write_axml;
+
+/// Index used for absent strings (no namespace, no comment, no raw value)
+const NO_INDEX: u32 = 0xffffffff;
+
+const FIXTURES: [&str; 7] = [
+    "AndroidManifest.xml",
+    "AndroidManifest-reordered.xml",
+    "LargeManifest.xml",
+    "aapt2-utf8.xml",
+    "aapt2-namespaces.xml",
+    "aapt2-references.xml",
+    "AndroidManifest-spoofed.xml",
+];
+
+fn fixture(name: &str) -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name)).unwrap()
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    let bytes = data.get(offset..offset + 2)
+                    .unwrap_or_else(|| panic!("reading u16 at {offset:#x}, past the end of the data"));
+    u16::from_le_bytes(bytes.try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    let bytes = data.get(offset..offset + 4)
+                    .unwrap_or_else(|| panic!("reading u32 at {offset:#x}, past the end of the data"));
+    u32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+/// Length prefix of a string, in UTF-8 (one or two bytes) or UTF-16 (one or
+/// two units). Returns the length and the size of the prefix.
+fn string_length(data: &[u8], offset: usize, utf8: bool) -> (usize, usize) {
+    if utf8 {
+        let first = usize::from(*data.get(offset).expect("UTF-8 length past the end of the pool"));
+        if first & 0x80 == 0 {
+            return (first, 1);
+        }
+        let second = usize::from(*data.get(offset + 1).expect("UTF-8 length past the end of the pool"));
+        (((first & 0x7f) << 8) | second, 2)
+    } else {
+        let first = usize::from(u16_at(data, offset));
+        if first & 0x8000 == 0 {
+            return (first, 2);
+        }
+        (((first & 0x7fff) << 16) | usize::from(u16_at(data, offset + 2)), 4)
+    }
+}
+
+/// Check the header and the string offsets of a string pool chunk, and that
+/// every string fits in the string data, terminator included. Returns the
+/// number of strings.
+fn assert_valid_string_pool(pool: &[u8]) -> usize {
+    let header = StringPoolHeader::from_bytes(pool).expect("truncated string pool header");
+    assert_eq!(u16_at(pool, 2), 28, "string pool header size");
+
+    let string_count = header.string_count as usize;
+    let style_count = header.style_count as usize;
+    let strings_start = header.strings_start as usize;
+    assert_eq!(strings_start, 28 + 4 * (string_count + style_count),
+               "string data does not start right after the offsets");
+    assert_eq!(strings_start % 4, 0, "string data is not aligned");
+
+    // The strings end where the styles start, if there are some
+    let strings_end = match header.style_count {
+        0 => {
+            assert_eq!(header.styles_start, 0, "styles start without styles");
+            pool.len()
+        },
+        _ => header.styles_start as usize,
+    };
+    assert!(strings_start <= strings_end && strings_end <= pool.len(),
+            "string data {strings_start:#x}..{strings_end:#x} out of the pool of {:#x} bytes", pool.len());
+
+    let utf8 = header.is_utf8();
+    for idx in 0..string_count {
+        let start = strings_start + u32_at(pool, 28 + 4 * idx) as usize;
+        assert!(start < strings_end, "string {idx} starts at {start:#x}, after the string data");
+
+        let end = if utf8 {
+            // Length in UTF-16 units, then in bytes
+            let (_, units_prefix) = string_length(pool, start, true);
+            let (bytes, bytes_prefix) = string_length(pool, start + units_prefix, true);
+            let data = start + units_prefix + bytes_prefix;
+            assert!(data + bytes < strings_end, "string {idx} ends after the string data");
+            assert!(std::str::from_utf8(&pool[data..data + bytes]).is_ok(), "string {idx} is not valid UTF-8");
+            assert_eq!(pool[data + bytes], 0, "string {idx} is not terminated");
+            data + bytes + 1
+        } else {
+            let (units, prefix) = string_length(pool, start, false);
+            let data = start + prefix;
+            assert!(data + 2 * units + 2 <= strings_end, "string {idx} ends after the string data");
+            assert_eq!(u16_at(pool, data + 2 * units), 0, "string {idx} is not terminated");
+            data + 2 * units + 2
+        };
+        assert!(end <= strings_end);
+    }
+
+    // The parser must decode the same strings, without replacing anything
+    let decoded = StringPool::from_buff(&mut Cursor::new(pool), &mut Vec::new()).unwrap();
+    assert_eq!(decoded.iter().count(), string_count);
+    assert!(decoded.iter().all(|string| !string.lossy), "lossy strings in the pool");
+
+    string_count
+}
+
+/// Check the chunks of an XML node (namespace, element, or text) against the
+/// string pool, and that elements and namespaces are balanced
+struct NodeChecker {
+    string_count: u32,
+    elements: Vec<(u32, u32)>,
+    namespaces: Vec<(u32, u32)>,
+    roots: usize,
+}
+
+impl NodeChecker {
+    fn string(&self, idx: u32, what: &str, offset: usize) {
+        assert!(idx < self.string_count, "{what} {idx} of the chunk at {offset:#x} is not in the pool");
+    }
+
+    fn optional_string(&self, idx: u32, what: &str, offset: usize) {
+        if idx != NO_INDEX {
+            self.string(idx, what, offset);
+        }
+    }
+
+    fn check(&mut self, chunk: &[u8], offset: usize, chunk_type: ChunkType) {
+        assert_eq!(u16_at(chunk, 2), 16, "header size of the node at {offset:#x}");
+        self.optional_string(u32_at(chunk, 12), "comment", offset);
+
+        match chunk_type {
+            ChunkType::ResXmlStartNamespaceType | ChunkType::ResXmlEndNamespaceType => {
+                assert_eq!(chunk.len(), 24, "size of the namespace chunk at {offset:#x}");
+                let namespace = (u32_at(chunk, 16), u32_at(chunk, 20));
+                self.string(namespace.0, "prefix", offset);
+                self.string(namespace.1, "URI", offset);
+                if chunk_type == ChunkType::ResXmlStartNamespaceType {
+                    self.namespaces.push(namespace);
+                } else {
+                    assert_eq!(self.namespaces.pop(), Some(namespace), "unbalanced namespace at {offset:#x}");
+                }
+            },
+            ChunkType::ResXmlStartElementType => {
+                let name = (u32_at(chunk, 16), u32_at(chunk, 20));
+                self.optional_string(name.0, "namespace", offset);
+                self.string(name.1, "name", offset);
+
+                assert_eq!(u16_at(chunk, 24), 0x14, "attribute start of the element at {offset:#x}");
+                assert_eq!(u16_at(chunk, 26), 20, "attribute size of the element at {offset:#x}");
+                let count = usize::from(u16_at(chunk, 28));
+                assert_eq!(chunk.len(), 36 + 20 * count, "size of the element at {offset:#x}");
+                for special in [30, 32, 34] {
+                    assert!(usize::from(u16_at(chunk, special)) <= count,
+                            "id, class, or style index out of the attributes at {offset:#x}");
+                }
+
+                for attr in (36..chunk.len()).step_by(20) {
+                    self.optional_string(u32_at(chunk, attr), "attribute namespace", offset);
+                    self.string(u32_at(chunk, attr + 4), "attribute name", offset);
+                    self.optional_string(u32_at(chunk, attr + 8), "raw value", offset);
+                    self.check_value(chunk, attr + 12, offset);
+                }
+
+                if self.elements.is_empty() {
+                    self.roots += 1;
+                }
+                self.elements.push(name);
+            },
+            ChunkType::ResXmlEndElementType => {
+                assert_eq!(chunk.len(), 24, "size of the end of element at {offset:#x}");
+                let name = (u32_at(chunk, 16), u32_at(chunk, 20));
+                assert_eq!(self.elements.pop(), Some(name), "unbalanced element at {offset:#x}");
+            },
+            ChunkType::ResXmlCDataType => {
+                assert_eq!(chunk.len(), 28, "size of the text at {offset:#x}");
+                assert!(!self.elements.is_empty(), "text outside of the root at {offset:#x}");
+                self.string(u32_at(chunk, 16), "text", offset);
+                self.check_value(chunk, 20, offset);
+            },
+            _ => panic!("unexpected {chunk_type:?} chunk at {offset:#x}"),
+        }
+    }
+
+    /// Check the typed value at `value` in `chunk`
+    fn check_value(&self, chunk: &[u8], value: usize, offset: usize) {
+        assert_eq!(u16_at(chunk, value), 8, "size of a value at {offset:#x}");
+        assert_eq!(chunk[value + 2], 0, "res0 of a value at {offset:#x}");
+        let data_type = DataValueType::try_from(chunk[value + 3])
+                            .unwrap_or_else(|e| panic!("value at {offset:#x}: {e}"));
+        if data_type == DataValueType::TypeString {
+            self.string(u32_at(chunk, value + 4), "string value", offset);
+        }
+    }
+}
+
+/// Check the layout of a binary XML document: a single XML chunk covering
+/// the whole data, whose chunks are contiguous, aligned on 4 bytes, and
+/// consistent with their headers; a valid string pool; a resource map
+/// indexed like the pool; and balanced namespaces and elements with one root,
+/// referring to strings of the pool.
+fn assert_valid_axml(data: &[u8]) {
+    let chunks: Vec<(usize, ChunkHeader)> = ChunkIterator::new(data).descend(true)
+                                                                    .collect::<Result<_, _>>()
+                                                                    .unwrap_or_else(|e| panic!("{e}"));
+
+    let (document, children) = chunks.split_first().expect("no chunk");
+    assert_eq!(document.0, 0);
+    assert_eq!(document.1.chunk_type, ChunkType::ResXmlType);
+    assert_eq!(document.1.header_size, 8, "XML chunk header size");
+    assert_eq!(document.1.chunk_size as usize, data.len(), "XML chunk size");
+
+    // The chunks of the document follow each other without any gap
+    let mut expected_offset = 8;
+    for (offset, header) in children.iter() {
+        assert_eq!(*offset, expected_offset, "gap or overlap before the chunk at {offset:#x}");
+        assert_eq!(header.chunk_size % 4, 0, "chunk at {offset:#x} is not aligned");
+        assert!(header.header_size >= 8 && header.header_size % 4 == 0 && u32::from(header.header_size) <= header.chunk_size,
+                "invalid header size of the chunk at {offset:#x}");
+        expected_offset += header.chunk_size as usize;
+    }
+    assert_eq!(expected_offset, data.len(), "the last chunk does not end the document");
+
+    let chunk_data = |offset: usize, header: &ChunkHeader| &data[offset..offset + header.chunk_size as usize];
+    let of_type = |chunk_type: ChunkType| children.iter().filter(move |(_, header)| header.chunk_type == chunk_type);
+
+    let mut pools = of_type(ChunkType::ResStringPoolType);
+    let (offset, header) = pools.next().expect("no string pool");
+    assert!(pools.next().is_none(), "several string pools");
+    let pool = chunk_data(*offset, header);
+    let string_count = assert_valid_string_pool(pool);
+
+    let mut maps = of_type(ChunkType::ResXmlResourceMapType);
+    if let Some((offset, header)) = maps.next() {
+        assert!(maps.next().is_none(), "several resource maps");
+        assert_eq!(header.header_size, 8, "resource map header size");
+        let map = chunk_data(*offset, header);
+        let ids: Vec<u32> = (8..map.len()).step_by(4).map(|idx| u32_at(map, idx)).collect();
+        assert!(ids.len() <= string_count, "more resource IDs than strings");
+
+        // The IDs of android attributes are given to their names
+        let strings = StringPool::from_buff(&mut Cursor::new(pool), &mut Vec::new()).unwrap();
+        for (string, id) in strings.iter().zip(ids.iter()) {
+            if let Some(name) = android_attribute_name(*id) {
+                assert_eq!(string.value, name, "resource ID {id:#010x} given to string {}", string.index);
+            }
+        }
+    }
+
+    let mut nodes = NodeChecker {
+        string_count: string_count as u32,
+        elements: Vec::new(),
+        namespaces: Vec::new(),
+        roots: 0,
+    };
+    for (offset, header) in children.iter() {
+        if !matches!(header.chunk_type, ChunkType::ResStringPoolType | ChunkType::ResXmlResourceMapType) {
+            nodes.check(chunk_data(*offset, header), *offset, header.chunk_type);
+        }
+    }
+    assert!(nodes.elements.is_empty(), "unclosed elements");
+    assert!(nodes.namespaces.is_empty(), "unclosed namespaces");
+    assert_eq!(nodes.roots, 1, "number of root elements");
+}
+
+/// Check that two trees are the same, except for the indices in the string
+/// pool of the string values, which depend on the layout of the pool, and the
+/// order of the attributes, which the writer sorts by resource ID
+fn assert_same_tree(a: &XmlElement, b: &XmlElement, path: &str) {
+    let path = format!("{path}/{}", a.element_type);
+    assert_eq!(a.element_type, b.element_type, "{path}");
+    assert_eq!(a.namespace, b.namespace, "namespace of {path}");
+
+    let (mut attrs_a, mut attrs_b): (Vec<_>, Vec<_>) = (a.attributes.iter_attributes().collect(),
+                                                        b.attributes.iter_attributes().collect());
+    attrs_a.sort_by(|x, y| x.name.cmp(&y.name));
+    attrs_b.sort_by(|x, y| x.name.cmp(&y.name));
+    assert_eq!(attrs_a.len(), attrs_b.len(), "attributes of {path}: {:?} and {:?}", a.attributes, b.attributes);
+    for (attr_a, attr_b) in attrs_a.iter().zip(attrs_b.iter()) {
+        assert_eq!(attr_a.name, attr_b.name, "attribute of {path}");
+        assert_eq!(attr_a.value, attr_b.value, "value of {path}@{}", attr_a.name);
+        match (attr_a.typed_value, attr_b.typed_value) {
+            (Some(typed_a), Some(typed_b)) if typed_a.data_type == DataValueType::TypeString => {
+                assert_eq!(typed_b.data_type, DataValueType::TypeString, "type of {path}@{}", attr_a.name);
+            },
+            (typed_a, typed_b) => assert_eq!(typed_a, typed_b, "typed value of {path}@{}", attr_a.name),
+        }
+    }
+
+    assert_eq!(a.children.len(), b.children.len(), "children of {path}");
+    for (child_a, child_b) in a.children.iter().zip(b.children.iter()) {
+        match (child_a, child_b) {
+            (XmlNode::Element(child_a), XmlNode::Element(child_b)) => {
+                assert_same_tree(&child_a.borrow(), &child_b.borrow(), &path);
+            },
+            (XmlNode::Text(text_a), XmlNode::Text(text_b)) => assert_eq!(text_a, text_b, "text of {path}"),
+            _ => panic!("children of {path}: {child_a:?} and {child_b:?}"),
+        }
+    }
+}
+
+/// Parse `data`, write it back, and check that nothing was lost. Returns the
+/// written document.
+fn assert_round_trip(data: &[u8]) -> (Rc<RefCell<XmlElement>>, Vec<u8>) {
+    let parsed = parse_xml_full(Cursor::new(data)).unwrap();
+    let written = write_axml(&parsed.root);
+    assert_valid_axml(&written);
+
+    let rewritten = parse_xml_full(Cursor::new(&written)).unwrap();
+    assert!(rewritten.warnings.is_empty(), "{:?}", rewritten.warnings);
+    assert_same_tree(&parsed.root.borrow(), &rewritten.root.borrow(), "");
+
+    // Writing the parsed output again gives the same data
+    assert_eq!(write_axml(&rewritten.root), written);
+    (rewritten.root, written)
+}
+
+#[test]
+fn test_fixtures_are_valid() {
+    // The reordered fixture has its string pool at the end on purpose
+    for name in FIXTURES.iter().filter(|name| **name != "AndroidManifest-reordered.xml") {
+        let data = fixture(name);
+        let result = std::panic::catch_unwind(|| assert_valid_axml(&data));
+        assert!(result.is_ok(), "invalid fixture {name}");
+    }
+}
+
+#[test]
+fn test_round_trip_fixtures() {
+    for name in FIXTURES {
+        let result = std::panic::catch_unwind(|| assert_round_trip(&fixture(name)));
+        assert!(result.is_ok(), "round trip of {name}");
+    }
+}
+
+#[test]
+fn test_round_trip_utf8() {
+    let (root, written) = assert_round_trip(&fixture("aapt2-utf8.xml"));
+    let root = root.borrow();
+    let application = root.child_elements().nth(1).unwrap().borrow();
+    assert_eq!(application.get_attr("label"), Some("Café ☕ 日本語"));
+    // Longer than 127 bytes, so with two-byte lengths
+    assert_eq!(application.get_attr("description"), Some("Une application d'exemple très complète, ".repeat(5).as_str()));
+
+    // The writer always uses UTF-16
+    let pool = &written[8..];
+    assert!(!StringPoolHeader::from_bytes(pool).unwrap().is_utf8());
+}
+
+#[test]
+fn test_round_trip_namespaces() {
+    let (root, _) = assert_round_trip(&fixture("aapt2-namespaces.xml"));
+    let root = root.borrow();
+    assert_eq!(root.attributes.get("tools:ignore").map(String::as_str), Some("MissingLeanbackLauncher"));
+
+    let module = root.child_elements().next().unwrap().borrow();
+    assert_eq!(module.qualified_name(), "dist:module");
+    assert_eq!(module.namespace.as_deref(), Some("http://schemas.android.com/apk/distribution"));
+
+    // `app` is only declared on the activity
+    let application = root.child_elements().nth(1).unwrap().borrow();
+    let activity = application.child_elements().next().unwrap().borrow();
+    assert_eq!(activity.attributes.get("xmlns:app").map(String::as_str), Some("http://schemas.android.com/apk/res-auto"));
+    assert_eq!(activity.attributes.get("app:custom").map(String::as_str), Some("value"));
+    assert!(application.attributes.get("xmlns:app").is_none());
+}
+
+#[test]
+fn test_round_trip_references() {
+    let (root, _) = assert_round_trip(&fixture("aapt2-references.xml"));
+    let root = root.borrow();
+    let application = root.child_elements().next().unwrap().borrow();
+    assert_eq!(application.get_attr_reference("theme"), Some(0x7f130005));
+
+    let typed = |element: &XmlElement, name: &str| element.attributes.find_local(name).unwrap().typed_value.unwrap().data_type;
+    let activity = application.child_elements().next().unwrap().borrow();
+    assert_eq!(typed(&activity, "theme"), DataValueType::TypeAttribute);
+
+    let view = application.child_elements().nth(1).unwrap().borrow();
+    assert_eq!(typed(&view, "layout_width"), DataValueType::TypeDimension);
+    assert_eq!(typed(&view, "pivotX"), DataValueType::TypeFraction);
+    assert_eq!(typed(&view, "textColor"), DataValueType::TypeIntColorArgb8);
+    assert_eq!(typed(&view, "enabled"), DataValueType::TypeReference);
+}
+
+#[test]
+#[should_panic(expected = "string 3 starts at")]
+fn test_invalid_string_offset() {
+    let mut data = write_axml(&parse_xml_full(Cursor::new(fixture("AndroidManifest.xml"))).unwrap().root);
+    let offset = 8 + 28 + 4 * 3;
+    data[offset..offset + 4].copy_from_slice(&0x10000u32.to_le_bytes());
+    assert_valid_axml(&data);
+}
+
+#[test]
+#[should_panic(expected = "attribute name")]
+fn test_invalid_attribute_name() {
+    let mut data = fixture("aapt2-references.xml");
+    let (offset, _) = ChunkIterator::new(&data).descend(true)
+                                               .map(Result::unwrap)
+                                               .find(|(_, header)| header.chunk_type == ChunkType::ResXmlStartElementType)
+                                               .unwrap();
+    // Name of the first attribute of the root
+    let name = offset + 36 + 4;
+    data[name..name + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    assert_valid_axml(&data);
+}